@@ -1,28 +1,92 @@
 use std::sync::Arc;
 
 use datafusion::{
+    common::{plan_err, tree_node::TreeNode},
     config::ConfigOptions,
     datasource::source_as_provider,
     error::{DataFusionError, Result},
-    logical_expr::{Expr, LogicalPlan, Projection, TableScan, TableSource},
+    logical_expr::{
+        utils::split_conjunction, BinaryExpr, Expr, ExprSchemable, LogicalPlan, LogicalPlanBuilder,
+        Operator, Prepare, Projection, TableScan, TableSource,
+    },
     optimizer::analyzer::AnalyzerRule,
 };
 
-use crate::{FederatedTableProviderAdaptor, FederatedTableSource, FederationProviderRef};
+use log::{debug, warn};
+
+use crate::{
+    coercion::coerce_join_keys,
+    collect_fallbacks,
+    compat::{transformed, unchanged},
+    federated_lineage,
+    join_hint::apply_join_hint,
+    query_shape, record_fallback, record_summary, FederatedTableProviderAdaptor,
+    FederatedTableSource, FederationConfig, FederationCoverage, FederationProviderRef, LineageSink,
+    OnUnsupported,
+};
 
 #[derive(Default)]
-pub struct FederationAnalyzerRule {}
+pub struct FederationAnalyzerRule {
+    lineage_sink: Option<Arc<dyn LineageSink>>,
+    coverage: Option<FederationCoverage>,
+}
 
 impl AnalyzerRule for FederationAnalyzerRule {
     // Walk over the plan, look for the largest subtrees that only have
     // TableScans from the same FederationProvider.
     // There 'largest sub-trees' are passed to their respective FederationProvider.optimizer.
     fn analyze(&self, plan: LogicalPlan, config: &ConfigOptions) -> Result<LogicalPlan> {
-        let (optimized, _) = self.optimize_recursively(&plan, None, config)?;
-        if let Some(result) = optimized {
-            return Ok(result);
+        // Keep prepared statements local, so DataFusion can substitute
+        // the parameters; the statement itself is federated.
+        if let LogicalPlan::Prepare(Prepare { input, .. }) = &plan {
+            let input = self.analyze(input.as_ref().clone(), config)?;
+            return plan.with_new_inputs(&[input]);
+        }
+
+        // Federation can be disabled per query to rule out pushdown bugs,
+        // each table is then read on its own with nothing pushed down.
+        if let Some(options) = config.extensions.get::<FederationConfig>() {
+            if !options.enabled {
+                debug!("federation decision=disabled reason=federation.enabled");
+                return self.federate_scans(plan, config);
+            }
         }
-        Ok(plan.clone())
+
+        // Coverage is tracked by query shape, fingerprinted before federation
+        let original = self.coverage.as_ref().map(|_| plan.clone());
+        let (optimized, fallbacks) =
+            collect_fallbacks(|| self.optimize_recursively(&plan, None, config));
+        let result = optimized?.0.unwrap_or(plan);
+        if !fallbacks.is_empty() {
+            let on_unsupported = match config.extensions.get::<FederationConfig>() {
+                Some(options) => options.on_unsupported()?,
+                None => OnUnsupported::Fallback,
+            };
+            let reasons = fallbacks
+                .iter()
+                .map(|(reason, count)| format!("{reason}={count}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            match on_unsupported {
+                OnUnsupported::Fallback => {}
+                OnUnsupported::Warn => {
+                    warn!("federation decision=fallback reasons=\"{reasons}\"")
+                }
+                OnUnsupported::Error => {
+                    return plan_err!(
+                    "plan can't be fully federated ({reasons}), federation.on_unsupported is error"
+                )
+                }
+            }
+        }
+        if let Some(sink) = &self.lineage_sink {
+            sink.emit(&federated_lineage(&result)?);
+        }
+        if let (Some(coverage), Some(original)) = (&self.coverage, &original) {
+            let shape = query_shape(original);
+            record_summary(coverage, shape, original, &result, fallbacks)?;
+        }
+        Ok(result)
     }
 
     /// A human readable name for this optimizer rule
@@ -36,6 +100,32 @@ impl FederationAnalyzerRule {
         Self::default()
     }
 
+    // Emits the column lineage of every analyzed query to the sink.
+    pub fn with_lineage_sink(mut self, sink: Arc<dyn LineageSink>) -> Self {
+        self.lineage_sink = Some(sink);
+        self
+    }
+
+    // Records a FederationSummary of every analyzed query in the coverage.
+    pub fn with_coverage(mut self, coverage: FederationCoverage) -> Self {
+        self.coverage = Some(coverage);
+        self
+    }
+
+    // Federates every table scan of the plan on its own.
+    fn federate_scans(&self, plan: LogicalPlan, config: &ConfigOptions) -> Result<LogicalPlan> {
+        plan.transform_up(&|plan| {
+            let Some(provider) = self.get_federation_provider(&plan)? else {
+                return Ok(unchanged(plan));
+            };
+            let Some(optimizer) = provider.analyzer() else {
+                return Ok(unchanged(plan));
+            };
+            let federated = optimizer.execute_and_check(&plan, config, log_rule)?;
+            Ok(transformed(federated))
+        })
+    }
+
     // optimize_recursively recursively finds the largest sub-plans that can be federated
     // to a single FederationProvider.
     // Returns a plan if a sub-tree was federated, otherwise None.
@@ -48,7 +138,11 @@ impl FederationAnalyzerRule {
     ) -> Result<(Option<LogicalPlan>, Option<FederationProviderRef>)> {
         // Check if this node determines the FederationProvider
         let sole_provider = self.get_federation_provider(plan)?;
-        if sole_provider.is_some() {
+        if let Some(provider) = &sole_provider {
+            debug!(
+                "federation decision=provider_found node=\"{}\" provider=\"{provider}\"",
+                plan.display()
+            );
             return Ok((None, sole_provider));
         }
 
@@ -74,9 +168,15 @@ impl FederationAnalyzerRule {
                 // federate the entire plan
                 if let Some(provider) = first_provider {
                     if let Some(optimizer) = provider.analyzer() {
-                        let optimized = optimizer.execute_and_check(plan, _config, |_, _| {})?;
+                        debug!(
+                            "federation decision=federate_plan node=\"{}\" provider=\"{provider}\"",
+                            plan.display()
+                        );
+                        let optimized = optimizer.execute_and_check(plan, _config, log_rule)?;
                         return Ok((Some(optimized), None));
                     }
+                    debug!("federation decision=local reason=no_analyzer provider=\"{provider}\"");
+                    record_fallback("no_analyzer");
                     return Ok((None, None));
                 }
                 return Ok((None, None));
@@ -87,6 +187,29 @@ impl FederationAnalyzerRule {
 
         // The plan is ambiguous, any inputs that are not federated and
         // have a sole provider, should be federated.
+        debug!(
+            "federation decision=split node=\"{}\" inputs={}",
+            plan.display(),
+            inputs.len()
+        );
+        // Only the boundary of the federated sub-plans counts as a fallback,
+        // not every local node above it
+        if providers.iter().any(Option::is_some) {
+            let distinct = providers.iter().flatten().fold(vec![], |mut distinct, p| {
+                if !distinct.contains(&p) {
+                    distinct.push(p);
+                }
+                distinct
+            });
+            match distinct.len() {
+                1 => record_fallback("local_input"),
+                _ => record_fallback("multiple_providers"),
+            }
+        }
+        // Sources may store join keys with different types
+        let coerced = coerce_join_keys(plan)?;
+        let plan = coerced.as_ref().unwrap_or(plan);
+        let join_keys = merge_join_keys(plan, &providers, _config);
         let new_inputs = new_inputs
             .into_iter()
             .enumerate()
@@ -99,20 +222,55 @@ impl FederationAnalyzerRule {
                 // Check if the input has a sole provider and can be federated.
                 if let Some(provider) = providers.get(i).unwrap() {
                     if let Some(optimizer) = provider.analyzer() {
-                        let wrapped = wrap_projection((*sub_plan).clone())?;
+                        debug!(
+                            "federation decision=federate_subplan node=\"{}\" provider=\"{provider}\"",
+                            sub_plan.display()
+                        );
+                        let mut wrapped = wrap_projection((*sub_plan).clone())?;
+                        if let Some(keys) = join_keys.as_ref().and_then(|k| k.get(i)) {
+                            debug!(
+                                "federation decision=push_ordering reason=merge_join provider=\"{provider}\""
+                            );
+                            wrapped = LogicalPlanBuilder::from(wrapped)
+                                .sort(keys.iter().map(|k| k.clone().sort(true, true)))?
+                                .build()?;
+                        }
 
-                        let optimized =
-                            optimizer.execute_and_check(&wrapped, _config, |_, _| {})?;
+                        let optimized = optimizer.execute_and_check(&wrapped, _config, log_rule)?;
                         return Ok(optimized);
                     }
                     // No federation for this sub-plan (no analyzer)
+                    record_fallback("no_analyzer");
+                    debug!(
+                        "federation decision=local reason=no_analyzer node=\"{}\" provider=\"{provider}\"",
+                        sub_plan.display()
+                    );
                     return Ok((*sub_plan).clone());
                 }
                 // No federation for this sub-plan (no provider)
+                debug!(
+                    "federation decision=local reason=no_provider node=\"{}\"",
+                    sub_plan.display()
+                );
                 Ok((*sub_plan).clone())
             })
             .collect::<Result<Vec<_>>>()?;
 
+        if let Some(options) = _config.extensions.get::<FederationConfig>() {
+            if let Some(hint) = options.join_hint()? {
+                let hinted = apply_join_hint(
+                    plan,
+                    &inputs,
+                    &new_inputs,
+                    &hint,
+                    options.join_in_list_limit,
+                )?;
+                if let Some(hinted) = hinted {
+                    return Ok((Some(hinted), None));
+                }
+            }
+        }
+
         let new_plan = plan.with_new_inputs(&new_inputs)?;
 
         Ok((Some(new_plan), None))
@@ -130,6 +288,73 @@ impl FederationAnalyzerRule {
     }
 }
 
+// Returns the join keys of both sides if a cross-source join can be executed as
+// a merge join over remotely sorted inputs. This requires both providers to
+// push down ordering and hash joins to be disabled (`datafusion.optimizer.prefer_hash_join`).
+// Keys other than numbers or times, e.g. strings, are sorted by the collation
+// of each source, so both must compare them by their bytes.
+fn merge_join_keys(
+    plan: &LogicalPlan,
+    providers: &[Option<FederationProviderRef>],
+    config: &ConfigOptions,
+) -> Option<Vec<Vec<Expr>>> {
+    let LogicalPlan::Join(join) = plan else {
+        return None;
+    };
+    if config.optimizer.prefer_hash_join {
+        return None;
+    }
+    let ordered = providers
+        .iter()
+        .all(|p| p.as_ref().is_some_and(|p| p.supports_ordering_pushdown()));
+    if !ordered {
+        return None;
+    }
+
+    // ON conditions are only moved to `on` by the optimizer,
+    // pick up the column equalities from the filter.
+    let mut on = join.on.clone();
+    for predicate in join.filter.iter().flat_map(split_conjunction) {
+        if let Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: Operator::Eq,
+            right,
+        }) = predicate
+        {
+            if let (Expr::Column(l), Expr::Column(r)) = (left.as_ref(), right.as_ref()) {
+                if join.left.schema().has_column(l) && join.right.schema().has_column(r) {
+                    on.push((*left.clone(), *right.clone()));
+                } else if join.left.schema().has_column(r) && join.right.schema().has_column(l) {
+                    on.push((*right.clone(), *left.clone()));
+                }
+            }
+        }
+    }
+    if on.is_empty() {
+        return None;
+    }
+    let binary = providers
+        .iter()
+        .all(|p| p.as_ref().is_some_and(|p| p.binary_collation()));
+    let ordered_as_arrow = on.iter().all(|(l, r)| {
+        [(l, join.left.schema()), (r, join.right.schema())]
+            .into_iter()
+            .all(|(key, schema)| {
+                key.get_type(schema.as_ref())
+                    .is_ok_and(|t| t.is_numeric() || t.is_temporal())
+            })
+    });
+    if !binary && !ordered_as_arrow {
+        return None;
+    }
+    let (left, right) = on.into_iter().unzip();
+    Some(vec![left, right])
+}
+
+fn log_rule(_plan: &LogicalPlan, rule: &dyn AnalyzerRule) {
+    debug!("federation rule_fired={}", rule.name());
+}
+
 fn wrap_projection(plan: LogicalPlan) -> Result<LogicalPlan> {
     // TODO: minimize requested columns
     match plan {
@@ -0,0 +1,176 @@
+use datafusion::{
+    arrow::datatypes::DataType,
+    common::{plan_err, DFSchema},
+    error::Result,
+    logical_expr::{
+        cast, utils::split_conjunction, BinaryExpr, Expr, ExprSchemable, Join, LogicalPlan,
+        Operator,
+    },
+};
+use log::debug;
+
+// Returns the type both sides of a cross-source join key are cast to, None
+// if the types can't be joined. Sources often store the same column with
+// slightly different types, the rules are:
+// - integers widen to the smallest integer holding both, signed if either is,
+//   UInt64 with a signed integer to Decimal128(20, 0)
+// - integers and floats to Float64
+// - Utf8 and LargeUtf8 to LargeUtf8
+// - decimals to the precision and scale holding both, at most 38 digits
+// - Date32 and Date64 to Date64
+pub fn join_key_type(left: &DataType, right: &DataType) -> Option<DataType> {
+    use DataType::*;
+    if left == right {
+        return Some(left.clone());
+    }
+    match (left, right) {
+        (l, r) if l.is_integer() && r.is_integer() => Some(integer_type(l, r)),
+        (l, r) if l.is_numeric() && r.is_numeric() && (is_float(l) || is_float(r)) => Some(Float64),
+        (Utf8 | LargeUtf8, Utf8 | LargeUtf8) => Some(LargeUtf8),
+        (Decimal128(p1, s1), Decimal128(p2, s2)) => Some(decimal_type(*p1, *s1, *p2, *s2)),
+        (Decimal128(p, s), i) | (i, Decimal128(p, s)) if i.is_integer() => {
+            let Decimal128(ip, is) = integer_decimal(i) else {
+                return None;
+            };
+            Some(decimal_type(*p, *s, ip, is))
+        }
+        (Date32 | Date64, Date32 | Date64) => Some(Date64),
+        _ => None,
+    }
+}
+
+fn is_float(t: &DataType) -> bool {
+    matches!(t, DataType::Float16 | DataType::Float32 | DataType::Float64)
+}
+
+// The bytes of an integer type and whether it is signed.
+fn integer_width(t: &DataType) -> (u8, bool) {
+    use DataType::*;
+    match t {
+        Int8 => (1, true),
+        Int16 => (2, true),
+        Int32 => (4, true),
+        Int64 => (8, true),
+        UInt8 => (1, false),
+        UInt16 => (2, false),
+        UInt32 => (4, false),
+        _ => (8, false),
+    }
+}
+
+fn integer_type(l: &DataType, r: &DataType) -> DataType {
+    use DataType::*;
+    let ((lw, ls), (rw, rs)) = (integer_width(l), integer_width(r));
+    if ls == rs {
+        return if lw >= rw { l.clone() } else { r.clone() };
+    }
+    // An unsigned integer needs the next wider signed integer
+    let (signed, unsigned) = if ls { (lw, rw) } else { (rw, lw) };
+    match signed.max(unsigned * 2) {
+        1 => Int8,
+        2 => Int16,
+        4 => Int32,
+        8 => Int64,
+        _ => Decimal128(20, 0),
+    }
+}
+
+// The decimal holding every value of an integer type.
+fn integer_decimal(t: &DataType) -> DataType {
+    let digits = match integer_width(t) {
+        (1, _) => 3,
+        (2, _) => 5,
+        (4, _) => 10,
+        (_, true) => 19,
+        (_, false) => 20,
+    };
+    DataType::Decimal128(digits, 0)
+}
+
+fn decimal_type(p1: u8, s1: i8, p2: u8, s2: i8) -> DataType {
+    let scale = s1.max(s2);
+    let integer_digits = (p1 as i16 - s1 as i16).max(p2 as i16 - s2 as i16);
+    let precision = (integer_digits + scale as i16).clamp(1, 38) as u8;
+    DataType::Decimal128(precision, scale)
+}
+
+// Casts the keys of a join to their common join_key_type where their types
+// differ, and fails with the keys and types if the `on` keys have none.
+pub(crate) fn coerce_join_keys(plan: &LogicalPlan) -> Result<Option<LogicalPlan>> {
+    let LogicalPlan::Join(join) = plan else {
+        return Ok(None);
+    };
+    let (left, right) = (join.left.schema(), join.right.schema());
+    let mut changed = false;
+    let on = join
+        .on
+        .iter()
+        .map(|(l, r)| {
+            let (l, r, cast) = coerce_pair(l, left.as_ref(), r, right.as_ref(), true)?;
+            changed |= cast;
+            Ok((l, r))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let filter = match &join.filter {
+        Some(filter) => {
+            let both = left.join(right)?;
+            let predicates = split_conjunction(filter)
+                .into_iter()
+                .map(|predicate| match predicate {
+                    Expr::BinaryExpr(BinaryExpr {
+                        left: l,
+                        op: Operator::Eq,
+                        right: r,
+                    }) => {
+                        let (l, r, cast) = coerce_pair(l, &both, r, &both, false)?;
+                        changed |= cast;
+                        Ok(l.eq(r))
+                    }
+                    predicate => Ok(predicate.clone()),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            predicates.into_iter().reduce(Expr::and)
+        }
+        None => None,
+    };
+    if !changed {
+        return Ok(None);
+    }
+    debug!(
+        "federation decision=coerce_join_keys node=\"{}\"",
+        plan.display()
+    );
+    Ok(Some(LogicalPlan::Join(Join {
+        on,
+        filter,
+        ..join.clone()
+    })))
+}
+
+fn coerce_pair(
+    l: &Expr,
+    left: &DFSchema,
+    r: &Expr,
+    right: &DFSchema,
+    required: bool,
+) -> Result<(Expr, Expr, bool)> {
+    let (lt, rt) = (l.get_type(left)?, r.get_type(right)?);
+    if lt == rt {
+        return Ok((l.clone(), r.clone(), false));
+    }
+    let Some(t) = join_key_type(&lt, &rt) else {
+        // Comparisons of the filter DataFusion accepts are left as they are
+        if !required {
+            return Ok((l.clone(), r.clone(), false));
+        }
+        return plan_err!(
+            "cross-source join keys {l} ({lt}) and {r} ({rt}) have incompatible types"
+        );
+    };
+    let coerce = |e: &Expr, et: &DataType| match et == &t {
+        true => e.clone(),
+        false => cast(e.clone(), t.clone()),
+    };
+    Ok((coerce(l, &lt), coerce(r, &rt), true))
+}
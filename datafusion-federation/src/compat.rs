@@ -0,0 +1,17 @@
+// Isolates the DataFusion APIs that change between major releases, so
+// supporting another release only touches this module.
+
+use datafusion::common::tree_node::Transformed;
+
+// The return value of TreeNode visitors, renamed TreeNodeRecursion in later releases
+pub(crate) use datafusion::common::tree_node::VisitRecursion as TreeNodeRecursion;
+
+// Returns a node rewritten by a TreeNode transform.
+pub(crate) fn transformed<T>(node: T) -> Transformed<T> {
+    Transformed::Yes(node)
+}
+
+// Returns a node left as is by a TreeNode transform.
+pub(crate) fn unchanged<T>(node: T) -> Transformed<T> {
+    Transformed::No(node)
+}
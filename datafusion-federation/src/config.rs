@@ -0,0 +1,249 @@
+use std::{any::Any, collections::BTreeMap};
+
+use datafusion::{
+    common::plan_err,
+    config::{ConfigEntry, ConfigExtension, ExtensionOptions},
+    error::Result,
+};
+
+use crate::{JoinHint, JoinStrategy};
+
+// FederationConfig holds the session options of the federation layer, set
+// with `SET federation.<option> = ...` once registered:
+// `SessionConfig::new().with_option_extension(FederationConfig::default())`
+//
+// Other keys, `federation.<source>.<variable>`, are session variables passed
+// through to the remote sessions of the source, e.g.
+// `SET federation.pg.statement_timeout = '30s'`.
+#[derive(Debug, Clone)]
+pub struct FederationConfig {
+    // Disables federation, every table is then read on its own with
+    // nothing pushed down, e.g. to rule out pushdown bugs for a query
+    pub enabled: bool,
+    // What happens when part of a plan can't be federated and runs
+    // locally: `fallback` silently, `warn` logs it, `error` fails the query
+    pub on_unsupported: String,
+    // The table driving cross-source joins reading it, see JoinHint
+    pub join_driver: String,
+    // How cross-source joins are executed: `auto`, `local_hash` or `in_list`
+    pub join_strategy: String,
+    // The most distinct driving keys sent as an IN list, larger key sets
+    // read the other side unfiltered
+    pub join_in_list_limit: usize,
+    // Overrides the default limit of the sources: a number of rows, or
+    // `none` to fetch every row. Empty keeps the sources' own
+    pub default_limit: String,
+    // The session variables of each source, by source and variable name
+    pub session_variables: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Default for FederationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            on_unsupported: "fallback".to_string(),
+            join_driver: String::new(),
+            join_strategy: "auto".to_string(),
+            join_in_list_limit: 1000,
+            default_limit: String::new(),
+            session_variables: BTreeMap::new(),
+        }
+    }
+}
+
+impl ConfigExtension for FederationConfig {
+    const PREFIX: &'static str = "federation";
+}
+
+impl ExtensionOptions for FederationConfig {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn cloned(&self) -> Box<dyn ExtensionOptions> {
+        Box::new(self.clone())
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        match key {
+            "enabled" => {
+                self.enabled = match value.to_ascii_lowercase().as_str() {
+                    "true" => true,
+                    "false" => false,
+                    _ => return plan_err!("invalid federation.enabled {value}"),
+                };
+                return Ok(());
+            }
+            "on_unsupported" => {
+                self.on_unsupported = value.to_string();
+                return Ok(());
+            }
+            "join_driver" => {
+                self.join_driver = value.to_string();
+                return Ok(());
+            }
+            "join_strategy" => {
+                self.join_strategy = value.to_string();
+                return Ok(());
+            }
+            "join_in_list_limit" => {
+                self.join_in_list_limit = match value.parse() {
+                    Ok(limit) => limit,
+                    Err(_) => return plan_err!("invalid federation.join_in_list_limit {value}"),
+                };
+                return Ok(());
+            }
+            "default_limit" => {
+                let previous = std::mem::replace(&mut self.default_limit, value.to_string());
+                if let Err(e) = self.default_limit() {
+                    self.default_limit = previous;
+                    return Err(e);
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        let Some((source, variable)) = key.split_once('.') else {
+            return plan_err!("unknown option federation.{key}");
+        };
+        self.session_variables
+            .entry(source.to_string())
+            .or_default()
+            .insert(variable.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn entries(&self) -> Vec<ConfigEntry> {
+        let mut entries = vec![
+            ConfigEntry {
+                key: "enabled".to_string(),
+                value: Some(self.enabled.to_string()),
+                description: "Disables federation, every table is then read on its own",
+            },
+            ConfigEntry {
+                key: "on_unsupported".to_string(),
+                value: Some(self.on_unsupported.clone()),
+                description:
+                    "What happens when part of a plan can't be federated: fallback, warn or error",
+            },
+            ConfigEntry {
+                key: "join_driver".to_string(),
+                value: Some(self.join_driver.clone()),
+                description: "The table driving cross-source joins reading it",
+            },
+            ConfigEntry {
+                key: "join_strategy".to_string(),
+                value: Some(self.join_strategy.clone()),
+                description: "How cross-source joins are executed: auto, local_hash or in_list",
+            },
+            ConfigEntry {
+                key: "join_in_list_limit".to_string(),
+                value: Some(self.join_in_list_limit.to_string()),
+                description: "The most distinct driving keys sent as an IN list",
+            },
+            ConfigEntry {
+                key: "default_limit".to_string(),
+                value: Some(self.default_limit.clone()),
+                description: "Overrides the default limit of the sources: a number of rows or none",
+            },
+        ];
+        for (source, variables) in &self.session_variables {
+            for (variable, value) in variables {
+                entries.push(ConfigEntry {
+                    key: format!("{source}.{variable}"),
+                    value: Some(value.clone()),
+                    description: "A session variable passed through to the source",
+                });
+            }
+        }
+        entries
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnUnsupported {
+    Fallback,
+    Warn,
+    Error,
+}
+
+impl FederationConfig {
+    pub fn on_unsupported(&self) -> Result<OnUnsupported> {
+        match self.on_unsupported.to_ascii_lowercase().as_str() {
+            "fallback" => Ok(OnUnsupported::Fallback),
+            "warn" => Ok(OnUnsupported::Warn),
+            "error" => Ok(OnUnsupported::Error),
+            other => plan_err!(
+                "invalid federation.on_unsupported {other}, expected error, fallback or warn"
+            ),
+        }
+    }
+
+    // The join hint set with `federation.join_driver` and
+    // `federation.join_strategy`, None if neither is set.
+    pub fn join_hint(&self) -> Result<Option<JoinHint>> {
+        let strategy = JoinStrategy::parse(&self.join_strategy)?;
+        let driver = (!self.join_driver.is_empty()).then(|| self.join_driver.clone());
+        if driver.is_none() && strategy == JoinStrategy::Auto {
+            return Ok(None);
+        }
+        Ok(Some(JoinHint { driver, strategy }))
+    }
+
+    // Sets the join hint, e.g. one read from a query with JoinHint::from_sql.
+    pub fn with_join_hint(mut self, hint: &JoinHint) -> Self {
+        self.join_driver = hint.driver.clone().unwrap_or_default();
+        self.join_strategy = hint.strategy.as_str().to_string();
+        self
+    }
+
+    // Applies the hints of the query's `/*+ ... */` comments: NO_FEDERATION
+    // disables federation, FEDERATION_JOIN sets the join hint.
+    pub fn with_sql_hints(mut self, sql: &str) -> Result<Self> {
+        let lower = sql.to_ascii_lowercase();
+        let mut rest = lower.as_str();
+        while let Some(start) = rest.find("/*+") {
+            rest = &rest[start + 3..];
+            let body = &rest[..rest.find("*/").unwrap_or(rest.len())];
+            if body.split_whitespace().any(|hint| hint == "no_federation") {
+                self.enabled = false;
+            }
+        }
+        if let Some(hint) = JoinHint::from_sql(sql)? {
+            self = self.with_join_hint(&hint);
+        }
+        Ok(self)
+    }
+
+    // The default limit set with `federation.default_limit`, None if it isn't
+    // set, Some(None) if every row is fetched.
+    pub fn default_limit(&self) -> Result<Option<Option<usize>>> {
+        match self.default_limit.to_ascii_lowercase().as_str() {
+            "" => Ok(None),
+            "none" => Ok(Some(None)),
+            limit => match limit.parse() {
+                Ok(limit) => Ok(Some(Some(limit))),
+                Err(_) => plan_err!(
+                    "invalid federation.default_limit {limit}, expected a number of rows or none"
+                ),
+            },
+        }
+    }
+
+    // The session variables set for the source, `federation.<source>.<variable>`.
+    pub fn session_variables(&self, source: &str) -> Vec<(String, String)> {
+        self.session_variables
+            .get(source)
+            .map(|variables| {
+                variables
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
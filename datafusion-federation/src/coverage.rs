@@ -0,0 +1,268 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use datafusion::{
+    common::tree_node::TreeNode,
+    error::{DataFusionError, Result},
+    logical_expr::{Extension, LogicalPlan},
+};
+use log::warn;
+use serde_json::{json, Value};
+
+use crate::{compat::TreeNodeRecursion, plan_hash::fingerprint, FederatedPlanNode};
+
+// FederationSummary describes how much of a plan was federated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FederationSummary {
+    // The number of sub-plans executed remotely
+    pub federated: usize,
+    // The number of plan nodes executed locally although their inputs
+    // read from federated sources
+    pub fallbacks: usize,
+    // The number of fallbacks by reason
+    pub reasons: BTreeMap<String, usize>,
+}
+
+impl FederationSummary {
+    fn merge(&mut self, other: &FederationSummary) {
+        self.federated += other.federated;
+        self.fallbacks += other.fallbacks;
+        for (reason, count) in &other.reasons {
+            *self.reasons.entry(reason.clone()).or_default() += count;
+        }
+    }
+}
+
+// FederationCoverage collects a FederationSummary for every plan analyzed by
+// a FederationAnalyzerRule, to track the pushdown coverage of a workload.
+// Install it in the session config extensions to read it from the session:
+// `SessionConfig::new().with_extension(Arc::new(coverage.clone()))` along
+// with `FederationAnalyzerRule::new().with_coverage(coverage)`.
+#[derive(Debug, Clone, Default)]
+pub struct FederationCoverage {
+    state: Arc<Mutex<CoverageState>>,
+}
+
+#[derive(Default)]
+struct CoverageState {
+    last: FederationSummary,
+    total: FederationSummary,
+    plans: usize,
+    // The history of each query shape, by shape fingerprint
+    shapes: BTreeMap<u64, ShapeHistory>,
+    observer: Option<Arc<dyn CoverageObserver>>,
+}
+
+impl std::fmt::Debug for CoverageState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CoverageState")
+            .field("last", &self.last)
+            .field("total", &self.total)
+            .field("plans", &self.plans)
+            .field("shapes", &self.shapes.len())
+            .finish()
+    }
+}
+
+// ShapeHistory is the pushdown coverage seen for a query shape, i.e. the
+// queries with the same plan up to literal values.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShapeHistory {
+    pub plans: usize,
+    // Whether a plan of the shape was ever federated without fallbacks
+    pub fully_federated: bool,
+    // The fraction of the last plan federated, federated / (federated + fallbacks)
+    pub last_coverage: f64,
+}
+
+// CoverageRegression is a query shape that was fully federated before and
+// now falls back, e.g. after an upgrade or a schema change.
+#[derive(Debug, Clone)]
+pub struct CoverageRegression {
+    pub shape: u64,
+    // The plan before federation
+    pub plan: String,
+    pub summary: FederationSummary,
+}
+
+// CoverageObserver is notified of coverage regressions, which are also
+// logged as warnings.
+pub trait CoverageObserver: Send + Sync {
+    fn regression(&self, regression: &CoverageRegression);
+}
+
+impl<F: Fn(&CoverageRegression) + Send + Sync> CoverageObserver for F {
+    fn regression(&self, regression: &CoverageRegression) {
+        self(regression)
+    }
+}
+
+impl FederationCoverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the summary of the last analyzed plan.
+    pub fn last(&self) -> FederationSummary {
+        self.state.lock().unwrap().last.clone()
+    }
+
+    // Returns the summaries of all analyzed plans added up.
+    pub fn total(&self) -> FederationSummary {
+        self.state.lock().unwrap().total.clone()
+    }
+
+    // Returns the number of analyzed plans.
+    pub fn plans(&self) -> usize {
+        self.state.lock().unwrap().plans
+    }
+
+    // Notifies the observer when a query shape that was fully federated
+    // starts falling back.
+    pub fn with_regression_observer(self, observer: Arc<dyn CoverageObserver>) -> Self {
+        self.state.lock().unwrap().observer = Some(observer);
+        self
+    }
+
+    // Returns the history of each query shape seen.
+    pub fn shapes(&self) -> BTreeMap<u64, ShapeHistory> {
+        self.state.lock().unwrap().shapes.clone()
+    }
+
+    // Returns the shape histories as JSON, to keep them across restarts
+    // with load_shapes, so regressions after an upgrade are detected.
+    pub fn shapes_json(&self) -> String {
+        let shapes = self
+            .state
+            .lock()
+            .unwrap()
+            .shapes
+            .iter()
+            .map(|(shape, history)| {
+                json!({
+                    "shape": shape.to_string(),
+                    "plans": history.plans,
+                    "fully_federated": history.fully_federated,
+                    "last_coverage": history.last_coverage,
+                })
+            })
+            .collect::<Vec<_>>();
+        Value::Array(shapes).to_string()
+    }
+
+    // Loads shape histories saved with shapes_json, merged into the current ones.
+    pub fn load_shapes(&self, json: &str) -> Result<()> {
+        let shapes: Value =
+            serde_json::from_str(json).map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let invalid = || DataFusionError::Execution("invalid coverage shapes".to_string());
+        let mut state = self.state.lock().unwrap();
+        for shape in shapes.as_array().ok_or_else(invalid)? {
+            let id = shape["shape"]
+                .as_str()
+                .and_then(|s| s.parse::<u64>().ok())
+                .ok_or_else(invalid)?;
+            let history = state.shapes.entry(id).or_default();
+            history.plans += shape["plans"].as_u64().unwrap_or_default() as usize;
+            history.fully_federated |= shape["fully_federated"].as_bool().unwrap_or_default();
+            history.last_coverage = shape["last_coverage"].as_f64().unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = CoverageState::default();
+    }
+
+    fn push(&self, shape: u64, plan: &LogicalPlan, summary: FederationSummary) {
+        let mut state = self.state.lock().unwrap();
+        state.total.merge(&summary);
+        state.plans += 1;
+
+        let history = state.shapes.entry(shape).or_default();
+        let regressed = history.fully_federated && summary.fallbacks > 0;
+        history.plans += 1;
+        history.fully_federated |= summary.federated > 0 && summary.fallbacks == 0;
+        history.last_coverage = match summary.federated + summary.fallbacks {
+            0 => 0.0,
+            nodes => summary.federated as f64 / nodes as f64,
+        };
+        if regressed {
+            let reasons = summary
+                .reasons
+                .iter()
+                .map(|(reason, count)| format!("{reason}={count}"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            warn!("federation coverage_regression shape={shape:016x} reasons=\"{reasons}\"");
+            if let Some(observer) = &state.observer {
+                observer.regression(&CoverageRegression {
+                    shape,
+                    plan: format!("{}", plan.display_indent()),
+                    summary: summary.clone(),
+                });
+            }
+        }
+        state.last = summary;
+    }
+}
+
+// Fingerprints the plan up to its literals, numbers and quoted strings are
+// ignored, see fingerprint.
+pub fn query_shape(plan: &LogicalPlan) -> u64 {
+    // String literals are displayed as Utf8("...")
+    fingerprint(&format!("{}", plan.display_indent()), b"'\"").0
+}
+
+thread_local! {
+    static FALLBACKS: RefCell<Option<BTreeMap<String, usize>>> = RefCell::new(None);
+}
+
+// Records that a plan node is executed locally for the given reason. Called by
+// the FederationAnalyzerRule and the provider analyzers, counted in the summary
+// of the plan being analyzed.
+pub fn record_fallback(reason: &str) {
+    FALLBACKS.with(|fallbacks| {
+        if let Some(fallbacks) = fallbacks.borrow_mut().as_mut() {
+            *fallbacks.entry(reason.to_string()).or_default() += 1;
+        }
+    });
+}
+
+// Runs the analysis, collecting the fallbacks recorded during it.
+pub(crate) fn collect_fallbacks<T>(
+    analyze: impl FnOnce() -> Result<T>,
+) -> (Result<T>, BTreeMap<String, usize>) {
+    let outer = FALLBACKS.with(|f| f.borrow_mut().replace(BTreeMap::new()));
+    let result = analyze();
+    let fallbacks = FALLBACKS.with(|f| std::mem::replace(&mut *f.borrow_mut(), outer));
+    (result, fallbacks.unwrap_or_default())
+}
+
+pub(crate) fn record_summary(
+    coverage: &FederationCoverage,
+    shape: u64,
+    original: &LogicalPlan,
+    plan: &LogicalPlan,
+    reasons: BTreeMap<String, usize>,
+) -> Result<()> {
+    let mut federated = 0;
+    plan.apply(&mut |plan| {
+        if let LogicalPlan::Extension(Extension { node }) = plan {
+            if node.as_any().downcast_ref::<FederatedPlanNode>().is_some() {
+                federated += 1;
+                return Ok(TreeNodeRecursion::Skip);
+            }
+        }
+        Ok(TreeNodeRecursion::Continue)
+    })?;
+    let summary = FederationSummary {
+        federated,
+        fallbacks: reasons.values().sum(),
+        reasons,
+    };
+    coverage.push(shape, original, summary);
+    Ok(())
+}
@@ -0,0 +1,663 @@
+use core::fmt;
+use std::{any::Any, collections::HashSet, sync::Arc};
+
+use datafusion::{
+    arrow::{array::Array, datatypes::SchemaRef, record_batch::RecordBatch},
+    common::{plan_err, tree_node::TreeNode, Column, DFSchemaRef, ScalarValue},
+    error::Result,
+    execution::{context::SessionState, TaskContext},
+    logical_expr::{
+        lit, utils::split_conjunction, BinaryExpr, Expr, Join, JoinType, LogicalPlan,
+        LogicalPlanBuilder, Operator, UserDefinedLogicalNodeCore,
+    },
+    physical_expr::PhysicalSortExpr,
+    physical_plan::{
+        coalesce_partitions::CoalescePartitionsExec,
+        collect,
+        expressions::Column as PhysicalColumn,
+        joins::{HashJoinExec, PartitionMode},
+        memory::MemoryExec,
+        projection::ProjectionExec,
+        stream::RecordBatchStreamAdapter,
+        DisplayAs, DisplayFormatType, ExecutionPlan, Partitioning, PhysicalExpr,
+        SendableRecordBatchStream,
+    },
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use log::debug;
+
+use crate::compat::TreeNodeRecursion;
+
+// JoinStrategy is how a cross-source join is executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JoinStrategy {
+    // Both sides are fetched and joined locally, as without a hint
+    Auto,
+    // Both sides are fetched and hash joined locally, the driving side is
+    // the build side
+    LocalHash,
+    // The driving side is fetched first, its distinct join keys are sent to
+    // the other side as an IN list
+    InList,
+}
+
+impl JoinStrategy {
+    pub fn parse(strategy: &str) -> Result<Self> {
+        match strategy.to_ascii_lowercase().as_str() {
+            "" | "auto" => Ok(JoinStrategy::Auto),
+            "local_hash" => Ok(JoinStrategy::LocalHash),
+            "in_list" => Ok(JoinStrategy::InList),
+            other => plan_err!(
+                "invalid federation.join_strategy {other}, expected auto, local_hash or in_list"
+            ),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            JoinStrategy::Auto => "auto",
+            JoinStrategy::LocalHash => "local_hash",
+            JoinStrategy::InList => "in_list",
+        }
+    }
+}
+
+// JoinHint pins the driving side and strategy of cross-source inner joins,
+// instead of leaving them to the planner. Set on the session with
+// `SET federation.join_driver = 'orders'` and
+// `SET federation.join_strategy = 'in_list'`, or FederationConfig::with_join_hint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JoinHint {
+    // The table whose side of the join drives it, the left side if None
+    pub driver: Option<String>,
+    pub strategy: JoinStrategy,
+}
+
+impl JoinHint {
+    // Reads a `/*+ FEDERATION_JOIN(driver [, strategy]) */` comment of the
+    // query. DataFusion's parser drops comments, so the hint is read from the
+    // query text and set on the session before running it.
+    pub fn from_sql(sql: &str) -> Result<Option<Self>> {
+        let lower = sql.to_ascii_lowercase();
+        let mut rest = lower.as_str();
+        while let Some(start) = rest.find("/*+") {
+            let body = &rest[start + 3..];
+            let body = &body[..body.find("*/").unwrap_or(body.len())];
+            if let Some(args) = body.trim().strip_prefix("federation_join") {
+                let Some(args) = args
+                    .trim()
+                    .strip_prefix('(')
+                    .and_then(|args| args.split_once(')'))
+                    .map(|(args, _)| args)
+                else {
+                    return plan_err!("invalid join hint {}", body.trim());
+                };
+                // The driver keeps the case it's written in
+                let offset = args.as_ptr() as usize - lower.as_ptr() as usize;
+                let args = sql[offset..offset + args.len()]
+                    .split(',')
+                    .map(|arg| arg.trim().trim_matches(|c| c == '\'' || c == '"'))
+                    .collect::<Vec<_>>();
+                let driver = args.first().filter(|d| !d.is_empty() && **d != "*");
+                let strategy = JoinStrategy::parse(args.get(1).copied().unwrap_or(""))?;
+                return Ok(Some(Self {
+                    driver: driver.map(|d| d.to_string()),
+                    strategy,
+                }));
+            }
+            rest = &rest[start + 3..];
+        }
+        Ok(None)
+    }
+}
+
+// Applies the hint to a cross-source join, given its unfederated and its
+// federated inputs. Returns None if the hint doesn't apply to the join.
+pub(crate) fn apply_join_hint(
+    plan: &LogicalPlan,
+    inputs: &[&LogicalPlan],
+    federated: &[LogicalPlan],
+    hint: &JoinHint,
+    in_list_limit: usize,
+) -> Result<Option<LogicalPlan>> {
+    let LogicalPlan::Join(join) = plan else {
+        return Ok(None);
+    };
+    if join.join_type != JoinType::Inner || inputs.len() != 2 || federated.len() != 2 {
+        return Ok(None);
+    }
+    let driving = match &hint.driver {
+        Some(driver) => match inputs.iter().position(|input| reads_table(input, driver)) {
+            Some(driving) => driving,
+            None => return Ok(None),
+        },
+        None => 0,
+    };
+    match hint.strategy {
+        JoinStrategy::Auto | JoinStrategy::LocalHash => {
+            if driving == 0 {
+                return Ok(None);
+            }
+            // The left side is the build side of local hash joins
+            debug!(
+                "federation decision=join_hint strategy=local_hash driver=right node=\"{}\"",
+                plan.display()
+            );
+            let on = join
+                .on
+                .iter()
+                .map(|(l, r)| l.clone().eq(r.clone()))
+                .chain(join.filter.clone());
+            let columns = join
+                .schema
+                .fields()
+                .iter()
+                .map(|f| Expr::Column(f.qualified_column()));
+            let swapped = LogicalPlanBuilder::from(federated[1].clone())
+                .join_on(federated[0].clone(), JoinType::Inner, on)?
+                .project(columns)?
+                .build()?;
+            Ok(Some(swapped))
+        }
+        JoinStrategy::InList => {
+            let Some(keys) = equi_keys(join) else {
+                debug!(
+                    "federation decision=join_hint strategy=local_hash reason=no_equi_keys node=\"{}\"",
+                    plan.display()
+                );
+                return Ok(None);
+            };
+            let probe = 1 - driving;
+            let keys = keys
+                .into_iter()
+                .map(|(l, r)| {
+                    let (d, p) = if driving == 0 { (l, r) } else { (r, l) };
+                    Ok((
+                        inputs[driving].schema().index_of_column(&d)?,
+                        d,
+                        inputs[probe].schema().index_of_column(&p)?,
+                        p,
+                    ))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            debug!(
+                "federation decision=join_hint strategy=in_list driver={} node=\"{}\"",
+                if driving == 0 { "left" } else { "right" },
+                plan.display()
+            );
+            let node = InListJoinNode {
+                driving: federated[driving].clone(),
+                probe: inputs[probe].clone(),
+                keys,
+                driving_left: driving == 0,
+                in_list_limit,
+                schema: join.schema.clone(),
+            };
+            Ok(Some(LogicalPlan::Extension(
+                datafusion::logical_expr::Extension {
+                    node: Arc::new(node),
+                },
+            )))
+        }
+    }
+}
+
+fn reads_table(plan: &LogicalPlan, table: &str) -> bool {
+    let mut found = false;
+    let _ = plan.apply(&mut |p| {
+        if let LogicalPlan::TableScan(scan) = p {
+            found = scan.table_name.to_string().eq_ignore_ascii_case(table)
+                || scan.table_name.table().eq_ignore_ascii_case(table);
+        }
+        Ok(if found {
+            TreeNodeRecursion::Stop
+        } else {
+            TreeNodeRecursion::Continue
+        })
+    });
+    found
+}
+
+// Returns the (left, right) column pairs the join is on, None if it has
+// other conditions.
+fn equi_keys(join: &Join) -> Option<Vec<(Column, Column)>> {
+    let mut conditions: Vec<(&Expr, &Expr)> = join.on.iter().map(|(l, r)| (l, r)).collect();
+    for predicate in join.filter.iter().flat_map(split_conjunction) {
+        let Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: Operator::Eq,
+            right,
+        }) = predicate
+        else {
+            return None;
+        };
+        conditions.push((left.as_ref(), right.as_ref()));
+    }
+    let keys = conditions
+        .into_iter()
+        .map(|condition| {
+            let (Expr::Column(l), Expr::Column(r)) = condition else {
+                return None;
+            };
+            if join.left.schema().has_column(l) && join.right.schema().has_column(r) {
+                Some((l.clone(), r.clone()))
+            } else if join.left.schema().has_column(r) && join.right.schema().has_column(l) {
+                Some((r.clone(), l.clone()))
+            } else {
+                None
+            }
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (!keys.is_empty()).then_some(keys)
+}
+
+// InListJoinNode is an inner join whose probe side is read filtered by the
+// keys of the driving side. The probe side is federated once the keys are
+// known, it isn't an input of the node.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InListJoinNode {
+    driving: LogicalPlan,
+    probe: LogicalPlan,
+    // The driving and probe key columns, with their index in their side
+    keys: Vec<(usize, Column, usize, Column)>,
+    driving_left: bool,
+    in_list_limit: usize,
+    schema: DFSchemaRef,
+}
+
+impl UserDefinedLogicalNodeCore for InListJoinNode {
+    fn name(&self) -> &str {
+        "InListJoin"
+    }
+
+    fn inputs(&self) -> Vec<&LogicalPlan> {
+        vec![&self.driving]
+    }
+
+    fn schema(&self) -> &DFSchemaRef {
+        &self.schema
+    }
+
+    fn expressions(&self) -> Vec<Expr> {
+        vec![]
+    }
+
+    fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let keys = self
+            .keys
+            .iter()
+            .map(|(_, d, _, p)| format!("{d} = {p}"))
+            .collect::<Vec<_>>();
+        write!(f, "InListJoin: keys=[{}]", keys.join(", "))
+    }
+
+    fn from_template(&self, _exprs: &[Expr], inputs: &[LogicalPlan]) -> Self {
+        assert_eq!(inputs.len(), 1, "input size inconsistent");
+        Self {
+            driving: inputs[0].clone(),
+            ..self.clone()
+        }
+    }
+}
+
+// Plans the node, its driving side is already planned.
+pub(crate) fn plan_in_list_join(
+    node: &InListJoinNode,
+    driving: Arc<dyn ExecutionPlan>,
+    session_state: &SessionState,
+) -> Arc<dyn ExecutionPlan> {
+    Arc::new(InListJoinExec {
+        driving,
+        probe: node.probe.clone(),
+        keys: node.keys.clone(),
+        driving_left: node.driving_left,
+        in_list_limit: node.in_list_limit,
+        schema: Arc::new(node.schema.as_ref().into()),
+        state: session_state.clone(),
+    })
+}
+
+#[derive(Clone)]
+struct InListJoinExec {
+    driving: Arc<dyn ExecutionPlan>,
+    probe: LogicalPlan,
+    keys: Vec<(usize, Column, usize, Column)>,
+    driving_left: bool,
+    in_list_limit: usize,
+    schema: SchemaRef,
+    state: SessionState,
+}
+
+impl InListJoinExec {
+    // Returns the filter of the probe side, None if there are more
+    // distinct keys than the limit.
+    fn probe_filter(&self, batches: &[RecordBatch]) -> Result<Option<Expr>> {
+        let mut filters = vec![];
+        for (driving, _, _, probe) in &self.keys {
+            let mut values = HashSet::new();
+            for batch in batches {
+                let column = batch.column(*driving);
+                for row in 0..batch.num_rows() {
+                    // NULL keys never match
+                    if column.is_valid(row) {
+                        values.insert(ScalarValue::try_from_array(column, row)?);
+                    }
+                }
+                if values.len() > self.in_list_limit {
+                    return Ok(None);
+                }
+            }
+            if values.is_empty() {
+                return Ok(Some(lit(false)));
+            }
+            // Sorted, so the same keys give the same remote query
+            let mut values = values.into_iter().collect::<Vec<_>>();
+            values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            let list = values.into_iter().map(lit).collect();
+            filters.push(Expr::Column(probe.clone()).in_list(list, false));
+        }
+        Ok(filters.into_iter().reduce(Expr::and))
+    }
+
+    async fn join(self, context: Arc<TaskContext>) -> Result<SendableRecordBatchStream> {
+        let batches = collect(self.driving.clone(), context.clone()).await?;
+        let probe = match self.probe_filter(&batches)? {
+            Some(filter) => {
+                debug!("federation decision=in_list_join keys={}", self.keys.len());
+                LogicalPlanBuilder::from(self.probe.clone())
+                    .filter(filter)?
+                    .build()?
+            }
+            None => {
+                debug!(
+                    "federation decision=in_list_join reason=limit limit={}",
+                    self.in_list_limit
+                );
+                self.probe.clone()
+            }
+        };
+        let probe = self.state.create_physical_plan(&probe).await?;
+
+        let driving_schema = self.driving.schema();
+        let build = Arc::new(MemoryExec::try_new(
+            &[batches],
+            driving_schema.clone(),
+            None,
+        )?);
+        let on = self
+            .keys
+            .iter()
+            .map(|(d, _, p, _)| {
+                (
+                    PhysicalColumn::new(driving_schema.field(*d).name(), *d),
+                    PhysicalColumn::new(probe.schema().field(*p).name(), *p),
+                )
+            })
+            .collect();
+        let join = HashJoinExec::try_new(
+            build,
+            probe,
+            on,
+            None,
+            &JoinType::Inner,
+            PartitionMode::CollectLeft,
+            false,
+        )?;
+        let mut plan: Arc<dyn ExecutionPlan> =
+            Arc::new(CoalescePartitionsExec::new(Arc::new(join)));
+        if !self.driving_left {
+            // The driving side is output first, the join had it on the right
+            let schema = plan.schema();
+            let width = driving_schema.fields().len();
+            let order = (width..schema.fields().len()).chain(0..width);
+            let exprs = order
+                .map(|i| {
+                    let name = schema.field(i).name().clone();
+                    let expr: Arc<dyn PhysicalExpr> = Arc::new(PhysicalColumn::new(&name, i));
+                    (expr, name)
+                })
+                .collect();
+            plan = Arc::new(ProjectionExec::try_new(exprs, plan)?);
+        }
+        let schema = self.schema.clone();
+        let stream = plan.execute(0, context)?.map(move |batch| {
+            Ok(RecordBatch::try_new(
+                schema.clone(),
+                batch?.columns().to_vec(),
+            )?)
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema.clone(),
+            stream,
+        )))
+    }
+}
+
+impl fmt::Debug for InListJoinExec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "InListJoinExec")
+    }
+}
+
+impl DisplayAs for InListJoinExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        let keys = self
+            .keys
+            .iter()
+            .map(|(_, d, _, p)| format!("{d} = {p}"))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "InListJoinExec: keys=[{}] limit={}",
+            keys.join(", "),
+            self.in_list_limit
+        )
+    }
+}
+
+impl ExecutionPlan for InListJoinExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::UnknownPartitioning(1)
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        None
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.driving.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self {
+            driving: children[0].clone(),
+            ..self.as_ref().clone()
+        }))
+    }
+
+    fn execute(
+        &self,
+        _partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let join = self.clone().join(context);
+        let stream = stream::once(join).try_flatten();
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema.clone(),
+            stream,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::{AsArray, Int64Array, StringArray},
+            compute::concat_batches,
+            datatypes::{DataType, Field, Int64Type, Schema},
+        },
+        datasource::{provider_as_source, MemTable},
+        execution::context::SessionContext,
+        logical_expr::col,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_from_sql() {
+        let hint = JoinHint::from_sql(
+            "SELECT /*+ INDEX(o) */ * FROM orders o /*+ FEDERATION_JOIN(Customers, IN_LIST) */",
+        )
+        .unwrap();
+        assert_eq!(
+            hint,
+            Some(JoinHint {
+                driver: Some("Customers".to_string()),
+                strategy: JoinStrategy::InList,
+            })
+        );
+        let hint = JoinHint::from_sql("SELECT /*+ federation_join(*) */ 1").unwrap();
+        assert_eq!(
+            hint,
+            Some(JoinHint {
+                driver: None,
+                strategy: JoinStrategy::Auto,
+            })
+        );
+        let hint = JoinHint::from_sql("SELECT /*+ FEDERATION_JOIN('orders', local_hash) */ 1");
+        assert_eq!(hint.unwrap().unwrap().driver, Some("orders".to_string()));
+
+        assert_eq!(
+            JoinHint::from_sql("SELECT /* FEDERATION_JOIN(orders) */ 1").unwrap(),
+            None
+        );
+        assert!(JoinHint::from_sql("SELECT /*+ FEDERATION_JOIN orders */ 1").is_err());
+        assert!(JoinHint::from_sql("SELECT /*+ FEDERATION_JOIN(orders, merge) */ 1").is_err());
+    }
+
+    fn customers() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![Some(3), Some(1), None, Some(3)])),
+                Arc::new(StringArray::from(vec!["c", "a", "n", "c"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    // Joins the orders to the customers driving the join, on the left or
+    // on the right of the output.
+    fn in_list_join(driving_left: bool, in_list_limit: usize) -> InListJoinExec {
+        let orders_schema = Arc::new(Schema::new(vec![
+            Field::new("customer_id", DataType::Int64, true),
+            Field::new("amount", DataType::Int64, true),
+        ]));
+        let orders = RecordBatch::try_new(
+            orders_schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 1, 2])),
+                Arc::new(Int64Array::from(vec![10, 20, 30])),
+            ],
+        )
+        .unwrap();
+        let orders = MemTable::try_new(orders_schema.clone(), vec![vec![orders]]).unwrap();
+        let probe = LogicalPlanBuilder::scan("orders", provider_as_source(Arc::new(orders)), None)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let customers = customers();
+        let driving =
+            MemoryExec::try_new(&[vec![customers.clone()]], customers.schema(), None).unwrap();
+        let fields = match driving_left {
+            true => [
+                customers.schema().fields().clone(),
+                orders_schema.fields().clone(),
+            ],
+            false => [
+                orders_schema.fields().clone(),
+                customers.schema().fields().clone(),
+            ],
+        };
+        let schema = Schema::new(
+            fields
+                .iter()
+                .flat_map(|f| f.iter().cloned())
+                .collect::<Vec<_>>(),
+        );
+        InListJoinExec {
+            driving: Arc::new(driving),
+            probe,
+            keys: vec![(
+                0,
+                Column::from_qualified_name("customers.id"),
+                0,
+                Column::from_qualified_name("orders.customer_id"),
+            )],
+            driving_left,
+            in_list_limit,
+            schema: Arc::new(schema),
+            state: SessionContext::new().state(),
+        }
+    }
+
+    #[test]
+    fn test_probe_filter() {
+        let batches = [customers()];
+        let filter = in_list_join(true, 10).probe_filter(&batches).unwrap();
+        // Distinct and sorted, without NULL
+        assert_eq!(
+            filter,
+            Some(col("orders.customer_id").in_list(vec![lit(1i64), lit(3i64)], false))
+        );
+
+        let filter = in_list_join(true, 1).probe_filter(&batches).unwrap();
+        assert_eq!(filter, None);
+
+        let nulls = customers().slice(2, 1);
+        let filter = in_list_join(true, 10).probe_filter(&[nulls]).unwrap();
+        assert_eq!(filter, Some(lit(false)));
+    }
+
+    #[tokio::test]
+    async fn test_output_order_driving_right() {
+        let join = Arc::new(in_list_join(false, 10));
+        let batches = collect(join.clone(), SessionContext::new().task_ctx())
+            .await
+            .unwrap();
+        let batch = concat_batches(&join.schema(), &batches).unwrap();
+
+        // The orders come first, as in the join the hint applies to
+        assert_eq!(batch.num_rows(), 2);
+        let customer_ids = batch.column(0).as_primitive::<Int64Type>();
+        assert_eq!(customer_ids.values().to_vec(), vec![1, 1]);
+        let mut amounts = batch
+            .column(1)
+            .as_primitive::<Int64Type>()
+            .values()
+            .to_vec();
+        amounts.sort();
+        assert_eq!(amounts, vec![10, 20]);
+        let ids = batch.column(2).as_primitive::<Int64Type>();
+        assert_eq!(ids.values().to_vec(), vec![1, 1]);
+        let names = batch.column(3).as_string::<i32>();
+        assert_eq!(names.iter().collect::<Vec<_>>(), vec![Some("a"), Some("a")]);
+    }
+}
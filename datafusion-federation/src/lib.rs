@@ -6,6 +6,8 @@ use std::{
 
 use datafusion::optimizer::analyzer::Analyzer;
 
+mod compat;
+
 mod analyzer;
 pub use analyzer::*;
 mod table_provider;
@@ -14,6 +16,39 @@ pub use table_provider::*;
 mod plan_node;
 pub use plan_node::*;
 
+mod query_handle;
+pub use query_handle::*;
+
+mod plan_export;
+pub use plan_export::*;
+
+mod subplans;
+pub use subplans::*;
+
+mod lineage;
+pub use lineage::*;
+
+mod coverage;
+pub use coverage::*;
+
+mod union;
+pub use union::*;
+
+mod config;
+pub use config::*;
+
+mod coercion;
+pub use coercion::join_key_type;
+
+mod join_hint;
+pub use join_hint::{InListJoinNode, JoinHint, JoinStrategy};
+
+mod plan_hash;
+pub use plan_hash::{plan_hash, PlanHash};
+
+mod manifest;
+pub use manifest::source_manifest;
+
 pub type FederationProviderRef = Arc<dyn FederationProvider>;
 pub trait FederationProvider: Send + Sync {
     // Returns the name of the provider, used for comparison.
@@ -26,6 +61,25 @@ pub trait FederationProvider: Send + Sync {
     // Returns an analyzer that can cut out part of the plan
     // to federate it.
     fn analyzer(&self) -> Option<Arc<Analyzer>>;
+
+    // Returns true if federated sub-plans with an ORDER BY are fetched
+    // sorted and report their ordering. Allows cross-source joins to
+    // be executed as merge joins.
+    fn supports_ordering_pushdown(&self) -> bool {
+        false
+    }
+
+    // Returns true if the source orders strings by their bytes, as Arrow
+    // does. Merge joins on keys other than numbers or times require it.
+    fn binary_collation(&self) -> bool {
+        false
+    }
+
+    // Describes the source's capabilities and pushdown settings, as listed
+    // by source_manifest.
+    fn describe(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
 }
 
 impl fmt::Display for dyn FederationProvider {
@@ -0,0 +1,171 @@
+use datafusion::{
+    common::{Column, OwnedTableReference},
+    error::Result,
+    logical_expr::{Expr, Extension, LogicalPlan, TableScan},
+};
+
+use crate::FederatedPlanNode;
+
+// LineageRecord describes the remote columns feeding an output column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineageRecord {
+    pub column: String,
+    pub sources: Vec<LineageSource>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineageSource {
+    // The compute context of the federated source, None for local tables
+    pub compute_context: Option<String>,
+    pub table: OwnedTableReference,
+    pub column: String,
+}
+
+// LineageSink receives the lineage of every analyzed query,
+// e.g. to forward it to a governance tool.
+pub trait LineageSink: Send + Sync {
+    fn emit(&self, records: &[LineageRecord]);
+}
+
+impl<F> LineageSink for F
+where
+    F: Fn(&[LineageRecord]) + Send + Sync,
+{
+    fn emit(&self, records: &[LineageRecord]) {
+        self(records)
+    }
+}
+
+// Returns the lineage of every output column of an analyzed plan.
+pub fn federated_lineage(plan: &LogicalPlan) -> Result<Vec<LineageRecord>> {
+    (0..plan.schema().fields().len())
+        .map(|i| {
+            let mut sources = vec![];
+            field_sources(plan, i, &None, &mut sources)?;
+            Ok(LineageRecord {
+                column: plan.schema().field(i).name().clone(),
+                sources,
+            })
+        })
+        .collect()
+}
+
+fn field_sources(
+    plan: &LogicalPlan,
+    index: usize,
+    context: &Option<String>,
+    sources: &mut Vec<LineageSource>,
+) -> Result<()> {
+    match plan {
+        LogicalPlan::Extension(Extension { node }) => {
+            if let Some(fed_node) = node.as_any().downcast_ref::<FederatedPlanNode>() {
+                let context = fed_node.planner().compute_context();
+                return field_sources(fed_node.plan(), index, &context, sources);
+            }
+            inputs_by_name(plan, index, context, sources)
+        }
+        LogicalPlan::TableScan(TableScan { table_name, .. }) => {
+            let source = LineageSource {
+                compute_context: context.clone(),
+                table: table_name.clone(),
+                column: plan.schema().field(index).name().clone(),
+            };
+            if !sources.contains(&source) {
+                sources.push(source);
+            }
+            Ok(())
+        }
+        LogicalPlan::Projection(projection) => {
+            expr_sources(&projection.expr[index], &projection.input, context, sources)
+        }
+        LogicalPlan::Aggregate(aggregate) => {
+            let group_len = aggregate.group_expr.len();
+            let expr = if index < group_len {
+                &aggregate.group_expr[index]
+            } else {
+                &aggregate.aggr_expr[index - group_len]
+            };
+            expr_sources(expr, &aggregate.input, context, sources)
+        }
+        LogicalPlan::Window(window) => {
+            let input_len = window.input.schema().fields().len();
+            if index < input_len {
+                field_sources(&window.input, index, context, sources)
+            } else {
+                expr_sources(
+                    &window.window_expr[index - input_len],
+                    &window.input,
+                    context,
+                    sources,
+                )
+            }
+        }
+        LogicalPlan::Join(_) | LogicalPlan::CrossJoin(_) => {
+            let inputs = plan.inputs();
+            let left_len = inputs[0].schema().fields().len();
+            if index < left_len {
+                field_sources(inputs[0], index, context, sources)
+            } else {
+                field_sources(inputs[1], index - left_len, context, sources)
+            }
+        }
+        LogicalPlan::Union(union) => {
+            for input in &union.inputs {
+                field_sources(input, index, context, sources)?;
+            }
+            Ok(())
+        }
+        _ => {
+            // Nodes passing their input through unchanged, e.g. filters and sorts
+            let inputs = plan.inputs();
+            if inputs.len() == 1
+                && inputs[0].schema().fields().len() == plan.schema().fields().len()
+            {
+                return field_sources(inputs[0], index, context, sources);
+            }
+            inputs_by_name(plan, index, context, sources)
+        }
+    }
+}
+
+fn expr_sources(
+    expr: &Expr,
+    input: &LogicalPlan,
+    context: &Option<String>,
+    sources: &mut Vec<LineageSource>,
+) -> Result<()> {
+    let mut columns = expr.to_columns()?.into_iter().collect::<Vec<_>>();
+    columns.sort_by_key(|c| c.flat_name());
+    for column in columns {
+        column_sources(input, &column, context, sources)?;
+    }
+    Ok(())
+}
+
+fn column_sources(
+    plan: &LogicalPlan,
+    column: &Column,
+    context: &Option<String>,
+    sources: &mut Vec<LineageSource>,
+) -> Result<()> {
+    match plan.schema().index_of_column(column) {
+        Ok(index) => field_sources(plan, index, context, sources),
+        // e.g. outer references of subqueries
+        Err(_) => Ok(()),
+    }
+}
+
+fn inputs_by_name(
+    plan: &LogicalPlan,
+    index: usize,
+    context: &Option<String>,
+    sources: &mut Vec<LineageSource>,
+) -> Result<()> {
+    let name = plan.schema().field(index).name();
+    for input in plan.inputs() {
+        if let Ok(Some(index)) = input.schema().index_of_column_by_name(None, name) {
+            field_sources(input, index, context, sources)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::collections::BTreeMap;
+
+use datafusion::{error::Result, execution::context::SessionState};
+use serde_json::{json, Value};
+
+use crate::FederatedTableProviderAdaptor;
+
+// Exports the federated sources registered in the session as JSON, e.g. for
+// support bundles or to diff the sources of two environments. Each source,
+// by name and compute context, lists its capabilities and pushdown settings
+// and the tables read from it with their schemas. Sources and tables are
+// sorted, so manifests of the same registrations are equal.
+pub async fn source_manifest(state: &SessionState) -> Result<Value> {
+    let mut sources: BTreeMap<(String, String), (Value, Vec<Value>)> = BTreeMap::new();
+    let catalogs = state.catalog_list();
+    for catalog_name in catalogs.catalog_names() {
+        let Some(catalog) = catalogs.catalog(&catalog_name) else {
+            continue;
+        };
+        for schema_name in catalog.schema_names() {
+            let Some(schema) = catalog.schema(&schema_name) else {
+                continue;
+            };
+            for table_name in schema.table_names() {
+                let Some(table) = schema.table(&table_name).await else {
+                    continue;
+                };
+                // Local tables aren't part of the manifest
+                let Some(adaptor) = table
+                    .as_any()
+                    .downcast_ref::<FederatedTableProviderAdaptor>()
+                else {
+                    continue;
+                };
+                let provider = adaptor.source.federation_provider();
+                let key = (
+                    provider.name().to_string(),
+                    provider.compute_context().unwrap_or_default(),
+                );
+                let columns = adaptor
+                    .source
+                    .schema()
+                    .fields()
+                    .iter()
+                    .map(|f| {
+                        json!({
+                            "name": f.name(),
+                            "type": f.data_type().to_string(),
+                            "nullable": f.is_nullable(),
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let (_, tables) = sources
+                    .entry(key)
+                    .or_insert_with(|| (provider.describe(), vec![]));
+                tables.push(json!({
+                    "name": format!("{catalog_name}.{schema_name}.{table_name}"),
+                    "columns": columns,
+                    "source": adaptor.source.describe(),
+                }));
+            }
+        }
+    }
+
+    let sources = sources
+        .into_iter()
+        .map(|((name, compute_context), (description, mut tables))| {
+            tables.sort_by(|a, b| a["name"].as_str().cmp(&b["name"].as_str()));
+            json!({
+                "provider": name,
+                "compute_context": compute_context,
+                "description": description,
+                "tables": tables,
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(json!({ "sources": sources }))
+}
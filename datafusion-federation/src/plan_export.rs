@@ -0,0 +1,119 @@
+use datafusion::{
+    error::Result,
+    logical_expr::{Extension, LogicalPlan},
+};
+use serde_json::{json, Value};
+
+use crate::FederatedPlanNode;
+
+// ExportNode is a plan node as exported, federated sub-trees
+// are collapsed into a single node.
+struct ExportNode {
+    label: String,
+    federated: Option<FederatedExport>,
+    children: Vec<ExportNode>,
+}
+
+struct FederatedExport {
+    compute_context: Option<String>,
+    query: Option<String>,
+    plan: String,
+}
+
+fn export_node(plan: &LogicalPlan) -> Result<ExportNode> {
+    if let LogicalPlan::Extension(Extension { node }) = plan {
+        if let Some(fed_node) = node.as_any().downcast_ref::<FederatedPlanNode>() {
+            let planner = fed_node.planner();
+            return Ok(ExportNode {
+                label: "Federated".to_string(),
+                federated: Some(FederatedExport {
+                    compute_context: planner.compute_context(),
+                    query: planner.federated_query(fed_node)?,
+                    plan: format!("{}", fed_node.plan().display_indent()),
+                }),
+                children: vec![],
+            });
+        }
+    }
+
+    Ok(ExportNode {
+        label: format!("{}", plan.display()),
+        federated: None,
+        children: plan
+            .inputs()
+            .into_iter()
+            .map(export_node)
+            .collect::<Result<Vec<_>>>()?,
+    })
+}
+
+// Exports an (analyzed) plan as Graphviz DOT, federated sub-trees are
+// rendered as a single node annotated with their target & generated query.
+pub fn federated_plan_to_dot(plan: &LogicalPlan) -> Result<String> {
+    let root = export_node(plan)?;
+    let mut lines = vec![
+        "digraph federated_plan {".to_string(),
+        "  node [shape=box];".to_string(),
+    ];
+    let mut next_id = 0;
+    write_dot(&root, &mut next_id, &mut lines);
+    lines.push("}".to_string());
+    Ok(lines.join("\n"))
+}
+
+fn write_dot(node: &ExportNode, next_id: &mut usize, lines: &mut Vec<String>) -> usize {
+    let id = *next_id;
+    *next_id += 1;
+
+    match &node.federated {
+        Some(fed) => {
+            let mut label = node.label.clone();
+            if let Some(context) = &fed.compute_context {
+                label.push_str(&format!("\nsource: {context}"));
+            }
+            if let Some(query) = &fed.query {
+                label.push_str(&format!("\n{query}"));
+            }
+            lines.push(format!(
+                "  n{id} [label=\"{}\", style=filled, fillcolor=lightblue];",
+                escape_dot(&label)
+            ));
+        }
+        None => lines.push(format!("  n{id} [label=\"{}\"];", escape_dot(&node.label))),
+    }
+
+    for child in &node.children {
+        let child_id = write_dot(child, next_id, lines);
+        lines.push(format!("  n{id} -> n{child_id};"));
+    }
+    id
+}
+
+fn escape_dot(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\l")
+}
+
+// Exports an (analyzed) plan as JSON, federated sub-trees are rendered
+// as a single node annotated with their target & generated query.
+pub fn federated_plan_to_json(plan: &LogicalPlan) -> Result<String> {
+    let root = export_node(plan)?;
+    Ok(to_json(&root).to_string())
+}
+
+fn to_json(node: &ExportNode) -> Value {
+    let federated = node.federated.as_ref().map(|fed| {
+        json!({
+            "compute_context": fed.compute_context,
+            "query": fed.query,
+            "plan": fed.plan,
+        })
+    });
+    json!({
+        "label": node.label,
+        "federated": federated,
+        "children": node.children.iter().map(to_json).collect::<Vec<_>>(),
+    })
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use datafusion::physical_plan::{displayable, ExecutionPlan};
+
+// PlanHash identifies an executable plan up to its literals, e.g. as the key
+// of external plan and result caches. The literals are the parameters, in
+// plan order, so a result cache keys on both.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PlanHash {
+    pub hash: u64,
+    pub parameters: Vec<String>,
+}
+
+// Hashes the physical plan after federation. The verbose display of its
+// nodes is hashed, which for federated nodes includes their source and
+// generated SQL. Numbers and quoted strings are extracted as parameters,
+// local string literals, displayed unquoted, remain part of the hash.
+pub fn plan_hash(plan: &Arc<dyn ExecutionPlan>) -> PlanHash {
+    let text = format!("{}", displayable(plan.as_ref()).indent(true));
+    // Double quotes are the identifiers of the generated SQL
+    let (hash, parameters) = fingerprint(&text, b"'");
+    PlanHash { hash, parameters }
+}
+
+// Hashes the text up to its literals with FNV-1a, which keeps hashes stable
+// across builds, and returns the literals skipped. Literals are numbers and
+// strings within the `literal_quotes`, other quoted text is hashed whole.
+// Numbers within words and column indexes, `a@0`, aren't literals.
+pub(crate) fn fingerprint(text: &str, literal_quotes: &[u8]) -> (u64, Vec<String>) {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut add = |b: u8| {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    };
+    let mut literals = vec![];
+    let bytes = text.as_bytes();
+    let mut prev = b' ';
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'"' || c == b'\'' {
+            let end = bytes[i + 1..]
+                .iter()
+                .position(|q| *q == c)
+                .map_or(bytes.len(), |p| i + 1 + p);
+            match literal_quotes.contains(&c) {
+                true => literals.push(text[i + 1..end].to_string()),
+                false => bytes[i + 1..end].iter().for_each(|b| add(*b)),
+            }
+            add(c);
+            prev = c;
+            i = end + 1;
+            continue;
+        }
+        let in_word = prev.is_ascii_alphanumeric() || prev == b'_' || prev == b'@';
+        if c.is_ascii_digit() && !in_word {
+            let end = bytes[i..]
+                .iter()
+                .position(|d| !(d.is_ascii_digit() || *d == b'.'))
+                .map_or(bytes.len(), |p| i + p);
+            literals.push(text[i..end].to_string());
+            add(b'?');
+            prev = b'?';
+            i = end;
+            continue;
+        }
+        add(c);
+        prev = c;
+        i += 1;
+    }
+    (hash, literals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint() {
+        let (hash, literals) = fingerprint("SELECT a FROM t WHERE b = 1 AND c = 'x'", b"'");
+        assert_eq!(literals, vec!["1", "x"]);
+        let (other, literals) = fingerprint("SELECT a FROM t WHERE b = 2.5 AND c = 'y'", b"'");
+        assert_eq!(literals, vec!["2.5", "y"]);
+        assert_eq!(hash, other);
+
+        // Identifiers, numbers within words and column indexes are hashed
+        let (hash, literals) = fingerprint("SELECT \"t1\".\"a\" FROM t1 WHERE a@0 = 3", b"'");
+        assert_eq!(literals, vec!["3"]);
+        let (other, _) = fingerprint("SELECT \"t2\".\"a\" FROM t1 WHERE a@0 = 3", b"'");
+        assert_ne!(hash, other);
+        let (other, _) = fingerprint("SELECT \"t1\".\"a\" FROM t1 WHERE a@1 = 3", b"'");
+        assert_ne!(hash, other);
+    }
+}
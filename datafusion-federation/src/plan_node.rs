@@ -1,5 +1,6 @@
 use core::fmt;
 use std::{
+    collections::HashMap,
     fmt::Debug,
     hash::{Hash, Hasher},
     sync::Arc,
@@ -7,27 +8,53 @@ use std::{
 
 use async_trait::async_trait;
 use datafusion::{
-    common::DFSchemaRef,
+    common::{tree_node::TreeNode, DFSchemaRef, TableReference},
+    datasource::TableProvider,
     error::Result,
     execution::context::{QueryPlanner, SessionState},
-    logical_expr::{Expr, LogicalPlan, UserDefinedLogicalNode, UserDefinedLogicalNodeCore},
+    logical_expr::{
+        expr::Placeholder, Expr, Extension, LogicalPlan, UserDefinedLogicalNode,
+        UserDefinedLogicalNodeCore,
+    },
     physical_plan::ExecutionPlan,
     physical_planner::{DefaultPhysicalPlanner, ExtensionPlanner, PhysicalPlanner},
 };
+use log::warn;
+
+use crate::{
+    compat::{transformed, unchanged, TreeNodeRecursion},
+    join_hint::plan_in_list_join,
+    FederatedTableProviderAdaptor, InListJoinNode,
+};
 
 pub struct FederatedPlanNode {
     plan: LogicalPlan,
     planner: Arc<dyn FederationPlanner>,
+    root: bool,
 }
 
 impl FederatedPlanNode {
     pub fn new(plan: LogicalPlan, planner: Arc<dyn FederationPlanner>) -> Self {
-        Self { plan, planner }
+        Self {
+            plan,
+            planner,
+            root: false,
+        }
     }
 
     pub fn plan(&self) -> &LogicalPlan {
         &self.plan
     }
+
+    pub fn planner(&self) -> &Arc<dyn FederationPlanner> {
+        &self.planner
+    }
+
+    // Whether the node's rows are the query's result, i.e. it's the root of
+    // the plan or only has projections above it. Set when it's planned.
+    pub fn is_root(&self) -> bool {
+        self.root
+    }
 }
 
 impl Debug for FederatedPlanNode {
@@ -49,8 +76,22 @@ impl UserDefinedLogicalNodeCore for FederatedPlanNode {
         self.plan.schema()
     }
 
+    // Exposes the placeholders of the federated plan, so DataFusion can
+    // substitute parameter values (see `from_template`).
     fn expressions(&self) -> Vec<Expr> {
-        Vec::new()
+        let mut placeholders = vec![];
+        let _ = self.plan.apply(&mut |plan| {
+            for expr in plan.expressions() {
+                expr.apply(&mut |e| {
+                    if let Expr::Placeholder(_) = e {
+                        placeholders.push(e.clone());
+                    }
+                    Ok(TreeNodeRecursion::Continue)
+                })?;
+            }
+            Ok(TreeNodeRecursion::Continue)
+        });
+        placeholders
     }
 
     fn fmt_for_explain(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -59,14 +100,63 @@ impl UserDefinedLogicalNodeCore for FederatedPlanNode {
 
     fn from_template(&self, exprs: &[Expr], inputs: &[LogicalPlan]) -> Self {
         assert_eq!(inputs.len(), 0, "input size inconsistent");
-        assert_eq!(exprs.len(), 0, "expression size inconsistent");
+        let placeholders = self.expressions();
+        assert_eq!(
+            exprs.len(),
+            placeholders.len(),
+            "expression size inconsistent"
+        );
+
+        let values: HashMap<String, Expr> = placeholders
+            .into_iter()
+            .zip(exprs.iter())
+            .filter(|(placeholder, value)| placeholder != *value)
+            .filter_map(|(placeholder, value)| match placeholder {
+                Expr::Placeholder(Placeholder { id, .. }) => Some((id, value.clone())),
+                _ => None,
+            })
+            .collect();
+        // The plan keeps its placeholders if they can't be substituted, the
+        // source then rejects the query instead of the planner panicking
+        let plan = match replace_placeholders(self.plan.clone(), &values) {
+            Ok(plan) => plan,
+            Err(e) => {
+                warn!("federation decision=keep_placeholders reason=\"{e}\"");
+                self.plan.clone()
+            }
+        };
+
         Self {
-            plan: self.plan.clone(),
+            plan,
             planner: self.planner.clone(),
+            root: self.root,
         }
     }
 }
 
+fn replace_placeholders(plan: LogicalPlan, values: &HashMap<String, Expr>) -> Result<LogicalPlan> {
+    if values.is_empty() {
+        return Ok(plan);
+    }
+    plan.transform_up(&|plan| {
+        let exprs = plan
+            .expressions()
+            .into_iter()
+            .map(|expr| {
+                expr.transform_up(&|e| match &e {
+                    Expr::Placeholder(Placeholder { id, .. }) => match values.get(id) {
+                        Some(value) => Ok(transformed(value.clone())),
+                        None => Ok(unchanged(e)),
+                    },
+                    _ => Ok(unchanged(e)),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let inputs = plan.inputs().into_iter().cloned().collect::<Vec<_>>();
+        Ok(transformed(plan.with_new_exprs(exprs, &inputs)?))
+    })
+}
+
 #[derive(Default)]
 
 pub struct FederatedQueryPlanner {}
@@ -90,12 +180,72 @@ impl QueryPlanner for FederatedQueryPlanner {
             DefaultPhysicalPlanner::with_extension_planners(vec![
                 Arc::new(FederatedPlanner::new()),
             ]);
+
+        // COPY ... TO a federated table inserts into it, like INSERT INTO or
+        // DataFrame::write_table, instead of writing files. The FORMAT option
+        // DataFusion requires for names without an extension is ignored.
+        if let LogicalPlan::Copy(copy) = logical_plan {
+            if let Some(table) = federated_table(session_state, &copy.output_url).await {
+                let input = physical_planner
+                    .create_physical_plan(&copy.input, session_state)
+                    .await?;
+                return table.insert_into(session_state, input, false).await;
+            }
+        }
+
+        let marked = mark_root(logical_plan)?;
         physical_planner
-            .create_physical_plan(logical_plan, session_state)
+            .create_physical_plan(marked.as_ref().unwrap_or(logical_plan), session_state)
             .await
     }
 }
 
+// Marks the federated node whose rows are the query's result, at the root of
+// the plan or under projections only. None if there is no such node.
+fn mark_root(plan: &LogicalPlan) -> Result<Option<LogicalPlan>> {
+    match plan {
+        LogicalPlan::Projection(projection) => {
+            let Some(input) = mark_root(&projection.input)? else {
+                return Ok(None);
+            };
+            Ok(Some(plan.with_new_inputs(&[input])?))
+        }
+        LogicalPlan::Extension(Extension { node }) => {
+            let Some(federated) = node.as_any().downcast_ref::<FederatedPlanNode>() else {
+                return Ok(None);
+            };
+            let node = FederatedPlanNode {
+                plan: federated.plan.clone(),
+                planner: federated.planner.clone(),
+                root: true,
+            };
+            Ok(Some(LogicalPlan::Extension(Extension {
+                node: Arc::new(node),
+            })))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Returns the federated table registered under the name, if any.
+async fn federated_table(
+    session_state: &SessionState,
+    name: &str,
+) -> Option<Arc<dyn TableProvider>> {
+    // Paths and URLs are never table names
+    if name.contains(['/', ':']) {
+        return None;
+    }
+    let table_ref = TableReference::from(name);
+    let schema = session_state.schema_for_ref(table_ref.clone()).ok()?;
+    let table = schema.table(table_ref.table()).await?;
+    table
+        .as_any()
+        .downcast_ref::<FederatedTableProviderAdaptor>()
+        .is_some()
+        .then_some(table)
+}
+
 #[async_trait]
 pub trait FederationPlanner: Send + Sync {
     async fn plan_federation(
@@ -103,6 +253,16 @@ pub trait FederationPlanner: Send + Sync {
         node: &FederatedPlanNode,
         session_state: &SessionState,
     ) -> Result<Arc<dyn ExecutionPlan>>;
+
+    // Returns the compute context the node is federated to, used to describe plans.
+    fn compute_context(&self) -> Option<String> {
+        None
+    }
+
+    // Returns the query (e.g. SQL) the node is federated as, used to describe plans.
+    fn federated_query(&self, _node: &FederatedPlanNode) -> Result<Option<String>> {
+        Ok(None)
+    }
 }
 
 impl PartialEq<FederatedPlanNode> for FederatedPlanNode {
@@ -148,6 +308,14 @@ impl ExtensionPlanner for FederatedPlanner {
             let exec_plan = fed_planner.plan_federation(fed_node, session_state).await?;
             return Ok(Some(exec_plan));
         }
+        if let Some(join) = node.as_any().downcast_ref::<InListJoinNode>() {
+            assert_eq!(physical_inputs.len(), 1, "Inconsistent number of inputs");
+            return Ok(Some(plan_in_list_join(
+                join,
+                physical_inputs[0].clone(),
+                session_state,
+            )));
+        }
         Ok(None)
     }
 }
@@ -0,0 +1,242 @@
+use std::{collections::VecDeque, sync::Arc, time::Duration};
+
+use datafusion::{
+    arrow::{
+        datatypes::{Schema, SchemaRef},
+        record_batch::RecordBatch,
+    },
+    common::{not_impl_err, Column},
+    dataframe::DataFrame,
+    error::Result,
+    physical_plan::SendableRecordBatchStream,
+    prelude::{col, lit},
+    scalar::ScalarValue,
+};
+use futures::StreamExt;
+use tokio::time::{self, Instant};
+
+// FederatedQueryHandle executes a federated query and pages through its
+// result, for serving layers exposing the result over e.g. REST endpoints.
+pub struct FederatedQueryHandle {
+    schema: SchemaRef,
+    cursor: Cursor,
+}
+
+enum Cursor {
+    // Pages through a single execution of the query.
+    Stream {
+        stream: SendableRecordBatchStream,
+        pending: VecDeque<RecordBatch>,
+        done: bool,
+    },
+    // Re-executes the query for every page, continuing after the last
+    // key seen. The key filter, sort & limit are pushed to the remote source.
+    Keyset {
+        df: DataFrame,
+        key: Column,
+        last: Option<ScalarValue>,
+        done: bool,
+    },
+}
+
+// PartialResult holds the rows that arrived by the deadline of
+// FederatedQueryHandle::fetch_within. Unless complete, the handle continues
+// with the remaining rows on the next fetch.
+#[derive(Debug)]
+pub struct PartialResult {
+    pub batches: Vec<RecordBatch>,
+    pub complete: bool,
+}
+
+impl FederatedQueryHandle {
+    pub async fn try_new(df: DataFrame) -> Result<Self> {
+        let schema = Arc::new(Schema::from(df.schema()));
+        let stream = df.execute_stream().await?;
+        Ok(Self {
+            schema,
+            cursor: Cursor::Stream {
+                stream,
+                pending: VecDeque::new(),
+                done: false,
+            },
+        })
+    }
+
+    // Pages using keyset continuation on `key`, which must be unique and sortable.
+    pub fn try_new_with_keyset(df: DataFrame, key: &str) -> Result<Self> {
+        let key = Column::from_qualified_name(key);
+        // Validate the key exists
+        df.schema().index_of_column(&key)?;
+        Ok(Self {
+            schema: Arc::new(Schema::from(df.schema())),
+            cursor: Cursor::Keyset {
+                df,
+                key,
+                last: None,
+                done: false,
+            },
+        })
+    }
+
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    pub fn is_done(&self) -> bool {
+        match &self.cursor {
+            Cursor::Stream { pending, done, .. } => *done && pending.is_empty(),
+            Cursor::Keyset { done, .. } => *done,
+        }
+    }
+
+    // Fetches up to `n` rows, returns no batches once the result is exhausted.
+    pub async fn fetch_next(&mut self, n: usize) -> Result<Vec<RecordBatch>> {
+        match &mut self.cursor {
+            Cursor::Stream {
+                stream,
+                pending,
+                done,
+            } => {
+                let mut batches = vec![];
+                let mut remaining = n;
+                while remaining > 0 {
+                    let batch = match pending.pop_front() {
+                        Some(batch) => batch,
+                        None if *done => break,
+                        None => match stream.next().await {
+                            Some(batch) => batch?,
+                            None => {
+                                *done = true;
+                                break;
+                            }
+                        },
+                    };
+                    if batch.num_rows() > remaining {
+                        pending.push_front(batch.slice(remaining, batch.num_rows() - remaining));
+                        batches.push(batch.slice(0, remaining));
+                        remaining = 0;
+                    } else {
+                        remaining -= batch.num_rows();
+                        batches.push(batch);
+                    }
+                }
+                Ok(batches)
+            }
+            Cursor::Keyset {
+                df,
+                key,
+                last,
+                done,
+            } => {
+                if *done {
+                    return Ok(vec![]);
+                }
+                let mut page = df.clone();
+                if let Some(last) = last.as_ref() {
+                    page = page.filter(col(key.clone()).gt(lit(last.clone())))?;
+                }
+                let batches = page
+                    .sort(vec![col(key.clone()).sort(true, false)])?
+                    .limit(0, Some(n))?
+                    .collect()
+                    .await?;
+
+                let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+                *done = rows < n;
+                if let Some(batch) = batches.iter().rev().find(|b| b.num_rows() > 0) {
+                    let index = df.schema().index_of_column(key)?;
+                    *last = Some(ScalarValue::try_from_array(
+                        batch.column(index),
+                        batch.num_rows() - 1,
+                    )?);
+                }
+                Ok(batches)
+            }
+        }
+    }
+
+    // Fetches the rows arriving within the timeout, e.g. for dashboards
+    // showing what slow sources returned so far. The query keeps running,
+    // the next fetch continues where this one stopped.
+    pub async fn fetch_within(&mut self, timeout: Duration) -> Result<PartialResult> {
+        let Cursor::Stream {
+            stream,
+            pending,
+            done,
+        } = &mut self.cursor
+        else {
+            return not_impl_err!("fetch_within with keyset pagination");
+        };
+        let mut batches: Vec<RecordBatch> = pending.drain(..).collect();
+        let deadline = Instant::now() + timeout;
+        while !*done {
+            // Dropping the pending next() at the deadline leaves the stream intact
+            match time::timeout_at(deadline, stream.next()).await {
+                Ok(Some(batch)) => batches.push(batch?),
+                Ok(None) => *done = true,
+                Err(_) => break,
+            }
+        }
+        Ok(PartialResult {
+            batches,
+            complete: *done,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::Int64Array,
+            datatypes::{DataType, Field},
+        },
+        physical_plan::stream::RecordBatchStreamAdapter,
+        prelude::SessionContext,
+    };
+    use futures::stream;
+
+    use super::*;
+
+    fn batch(schema: SchemaRef) -> RecordBatch {
+        RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![1, 2]))]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_within() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        // One batch, then the source stalls
+        let stalled = stream::iter(vec![Ok(batch(schema.clone()))]).chain(stream::pending());
+        let mut handle = FederatedQueryHandle {
+            schema: schema.clone(),
+            cursor: Cursor::Stream {
+                stream: Box::pin(RecordBatchStreamAdapter::new(schema.clone(), stalled)),
+                pending: VecDeque::new(),
+                done: false,
+            },
+        };
+        let result = handle
+            .fetch_within(Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert_eq!(result.batches.len(), 1);
+        assert!(!result.complete);
+        let result = handle
+            .fetch_within(Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert!(result.batches.is_empty());
+        assert!(!result.complete);
+
+        let ctx = SessionContext::new();
+        let df = ctx.read_batch(batch(schema)).unwrap();
+        let mut handle = FederatedQueryHandle::try_new(df).await.unwrap();
+        let result = handle.fetch_within(Duration::from_secs(10)).await.unwrap();
+        assert_eq!(
+            result.batches.iter().map(|b| b.num_rows()).sum::<usize>(),
+            2
+        );
+        assert!(result.complete);
+        assert!(handle.is_done());
+    }
+}
@@ -0,0 +1,56 @@
+use datafusion::{
+    common::{tree_node::TreeNode, DFSchemaRef, OwnedTableReference},
+    error::Result,
+    logical_expr::{Extension, LogicalPlan, TableScan},
+};
+
+use crate::{compat::TreeNodeRecursion, FederatedPlanNode};
+
+// FederatedSubplan describes a sub-plan that is federated to a remote source.
+#[derive(Debug, Clone)]
+pub struct FederatedSubplan {
+    // The compute context of the source, e.g. database instance & catalog
+    pub compute_context: Option<String>,
+    // The query sent to the source, if the planner generates one
+    pub query: Option<String>,
+    pub schema: DFSchemaRef,
+    pub tables: Vec<OwnedTableReference>,
+    pub plan: LogicalPlan,
+}
+
+// Returns the federated sub-plans of an analyzed plan, in plan order.
+// Nothing is executed, which makes it usable for lineage or cost estimation.
+pub fn extract_federated_subplans(plan: &LogicalPlan) -> Result<Vec<FederatedSubplan>> {
+    let mut subplans = vec![];
+    plan.apply(&mut |plan| {
+        let LogicalPlan::Extension(Extension { node }) = plan else {
+            return Ok(TreeNodeRecursion::Continue);
+        };
+        let Some(fed_node) = node.as_any().downcast_ref::<FederatedPlanNode>() else {
+            return Ok(TreeNodeRecursion::Continue);
+        };
+        let planner = fed_node.planner();
+        subplans.push(FederatedSubplan {
+            compute_context: planner.compute_context(),
+            query: planner.federated_query(fed_node)?,
+            schema: fed_node.plan().schema().clone(),
+            tables: referenced_tables(fed_node.plan()),
+            plan: fed_node.plan().clone(),
+        });
+        Ok(TreeNodeRecursion::Skip)
+    })?;
+    Ok(subplans)
+}
+
+fn referenced_tables(plan: &LogicalPlan) -> Vec<OwnedTableReference> {
+    let mut tables: Vec<OwnedTableReference> = vec![];
+    let _ = plan.apply(&mut |plan| {
+        if let LogicalPlan::TableScan(TableScan { table_name, .. }) = plan {
+            if !tables.contains(table_name) {
+                tables.push(table_name.clone());
+            }
+        }
+        Ok(TreeNodeRecursion::Continue)
+    });
+    tables
+}
@@ -2,7 +2,7 @@ use std::{any::Any, sync::Arc};
 
 use async_trait::async_trait;
 use datafusion::{
-    arrow::datatypes::SchemaRef,
+    arrow::{datatypes::SchemaRef, record_batch::RecordBatch},
     common::Constraints,
     datasource::TableProvider,
     error::{DataFusionError, Result},
@@ -23,6 +23,20 @@ impl FederatedTableProviderAdaptor {
     pub fn new(source: Arc<dyn FederatedTableSource>) -> Self {
         Self { source }
     }
+
+    // Fetches the first `limit` rows of the table directly from the source,
+    // without planning a query. Useful to check a source after registering it.
+    pub async fn preview(&self, limit: usize) -> Result<TablePreview> {
+        self.source.clone().preview(limit).await
+    }
+}
+
+// TablePreview holds the first rows of a remote table, with the schema the
+// source returned them in, which may differ from the declared one.
+#[derive(Debug, Clone)]
+pub struct TablePreview {
+    pub schema: SchemaRef,
+    pub batches: Vec<RecordBatch>,
 }
 
 #[async_trait]
@@ -46,6 +60,15 @@ impl TableProvider for FederatedTableProviderAdaptor {
         self.source.get_column_default(column)
     }
 
+    async fn insert_into(
+        &self,
+        state: &SessionState,
+        input: Arc<dyn ExecutionPlan>,
+        overwrite: bool,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        self.source.insert_into(state, input, overwrite).await
+    }
+
     // Scan is not supported; the adaptor should be replaced
     // with a virtual TableProvider that provides federation for a sub-plan.
     async fn scan(
@@ -67,4 +90,29 @@ impl TableProvider for FederatedTableProviderAdaptor {
 pub trait FederatedTableSource: TableSource {
     // Return the FederationProvider associated with this Table
     fn federation_provider(&self) -> Arc<dyn FederationProvider>;
+
+    // Return a plan writing the input to the remote table
+    async fn insert_into(
+        &self,
+        _state: &SessionState,
+        _input: Arc<dyn ExecutionPlan>,
+        _overwrite: bool,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Err(DataFusionError::NotImplemented(
+            "insert into federated table".to_string(),
+        ))
+    }
+
+    // Describe the remote table, e.g. its name in the source, as listed by
+    // source_manifest
+    fn describe(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
+
+    // Return the first `limit` rows of the remote table
+    async fn preview(self: Arc<Self>, _limit: usize) -> Result<TablePreview> {
+        Err(DataFusionError::NotImplemented(
+            "preview of federated table".to_string(),
+        ))
+    }
 }
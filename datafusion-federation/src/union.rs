@@ -0,0 +1,256 @@
+use datafusion::{
+    arrow::datatypes::DataType,
+    common::{plan_err, ScalarValue},
+    error::Result,
+    logical_expr::{
+        cast, type_coercion::binary::comparison_coercion, Expr, LogicalPlan, LogicalPlanBuilder,
+    },
+};
+
+// UnionAlignment configures how align_union reconciles the inputs of a union
+// whose schemas are compatible but unequal, e.g. tables of different sources
+// that use different integer widths or nullability for the same column.
+#[derive(Debug, Clone)]
+pub struct UnionAlignment {
+    // Matches columns by name instead of by position. The output has the
+    // columns of all inputs, in order of first appearance.
+    pub by_name: bool,
+    // Fills columns missing from an input with NULL instead of failing.
+    // Only applies when matching by name.
+    pub null_fill_missing: bool,
+}
+
+impl Default for UnionAlignment {
+    fn default() -> Self {
+        Self {
+            by_name: true,
+            null_fill_missing: true,
+        }
+    }
+}
+
+// Builds a UNION ALL of the inputs, casting every column to the common type
+// of that column across the inputs. Each input gets a projection doing the
+// casts, which the FederationAnalyzerRule pushes down along with the input
+// if it reads from a single source.
+pub fn align_union(inputs: Vec<LogicalPlan>, alignment: &UnionAlignment) -> Result<LogicalPlan> {
+    let Some(first) = inputs.first() else {
+        return plan_err!("a union needs at least one input");
+    };
+
+    let names: Vec<String> = if alignment.by_name {
+        let mut names: Vec<String> = vec![];
+        for input in &inputs {
+            for field in input.schema().fields() {
+                if !names.contains(field.name()) {
+                    names.push(field.name().clone());
+                }
+            }
+        }
+        names
+    } else {
+        let width = first.schema().fields().len();
+        if let Some(i) = inputs
+            .iter()
+            .position(|input| input.schema().fields().len() != width)
+        {
+            return plan_err!(
+                "union input {i} has {} columns, expected {width}",
+                inputs[i].schema().fields().len()
+            );
+        }
+        first
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect()
+    };
+
+    // Resolves the input column of every output column, None if it is missing
+    let columns = inputs
+        .iter()
+        .enumerate()
+        .map(|(i, input)| {
+            names
+                .iter()
+                .enumerate()
+                .map(|(pos, name)| {
+                    let field = if alignment.by_name {
+                        input
+                            .schema()
+                            .fields_with_unqualified_name(name)
+                            .first()
+                            .copied()
+                    } else {
+                        Some(input.schema().field(pos))
+                    };
+                    match field {
+                        Some(field) => Ok(Some(field)),
+                        None if alignment.null_fill_missing => Ok(None),
+                        None => plan_err!("union input {i} has no column {name}"),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let types = names
+        .iter()
+        .enumerate()
+        .map(|(pos, name)| {
+            let mut data_type: Option<DataType> = None;
+            for field in columns.iter().filter_map(|c| c[pos]) {
+                data_type = match data_type {
+                    None => Some(field.data_type().clone()),
+                    Some(t) => match union_coercion(&t, field.data_type()) {
+                        Some(t) => Some(t),
+                        None => {
+                            return plan_err!(
+                                "union column {name} has incompatible types {t} and {}",
+                                field.data_type()
+                            )
+                        }
+                    },
+                };
+            }
+            Ok(data_type.unwrap_or(DataType::Null))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut builder: Option<LogicalPlanBuilder> = None;
+    for (input, fields) in inputs.iter().zip(&columns) {
+        let expr = fields
+            .iter()
+            .zip(names.iter().zip(&types))
+            .map(|(field, (name, data_type))| {
+                let expr = match field {
+                    Some(field) if field.data_type() == data_type => {
+                        Expr::Column(field.qualified_column())
+                    }
+                    Some(field) => cast(Expr::Column(field.qualified_column()), data_type.clone()),
+                    None => Expr::Literal(ScalarValue::try_from(data_type)?),
+                };
+                Ok(expr.alias(name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let projected = LogicalPlanBuilder::from(input.clone())
+            .project(expr)?
+            .build()?;
+        builder = Some(match builder {
+            None => LogicalPlanBuilder::from(projected),
+            Some(builder) => builder.union(projected)?,
+        });
+    }
+    builder.unwrap().build()
+}
+
+// The type both columns widen to in a union. Unlike comparisons, a union
+// only widens within numbers, strings or temporal types, e.g. Int64 and Utf8
+// are rejected instead of turning the numbers into strings.
+fn union_coercion(left: &DataType, right: &DataType) -> Option<DataType> {
+    let family = |t: &DataType| {
+        if t.is_numeric() {
+            Some(0)
+        } else if matches!(t, DataType::Utf8 | DataType::LargeUtf8) {
+            Some(1)
+        } else if t.is_temporal() {
+            Some(2)
+        } else {
+            None
+        }
+    };
+    match (left, right) {
+        _ if left == right => Some(left.clone()),
+        (DataType::Null, t) | (t, DataType::Null) => Some(t.clone()),
+        _ if family(left).is_some() && family(left) == family(right) => {
+            comparison_coercion(left, right)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::datatypes::{Field, Schema},
+        logical_expr::table_scan,
+    };
+
+    use super::*;
+
+    fn scan(name: &str, fields: Vec<Field>) -> LogicalPlan {
+        table_scan(Some(name), &Schema::new(fields), None)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_align_union() {
+        let inputs = || {
+            vec![
+                scan(
+                    "a",
+                    vec![
+                        Field::new("id", DataType::Int32, false),
+                        Field::new("name", DataType::Utf8, true),
+                    ],
+                ),
+                scan(
+                    "b",
+                    vec![
+                        Field::new("extra", DataType::Utf8, true),
+                        Field::new("id", DataType::Int64, true),
+                    ],
+                ),
+            ]
+        };
+
+        let plan = align_union(inputs(), &UnionAlignment::default()).unwrap();
+        let columns = plan
+            .schema()
+            .fields()
+            .iter()
+            .map(|f| (f.name().clone(), f.data_type().clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            vec![
+                ("id".to_string(), DataType::Int64),
+                ("name".to_string(), DataType::Utf8),
+                ("extra".to_string(), DataType::Utf8),
+            ]
+        );
+
+        let alignment = UnionAlignment {
+            by_name: true,
+            null_fill_missing: false,
+        };
+        assert!(align_union(inputs(), &alignment).is_err());
+
+        // By position the inputs need the same number of columns
+        let alignment = UnionAlignment {
+            by_name: false,
+            null_fill_missing: false,
+        };
+        let mut narrow = inputs();
+        narrow.push(scan("c", vec![Field::new("id", DataType::Int64, true)]));
+        assert!(align_union(narrow.clone(), &alignment).is_err());
+        assert!(align_union(narrow, &UnionAlignment::default()).is_ok());
+        assert!(align_union(vec![], &UnionAlignment::default()).is_err());
+
+        // Numbers aren't turned into strings
+        let mixed = vec![
+            scan("a", vec![Field::new("id", DataType::Int64, false)]),
+            scan("b", vec![Field::new("id", DataType::Utf8, false)]),
+        ];
+        assert!(align_union(mixed, &UnionAlignment::default()).is_err());
+        let widened = vec![
+            scan("a", vec![Field::new("v", DataType::Int32, false)]),
+            scan("b", vec![Field::new("v", DataType::Float64, false)]),
+        ];
+        let plan = align_union(widened, &UnionAlignment::default()).unwrap();
+        assert_eq!(plan.schema().field(0).data_type(), &DataType::Float64);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use arrow_flight::{
+    decode::FlightRecordBatchStream, encode::FlightDataEncoderBuilder, error::FlightError,
+    flight_service_client::FlightServiceClient, FlightDescriptor,
+};
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{datatypes::Schema, record_batch::RecordBatch},
+    error::{DataFusionError, Result},
+    physical_plan::{
+        stream::RecordBatchStreamAdapter, EmptyRecordBatchStream, SendableRecordBatchStream,
+    },
+};
+use datafusion_federation_sql::executor::SQLExecutor;
+use futures::{
+    channel::oneshot,
+    future::{self, Fuse},
+    stream, FutureExt, StreamExt, TryStreamExt,
+};
+use tonic::transport::Channel;
+
+mod sql;
+pub use sql::*;
+
+// FlightExchangeExecutor runs queries using Flight DoExchange. Besides plain
+// queries, it can ship a (small) local table to the remote service and get the
+// query result back in a single exchange, e.g. to join the shipped build side
+// remotely.
+//
+// The FlightDescriptor of the exchange carries the SQL as command and the
+// name under which the shipped batches should be visible as path.
+pub struct FlightExchangeExecutor {
+    url: String,
+    client: FlightServiceClient<Channel>,
+}
+
+impl FlightExchangeExecutor {
+    pub async fn try_new(url: String) -> Result<Self> {
+        let channel = Channel::from_shared(url.clone())
+            .map_err(flight_error_to_df)?
+            .connect()
+            .await
+            .map_err(flight_error_to_df)?;
+        Ok(Self {
+            url,
+            client: FlightServiceClient::new(channel),
+        })
+    }
+
+    // Ships `input` to the remote service as `table` and runs `sql` against it.
+    pub async fn exchange(
+        &self,
+        sql: &str,
+        table: &str,
+        input: SendableRecordBatchStream,
+    ) -> Result<SendableRecordBatchStream> {
+        let descriptor = FlightDescriptor {
+            path: vec![table.to_string()],
+            ..FlightDescriptor::new_cmd(sql.to_string())
+        };
+        self.do_exchange(descriptor, input).await
+    }
+
+    async fn do_exchange(
+        &self,
+        descriptor: FlightDescriptor,
+        input: SendableRecordBatchStream,
+    ) -> Result<SendableRecordBatchStream> {
+        // tonic requests can't carry errors and ending the upload would have
+        // the remote side compute over partial input. A failed upload is left
+        // open instead and its error returned, dropping the call aborts it.
+        let (abort, aborted) = oneshot::channel();
+        let mut abort = Some(abort);
+        let request = FlightDataEncoderBuilder::new()
+            .with_schema(input.schema())
+            .with_flight_descriptor(Some(descriptor))
+            .build(input.map_err(|e| FlightError::ExternalError(Box::new(e))))
+            .flat_map(move |data| match data {
+                Ok(data) => stream::once(future::ready(data)).left_stream(),
+                Err(e) => {
+                    if let Some(abort) = abort.take() {
+                        let _ = abort.send(e);
+                    }
+                    stream::pending().right_stream()
+                }
+            });
+        let mut aborted = aborted.fuse();
+
+        let mut client = self.client.clone();
+        let mut call = Box::pin(client.do_exchange(request));
+        let response = future::poll_fn(|cx| {
+            if let Poll::Ready(Ok(e)) = aborted.poll_unpin(cx) {
+                return Poll::Ready(Err(upload_error(e)));
+            }
+            call.poll_unpin(cx).map_err(flight_error_to_df)
+        })
+        .await?;
+
+        let mut batches = FlightRecordBatchStream::new_from_flight_data(
+            response.into_inner().map_err(FlightError::Tonic),
+        );
+        // The schema is only known once the first message arrived
+        let first = future::poll_fn(|cx| poll_exchange(&mut aborted, &mut batches, cx))
+            .await
+            .transpose()?;
+        let schema = match (batches.schema(), &first) {
+            (Some(schema), _) => schema.clone(),
+            (None, Some(batch)) => batch.schema(),
+            (None, None) => Arc::new(Schema::empty()),
+        };
+        let mut failed = false;
+        let rest = stream::poll_fn(move |cx| {
+            if failed {
+                return Poll::Ready(None);
+            }
+            let next = poll_exchange(&mut aborted, &mut batches, cx);
+            failed = matches!(next, Poll::Ready(Some(Err(_))));
+            next
+        });
+        let batches = stream::iter(first.map(Ok)).chain(rest);
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, batches)))
+    }
+}
+
+#[async_trait]
+impl SQLExecutor for FlightExchangeExecutor {
+    fn name(&self) -> &str {
+        "flight_exchange_executor"
+    }
+    fn compute_context(&self) -> Option<String> {
+        Some(self.url.clone())
+    }
+    async fn execute(&self, sql: &str) -> Result<SendableRecordBatchStream> {
+        let input = EmptyRecordBatchStream::new(Arc::new(Schema::empty()));
+        self.do_exchange(FlightDescriptor::new_cmd(sql.to_string()), Box::pin(input))
+            .await
+    }
+}
+
+// Polls the next result batch, failing as soon as the upload failed.
+fn poll_exchange(
+    aborted: &mut Fuse<oneshot::Receiver<FlightError>>,
+    batches: &mut FlightRecordBatchStream,
+    cx: &mut Context<'_>,
+) -> Poll<Option<Result<RecordBatch>>> {
+    if let Poll::Ready(Ok(e)) = aborted.poll_unpin(cx) {
+        return Poll::Ready(Some(Err(upload_error(e))));
+    }
+    batches
+        .poll_next_unpin(cx)
+        .map(|batch| batch.map(|b| b.map_err(flight_error_to_df)))
+}
+
+fn upload_error(err: FlightError) -> DataFusionError {
+    DataFusionError::External(format!("Flight exchange upload failed: {err:?}").into())
+}
+
+fn flight_error_to_df(err: impl std::fmt::Debug) -> DataFusionError {
+    DataFusionError::External(format!("Flight exchange failed: {err:?}").into())
+}
@@ -0,0 +1,309 @@
+use arrow_flight::{
+    decode::FlightRecordBatchStream, error::FlightError,
+    flight_service_client::FlightServiceClient, sql::client::FlightSqlServiceClient,
+    utils::flight_data_to_arrow_batch, FlightData, Ticket,
+};
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{
+        datatypes::{DataType, Schema, SchemaRef},
+        ipc::{root_as_message, MessageHeader},
+        record_batch::RecordBatch,
+    },
+    error::{DataFusionError, Result},
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use datafusion_federation_sql::executor::SQLExecutor;
+use futures::{future, stream, stream::BoxStream, StreamExt, TryStreamExt};
+use std::{collections::HashMap, sync::Arc};
+use tonic::{
+    metadata::{MetadataKey, MetadataMap, MetadataValue},
+    transport::{Certificate, Channel, ClientTlsConfig, Endpoint},
+    Request,
+};
+
+// FlightSQLExecutor runs queries on an Arrow Flight SQL service, e.g. Dremio
+// or InfluxDB 3.0. Results are streamed as Arrow without conversion.
+pub struct FlightSQLExecutor {
+    url: String,
+    client: FlightSqlServiceClient<Channel>,
+    // Decodes the record batches of large results in parallel, if set
+    parallel_get: Option<ParallelGet>,
+}
+
+impl FlightSQLExecutor {
+    pub fn builder(url: impl Into<String>) -> FlightSQLExecutorBuilder {
+        FlightSQLExecutorBuilder {
+            url: url.into(),
+            credentials: None,
+            token: None,
+            headers: vec![],
+            tls: false,
+            ca_certificate: None,
+            domain_name: None,
+            decode_parallelism: 1,
+        }
+    }
+
+    // Returns the schema of the query's result, without running it.
+    pub async fn schema(&self, sql: &str) -> Result<SchemaRef> {
+        let info = self
+            .client
+            .clone()
+            .execute(sql.to_string(), None)
+            .await
+            .map_err(flight_sql_error_to_df)?;
+        Ok(Arc::new(
+            info.try_decode_schema().map_err(flight_sql_error_to_df)?,
+        ))
+    }
+}
+
+#[derive(Clone)]
+pub struct FlightSQLExecutorBuilder {
+    url: String,
+    credentials: Option<(String, String)>,
+    token: Option<String>,
+    headers: Vec<(String, String)>,
+    tls: bool,
+    ca_certificate: Option<String>,
+    domain_name: Option<String>,
+    decode_parallelism: usize,
+}
+
+impl FlightSQLExecutorBuilder {
+    // Authenticates with a handshake, the returned bearer token is sent with
+    // every request.
+    pub fn basic_auth(
+        &mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> &mut Self {
+        self.credentials = Some((username.into(), password.into()));
+        self
+    }
+    // Sends the bearer token with every request.
+    pub fn token(&mut self, token: impl Into<String>) -> &mut Self {
+        self.token = Some(token.into());
+        self
+    }
+    // Sends the header with every request, e.g. a Dremio `routing_tag`.
+    pub fn header(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.headers.push((key.into(), value.into()));
+        self
+    }
+    // Connects with TLS, verifying the server with the system roots.
+    pub fn tls(&mut self, enabled: bool) -> &mut Self {
+        self.tls = enabled;
+        self
+    }
+    // Verifies the server with the PEM encoded CA certificate, enables TLS.
+    pub fn ca_certificate(&mut self, pem: impl Into<String>) -> &mut Self {
+        self.tls = true;
+        self.ca_certificate = Some(pem.into());
+        self
+    }
+    // The name the server certificate is verified against, if it differs
+    // from the host of the url.
+    pub fn domain_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.domain_name = Some(name.into());
+        self
+    }
+
+    // Decodes up to `parallelism` record batches of a result at once on
+    // blocking threads, keeping their order, so decoding large IPC messages
+    // doesn't serialize on one task. Results with dictionary encoded columns
+    // are decoded sequentially. Defaults to 1, decoding sequentially.
+    pub fn decode_parallelism(&mut self, parallelism: usize) -> &mut Self {
+        self.decode_parallelism = parallelism.max(1);
+        self
+    }
+
+    pub async fn build(&self) -> Result<FlightSQLExecutor> {
+        let mut endpoint =
+            Endpoint::from_shared(self.url.clone()).map_err(flight_sql_error_to_df)?;
+        if self.tls {
+            let mut tls = ClientTlsConfig::new();
+            if let Some(pem) = &self.ca_certificate {
+                tls = tls.ca_certificate(Certificate::from_pem(pem));
+            }
+            if let Some(name) = &self.domain_name {
+                tls = tls.domain_name(name);
+            }
+            endpoint = endpoint.tls_config(tls).map_err(flight_sql_error_to_df)?;
+        }
+        let channel = endpoint.connect().await.map_err(flight_sql_error_to_df)?;
+
+        let mut client = FlightSqlServiceClient::new(channel.clone());
+        for (key, value) in &self.headers {
+            client.set_header(key, value);
+        }
+        if let Some(token) = &self.token {
+            client.set_token(token.clone());
+        }
+        if let Some((username, password)) = &self.credentials {
+            client
+                .handshake(username, password)
+                .await
+                .map_err(flight_sql_error_to_df)?;
+        }
+
+        let parallel_get = match self.decode_parallelism {
+            1 => None,
+            parallelism => {
+                // The raw requests carry the headers and token of the client
+                let mut metadata = MetadataMap::new();
+                let token = client.token().map(|t| format!("Bearer {t}"));
+                let authorization = token.map(|t| ("authorization".to_string(), t));
+                for (key, value) in self.headers.iter().cloned().chain(authorization) {
+                    let key =
+                        MetadataKey::from_bytes(key.as_bytes()).map_err(flight_sql_error_to_df)?;
+                    let value =
+                        MetadataValue::try_from(value.as_str()).map_err(flight_sql_error_to_df)?;
+                    metadata.insert(key, value);
+                }
+                Some(ParallelGet {
+                    channel,
+                    metadata,
+                    parallelism,
+                })
+            }
+        };
+        Ok(FlightSQLExecutor {
+            url: self.url.clone(),
+            client,
+            parallel_get,
+        })
+    }
+}
+
+// ParallelGet fetches tickets with raw DoGet requests to decode the
+// record batch messages in parallel.
+#[derive(Clone)]
+struct ParallelGet {
+    channel: Channel,
+    metadata: MetadataMap,
+    parallelism: usize,
+}
+
+impl ParallelGet {
+    async fn do_get(self, ticket: Ticket) -> Result<BoxStream<'static, Result<RecordBatch>>> {
+        let mut request = Request::new(ticket);
+        *request.metadata_mut() = self.metadata;
+        let mut data = FlightServiceClient::new(self.channel)
+            .do_get(request)
+            .await
+            .map_err(flight_sql_error_to_df)?
+            .into_inner();
+
+        // The first message is the schema
+        let Some(first) = data
+            .next()
+            .await
+            .transpose()
+            .map_err(flight_sql_error_to_df)?
+        else {
+            return Ok(stream::empty().boxed());
+        };
+        let schema = Arc::new(Schema::try_from(&first).map_err(flight_sql_error_to_df)?);
+        // Dictionaries are sent as messages of their own the batches depend on
+        let dictionaries = schema
+            .fields()
+            .iter()
+            .any(|f| matches!(f.data_type(), DataType::Dictionary(..)));
+        if dictionaries {
+            let data = stream::iter([Ok(first)])
+                .chain(data)
+                .map_err(FlightError::Tonic);
+            return Ok(FlightRecordBatchStream::new_from_flight_data(data)
+                .map_err(flight_sql_error_to_df)
+                .boxed());
+        }
+
+        let batches = data
+            .map_err(flight_sql_error_to_df)
+            .try_filter(|data| future::ready(is_record_batch(data)))
+            .map(move |data| {
+                let schema = schema.clone();
+                async move {
+                    let data = data?;
+                    tokio::task::spawn_blocking(move || {
+                        flight_data_to_arrow_batch(&data, schema, &HashMap::new())
+                    })
+                    .await
+                    .map_err(flight_sql_error_to_df)?
+                    .map_err(flight_sql_error_to_df)
+                }
+            })
+            .buffered(self.parallelism);
+        Ok(batches.boxed())
+    }
+}
+
+fn is_record_batch(data: &FlightData) -> bool {
+    root_as_message(&data.data_header)
+        .map(|message| message.header_type() == MessageHeader::RecordBatch)
+        .unwrap_or(false)
+}
+
+#[async_trait]
+impl SQLExecutor for FlightSQLExecutor {
+    fn name(&self) -> &str {
+        "flight_sql_executor"
+    }
+    fn compute_context(&self) -> Option<String> {
+        Some(self.url.clone())
+    }
+    async fn execute(&self, sql: &str) -> Result<SendableRecordBatchStream> {
+        let mut client = self.client.clone();
+        let info = client
+            .execute(sql.to_string(), None)
+            .await
+            .map_err(flight_sql_error_to_df)?;
+        let schema = Arc::new(
+            info.clone()
+                .try_decode_schema()
+                .map_err(flight_sql_error_to_df)?,
+        );
+
+        // The endpoints are fetched in order from the same service,
+        // locations of other services aren't followed
+        let tickets = info
+            .endpoint
+            .into_iter()
+            .filter_map(|endpoint| endpoint.ticket)
+            .collect::<Vec<_>>();
+        let parallel_get = self.parallel_get.clone();
+        let batches = stream::iter(tickets)
+            .then(move |ticket| {
+                let mut client = client.clone();
+                let parallel_get = parallel_get.clone();
+                async move {
+                    if let Some(parallel_get) = parallel_get {
+                        return parallel_get.do_get(ticket).await;
+                    }
+                    let batches = client
+                        .do_get(ticket)
+                        .await
+                        .map_err(flight_sql_error_to_df)?;
+                    Ok(batches.map_err(flight_sql_error_to_df).boxed())
+                }
+            })
+            .try_flatten();
+
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, batches)))
+    }
+    async fn execute_statement(&self, statement: &str) -> Result<u64> {
+        let rows = self
+            .client
+            .clone()
+            .execute_update(statement.to_string(), None)
+            .await
+            .map_err(flight_sql_error_to_df)?;
+        Ok(rows.max(0) as u64)
+    }
+}
+
+fn flight_sql_error_to_df(err: impl std::fmt::Debug) -> DataFusionError {
+    DataFusionError::External(format!("Flight SQL failed: {err:?}").into())
+}
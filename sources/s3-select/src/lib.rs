@@ -0,0 +1,434 @@
+use core::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use aws_sdk_s3::{
+    types::{
+        CsvOutput, ExpressionType, InputSerialization, OutputSerialization,
+        SelectObjectContentEventStream,
+    },
+    Client,
+};
+use datafusion::{
+    arrow::{
+        csv::{reader::Decoder, ReaderBuilder},
+        datatypes::{Field, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    },
+    common::{
+        not_impl_err,
+        tree_node::{TreeNode, VisitRecursion},
+    },
+    error::{DataFusionError, Result},
+    logical_expr::{Expr, LogicalPlan},
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+    sql::sqlparser::{
+        ast::{self, Expr as SQLExpr},
+        dialect::GenericDialect,
+        parser::Parser,
+    },
+};
+use datafusion_federation_sql::{dialect::SQLDialect, executor::SQLExecutor};
+use futures::{stream, Stream, StreamExt};
+
+// S3SelectExecutor runs the federated SQL in-place on a single S3 object
+// using S3 Select. Only single-table projections, filters and limits can be
+// pushed; the object schema has to be declared since S3 Select has no catalog.
+pub struct S3SelectExecutor {
+    client: Client,
+    bucket: String,
+    key: String,
+    input_serialization: InputSerialization,
+    schema: SchemaRef,
+}
+
+impl S3SelectExecutor {
+    pub fn new(
+        client: Client,
+        bucket: String,
+        key: String,
+        input_serialization: InputSerialization,
+        schema: SchemaRef,
+    ) -> Self {
+        Self {
+            client,
+            bucket,
+            key,
+            input_serialization,
+            schema,
+        }
+    }
+
+    // Rewrites the generated SQL to the S3 Select flavor and returns it
+    // together with the schema of the rows it produces.
+    fn rewrite(&self, sql: &str) -> Result<(String, SchemaRef)> {
+        let mut statements = Parser::parse_sql(&GenericDialect {}, sql)
+            .map_err(|e| DataFusionError::External(format!("S3 Select: {e:?}").into()))?;
+        if statements.len() != 1 {
+            return not_impl_err!("S3 Select only supports a single statement: {sql}");
+        }
+        let mut query = match statements.pop() {
+            Some(ast::Statement::Query(query)) => query,
+            _ => return not_impl_err!("S3 Select only supports queries: {sql}"),
+        };
+        if !query.order_by.is_empty() || query.offset.is_some() {
+            return not_impl_err!("S3 Select does not support ORDER BY or OFFSET: {sql}");
+        }
+        let select = match query.body.as_mut() {
+            ast::SetExpr::Select(select) => select,
+            _ => return not_impl_err!("S3 Select only supports SELECT: {sql}"),
+        };
+        if select.from.len() != 1 || !select.from[0].joins.is_empty() {
+            return not_impl_err!("S3 Select only supports a single table: {sql}");
+        }
+        if select.group_by != ast::GroupByExpr::Expressions(vec![]) || select.having.is_some() {
+            return not_impl_err!("S3 Select does not support aggregations: {sql}");
+        }
+
+        // S3 Select always reads from `S3Object`, keep the original
+        // table name as alias so column qualifiers still resolve.
+        let binding = match &select.from[0].relation {
+            ast::TableFactor::Table { name, alias, .. } => alias
+                .as_ref()
+                .map(|a| a.name.value.clone())
+                .or_else(|| name.0.last().map(|i| i.value.clone()))
+                .unwrap_or_default(),
+            _ => return not_impl_err!("S3 Select only supports plain tables: {sql}"),
+        };
+        select.from[0].relation = ast::TableFactor::Table {
+            name: ast::ObjectName(vec![ast::Ident::new("S3Object")]),
+            alias: Some(ast::TableAlias {
+                name: ast::Ident::with_quote('"', binding),
+                columns: vec![],
+            }),
+            args: None,
+            with_hints: vec![],
+            version: None,
+            partitions: vec![],
+        };
+
+        let mut fields = Vec::with_capacity(select.projection.len());
+        for item in select.projection.iter_mut() {
+            match item {
+                ast::SelectItem::UnnamedExpr(expr) => {
+                    requote_expr(expr)?;
+                    fields.push(self.projected_field(expr, None)?);
+                }
+                ast::SelectItem::ExprWithAlias { expr, alias } => {
+                    requote_expr(expr)?;
+                    alias.quote_style = Some('"');
+                    fields.push(self.projected_field(expr, Some(&alias.value))?);
+                }
+                ast::SelectItem::Wildcard(_) | ast::SelectItem::QualifiedWildcard(..) => {
+                    fields.extend(self.schema.fields().iter().map(|f| f.as_ref().clone()));
+                }
+            }
+        }
+        if let Some(selection) = select.selection.as_mut() {
+            requote_expr(selection)?;
+        }
+
+        Ok((format!("{query}"), Arc::new(Schema::new(fields))))
+    }
+
+    fn projected_field(&self, expr: &SQLExpr, alias: Option<&str>) -> Result<Field> {
+        let column = match expr {
+            SQLExpr::Identifier(ident) => &ident.value,
+            SQLExpr::CompoundIdentifier(idents) if !idents.is_empty() => {
+                &idents[idents.len() - 1].value
+            }
+            _ => return not_impl_err!("S3 Select only supports column projections: {expr}"),
+        };
+        let field = self
+            .schema
+            .fields()
+            .iter()
+            .find(|f| f.name().eq_ignore_ascii_case(column))
+            .ok_or_else(|| DataFusionError::Plan(format!("S3 Select: unknown column {column}")))?;
+        Ok(Field::new(
+            alias.unwrap_or(field.name()),
+            field.data_type().clone(),
+            true,
+        ))
+    }
+}
+
+// S3 Select only accepts double quoted identifiers.
+fn requote_expr(expr: &mut SQLExpr) -> Result<()> {
+    match expr {
+        SQLExpr::Identifier(ident) => ident.quote_style = Some('"'),
+        SQLExpr::CompoundIdentifier(idents) => {
+            idents.iter_mut().for_each(|i| i.quote_style = Some('"'))
+        }
+        SQLExpr::BinaryOp { left, right, .. } => {
+            requote_expr(left)?;
+            requote_expr(right)?;
+        }
+        SQLExpr::UnaryOp { expr, .. }
+        | SQLExpr::Nested(expr)
+        | SQLExpr::IsNull(expr)
+        | SQLExpr::IsNotNull(expr) => requote_expr(expr)?,
+        SQLExpr::Value(_) => {}
+        _ => return not_impl_err!("S3 Select: unsupported expression {expr}"),
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl SQLExecutor for S3SelectExecutor {
+    fn name(&self) -> &str {
+        "s3_select_executor"
+    }
+    fn compute_context(&self) -> Option<String> {
+        Some(format!("s3://{}/{}", self.bucket, self.key))
+    }
+    async fn execute(&self, sql: &str) -> Result<SendableRecordBatchStream> {
+        let (expression, schema) = self.rewrite(sql)?;
+
+        let output = self
+            .client
+            .select_object_content()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .expression_type(ExpressionType::Sql)
+            .expression(expression)
+            .input_serialization(self.input_serialization.clone())
+            .output_serialization(
+                OutputSerialization::builder()
+                    .csv(CsvOutput::builder().build())
+                    .build(),
+            )
+            .send()
+            .await
+            .map_err(s3_error_to_df)?;
+
+        // The records of the response are decoded as they arrive
+        let chunks = stream::try_unfold(output.payload, |mut payload| async move {
+            loop {
+                match payload.recv().await.map_err(s3_error_to_df)? {
+                    Some(SelectObjectContentEventStream::Records(records)) => {
+                        let chunk = records.payload().map(|p| p.as_ref().to_vec());
+                        return Ok(Some((chunk.unwrap_or_default(), payload)));
+                    }
+                    // Stats, progress and end events
+                    Some(_) => {}
+                    None => return Ok(None),
+                }
+            }
+        });
+        let decoder = ReaderBuilder::new(schema.clone())
+            .with_header(false)
+            .build_decoder();
+        let batches = decode_records(decoder, chunks);
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, batches)))
+    }
+    fn dialect(&self) -> Arc<dyn SQLDialect> {
+        Arc::new(S3SelectDialect {})
+    }
+}
+
+// Decodes the CSV records as the chunks arrive, records may span chunks.
+fn decode_records(
+    decoder: Decoder,
+    chunks: impl Stream<Item = Result<Vec<u8>>> + Send + 'static,
+) -> impl Stream<Item = Result<RecordBatch>> + Send {
+    let state = (decoder, Box::pin(chunks), Vec::new(), false);
+    stream::try_unfold(
+        state,
+        |(mut decoder, mut chunks, mut pending, mut eof)| async move {
+            loop {
+                let decoded = decoder.decode(&pending)?;
+                pending.drain(..decoded);
+                // A batch is full, or the last rows arrived
+                if decoder.capacity() == 0 || eof {
+                    let batch = decoder.flush()?;
+                    return Ok(batch.map(|batch| (batch, (decoder, chunks, pending, eof))));
+                }
+                match chunks.next().await {
+                    Some(chunk) => pending.extend_from_slice(&chunk?),
+                    None => eof = true,
+                }
+            }
+        },
+    )
+}
+
+// S3SelectDialect only pushes down what S3 Select runs on a single object,
+// `SELECT <columns> FROM S3Object WHERE <predicate> LIMIT <n>`. Other nodes,
+// e.g. aggregations, sorts and joins, run locally.
+pub struct S3SelectDialect {}
+
+impl SQLDialect for S3SelectDialect {
+    fn name(&self) -> &str {
+        "s3_select"
+    }
+
+    fn supports_limit_in_subquery(&self) -> bool {
+        false
+    }
+
+    fn supports_window_functions(&self) -> bool {
+        false
+    }
+
+    fn supports_plan(&self, plan: &LogicalPlan) -> bool {
+        let mut plan = plan;
+        if let LogicalPlan::Limit(limit) = plan {
+            if limit.skip > 0 {
+                return false;
+            }
+            plan = limit.input.as_ref();
+        }
+        while let LogicalPlan::Projection(projection) = plan {
+            let columns = projection
+                .expr
+                .iter()
+                .all(|e| matches!(e.clone().unalias(), Expr::Column(_)));
+            if !columns {
+                return false;
+            }
+            plan = projection.input.as_ref();
+        }
+        if let LogicalPlan::Filter(filter) = plan {
+            if !is_select_predicate(&filter.predicate) {
+                return false;
+            }
+            plan = filter.input.as_ref();
+        }
+        if let LogicalPlan::SubqueryAlias(alias) = plan {
+            plan = alias.input.as_ref();
+        }
+        matches!(plan, LogicalPlan::TableScan(_))
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('"')
+    }
+}
+
+// Whether the predicate only compares columns and literals, as requote_expr
+// accepts.
+fn is_select_predicate(predicate: &Expr) -> bool {
+    let mut supported = true;
+    let _ = predicate.apply(&mut |e| {
+        supported = matches!(
+            e,
+            Expr::Column(_)
+                | Expr::Literal(_)
+                | Expr::BinaryExpr(_)
+                | Expr::Not(_)
+                | Expr::Negative(_)
+                | Expr::IsNull(_)
+                | Expr::IsNotNull(_)
+        );
+        Ok(if supported {
+            VisitRecursion::Continue
+        } else {
+            VisitRecursion::Stop
+        })
+    });
+    supported
+}
+
+fn s3_error_to_df<E: fmt::Debug>(err: E) -> DataFusionError {
+    DataFusionError::External(format!("S3 Select failed to run query: {err:?}").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::AsArray,
+            datatypes::{DataType, Int64Type},
+        },
+        logical_expr::{col, lit, logical_plan::table_scan, sum},
+    };
+    use futures::TryStreamExt;
+
+    use super::*;
+
+    fn schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("name", DataType::Utf8, true),
+        ]))
+    }
+
+    #[tokio::test]
+    async fn test_decode_records() {
+        // Records span the chunks of the response
+        let chunks = ["1,a\n2,", "b\n3", ",c\n"]
+            .into_iter()
+            .map(|chunk| Ok(chunk.as_bytes().to_vec()));
+        let decoder = ReaderBuilder::new(schema())
+            .with_header(false)
+            .with_batch_size(2)
+            .build_decoder();
+        let batches: Vec<RecordBatch> = decode_records(decoder, stream::iter(chunks))
+            .try_collect()
+            .await
+            .unwrap();
+        let rows: Vec<usize> = batches.iter().map(|b| b.num_rows()).collect();
+        assert_eq!(rows, vec![2, 1]);
+        let ids: Vec<i64> = batches
+            .iter()
+            .flat_map(|b| b.column(0).as_primitive::<Int64Type>().values().to_vec())
+            .collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+
+        let chunks = vec![Ok(b"1,a\n".to_vec()), failed_chunk()];
+        let decoder = ReaderBuilder::new(schema())
+            .with_header(false)
+            .build_decoder();
+        let result: Result<Vec<RecordBatch>> = decode_records(decoder, stream::iter(chunks))
+            .try_collect()
+            .await;
+        assert!(result.is_err());
+    }
+
+    fn failed_chunk() -> Result<Vec<u8>> {
+        Err(DataFusionError::External("connection reset".into()))
+    }
+
+    #[test]
+    fn test_supports_plan() {
+        let dialect = S3SelectDialect {};
+        let scan = || table_scan(Some("t"), &schema(), None).unwrap();
+
+        let plan = scan()
+            .filter(col("id").gt(lit(1)).and(col("name").is_not_null()))
+            .unwrap()
+            .project(vec![col("name").alias("n")])
+            .unwrap()
+            .limit(0, Some(10))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(dialect.supports_plan(&plan));
+
+        let unsupported = [
+            scan().limit(5, Some(10)).unwrap(),
+            scan().project(vec![col("id") + lit(1)]).unwrap(),
+            scan()
+                .aggregate(vec![col("name")], vec![sum(col("id"))])
+                .unwrap(),
+            scan().sort(vec![col("id").sort(true, false)]).unwrap(),
+            scan()
+                .limit(0, Some(10))
+                .unwrap()
+                .filter(col("id").gt(lit(1)))
+                .unwrap(),
+            scan()
+                .cross_join(
+                    table_scan(Some("u"), &schema(), None)
+                        .unwrap()
+                        .build()
+                        .unwrap(),
+                )
+                .unwrap(),
+        ];
+        for plan in unsupported {
+            assert!(!dialect.supports_plan(&plan.build().unwrap()));
+        }
+    }
+}
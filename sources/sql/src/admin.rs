@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use datafusion::{error::Result, sql::sqlparser::ast};
+
+use crate::executor::SQLExecutor;
+
+// SQLSourceAdmin manages the namespaces and tables of a source, for pipelines
+// writing through the federation layer. Executors supporting DDL implement it
+// and return themselves from SQLExecutor::admin.
+#[async_trait]
+pub trait SQLSourceAdmin: SQLExecutor {
+    async fn create_schema(&self, schema: &str, if_not_exists: bool) -> Result<()> {
+        let if_not_exists = if if_not_exists { "IF NOT EXISTS " } else { "" };
+        let statement = format!("CREATE SCHEMA {if_not_exists}{}", self.object_name(schema));
+        self.execute_statement(&statement).await?;
+        Ok(())
+    }
+
+    async fn drop_schema(&self, schema: &str, if_exists: bool) -> Result<()> {
+        let if_exists = if if_exists { "IF EXISTS " } else { "" };
+        let statement = format!("DROP SCHEMA {if_exists}{}", self.object_name(schema));
+        self.execute_statement(&statement).await?;
+        Ok(())
+    }
+
+    async fn drop_table(&self, table: &str, if_exists: bool) -> Result<()> {
+        let if_exists = if if_exists { "IF EXISTS " } else { "" };
+        let statement = format!("DROP TABLE {if_exists}{}", self.object_name(table));
+        self.execute_statement(&statement).await?;
+        Ok(())
+    }
+
+    // Quotes the parts of a dotted name in the executor's dialect.
+    fn object_name(&self, name: &str) -> ast::ObjectName {
+        let quote_style = self.dialect().identifier_quote_style();
+        ast::ObjectName(
+            name.split('.')
+                .map(|part| ast::Ident {
+                    value: part.to_string(),
+                    quote_style,
+                })
+                .collect(),
+        )
+    }
+}
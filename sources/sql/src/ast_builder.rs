@@ -142,6 +142,12 @@ impl SelectBuilder {
         new.top = value;
         new
     }
+    pub fn already_projected(&self) -> bool {
+        !self.projection.is_empty()
+    }
+    pub fn projection_items(&self) -> &[ast::SelectItem] {
+        &self.projection
+    }
     #[allow(unused_mut)]
     pub fn projection(&mut self, value: Vec<ast::SelectItem>) -> &mut Self {
         let mut new = self;
@@ -0,0 +1,71 @@
+use std::{
+    fmt,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use datafusion::error::{DataFusionError, Result};
+use tokio::sync::oneshot;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+// BlockingPool runs the blocking calls of one source, e.g. a synchronous
+// driver, on its own threads. Unlike tokio's shared spawn_blocking pool, a
+// source saturating its pool only queues its own fetches, other sources'
+// fetches keep running.
+pub struct BlockingPool {
+    name: String,
+    threads: usize,
+    jobs: Mutex<mpsc::Sender<Job>>,
+}
+
+impl BlockingPool {
+    pub fn new(name: impl Into<String>, threads: usize) -> Result<Self> {
+        let name = name.into();
+        let threads = threads.max(1);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for i in 0..threads {
+            let receiver = receiver.clone();
+            thread::Builder::new()
+                .name(format!("{name}-{i}"))
+                .spawn(move || loop {
+                    // The threads exit once the pool is dropped
+                    let job = match receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => return,
+                    };
+                    job();
+                })?;
+        }
+        Ok(Self {
+            name,
+            threads,
+            jobs: Mutex::new(sender),
+        })
+    }
+
+    // Runs the function on a thread of the pool, waiting for a free thread.
+    pub async fn run<F, R>(&self, f: F) -> Result<R>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        let job: Job = Box::new(move || {
+            let _ = sender.send(f());
+        });
+        self.jobs.lock().unwrap().send(job).map_err(|_| {
+            DataFusionError::Execution(format!("blocking pool {} is shut down", self.name))
+        })?;
+        receiver.await.map_err(|_| {
+            DataFusionError::Execution(format!("blocking pool {} task panicked", self.name))
+        })
+    }
+}
+
+impl fmt::Debug for BlockingPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BlockingPool({}, {} threads)", self.name, self.threads)
+    }
+}
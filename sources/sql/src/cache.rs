@@ -0,0 +1,164 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{
+        datatypes::SchemaRef, record_batch::RecordBatch, util::display::array_value_to_string,
+    },
+    error::Result,
+    physical_plan::{memory::MemoryStream, SendableRecordBatchStream},
+};
+use futures::TryStreamExt;
+
+use crate::{
+    dialect::SQLDialect,
+    executor::{SQLConnection, SQLExecutor},
+    RemoteWarnings, SQLSourceAdmin, ServerVersion,
+};
+
+// RemoteVersion reports the version of the remote data a query reads,
+// e.g. Snowflake's LAST_ALTERED, Postgres' pg_stat counters or an Iceberg
+// snapshot id. `None` means the version is unknown and the query isn't cached.
+#[async_trait]
+pub trait RemoteVersion: Send + Sync {
+    async fn version(&self, sql: &str) -> Result<Option<String>>;
+}
+
+// VersionQuery reads the version by running `query` on the source,
+// the first value of the result is used as version, e.g.
+// `SELECT max(last_altered) FROM information_schema.tables WHERE table_schema = 'PUBLIC'`
+pub struct VersionQuery {
+    executor: Arc<dyn SQLExecutor>,
+    query: String,
+}
+
+impl VersionQuery {
+    pub fn new(executor: Arc<dyn SQLExecutor>, query: String) -> Self {
+        Self { executor, query }
+    }
+}
+
+#[async_trait]
+impl RemoteVersion for VersionQuery {
+    async fn version(&self, _sql: &str) -> Result<Option<String>> {
+        let batches = self
+            .executor
+            .execute(&self.query)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        match batches
+            .iter()
+            .find(|b| b.num_rows() > 0 && b.num_columns() > 0)
+        {
+            Some(batch) if !batch.column(0).is_null(0) => {
+                Ok(Some(array_value_to_string(batch.column(0), 0)?))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+// CachingExecutor caches query results keyed by the SQL and the remote
+// version, results are served from the cache until the remote data changes.
+pub struct CachingExecutor {
+    executor: Arc<dyn SQLExecutor>,
+    version: Arc<dyn RemoteVersion>,
+    cache: Mutex<HashMap<String, CachedResult>>,
+}
+
+struct CachedResult {
+    version: String,
+    schema: SchemaRef,
+    batches: Vec<RecordBatch>,
+}
+
+impl CachingExecutor {
+    pub fn new(executor: Arc<dyn SQLExecutor>, version: Arc<dyn RemoteVersion>) -> Self {
+        Self {
+            executor,
+            version,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cached(&self, sql: &str, version: &str) -> Option<Result<SendableRecordBatchStream>> {
+        let cache = self.cache.lock().unwrap();
+        let cached = cache.get(sql).filter(|c| c.version == version)?;
+        Some(
+            MemoryStream::try_new(cached.batches.clone(), cached.schema.clone(), None)
+                .map(|s| Box::pin(s) as SendableRecordBatchStream),
+        )
+    }
+}
+
+#[async_trait]
+impl SQLExecutor for CachingExecutor {
+    fn name(&self) -> &str {
+        self.executor.name()
+    }
+    fn compute_context(&self) -> Option<String> {
+        self.executor.compute_context()
+    }
+    fn dialect(&self) -> Arc<dyn SQLDialect> {
+        self.executor.dialect()
+    }
+    async fn execute(&self, sql: &str) -> Result<SendableRecordBatchStream> {
+        let Some(version) = self.version.version(sql).await? else {
+            return self.executor.execute(sql).await;
+        };
+        if let Some(stream) = self.cached(sql, &version) {
+            return stream;
+        }
+
+        let stream = self.executor.execute(sql).await?;
+        let schema = stream.schema();
+        let batches = stream.try_collect::<Vec<_>>().await?;
+        // Entries of outdated versions are replaced
+        self.cache.lock().unwrap().insert(
+            sql.to_string(),
+            CachedResult {
+                version,
+                schema: schema.clone(),
+                batches: batches.clone(),
+            },
+        );
+        Ok(Box::pin(MemoryStream::try_new(batches, schema, None)?))
+    }
+    // Statements aren't cached
+    async fn execute_statement(&self, statement: &str) -> Result<u64> {
+        self.executor.execute_statement(statement).await
+    }
+    async fn execute_transaction(&self, statements: &[String]) -> Result<u64> {
+        self.executor.execute_transaction(statements).await
+    }
+    // Notices are reported by each execution, such queries aren't cached
+    async fn execute_with_warnings(
+        &self,
+        query: &str,
+        warnings: RemoteWarnings,
+    ) -> Result<SendableRecordBatchStream> {
+        self.executor.execute_with_warnings(query, warnings).await
+    }
+    async fn copy_in(&self, statement: &str, data: Vec<u8>) -> Result<u64> {
+        self.executor.copy_in(statement, data).await
+    }
+    async fn connect(&self) -> Result<Box<dyn SQLConnection>> {
+        self.executor.connect().await
+    }
+    async fn server_version(&self) -> Result<Option<ServerVersion>> {
+        self.executor.server_version().await
+    }
+    fn admin(&self) -> Option<&dyn SQLSourceAdmin> {
+        self.executor.admin()
+    }
+    fn partition_count(&self) -> usize {
+        self.executor.partition_count()
+    }
+    async fn split_query(&self, query: &str) -> Result<Vec<String>> {
+        self.executor.split_query(query).await
+    }
+}
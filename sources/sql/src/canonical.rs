@@ -0,0 +1,121 @@
+use datafusion::sql::sqlparser::ast::{self, Expr as SQLExpr};
+
+// Canonicalizes the generated SQL so equivalent plans produce byte-identical
+// SQL, which keeps remote plan caches and golden tests stable.
+// Currently the conjuncts of WHERE and JOIN ON conditions are ordered.
+pub(crate) fn canonicalize(statement: &mut ast::Statement) {
+    if let ast::Statement::Query(query) = statement {
+        canonicalize_query(query);
+    }
+}
+
+fn canonicalize_query(query: &mut ast::Query) {
+    canonicalize_set_expr(&mut query.body);
+}
+
+fn canonicalize_set_expr(body: &mut ast::SetExpr) {
+    match body {
+        ast::SetExpr::Select(select) => {
+            if let Some(selection) = select.selection.take() {
+                select.selection = Some(sort_conjuncts(selection));
+            }
+            for twj in select.from.iter_mut() {
+                canonicalize_table_factor(&mut twj.relation);
+                for join in twj.joins.iter_mut() {
+                    canonicalize_table_factor(&mut join.relation);
+                    canonicalize_join_operator(&mut join.join_operator);
+                }
+            }
+        }
+        ast::SetExpr::Query(query) => canonicalize_query(query),
+        ast::SetExpr::SetOperation { left, right, .. } => {
+            canonicalize_set_expr(left);
+            canonicalize_set_expr(right);
+        }
+        _ => {}
+    }
+}
+
+fn canonicalize_table_factor(relation: &mut ast::TableFactor) {
+    if let ast::TableFactor::Derived { subquery, .. } = relation {
+        canonicalize_query(subquery);
+    }
+}
+
+fn canonicalize_join_operator(operator: &mut ast::JoinOperator) {
+    let constraint = match operator {
+        ast::JoinOperator::Inner(c)
+        | ast::JoinOperator::LeftOuter(c)
+        | ast::JoinOperator::RightOuter(c)
+        | ast::JoinOperator::FullOuter(c)
+        | ast::JoinOperator::LeftSemi(c)
+        | ast::JoinOperator::RightSemi(c)
+        | ast::JoinOperator::LeftAnti(c)
+        | ast::JoinOperator::RightAnti(c) => c,
+        _ => return,
+    };
+    if let ast::JoinConstraint::On(expr) = constraint {
+        let on = std::mem::replace(expr, SQLExpr::Value(ast::Value::Null));
+        *expr = sort_conjuncts(on);
+    }
+}
+
+fn sort_conjuncts(expr: SQLExpr) -> SQLExpr {
+    let mut conjuncts = vec![];
+    split_and(expr, &mut conjuncts);
+    let mut conjuncts = conjuncts
+        .into_iter()
+        .map(|e| (e.to_string(), e))
+        .collect::<Vec<_>>();
+    conjuncts.sort_by(|(a, _), (b, _)| a.cmp(b));
+    conjuncts
+        .into_iter()
+        .map(|(_, e)| e)
+        .reduce(|l, r| SQLExpr::BinaryOp {
+            left: Box::new(l),
+            op: ast::BinaryOperator::And,
+            right: Box::new(r),
+        })
+        .unwrap()
+}
+
+fn split_and(expr: SQLExpr, conjuncts: &mut Vec<SQLExpr>) {
+    match expr {
+        SQLExpr::BinaryOp {
+            left,
+            op: ast::BinaryOperator::And,
+            right,
+        } => {
+            split_and(*left, conjuncts);
+            split_and(*right, conjuncts);
+        }
+        // Nested conjunctions are ordered on their own
+        SQLExpr::Nested(inner) => conjuncts.push(SQLExpr::Nested(Box::new(sort_conjuncts(*inner)))),
+        _ => conjuncts.push(expr),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::sql::sqlparser::{dialect::GenericDialect, parser::Parser};
+
+    use super::*;
+
+    #[test]
+    fn test_canonical_predicates() {
+        let canonical = |sql: &str| {
+            let mut statement = Parser::parse_sql(&GenericDialect {}, sql)
+                .unwrap()
+                .remove(0);
+            canonicalize(&mut statement);
+            statement.to_string()
+        };
+
+        let expected = "SELECT a FROM t JOIN u ON t.x = u.x AND t.y = u.y WHERE a > 1 AND b < 2";
+        assert_eq!(
+            canonical("SELECT a FROM t JOIN u ON t.y = u.y AND t.x = u.x WHERE b < 2 AND a > 1"),
+            expected
+        );
+        assert_eq!(canonical(expected), expected);
+    }
+}
@@ -0,0 +1,297 @@
+use core::fmt;
+use std::{collections::BTreeMap, sync::Arc};
+
+use datafusion::{
+    arrow::{datatypes::DataType, record_batch::RecordBatch, util::display::array_value_to_string},
+    error::Result,
+    logical_expr::LogicalPlan,
+    sql::sqlparser::{
+        ast::{self, Expr as SQLExpr},
+        dialect as parser,
+    },
+};
+use futures::TryStreamExt;
+use log::debug;
+
+use crate::{
+    dialect::{
+        AsOf, GroupByStrategy, LimitStyle, NullSafeFallbackDialect, SQLDialect, UpsertStrategy,
+    },
+    executor::SQLExecutor,
+    ServerVersion, SortCostProfile,
+};
+
+const DECIMAL_PROBE: &str = "12345678901234567890.1234567890";
+
+// CapabilityReport is what probe_capabilities found a live source to
+// support, a capability is false when its probe query failed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CapabilityReport {
+    pub server_version: Option<ServerVersion>,
+    pub window_functions: bool,
+    pub ilike: bool,
+    pub limit_in_subquery: bool,
+    pub is_distinct_from: bool,
+    // DECIMAL(30, 10) values are read back without losing digits
+    pub decimal_round_trip: bool,
+    // Why each failed probe failed, by capability
+    pub errors: BTreeMap<String, String>,
+}
+
+impl CapabilityReport {
+    // Adjusts the dialect flags to the capabilities found.
+    pub fn apply(&self, dialect: Arc<dyn SQLDialect>) -> Arc<dyn SQLDialect> {
+        Arc::new(ProbedDialect {
+            dialect,
+            report: self.clone(),
+        })
+    }
+}
+
+impl fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.server_version {
+            Some(version) => writeln!(f, "server_version: {version}")?,
+            None => writeln!(f, "server_version: unknown")?,
+        }
+        let capabilities = [
+            ("window_functions", self.window_functions),
+            ("ilike", self.ilike),
+            ("limit_in_subquery", self.limit_in_subquery),
+            ("is_distinct_from", self.is_distinct_from),
+            ("decimal_round_trip", self.decimal_round_trip),
+        ];
+        for (name, supported) in capabilities {
+            match (supported, self.errors.get(name)) {
+                (true, _) => writeln!(f, "{name}: supported")?,
+                (false, Some(e)) => writeln!(f, "{name}: unsupported ({e})")?,
+                (false, None) => writeln!(f, "{name}: unsupported")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Runs a battery of small queries against the source and reports what it
+// supports, e.g. to configure the dialect of a source of unknown version
+// with CapabilityReport::apply, or to diagnose failing pushdowns.
+pub async fn probe_capabilities(executor: &dyn SQLExecutor) -> CapabilityReport {
+    let dialect = executor.dialect();
+    // Oracle can't select without a table
+    let dual = match dialect.name() {
+        "oracle" => " FROM DUAL",
+        _ => "",
+    };
+    let one = format!("(SELECT 1 AS x{dual}) t");
+    let limit = match dialect.limit_style() {
+        LimitStyle::LimitOffset => "LIMIT 1",
+        LimitStyle::OffsetFetch => "ORDER BY u.x OFFSET 0 ROWS FETCH NEXT 1 ROWS ONLY",
+        LimitStyle::FetchFirst => "FETCH FIRST 1 ROWS ONLY",
+    };
+
+    let mut report = CapabilityReport {
+        server_version: executor.server_version().await.ok().flatten(),
+        ..Default::default()
+    };
+    let mut probe = |name: &str, result: Result<bool>| match result {
+        Ok(supported) => supported,
+        Err(e) => {
+            report.errors.insert(name.to_string(), e.to_string());
+            false
+        }
+    };
+
+    let window = format!("SELECT ROW_NUMBER() OVER (ORDER BY t.x) AS n FROM {one}");
+    let window = probe(
+        "window_functions",
+        rows(executor, &window).await.map(|_| true),
+    );
+    let ilike = format!("SELECT t.x FROM {one} WHERE 'A' ILIKE 'a'");
+    let ilike = probe("ilike", rows(executor, &ilike).await.map(|n| n == 1));
+    let subquery = format!(
+        "SELECT t.x FROM {one} WHERE t.x IN (SELECT u.x FROM (SELECT 1 AS x{dual}) u {limit})"
+    );
+    let limit_in_subquery = probe(
+        "limit_in_subquery",
+        rows(executor, &subquery).await.map(|n| n == 1),
+    );
+    let distinct = format!("SELECT t.x FROM {one} WHERE t.x IS DISTINCT FROM NULL");
+    let is_distinct_from = probe(
+        "is_distinct_from",
+        rows(executor, &distinct).await.map(|n| n == 1),
+    );
+    let decimal = format!("SELECT CAST('{DECIMAL_PROBE}' AS DECIMAL(30, 10)) AS d{dual}");
+    let decimal_round_trip = probe(
+        "decimal_round_trip",
+        decimal_value(executor, &decimal).await,
+    );
+
+    report.window_functions = window;
+    report.ilike = ilike;
+    report.limit_in_subquery = limit_in_subquery;
+    report.is_distinct_from = is_distinct_from;
+    report.decimal_round_trip = decimal_round_trip;
+    debug!(
+        "federation rule=federate_sql capabilities context={:?} report={report:?}",
+        executor.compute_context()
+    );
+    report
+}
+
+async fn collect(executor: &dyn SQLExecutor, query: &str) -> Result<Vec<RecordBatch>> {
+    executor.execute(query).await?.try_collect().await
+}
+
+// The number of rows the query returns.
+async fn rows(executor: &dyn SQLExecutor, query: &str) -> Result<usize> {
+    let batches = collect(executor, query).await?;
+    Ok(batches.iter().map(|b| b.num_rows()).sum())
+}
+
+// Whether the decimal probe is read back with all its digits.
+async fn decimal_value(executor: &dyn SQLExecutor, query: &str) -> Result<bool> {
+    let batches = collect(executor, query).await?;
+    let Some(batch) = batches.iter().find(|b| b.num_rows() > 0) else {
+        return Ok(false);
+    };
+    let value = array_value_to_string(batch.column(0), 0)?;
+    Ok(value == DECIMAL_PROBE)
+}
+
+// ProbedDialect turns off the capabilities of a dialect a probe found missing.
+struct ProbedDialect {
+    dialect: Arc<dyn SQLDialect>,
+    report: CapabilityReport,
+}
+
+impl SQLDialect for ProbedDialect {
+    fn name(&self) -> &str {
+        self.dialect.name()
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        self.dialect.parser_dialect()
+    }
+
+    fn distinct_from(&self, l: SQLExpr, r: SQLExpr, not_distinct: bool) -> SQLExpr {
+        // Only the standard operators are probed, e.g. MySQL's <=> is kept
+        let expr = self
+            .dialect
+            .distinct_from(l.clone(), r.clone(), not_distinct);
+        match expr {
+            SQLExpr::IsDistinctFrom(..) | SQLExpr::IsNotDistinctFrom(..)
+                if !self.report.is_distinct_from =>
+            {
+                NullSafeFallbackDialect {}.distinct_from(l, r, not_distinct)
+            }
+            expr => expr,
+        }
+    }
+
+    fn supports_window_functions(&self) -> bool {
+        self.report.window_functions && self.dialect.supports_window_functions()
+    }
+
+    fn supports_plan(&self, plan: &LogicalPlan) -> bool {
+        self.dialect.supports_plan(plan)
+    }
+
+    fn version_query(&self) -> Option<&str> {
+        self.dialect.version_query()
+    }
+
+    fn set_variable(&self, name: &str, value: &str) -> String {
+        self.dialect.set_variable(name, value)
+    }
+
+    fn supports_limit_in_subquery(&self) -> bool {
+        self.report.limit_in_subquery && self.dialect.supports_limit_in_subquery()
+    }
+
+    fn max_statement_size(&self) -> Option<usize> {
+        self.dialect.max_statement_size()
+    }
+
+    fn supports_returning(&self) -> bool {
+        self.dialect.supports_returning()
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        self.dialect.upsert_strategy()
+    }
+
+    fn merge(
+        &self,
+        table: &str,
+        rows: &ast::Values,
+        columns: &[String],
+        on: &str,
+        clauses: &str,
+    ) -> String {
+        self.dialect.merge(table, rows, columns, on, clauses)
+    }
+
+    fn group_by_strategy(&self) -> GroupByStrategy {
+        self.dialect.group_by_strategy()
+    }
+
+    fn time_travel(&self, as_of: &AsOf) -> Option<String> {
+        self.dialect.time_travel(as_of)
+    }
+
+    fn unload(&self, query: &str, location: &str, options: Option<&str>) -> Option<String> {
+        self.dialect.unload(query, location, options)
+    }
+
+    fn load(&self, table: &str, location: &str, options: Option<&str>) -> Option<String> {
+        self.dialect.load(table, location, options)
+    }
+
+    fn empty_string_is_null(&self) -> bool {
+        self.dialect.empty_string_is_null()
+    }
+
+    fn supports_table_functions(&self) -> bool {
+        self.dialect.supports_table_functions()
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        self.dialect.identifier_quote_style()
+    }
+
+    fn limit_style(&self) -> LimitStyle {
+        self.dialect.limit_style()
+    }
+
+    fn supports_nulls_ordering(&self) -> bool {
+        self.dialect.supports_nulls_ordering()
+    }
+
+    fn binary_collation(&self) -> bool {
+        self.dialect.binary_collation()
+    }
+
+    fn supports_table_alias_as(&self) -> bool {
+        self.dialect.supports_table_alias_as()
+    }
+
+    fn cast_data_type(&self, data_type: &DataType) -> Option<ast::DataType> {
+        self.dialect.cast_data_type(data_type)
+    }
+
+    fn scalar_function(&self, name: &str, args: Vec<SQLExpr>) -> Option<SQLExpr> {
+        self.dialect.scalar_function(name, args)
+    }
+
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        self.dialect.sort_cost_profile()
+    }
+
+    fn date_literal(&self, date: &str) -> SQLExpr {
+        self.dialect.date_literal(date)
+    }
+
+    fn timestamp_literal(&self, timestamp: &str) -> SQLExpr {
+        self.dialect.timestamp_literal(timestamp)
+    }
+}
@@ -0,0 +1,280 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{array::Array, record_batch::RecordBatch, util::display::array_value_to_string},
+    common::not_impl_err,
+    error::{DataFusionError, Result},
+    physical_plan::SendableRecordBatchStream,
+    sql::sqlparser::{ast, parser::Parser},
+};
+use futures::TryStreamExt;
+
+use crate::{dialect::SQLDialect, executor::SQLExecutor};
+
+// ChunkingStrategy splits a query into chunks returning disjoint parts of its
+// result, each fetched as its own partition.
+#[async_trait]
+pub trait ChunkingStrategy: Send + Sync {
+    fn chunk_count(&self) -> usize;
+    async fn split(&self, executor: &dyn SQLExecutor, query: &str) -> Result<Vec<String>>;
+}
+
+// ChunkedExecutor splits the queries of its executor with the strategy,
+// instead of the executor's own partitioning.
+pub struct ChunkedExecutor {
+    executor: Arc<dyn SQLExecutor>,
+    strategy: Arc<dyn ChunkingStrategy>,
+}
+
+impl ChunkedExecutor {
+    pub fn new(executor: Arc<dyn SQLExecutor>, strategy: Arc<dyn ChunkingStrategy>) -> Self {
+        Self { executor, strategy }
+    }
+}
+
+#[async_trait]
+impl SQLExecutor for ChunkedExecutor {
+    fn name(&self) -> &str {
+        self.executor.name()
+    }
+    fn compute_context(&self) -> Option<String> {
+        self.executor.compute_context()
+    }
+    fn dialect(&self) -> Arc<dyn SQLDialect> {
+        self.executor.dialect()
+    }
+    async fn execute(&self, sql: &str) -> Result<SendableRecordBatchStream> {
+        self.executor.execute(sql).await
+    }
+    async fn execute_statement(&self, statement: &str) -> Result<u64> {
+        self.executor.execute_statement(statement).await
+    }
+    async fn execute_transaction(&self, statements: &[String]) -> Result<u64> {
+        self.executor.execute_transaction(statements).await
+    }
+    fn partition_count(&self) -> usize {
+        self.strategy.chunk_count()
+    }
+    async fn split_query(&self, query: &str) -> Result<Vec<String>> {
+        self.strategy.split(self.executor.as_ref(), query).await
+    }
+}
+
+// CtidChunking splits queries over a single PostgreSQL table into ranges of
+// its pages, read with TID range scans (PostgreSQL 14+). Unlike keyset
+// chunking it needs no indexed key, and unlike OFFSET it reads every page once.
+#[derive(Debug, Clone)]
+pub struct CtidChunking {
+    chunks: usize,
+}
+
+impl CtidChunking {
+    pub fn new(chunks: usize) -> Self {
+        Self {
+            chunks: chunks.max(1),
+        }
+    }
+}
+
+#[async_trait]
+impl ChunkingStrategy for CtidChunking {
+    fn chunk_count(&self) -> usize {
+        self.chunks
+    }
+
+    async fn split(&self, executor: &dyn SQLExecutor, query: &str) -> Result<Vec<String>> {
+        let table = SingleTableQuery::parse(executor.dialect().as_ref(), query)?;
+        let pages = format!(
+            "SELECT pg_relation_size('{}') / current_setting('block_size')::bigint",
+            table.name.replace('\'', "''")
+        );
+        let pages = first_row(executor, &pages).await?;
+        let pages = pages
+            .first()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or_default();
+        if pages == 0 {
+            return Ok(vec![query.to_string()]);
+        }
+        // The last range is open, pages added since are read by it
+        let per_chunk = pages.div_ceil(self.chunks as u64);
+        (0..self.chunks as u64)
+            .map(|i| {
+                let lo = format!("{}.ctid >= '({},0)'::tid", table.qualifier, i * per_chunk);
+                let predicate = match i + 1 == self.chunks as u64 {
+                    true => lo,
+                    false => format!(
+                        "{lo} AND {}.ctid < '({},0)'::tid",
+                        table.qualifier,
+                        (i + 1) * per_chunk
+                    ),
+                };
+                table.with_predicate(&predicate)
+            })
+            .collect()
+    }
+}
+
+// RowIdChunking splits queries over a single Oracle table into ROWID ranges
+// of its extents, in the manner of DBMS_PARALLEL_EXECUTE. Requires access
+// to USER_EXTENTS, i.e. tables of the connected schema.
+#[derive(Debug, Clone)]
+pub struct RowIdChunking {
+    chunks: usize,
+}
+
+impl RowIdChunking {
+    pub fn new(chunks: usize) -> Self {
+        Self {
+            chunks: chunks.max(1),
+        }
+    }
+}
+
+#[async_trait]
+impl ChunkingStrategy for RowIdChunking {
+    fn chunk_count(&self) -> usize {
+        self.chunks
+    }
+
+    async fn split(&self, executor: &dyn SQLExecutor, query: &str) -> Result<Vec<String>> {
+        let table = SingleTableQuery::parse(executor.dialect().as_ref(), query)?;
+        let segment = table
+            .name
+            .rsplit('.')
+            .next()
+            .unwrap_or_default()
+            .trim_matches('"')
+            .replace('\'', "''");
+        let extents = format!(
+            "SELECT DBMS_ROWID.ROWID_CREATE(1, o.data_object_id, e.relative_fno, e.block_id, 0), \
+             DBMS_ROWID.ROWID_CREATE(1, o.data_object_id, e.relative_fno, e.block_id + e.blocks - 1, 32767) \
+             FROM user_extents e JOIN user_objects o \
+             ON o.object_name = e.segment_name AND o.object_type = 'TABLE' \
+             WHERE e.segment_name = '{segment}' ORDER BY e.relative_fno, e.block_id"
+        );
+        let batches: Vec<RecordBatch> = executor.execute(&extents).await?.try_collect().await?;
+        let mut ranges = vec![];
+        for batch in &batches {
+            for row in 0..batch.num_rows() {
+                ranges.push((
+                    array_value_to_string(batch.column(0), row)?,
+                    array_value_to_string(batch.column(1), row)?,
+                ));
+            }
+        }
+        if ranges.is_empty() {
+            return Ok(vec![query.to_string()]);
+        }
+        // Contiguous extents are grouped into the chunks, chunks without
+        // extents read nothing
+        let per_chunk = ranges.len().div_ceil(self.chunks);
+        let mut queries = ranges
+            .chunks(per_chunk)
+            .map(|chunk| {
+                let predicate = chunk
+                    .iter()
+                    .map(|(lo, hi)| {
+                        format!(
+                            "{}.ROWID BETWEEN CHARTOROWID('{lo}') AND CHARTOROWID('{hi}')",
+                            table.qualifier
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" OR ");
+                table.with_predicate(&format!("({predicate})"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        queries.resize(self.chunks, table.with_predicate("1 = 0")?);
+        Ok(queries)
+    }
+}
+
+// SingleTableQuery is a SELECT reading a single table, the queries row id
+// chunking applies to.
+struct SingleTableQuery {
+    statement: ast::Statement,
+    // The table name as written in the query
+    name: String,
+    // The alias or name columns of the table are qualified with
+    qualifier: String,
+    dialect: Box<dyn datafusion::sql::sqlparser::dialect::Dialect>,
+}
+
+impl SingleTableQuery {
+    fn parse(dialect: &dyn SQLDialect, query: &str) -> Result<Self> {
+        let parser_dialect = dialect.parser_dialect();
+        let mut statements = Parser::parse_sql(parser_dialect.as_ref(), query)
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        let (Some(statement), true) = (statements.pop(), statements.is_empty()) else {
+            return not_impl_err!("row id chunking of {query}");
+        };
+        let ast::Statement::Query(q) = &statement else {
+            return not_impl_err!("row id chunking of {query}");
+        };
+        let ast::SetExpr::Select(select) = q.body.as_ref() else {
+            return not_impl_err!("row id chunking of {query}");
+        };
+        let [from] = select.from.as_slice() else {
+            return not_impl_err!("row id chunking of {query}");
+        };
+        let ast::TableFactor::Table {
+            name, alias, args, ..
+        } = &from.relation
+        else {
+            return not_impl_err!("row id chunking of {query}");
+        };
+        if !from.joins.is_empty() || args.is_some() {
+            return not_impl_err!("row id chunking of {query}");
+        }
+        let qualifier = match alias {
+            Some(alias) => alias.name.to_string(),
+            None => name.0.last().map(|i| i.to_string()).unwrap_or_default(),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            qualifier,
+            statement,
+            dialect: parser_dialect,
+        })
+    }
+
+    // Returns the query with the predicate added to its WHERE clause.
+    fn with_predicate(&self, predicate: &str) -> Result<String> {
+        let mut statement = self.statement.clone();
+        let predicate = Parser::new(self.dialect.as_ref())
+            .try_with_sql(predicate)
+            .and_then(|mut parser| parser.parse_expr())
+            .map_err(|e| DataFusionError::External(Box::new(e)))?;
+        if let ast::Statement::Query(q) = &mut statement {
+            if let ast::SetExpr::Select(select) = q.body.as_mut() {
+                select.selection = Some(match select.selection.take() {
+                    Some(selection) => ast::Expr::BinaryOp {
+                        left: Box::new(ast::Expr::Nested(Box::new(selection))),
+                        op: ast::BinaryOperator::And,
+                        right: Box::new(ast::Expr::Nested(Box::new(predicate))),
+                    },
+                    None => predicate,
+                });
+            }
+        }
+        Ok(format!("{statement}"))
+    }
+}
+
+// Returns the values of the first row of the query's result.
+async fn first_row(executor: &dyn SQLExecutor, query: &str) -> Result<Vec<String>> {
+    let batches: Vec<RecordBatch> = executor.execute(query).await?.try_collect().await?;
+    let Some(batch) = batches.iter().find(|b| b.num_rows() > 0) else {
+        return Ok(vec![]);
+    };
+    batch
+        .columns()
+        .iter()
+        .map(|column| match column.is_null(0) {
+            true => Ok(String::new()),
+            false => Ok(array_value_to_string(column, 0)?),
+        })
+        .collect()
+}
@@ -0,0 +1,34 @@
+use datafusion::physical_plan::{
+    metrics::{Count, ExecutionPlanMetricsSet, MetricBuilder},
+    stream::RecordBatchStreamAdapter,
+    SendableRecordBatchStream,
+};
+use futures::StreamExt;
+
+// Counts the bytes fetched per column in a `bytes_transferred.<column>`
+// metric, e.g. to find the wide columns dominating the transfer of a scan.
+// The bytes are the Arrow memory size of the batches the driver returned,
+// which approximates what was sent over the wire.
+pub(crate) fn column_bytes_stream(
+    stream: SendableRecordBatchStream,
+    metrics: &ExecutionPlanMetricsSet,
+    partition: usize,
+) -> SendableRecordBatchStream {
+    let schema = stream.schema();
+    let counters: Vec<Count> = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            MetricBuilder::new(metrics)
+                .counter(format!("bytes_transferred.{}", field.name()), partition)
+        })
+        .collect();
+    let stream = stream.inspect(move |batch| {
+        if let Ok(batch) = batch {
+            for (counter, column) in counters.iter().zip(batch.columns()) {
+                counter.add(column.get_array_memory_size());
+            }
+        }
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, stream))
+}
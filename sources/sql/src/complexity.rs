@@ -0,0 +1,262 @@
+use core::fmt;
+use std::sync::Arc;
+
+use datafusion::sql::sqlparser::ast::{self, Expr as SQLExpr};
+use log::warn;
+
+// SQLComplexity measures generated SQL. Oversized SQL is an early sign of
+// planner pathologies, e.g. IN lists or CASE expressions growing with data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SQLComplexity {
+    pub bytes: usize,
+    // The deepest nesting of queries, 1 for a query without subqueries
+    pub nesting_depth: usize,
+    // The comparisons and other predicates of WHERE, HAVING and JOIN ON
+    pub predicates: usize,
+}
+
+impl SQLComplexity {
+    pub fn measure(statement: &ast::Statement, sql: &str) -> Self {
+        let mut complexity = Self {
+            bytes: sql.len(),
+            ..Default::default()
+        };
+        if let ast::Statement::Query(query) = statement {
+            complexity.visit_query(query, 1);
+        }
+        complexity
+    }
+
+    fn visit_query(&mut self, query: &ast::Query, depth: usize) {
+        self.nesting_depth = self.nesting_depth.max(depth);
+        self.visit_set_expr(&query.body, depth);
+    }
+
+    fn visit_set_expr(&mut self, body: &ast::SetExpr, depth: usize) {
+        match body {
+            ast::SetExpr::Select(select) => {
+                for item in &select.projection {
+                    if let ast::SelectItem::UnnamedExpr(e)
+                    | ast::SelectItem::ExprWithAlias { expr: e, .. } = item
+                    {
+                        self.visit_expr(e, depth, false);
+                    }
+                }
+                for twj in &select.from {
+                    self.visit_table_factor(&twj.relation, depth);
+                    for join in &twj.joins {
+                        self.visit_table_factor(&join.relation, depth);
+                        if let Some(ast::JoinConstraint::On(on)) =
+                            join_constraint(&join.join_operator)
+                        {
+                            self.visit_expr(on, depth, true);
+                        }
+                    }
+                }
+                if let Some(selection) = &select.selection {
+                    self.visit_expr(selection, depth, true);
+                }
+                if let Some(having) = &select.having {
+                    self.visit_expr(having, depth, true);
+                }
+            }
+            ast::SetExpr::Query(query) => self.visit_query(query, depth + 1),
+            ast::SetExpr::SetOperation { left, right, .. } => {
+                self.visit_set_expr(left, depth);
+                self.visit_set_expr(right, depth);
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_table_factor(&mut self, relation: &ast::TableFactor, depth: usize) {
+        if let ast::TableFactor::Derived { subquery, .. } = relation {
+            self.visit_query(subquery, depth + 1);
+        }
+    }
+
+    // Counts the leaves of AND and OR trees as predicates when `predicate` is
+    // set, and follows subqueries.
+    fn visit_expr(&mut self, expr: &SQLExpr, depth: usize, predicate: bool) {
+        match expr {
+            SQLExpr::BinaryOp {
+                left,
+                op: ast::BinaryOperator::And | ast::BinaryOperator::Or,
+                right,
+            } => {
+                self.visit_expr(left, depth, predicate);
+                self.visit_expr(right, depth, predicate);
+            }
+            SQLExpr::Nested(e) | SQLExpr::UnaryOp { expr: e, .. } => {
+                self.visit_expr(e, depth, predicate)
+            }
+            SQLExpr::Subquery(query) => self.visit_query(query, depth + 1),
+            SQLExpr::Exists { subquery, .. } | SQLExpr::InSubquery { subquery, .. } => {
+                self.predicates += predicate as usize;
+                self.visit_query(subquery, depth + 1);
+            }
+            SQLExpr::Case {
+                conditions,
+                results,
+                else_result,
+                ..
+            } => {
+                self.predicates += predicate as usize;
+                for e in conditions.iter().chain(results) {
+                    self.visit_expr(e, depth, false);
+                }
+                if let Some(e) = else_result {
+                    self.visit_expr(e, depth, false);
+                }
+            }
+            SQLExpr::BinaryOp { left, right, .. } => {
+                self.predicates += predicate as usize;
+                self.visit_expr(left, depth, false);
+                self.visit_expr(right, depth, false);
+            }
+            _ => self.predicates += predicate as usize,
+        }
+    }
+}
+
+fn join_constraint(operator: &ast::JoinOperator) -> Option<&ast::JoinConstraint> {
+    match operator {
+        ast::JoinOperator::Inner(c)
+        | ast::JoinOperator::LeftOuter(c)
+        | ast::JoinOperator::RightOuter(c)
+        | ast::JoinOperator::FullOuter(c)
+        | ast::JoinOperator::LeftSemi(c)
+        | ast::JoinOperator::RightSemi(c)
+        | ast::JoinOperator::LeftAnti(c)
+        | ast::JoinOperator::RightAnti(c) => Some(c),
+        _ => None,
+    }
+}
+
+// SQLComplexityThresholds are the limits generated SQL is alerted on, None
+// doesn't limit that measure.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SQLComplexityThresholds {
+    pub max_bytes: Option<usize>,
+    pub max_nesting_depth: Option<usize>,
+    pub max_predicates: Option<usize>,
+}
+
+impl SQLComplexityThresholds {
+    // The names of the measures over their threshold.
+    pub fn exceeded(&self, complexity: &SQLComplexity) -> Vec<&'static str> {
+        let over = |value: usize, max: Option<usize>| max.is_some_and(|max| value > max);
+        let mut exceeded = vec![];
+        if over(complexity.bytes, self.max_bytes) {
+            exceeded.push("bytes");
+        }
+        if over(complexity.nesting_depth, self.max_nesting_depth) {
+            exceeded.push("nesting_depth");
+        }
+        if over(complexity.predicates, self.max_predicates) {
+            exceeded.push("predicates");
+        }
+        exceeded
+    }
+}
+
+// SQLComplexityAlert describes generated SQL over a threshold.
+#[derive(Debug, Clone)]
+pub struct SQLComplexityAlert {
+    pub compute_context: Option<String>,
+    pub sql: String,
+    pub complexity: SQLComplexity,
+    pub exceeded: Vec<&'static str>,
+}
+
+pub trait SQLComplexityObserver: Send + Sync {
+    fn alert(&self, alert: &SQLComplexityAlert);
+}
+
+impl<F> SQLComplexityObserver for F
+where
+    F: Fn(&SQLComplexityAlert) + Send + Sync,
+{
+    fn alert(&self, alert: &SQLComplexityAlert) {
+        self(alert)
+    }
+}
+
+// SQLComplexityAlerts warns about and reports generated SQL over the thresholds.
+pub struct SQLComplexityAlerts {
+    thresholds: SQLComplexityThresholds,
+    observer: Option<Arc<dyn SQLComplexityObserver>>,
+}
+
+impl SQLComplexityAlerts {
+    pub fn new(thresholds: SQLComplexityThresholds) -> Self {
+        Self {
+            thresholds,
+            observer: None,
+        }
+    }
+
+    pub fn with_observer(mut self, observer: Arc<dyn SQLComplexityObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    pub(crate) fn check(
+        &self,
+        compute_context: Option<String>,
+        sql: &str,
+        complexity: SQLComplexity,
+    ) {
+        let exceeded = self.thresholds.exceeded(&complexity);
+        if exceeded.is_empty() {
+            return;
+        }
+        warn!(
+            "federation sql_complexity context={compute_context:?} exceeded={} bytes={} nesting_depth={} predicates={}",
+            exceeded.join(","),
+            complexity.bytes,
+            complexity.nesting_depth,
+            complexity.predicates
+        );
+        if let Some(observer) = &self.observer {
+            observer.alert(&SQLComplexityAlert {
+                compute_context,
+                sql: sql.to_string(),
+                complexity,
+                exceeded,
+            });
+        }
+    }
+}
+
+impl fmt::Debug for SQLComplexityAlerts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SQLComplexityAlerts({:?})", self.thresholds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use datafusion::sql::sqlparser::{dialect::GenericDialect, parser::Parser};
+
+    #[test]
+    fn test_sql_complexity() {
+        let sql = "SELECT a FROM (SELECT a, b FROM t WHERE b > 1) AS d \
+                   WHERE a = 1 AND (b < 2 OR a IN (SELECT c FROM u WHERE c IS NOT NULL))";
+        let statement = Parser::parse_sql(&GenericDialect {}, sql)
+            .unwrap()
+            .remove(0);
+        let complexity = SQLComplexity::measure(&statement, sql);
+        assert_eq!(complexity.bytes, sql.len());
+        assert_eq!(complexity.nesting_depth, 2);
+        assert_eq!(complexity.predicates, 5);
+
+        let thresholds = SQLComplexityThresholds {
+            max_predicates: Some(4),
+            max_nesting_depth: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(thresholds.exceeded(&complexity), vec!["predicates"]);
+    }
+}
@@ -0,0 +1,268 @@
+use core::fmt;
+use std::{collections::HashMap, sync::Arc};
+
+use datafusion::{
+    common::{
+        plan_err,
+        tree_node::{TreeNode, VisitRecursion},
+    },
+    error::Result,
+    execution::context::SessionState,
+    logical_expr::LogicalPlan,
+};
+use log::debug;
+
+use crate::Tenant;
+
+// RemoteCost is what a query is estimated to scan in the source.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RemoteCost {
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+// TableStatistics are the sizes of remote tables by name, e.g. read from the
+// catalog of the source, that queries' scans are estimated with.
+#[derive(Debug, Clone, Default)]
+pub struct TableStatistics {
+    tables: HashMap<String, RemoteCost>,
+    // The size assumed for tables without statistics
+    default: RemoteCost,
+}
+
+impl TableStatistics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_table(mut self, table: impl Into<String>, rows: u64, bytes: u64) -> Self {
+        self.tables.insert(table.into(), RemoteCost { rows, bytes });
+        self
+    }
+
+    pub fn with_default(mut self, rows: u64, bytes: u64) -> Self {
+        self.default = RemoteCost { rows, bytes };
+        self
+    }
+
+    // Sums the scanned tables, the bytes of a table in proportion to the
+    // columns read. Filters are ignored, warehouses bill whole columns.
+    pub fn estimate(&self, plan: &LogicalPlan) -> RemoteCost {
+        let mut cost = RemoteCost::default();
+        let _ = plan.apply(&mut |plan| {
+            if let LogicalPlan::TableScan(scan) = plan {
+                let name = scan.table_name.to_string();
+                let table = self
+                    .tables
+                    .get(&name)
+                    .or_else(|| self.tables.get(scan.table_name.table()))
+                    .unwrap_or(&self.default);
+                let columns = scan.source.schema().fields().len().max(1) as u64;
+                let read = scan.projection.as_ref().map_or(columns, |p| p.len() as u64);
+                cost.rows += table.rows;
+                cost.bytes += table.bytes / columns * read;
+            }
+            Ok(VisitRecursion::Continue)
+        });
+        cost
+    }
+}
+
+// CostConfirmation decides whether a query over its budget runs anyway,
+// e.g. after asking the user.
+pub trait CostConfirmation: Send + Sync {
+    fn confirm(&self, cost: &RemoteCost, sql: &str) -> bool;
+}
+
+impl<F> CostConfirmation for F
+where
+    F: Fn(&RemoteCost, &str) -> bool + Send + Sync,
+{
+    fn confirm(&self, cost: &RemoteCost, sql: &str) -> bool {
+        self(cost, sql)
+    }
+}
+
+// CostBudget limits what a query may scan in a source before it is sent.
+// Queries over the budget are rejected unless confirmed. A session's budget
+// is read from the session config extensions, overriding the provider's:
+// `SessionConfig::new().with_extension(Arc::new(CostBudget::new().with_max_bytes(1 << 40)))`
+#[derive(Clone, Default)]
+pub struct CostBudget {
+    pub max_rows: Option<u64>,
+    pub max_bytes: Option<u64>,
+    confirmation: Option<Arc<dyn CostConfirmation>>,
+}
+
+impl CostBudget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_rows(mut self, rows: u64) -> Self {
+        self.max_rows = Some(rows);
+        self
+    }
+
+    pub fn with_max_bytes(mut self, bytes: u64) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    pub fn with_confirmation(mut self, confirmation: Arc<dyn CostConfirmation>) -> Self {
+        self.confirmation = Some(confirmation);
+        self
+    }
+
+    fn exceeded_by(&self, cost: &RemoteCost) -> bool {
+        self.max_rows.is_some_and(|max| cost.rows > max)
+            || self.max_bytes.is_some_and(|max| cost.bytes > max)
+    }
+}
+
+impl fmt::Debug for CostBudget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CostBudget")
+            .field("max_rows", &self.max_rows)
+            .field("max_bytes", &self.max_bytes)
+            .field("confirmation", &self.confirmation.is_some())
+            .finish()
+    }
+}
+
+// CostBudgets are the budgets of a source by tenant, see Tenant, with a
+// default for other tenants.
+#[derive(Debug, Clone, Default)]
+pub struct CostBudgets {
+    pub statistics: TableStatistics,
+    pub default: Option<CostBudget>,
+    pub tenants: HashMap<String, CostBudget>,
+}
+
+impl CostBudgets {
+    pub fn new(statistics: TableStatistics) -> Self {
+        Self {
+            statistics,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_default(mut self, budget: CostBudget) -> Self {
+        self.default = Some(budget);
+        self
+    }
+
+    pub fn with_tenant(mut self, tenant: impl Into<String>, budget: CostBudget) -> Self {
+        self.tenants.insert(tenant.into(), budget);
+        self
+    }
+
+    // Admits the plan if its estimated cost is within the budget of the
+    // session or its tenant, before anything is sent to the source.
+    pub(crate) fn admit(&self, plan: &LogicalPlan, sql: &str, state: &SessionState) -> Result<()> {
+        let config = state.config();
+        let session = config.get_extension::<CostBudget>();
+        let tenant = config.get_extension::<Tenant>();
+        let budget = match (&session, &tenant) {
+            (Some(budget), _) => Some(budget.as_ref()),
+            (None, Some(tenant)) => self.tenants.get(&tenant.0).or(self.default.as_ref()),
+            (None, None) => self.default.as_ref(),
+        };
+        let Some(budget) = budget else {
+            return Ok(());
+        };
+        let cost = self.statistics.estimate(plan);
+        if !budget.exceeded_by(&cost) {
+            return Ok(());
+        }
+        if let Some(confirmation) = &budget.confirmation {
+            if confirmation.confirm(&cost, sql) {
+                debug!(
+                    "federation rule=federate_sql decision=admit reason=confirmed rows={} bytes={}",
+                    cost.rows, cost.bytes
+                );
+                return Ok(());
+            }
+        }
+        plan_err!(
+            "query estimated to scan {} rows and {} bytes exceeds the remote cost budget of {} rows and {} bytes\nsql: {sql}",
+            cost.rows,
+            cost.bytes,
+            budget.max_rows.map_or("unlimited".to_string(), |r| r.to_string()),
+            budget.max_bytes.map_or("unlimited".to_string(), |b| b.to_string())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::datatypes::{DataType, Field, Schema},
+        execution::context::{SessionConfig, SessionContext},
+        logical_expr::table_scan,
+    };
+
+    use super::*;
+
+    fn scan(projection: Option<Vec<usize>>) -> LogicalPlan {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]);
+        table_scan(Some("events"), &schema, projection)
+            .unwrap()
+            .build()
+            .unwrap()
+    }
+
+    fn state(config: SessionConfig) -> SessionState {
+        SessionContext::new_with_config(config).state()
+    }
+
+    #[test]
+    fn test_estimate() {
+        let statistics = TableStatistics::new()
+            .with_table("events", 100, 1000)
+            .with_default(1, 10);
+        let cost = RemoteCost {
+            rows: 100,
+            bytes: 500,
+        };
+        assert_eq!(statistics.estimate(&scan(Some(vec![1]))), cost);
+        let cost = RemoteCost {
+            rows: 100,
+            bytes: 1000,
+        };
+        assert_eq!(statistics.estimate(&scan(None)), cost);
+    }
+
+    #[test]
+    fn test_admit() {
+        let statistics = TableStatistics::new().with_table("events", 100, 1000);
+        let budgets = CostBudgets::new(statistics)
+            .with_default(CostBudget::new().with_max_rows(10))
+            .with_tenant("etl", CostBudget::new().with_max_rows(1000));
+        let plan = scan(None);
+        let sql = "SELECT * FROM events";
+
+        assert!(budgets
+            .admit(&plan, sql, &state(SessionConfig::new()))
+            .is_err());
+        let tenant = SessionConfig::new().with_extension(Arc::new(Tenant("etl".to_string())));
+        assert!(budgets.admit(&plan, sql, &state(tenant)).is_ok());
+
+        // The session's budget overrides the tenant's, confirmed queries run
+        let confirm = |cost: &RemoteCost, _sql: &str| cost.bytes <= 1000;
+        let session = SessionConfig::new()
+            .with_extension(Arc::new(Tenant("etl".to_string())))
+            .with_extension(Arc::new(
+                CostBudget::new()
+                    .with_max_bytes(100)
+                    .with_confirmation(Arc::new(confirm)),
+            ));
+        assert!(budgets.admit(&plan, sql, &state(session)).is_ok());
+        let session =
+            SessionConfig::new().with_extension(Arc::new(CostBudget::new().with_max_bytes(100)));
+        assert!(budgets.admit(&plan, sql, &state(session)).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{datatypes::SchemaRef, record_batch::RecordBatch},
+    error::Result,
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use log::debug;
+
+use crate::{
+    dialect::SQLDialect,
+    executor::{SQLConnection, SQLExecutor},
+};
+
+// CursorExecutor streams query results through a PostgreSQL server-side
+// cursor, fetching `fetch_size` rows at a time on a dedicated connection.
+// Memory stays bounded by the fetch size even with drivers that buffer
+// whole results. The executor must support SQLExecutor::connect.
+pub struct CursorExecutor {
+    executor: Arc<dyn SQLExecutor>,
+    fetch_size: usize,
+}
+
+impl CursorExecutor {
+    pub fn new(executor: Arc<dyn SQLExecutor>, fetch_size: usize) -> Self {
+        Self {
+            executor,
+            fetch_size: fetch_size.max(1),
+        }
+    }
+}
+
+// Fetches the next rows of the cursor, the cursor is exhausted once fewer
+// than `fetch_size` rows are returned.
+async fn fetch(
+    connection: &mut Box<dyn SQLConnection>,
+    fetch_size: usize,
+) -> Result<(SchemaRef, Vec<RecordBatch>, bool)> {
+    let stream = connection
+        .execute(&format!(
+            "FETCH FORWARD {fetch_size} FROM federation_cursor"
+        ))
+        .await?;
+    let schema = stream.schema();
+    let batches: Vec<RecordBatch> = stream.try_collect().await?;
+    let exhausted = batches.iter().map(|b| b.num_rows()).sum::<usize>() < fetch_size;
+    Ok((schema, batches, exhausted))
+}
+
+#[async_trait]
+impl SQLExecutor for CursorExecutor {
+    fn name(&self) -> &str {
+        self.executor.name()
+    }
+    fn compute_context(&self) -> Option<String> {
+        self.executor.compute_context()
+    }
+    fn dialect(&self) -> Arc<dyn SQLDialect> {
+        self.executor.dialect()
+    }
+    async fn execute(&self, query: &str) -> Result<SendableRecordBatchStream> {
+        let mut connection = self.executor.connect().await?;
+        // Cursors only live within a transaction
+        connection.execute_statement("BEGIN READ ONLY").await?;
+        connection
+            .execute_statement(&format!(
+                "DECLARE federation_cursor NO SCROLL CURSOR FOR {query}"
+            ))
+            .await?;
+        debug!(
+            "federation decision=cursor context={:?} fetch_size={}",
+            self.executor.compute_context(),
+            self.fetch_size
+        );
+
+        // The first fetch provides the schema
+        let (schema, first, exhausted) = fetch(&mut connection, self.fetch_size).await?;
+        let fetch_size = self.fetch_size;
+        let batches = stream::try_unfold(
+            (connection, exhausted),
+            move |(mut connection, exhausted)| async move {
+                if exhausted {
+                    connection
+                        .execute_statement("CLOSE federation_cursor")
+                        .await?;
+                    connection.execute_statement("COMMIT").await?;
+                    return Ok(None);
+                }
+                let (_, next, exhausted) = fetch(&mut connection, fetch_size).await?;
+                Ok(Some((
+                    stream::iter(next.into_iter().map(Ok)),
+                    (connection, exhausted),
+                )))
+            },
+        )
+        .try_flatten();
+        let stream = stream::iter(first.into_iter().map(Ok)).chain(batches);
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+    }
+    async fn execute_statement(&self, statement: &str) -> Result<u64> {
+        self.executor.execute_statement(statement).await
+    }
+    async fn execute_transaction(&self, statements: &[String]) -> Result<u64> {
+        self.executor.execute_transaction(statements).await
+    }
+    async fn connect(&self) -> Result<Box<dyn SQLConnection>> {
+        self.executor.connect().await
+    }
+    fn partition_count(&self) -> usize {
+        self.executor.partition_count()
+    }
+    async fn split_query(&self, query: &str) -> Result<Vec<String>> {
+        self.executor.split_query(query).await
+    }
+}
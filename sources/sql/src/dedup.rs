@@ -0,0 +1,128 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{datatypes::SchemaRef, record_batch::RecordBatch},
+    error::{DataFusionError, Result},
+    physical_plan::{memory::MemoryStream, SendableRecordBatchStream},
+};
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt, TryFutureExt, TryStreamExt,
+};
+
+use crate::{
+    dialect::SQLDialect,
+    executor::{SQLConnection, SQLExecutor},
+    RemoteWarnings, SQLSourceAdmin, ServerVersion,
+};
+
+type SharedResult =
+    Shared<BoxFuture<'static, Result<(SchemaRef, Arc<Vec<RecordBatch>>), Arc<DataFusionError>>>>;
+
+// DedupExecutor coalesces byte-identical queries issued while the first one
+// runs, for at most `window`, into a single remote execution, fanning the
+// result out to every caller. Useful for dashboard stampedes. Results are
+// buffered to allow the fan-out, finished results aren't served again.
+pub struct DedupExecutor {
+    executor: Arc<dyn SQLExecutor>,
+    window: Duration,
+    in_flight: Mutex<HashMap<String, (Instant, SharedResult)>>,
+}
+
+impl DedupExecutor {
+    pub fn new(executor: Arc<dyn SQLExecutor>, window: Duration) -> Self {
+        Self {
+            executor,
+            window,
+            in_flight: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn shared_result(&self, sql: &str) -> SharedResult {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let now = Instant::now();
+        in_flight.retain(|_, (started, _)| now.duration_since(*started) < self.window);
+        if let Some((_, shared)) = in_flight.get(sql) {
+            return shared.clone();
+        }
+
+        let executor = self.executor.clone();
+        let query = sql.to_string();
+        let shared = async move {
+            let stream = executor.execute(&query).await?;
+            let schema = stream.schema();
+            let batches = stream.try_collect::<Vec<_>>().await?;
+            Ok::<_, DataFusionError>((schema, Arc::new(batches)))
+        }
+        .map_err(Arc::new)
+        .boxed()
+        .shared();
+        in_flight.insert(sql.to_string(), (now, shared.clone()));
+        shared
+    }
+}
+
+#[async_trait]
+impl SQLExecutor for DedupExecutor {
+    fn name(&self) -> &str {
+        self.executor.name()
+    }
+    fn compute_context(&self) -> Option<String> {
+        self.executor.compute_context()
+    }
+    fn dialect(&self) -> Arc<dyn SQLDialect> {
+        self.executor.dialect()
+    }
+    async fn execute(&self, sql: &str) -> Result<SendableRecordBatchStream> {
+        let shared = self.shared_result(sql);
+        let result = shared.clone().await;
+        self.in_flight
+            .lock()
+            .unwrap()
+            .retain(|_, (_, other)| !other.ptr_eq(&shared));
+        let (schema, batches) = result.map_err(|e| DataFusionError::External(Box::new(e)))?;
+        Ok(Box::pin(MemoryStream::try_new(
+            batches.as_ref().clone(),
+            schema,
+            None,
+        )?))
+    }
+    // Statements aren't deduplicated
+    async fn execute_statement(&self, statement: &str) -> Result<u64> {
+        self.executor.execute_statement(statement).await
+    }
+    async fn execute_transaction(&self, statements: &[String]) -> Result<u64> {
+        self.executor.execute_transaction(statements).await
+    }
+    // Notices are reported by each execution, such queries aren't deduplicated
+    async fn execute_with_warnings(
+        &self,
+        query: &str,
+        warnings: RemoteWarnings,
+    ) -> Result<SendableRecordBatchStream> {
+        self.executor.execute_with_warnings(query, warnings).await
+    }
+    async fn copy_in(&self, statement: &str, data: Vec<u8>) -> Result<u64> {
+        self.executor.copy_in(statement, data).await
+    }
+    async fn connect(&self) -> Result<Box<dyn SQLConnection>> {
+        self.executor.connect().await
+    }
+    async fn server_version(&self) -> Result<Option<ServerVersion>> {
+        self.executor.server_version().await
+    }
+    fn admin(&self) -> Option<&dyn SQLSourceAdmin> {
+        self.executor.admin()
+    }
+    fn partition_count(&self) -> usize {
+        self.executor.partition_count()
+    }
+    async fn split_query(&self, query: &str) -> Result<Vec<String>> {
+        self.executor.split_query(query).await
+    }
+}
@@ -0,0 +1,89 @@
+use datafusion::{
+    error::Result,
+    logical_expr::{LogicalPlan, LogicalPlanBuilder},
+};
+
+// DefaultLimit overrides the source's default limit for a session, None
+// fetching every row. It is read from the session config extensions:
+// `SessionConfig::new().with_extension(Arc::new(DefaultLimit(None)))`
+// In SQL, `SET federation.default_limit = 'none'` or to a number of rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DefaultLimit(pub Option<usize>);
+
+// Limits the plan to `limit` rows unless its result is already bounded by a
+// limit or an aggregation at its top. Limits further down, e.g. in a
+// subquery, don't bound the rows of the outer query.
+pub(crate) fn apply_default_limit(plan: &LogicalPlan, limit: usize) -> Result<Option<LogicalPlan>> {
+    let mut top = plan;
+    loop {
+        top = match top {
+            LogicalPlan::Projection(projection) => projection.input.as_ref(),
+            LogicalPlan::Filter(filter) => filter.input.as_ref(),
+            LogicalPlan::Sort(sort) if sort.fetch.is_none() => sort.input.as_ref(),
+            LogicalPlan::Limit(_) | LogicalPlan::Aggregate(_) | LogicalPlan::Sort(_) => {
+                return Ok(None)
+            }
+            _ => break,
+        };
+    }
+    let plan = LogicalPlanBuilder::from(plan.clone())
+        .limit(0, Some(limit))?
+        .build()?;
+    Ok(Some(plan))
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::datatypes::{DataType, Field, Schema},
+        logical_expr::{col, lit, table_scan, Expr},
+    };
+
+    use super::*;
+
+    fn scan_table(name: &str) -> LogicalPlanBuilder {
+        let schema = Schema::new(vec![Field::new("id", DataType::Int64, false)]);
+        table_scan(Some(name), &schema, None).unwrap()
+    }
+
+    fn scan() -> LogicalPlanBuilder {
+        scan_table("t")
+    }
+
+    #[test]
+    fn test_apply_default_limit() {
+        let limited = |plan: LogicalPlan| apply_default_limit(&plan, 10).unwrap().is_some();
+
+        assert!(limited(scan().build().unwrap()));
+        assert!(limited(
+            scan()
+                .filter(col("id").gt(lit(1)))
+                .unwrap()
+                .sort(vec![col("id").sort(true, false)])
+                .unwrap()
+                .build()
+                .unwrap()
+        ));
+        assert!(!limited(
+            scan()
+                .limit(0, Some(5))
+                .unwrap()
+                .project(vec![col("id")])
+                .unwrap()
+                .build()
+                .unwrap()
+        ));
+        assert!(!limited(
+            scan()
+                .aggregate(vec![col("id")], Vec::<Expr>::new())
+                .unwrap()
+                .build()
+                .unwrap()
+        ));
+
+        // The limit of the subquery doesn't bound the outer scan
+        let subquery = scan_table("s").limit(0, Some(5)).unwrap().build().unwrap();
+        let plan = scan().cross_join(subquery).unwrap().build().unwrap();
+        assert!(limited(plan));
+    }
+}
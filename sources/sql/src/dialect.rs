@@ -0,0 +1,1064 @@
+use core::fmt;
+
+use datafusion::{
+    arrow::datatypes::DataType,
+    logical_expr::LogicalPlan,
+    sql::sqlparser::{
+        ast::{self, Expr as SQLExpr},
+        dialect as parser,
+    },
+};
+
+use crate::SortCostProfile;
+
+mod registry;
+pub use registry::*;
+
+// SQLDialect adjusts the generated SQL to what the remote engine accepts.
+pub trait SQLDialect: Send + Sync {
+    fn name(&self) -> &str;
+
+    // The sqlparser dialect accepting the engine's SQL, used to validate generated SQL.
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        Box::new(parser::GenericDialect {})
+    }
+
+    // Renders the null-safe comparison `l IS [NOT] DISTINCT FROM r`,
+    // `not_distinct` is the null-safe equality used for join keys.
+    fn distinct_from(&self, l: SQLExpr, r: SQLExpr, not_distinct: bool) -> SQLExpr {
+        if not_distinct {
+            SQLExpr::IsNotDistinctFrom(Box::new(l), Box::new(r))
+        } else {
+            SQLExpr::IsDistinctFrom(Box::new(l), Box::new(r))
+        }
+    }
+
+    // Whether LIMIT is accepted in IN/EXISTS subqueries and derived tables,
+    // otherwise it is rewritten to a ROW_NUMBER() filter.
+    fn supports_limit_in_subquery(&self) -> bool {
+        true
+    }
+
+    // The maximum size of a statement in bytes, larger queries are split.
+    fn max_statement_size(&self) -> Option<usize> {
+        None
+    }
+
+    // Whether window functions are pushed down, otherwise window nodes run locally.
+    fn supports_window_functions(&self) -> bool {
+        true
+    }
+
+    // Whether the engine runs the plan, e.g. engines querying files in place
+    // only filter and project a single file. The nodes of plans it doesn't
+    // run are executed locally, their inputs are federated.
+    fn supports_plan(&self, _plan: &LogicalPlan) -> bool {
+        true
+    }
+
+    // The query returning the server version in its first column, None if
+    // the version isn't detected.
+    fn version_query(&self) -> Option<&str> {
+        None
+    }
+
+    // The statement setting a variable of the remote session, the value is
+    // already rendered as a literal.
+    fn set_variable(&self, name: &str, value: &str) -> String {
+        format!("SET {name} = {value}")
+    }
+
+    // Whether INSERT/UPDATE/DELETE accept `RETURNING *`.
+    fn supports_returning(&self) -> bool {
+        false
+    }
+
+    // How upserts are rendered, None if the engine has no upsert.
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        None
+    }
+
+    // Renders an upsert with the Merge strategy. The rows are merged as
+    // `source` into the table as `target`, the ON condition and the WHEN
+    // clauses refer to both.
+    fn merge(
+        &self,
+        table: &str,
+        rows: &ast::Values,
+        columns: &[String],
+        on: &str,
+        clauses: &str,
+    ) -> String {
+        format!(
+            "MERGE INTO {table} AS target USING ({rows}) AS source ({}) ON {on} {clauses}",
+            columns.join(", ")
+        )
+    }
+
+    // How computed GROUP BY keys refer to the select list.
+    fn group_by_strategy(&self) -> GroupByStrategy {
+        GroupByStrategy::Expression
+    }
+
+    // The clause following a table name to read it as of a past point in
+    // time, None if the engine has no time travel.
+    fn time_travel(&self, _as_of: &AsOf) -> Option<String> {
+        None
+    }
+
+    // The statement writing the query's result as Parquet files under the
+    // location, None if the engine can't unload to object storage. `options`
+    // are extra clauses, e.g. credentials.
+    fn unload(&self, _query: &str, _location: &str, _options: Option<&str>) -> Option<String> {
+        None
+    }
+
+    // The statement loading the Parquet files under the location into the
+    // table, None if the engine can't load from object storage. `options`
+    // are extra clauses, e.g. credentials.
+    fn load(&self, _table: &str, _location: &str, _options: Option<&str>) -> Option<String> {
+        None
+    }
+
+    // Returns true if the engine stores the empty string as NULL, comparisons
+    // with '' are then rendered as NULL checks.
+    fn empty_string_is_null(&self) -> bool {
+        false
+    }
+
+    // Returns true if table functions can be called in FROM, as `fn(args)`.
+    fn supports_table_functions(&self) -> bool {
+        true
+    }
+
+    // The character identifiers are quoted with, None leaves them unquoted.
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('`')
+    }
+
+    // How LIMIT and OFFSET are rendered.
+    fn limit_style(&self) -> LimitStyle {
+        LimitStyle::LimitOffset
+    }
+
+    // Whether ORDER BY accepts NULLS FIRST/LAST, otherwise the null ordering
+    // is rendered as a leading sort key.
+    fn supports_nulls_ordering(&self) -> bool {
+        true
+    }
+
+    // Whether strings are compared by their bytes by default, as in Arrow.
+    // Otherwise the remote ordering of strings isn't relied on.
+    fn binary_collation(&self) -> bool {
+        false
+    }
+
+    // Whether table aliases are rendered as `table AS alias`, otherwise as
+    // `table alias`.
+    fn supports_table_alias_as(&self) -> bool {
+        true
+    }
+
+    // The engine type a CAST to the DataFusion type is rendered as,
+    // None if the cast isn't pushed down.
+    fn cast_data_type(&self, data_type: &DataType) -> Option<ast::DataType> {
+        ansi_data_type(data_type)
+    }
+
+    // Renders a call of the DataFusion scalar function `name`,
+    // None if the function isn't pushed down.
+    fn scalar_function(&self, name: &str, args: Vec<SQLExpr>) -> Option<SQLExpr> {
+        ansi_function(name, args)
+    }
+
+    // The default costs deciding whether ORDER BY is pushed down.
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        SortCostProfile::default()
+    }
+
+    // Renders a date given as `YYYY-MM-DD`. Literals are explicitly typed, so they
+    // don't depend on the session's date style settings.
+    fn date_literal(&self, date: &str) -> SQLExpr {
+        cast_literal(date, ast::DataType::Date)
+    }
+
+    // Renders a timestamp without time zone given as `YYYY-MM-DD HH:MM:SS[.fraction]`.
+    fn timestamp_literal(&self, timestamp: &str) -> SQLExpr {
+        cast_literal(
+            timestamp,
+            ast::DataType::Timestamp(None, ast::TimezoneInfo::None),
+        )
+    }
+}
+
+// Renders `CAST('value' AS data_type)`.
+pub fn cast_literal(value: &str, data_type: ast::DataType) -> SQLExpr {
+    SQLExpr::Cast {
+        expr: Box::new(SQLExpr::Value(ast::Value::SingleQuotedString(
+            value.to_string(),
+        ))),
+        data_type,
+        format: None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitStyle {
+    // LIMIT n OFFSET m
+    LimitOffset,
+    // OFFSET m ROWS FETCH NEXT n ROWS ONLY, which requires an ORDER BY
+    OffsetFetch,
+    // OFFSET m ROWS FETCH FIRST n ROWS ONLY, without ORDER BY
+    FetchFirst,
+}
+
+// Renders `name(args)`, or the bare name for niladic SQL functions
+// like CURRENT_TIMESTAMP when `special` is set.
+pub fn function_call(name: &str, args: Vec<SQLExpr>, special: bool) -> SQLExpr {
+    SQLExpr::Function(ast::Function {
+        name: ast::ObjectName(vec![ast::Ident::new(name)]),
+        args: args
+            .into_iter()
+            .map(|arg| ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(arg)))
+            .collect(),
+        filter: None,
+        null_treatment: None,
+        over: None,
+        distinct: false,
+        special,
+        order_by: vec![],
+    })
+}
+
+// The functions every supported engine has under their standard name.
+// Dialects of other crates can fall back to it for functions they don't map.
+pub fn ansi_function(name: &str, args: Vec<SQLExpr>) -> Option<SQLExpr> {
+    match name {
+        "now" => Some(function_call("CURRENT_TIMESTAMP", vec![], true)),
+        "abs" | "ceil" | "floor" | "round" | "lower" | "upper" | "coalesce" | "nullif"
+        | "character_length" => Some(function_call(&name.to_uppercase(), args, false)),
+        _ => None,
+    }
+}
+
+// The standard SQL type of the DataFusion type, None if it has none.
+pub fn ansi_data_type(data_type: &DataType) -> Option<ast::DataType> {
+    match data_type {
+        DataType::Boolean => Some(ast::DataType::Boolean),
+        DataType::Int8 | DataType::Int16 => Some(ast::DataType::SmallInt(None)),
+        DataType::Int32 => Some(ast::DataType::Int(None)),
+        DataType::Int64 => Some(ast::DataType::BigInt(None)),
+        DataType::Float32 => Some(ast::DataType::Real),
+        DataType::Float64 => Some(ast::DataType::Double),
+        DataType::Utf8 | DataType::LargeUtf8 => Some(ast::DataType::Varchar(None)),
+        DataType::Date32 | DataType::Date64 => Some(ast::DataType::Date),
+        DataType::Timestamp(_, None) => {
+            Some(ast::DataType::Timestamp(None, ast::TimezoneInfo::None))
+        }
+        DataType::Decimal128(precision, scale) if *scale >= 0 => Some(ast::DataType::Decimal(
+            ast::ExactNumberInfo::PrecisionAndScale(*precision as u64, *scale as u64),
+        )),
+        _ => None,
+    }
+}
+
+// AsOf is the point in time a table is read at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsOf {
+    // A timestamp literal, e.g. `2024-01-01 00:00:00`
+    Timestamp(String),
+    // An engine specific snapshot, e.g. an Iceberg snapshot id
+    Snapshot(String),
+}
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// Renders the rows as `SELECT ... UNION ALL SELECT ...` with the columns
+// as aliases, for engines without VALUES tables.
+fn select_rows(rows: &ast::Values, columns: &[String], from: &str) -> String {
+    rows.rows
+        .iter()
+        .map(|row| {
+            let items = row
+                .iter()
+                .zip(columns)
+                .map(|(value, column)| format!("{value} AS {column}"))
+                .collect::<Vec<_>>();
+            format!("SELECT {}{from}", items.join(", "))
+        })
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupByStrategy {
+    // Repeats the full key expression, accepted by every engine
+    Expression,
+    // Refers to the key's alias in the select list
+    Alias,
+    // Refers to the key's 1-based position in the select list
+    Ordinal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertStrategy {
+    // INSERT ... ON CONFLICT (keys) DO UPDATE
+    OnConflict,
+    // INSERT ... ON DUPLICATE KEY UPDATE, conflicts on any unique key
+    OnDuplicateKey,
+    // MERGE INTO ... USING, rendered by the dialect
+    Merge,
+}
+
+impl fmt::Debug for dyn SQLDialect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// DefaultDialect generates ANSI SQL.
+#[derive(Debug, Default)]
+pub struct DefaultDialect {}
+
+impl SQLDialect for DefaultDialect {
+    fn name(&self) -> &str {
+        "default"
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MySqlDialect {}
+
+impl SQLDialect for MySqlDialect {
+    fn name(&self) -> &str {
+        "mysql"
+    }
+
+    // Only JSON_TABLE, which has its own syntax
+    fn supports_table_functions(&self) -> bool {
+        false
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        Box::new(parser::MySqlDialect {})
+    }
+
+    // MySQL has no IS DISTINCT FROM, but `<=>` is its null-safe equality
+    fn distinct_from(&self, l: SQLExpr, r: SQLExpr, not_distinct: bool) -> SQLExpr {
+        let eq = SQLExpr::BinaryOp {
+            left: Box::new(l),
+            op: ast::BinaryOperator::Spaceship,
+            right: Box::new(r),
+        };
+        if not_distinct {
+            eq
+        } else {
+            SQLExpr::UnaryOp {
+                op: ast::UnaryOperator::Not,
+                expr: Box::new(SQLExpr::Nested(Box::new(eq))),
+            }
+        }
+    }
+
+    // MySQL rejects LIMIT in IN/ALL/ANY/SOME subqueries
+    fn supports_limit_in_subquery(&self) -> bool {
+        false
+    }
+
+    fn supports_nulls_ordering(&self) -> bool {
+        false
+    }
+
+    fn version_query(&self) -> Option<&str> {
+        Some("SELECT version()")
+    }
+
+    fn set_variable(&self, name: &str, value: &str) -> String {
+        format!("SET SESSION {name} = {value}")
+    }
+
+    // The max_allowed_packet default before MySQL 8
+    fn max_statement_size(&self) -> Option<usize> {
+        Some(4 * 1024 * 1024)
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        Some(UpsertStrategy::OnDuplicateKey)
+    }
+
+    // ONLY_FULL_GROUP_BY doesn't always match repeated expressions
+    fn group_by_strategy(&self) -> GroupByStrategy {
+        GroupByStrategy::Alias
+    }
+
+    fn cast_data_type(&self, data_type: &DataType) -> Option<ast::DataType> {
+        mysql_data_type(data_type)
+    }
+
+    fn scalar_function(&self, name: &str, args: Vec<SQLExpr>) -> Option<SQLExpr> {
+        match name {
+            "now" => Some(function_call("NOW", vec![], false)),
+            "character_length" => Some(function_call("CHAR_LENGTH", args, false)),
+            _ => ansi_function(name, args),
+        }
+    }
+
+    // CAST in MySQL has no TIMESTAMP target
+    fn timestamp_literal(&self, timestamp: &str) -> SQLExpr {
+        cast_literal(timestamp, ast::DataType::Datetime(Some(6)))
+    }
+
+    // ORDER BY is served from B-tree indexes
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        SortCostProfile::default().with_remote_cost(0.5)
+    }
+}
+
+// CAST in MySQL only accepts a few target types, integers are SIGNED.
+fn mysql_data_type(data_type: &DataType) -> Option<ast::DataType> {
+    let signed = || ast::DataType::Custom(ast::ObjectName(vec![ast::Ident::new("SIGNED")]), vec![]);
+    match data_type {
+        DataType::Int8 | DataType::Int16 | DataType::Int32 | DataType::Int64 => Some(signed()),
+        DataType::Float32 | DataType::Float64 => Some(ast::DataType::Double),
+        DataType::Utf8 | DataType::LargeUtf8 => Some(ast::DataType::Char(None)),
+        DataType::Timestamp(_, None) => Some(ast::DataType::Datetime(None)),
+        DataType::Boolean => None,
+        _ => ansi_data_type(data_type),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MariaDbDialect {}
+
+impl SQLDialect for MariaDbDialect {
+    fn name(&self) -> &str {
+        "mariadb"
+    }
+
+    fn supports_table_functions(&self) -> bool {
+        false
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        Box::new(parser::MySqlDialect {})
+    }
+
+    fn distinct_from(&self, l: SQLExpr, r: SQLExpr, not_distinct: bool) -> SQLExpr {
+        MySqlDialect {}.distinct_from(l, r, not_distinct)
+    }
+
+    fn supports_limit_in_subquery(&self) -> bool {
+        false
+    }
+
+    fn supports_nulls_ordering(&self) -> bool {
+        false
+    }
+
+    fn max_statement_size(&self) -> Option<usize> {
+        MySqlDialect {}.max_statement_size()
+    }
+
+    fn version_query(&self) -> Option<&str> {
+        Some("SELECT version()")
+    }
+
+    // Since MariaDB 10.5
+    fn supports_returning(&self) -> bool {
+        true
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        Some(UpsertStrategy::OnDuplicateKey)
+    }
+
+    // ONLY_FULL_GROUP_BY doesn't always match repeated expressions
+    fn group_by_strategy(&self) -> GroupByStrategy {
+        GroupByStrategy::Alias
+    }
+
+    fn cast_data_type(&self, data_type: &DataType) -> Option<ast::DataType> {
+        mysql_data_type(data_type)
+    }
+
+    fn scalar_function(&self, name: &str, args: Vec<SQLExpr>) -> Option<SQLExpr> {
+        MySqlDialect {}.scalar_function(name, args)
+    }
+
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        MySqlDialect {}.sort_cost_profile()
+    }
+
+    fn timestamp_literal(&self, timestamp: &str) -> SQLExpr {
+        MySqlDialect {}.timestamp_literal(timestamp)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PostgreSqlDialect {}
+
+impl SQLDialect for PostgreSqlDialect {
+    fn name(&self) -> &str {
+        "postgresql"
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        Box::new(parser::PostgreSqlDialect {})
+    }
+
+    fn version_query(&self) -> Option<&str> {
+        Some("SELECT version()")
+    }
+
+    fn supports_returning(&self) -> bool {
+        true
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        Some(UpsertStrategy::OnConflict)
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('"')
+    }
+
+    fn cast_data_type(&self, data_type: &DataType) -> Option<ast::DataType> {
+        match data_type {
+            DataType::Float64 => Some(ast::DataType::DoublePrecision),
+            DataType::Utf8 | DataType::LargeUtf8 => Some(ast::DataType::Text),
+            _ => ansi_data_type(data_type),
+        }
+    }
+
+    // now() is the transaction start, like DataFusion's query start
+    fn scalar_function(&self, name: &str, args: Vec<SQLExpr>) -> Option<SQLExpr> {
+        match name {
+            "now" => Some(function_call("now", vec![], false)),
+            _ => ansi_function(name, args),
+        }
+    }
+
+    // ORDER BY is served from B-tree indexes
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        SortCostProfile::default().with_remote_cost(0.5)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SqliteDialect {}
+
+impl SQLDialect for SqliteDialect {
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        Box::new(parser::SQLiteDialect {})
+    }
+
+    fn version_query(&self) -> Option<&str> {
+        Some("SELECT sqlite_version()")
+    }
+
+    // Since SQLite 3.35
+    fn supports_returning(&self) -> bool {
+        true
+    }
+
+    // BINARY is the default collation
+    fn binary_collation(&self) -> bool {
+        true
+    }
+
+    // Since SQLite 3.24
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        Some(UpsertStrategy::OnConflict)
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('"')
+    }
+
+    // Casts only set a type affinity, dates and decimals stay text or
+    // become lossy numerics, so they are computed locally
+    fn cast_data_type(&self, data_type: &DataType) -> Option<ast::DataType> {
+        match data_type {
+            DataType::Boolean
+            | DataType::Int8
+            | DataType::Int16
+            | DataType::Int32
+            | DataType::Int64 => Some(ast::DataType::Integer(None)),
+            DataType::Float32 | DataType::Float64 => Some(ast::DataType::Real),
+            DataType::Utf8 | DataType::LargeUtf8 => Some(ast::DataType::Text),
+            _ => None,
+        }
+    }
+
+    // CEIL and FLOOR need SQLite's optional math functions
+    fn scalar_function(&self, name: &str, args: Vec<SQLExpr>) -> Option<SQLExpr> {
+        match name {
+            "ceil" | "floor" => None,
+            "character_length" => Some(function_call("LENGTH", args, false)),
+            _ => ansi_function(name, args),
+        }
+    }
+
+    // SQLite sorts unindexed rows single threaded, slower than DataFusion
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        SortCostProfile::default().with_remote_cost(2.0)
+    }
+
+    // SQLite stores dates as ISO 8601 text, which compares correctly as is.
+    // Casting it to DATE would yield the year as a number.
+    fn date_literal(&self, date: &str) -> SQLExpr {
+        SQLExpr::Value(ast::Value::SingleQuotedString(date.to_string()))
+    }
+
+    fn timestamp_literal(&self, timestamp: &str) -> SQLExpr {
+        SQLExpr::Value(ast::Value::SingleQuotedString(timestamp.to_string()))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MsSqlDialect {}
+
+impl SQLDialect for MsSqlDialect {
+    fn name(&self) -> &str {
+        "mssql"
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        Box::new(parser::MsSqlDialect {})
+    }
+
+    // IS DISTINCT FROM is only accepted since SQL Server 2022, servers
+    // detected as such render it natively.
+    fn distinct_from(&self, l: SQLExpr, r: SQLExpr, not_distinct: bool) -> SQLExpr {
+        NullSafeFallbackDialect {}.distinct_from(l, r, not_distinct)
+    }
+
+    fn version_query(&self) -> Option<&str> {
+        Some("SELECT CAST(SERVERPROPERTY('ProductVersion') AS VARCHAR(128))")
+    }
+
+    // e.g. SET LOCK_TIMEOUT 1000
+    fn set_variable(&self, name: &str, value: &str) -> String {
+        format!("SET {name} {value}")
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        Some(UpsertStrategy::Merge)
+    }
+
+    // SQL Server requires MERGE to be terminated
+    fn merge(
+        &self,
+        table: &str,
+        rows: &ast::Values,
+        columns: &[String],
+        on: &str,
+        clauses: &str,
+    ) -> String {
+        let merge = DefaultDialect {}.merge(table, rows, columns, on, clauses);
+        format!("{merge};")
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('[')
+    }
+
+    fn limit_style(&self) -> LimitStyle {
+        LimitStyle::OffsetFetch
+    }
+
+    fn supports_nulls_ordering(&self) -> bool {
+        false
+    }
+
+    fn cast_data_type(&self, data_type: &DataType) -> Option<ast::DataType> {
+        match data_type {
+            DataType::Boolean => Some(ast::DataType::Custom(
+                ast::ObjectName(vec![ast::Ident::new("BIT")]),
+                vec![],
+            )),
+            DataType::Float64 => Some(ast::DataType::Float(None)),
+            DataType::Utf8 | DataType::LargeUtf8 => Some(ast::DataType::Nvarchar(Some(4000))),
+            DataType::Timestamp(_, None) => Some(ast::DataType::Custom(
+                ast::ObjectName(vec![ast::Ident::new("DATETIME2")]),
+                vec![],
+            )),
+            _ => ansi_data_type(data_type),
+        }
+    }
+
+    fn scalar_function(&self, name: &str, args: Vec<SQLExpr>) -> Option<SQLExpr> {
+        match name {
+            "ceil" => Some(function_call("CEILING", args, false)),
+            "character_length" => None,
+            _ => ansi_function(name, args),
+        }
+    }
+
+    // Only the `T` separated form is independent of DATEFORMAT and LANGUAGE
+    fn timestamp_literal(&self, timestamp: &str) -> SQLExpr {
+        cast_literal(
+            &timestamp.replacen(' ', "T", 1),
+            ast::DataType::Custom(ast::ObjectName(vec![ast::Ident::new("DATETIME2")]), vec![]),
+        )
+    }
+
+    // ORDER BY is served from B-tree indexes
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        SortCostProfile::default().with_remote_cost(0.5)
+    }
+
+    // System-versioned temporal tables, since SQL Server 2016
+    fn time_travel(&self, as_of: &AsOf) -> Option<String> {
+        match as_of {
+            AsOf::Timestamp(ts) => Some(format!("FOR SYSTEM_TIME AS OF {}", quote_literal(ts))),
+            AsOf::Snapshot(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct OracleDialect {}
+
+impl SQLDialect for OracleDialect {
+    fn name(&self) -> &str {
+        "oracle"
+    }
+
+    // Table functions are called as TABLE(fn(args))
+    fn supports_table_functions(&self) -> bool {
+        false
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('"')
+    }
+
+    fn empty_string_is_null(&self) -> bool {
+        true
+    }
+
+    // Since Oracle 12c
+    fn limit_style(&self) -> LimitStyle {
+        LimitStyle::FetchFirst
+    }
+
+    fn supports_table_alias_as(&self) -> bool {
+        false
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        Some(UpsertStrategy::Merge)
+    }
+
+    // Oracle has no VALUES tables before 23c, the ON condition must be
+    // parenthesized
+    fn merge(
+        &self,
+        table: &str,
+        rows: &ast::Values,
+        columns: &[String],
+        on: &str,
+        clauses: &str,
+    ) -> String {
+        format!(
+            "MERGE INTO {table} target USING ({}) source ON ({on}) {clauses}",
+            select_rows(rows, columns, " FROM DUAL")
+        )
+    }
+
+    // Oracle has no BOOLEAN before 23c, no BIGINT or DOUBLE and requires a
+    // length for VARCHAR2
+    fn cast_data_type(&self, data_type: &DataType) -> Option<ast::DataType> {
+        let custom = |name: &str, args: &[&str]| {
+            ast::DataType::Custom(
+                ast::ObjectName(vec![ast::Ident::new(name)]),
+                args.iter().map(|a| a.to_string()).collect(),
+            )
+        };
+        match data_type {
+            DataType::Boolean => None,
+            DataType::Int8 => Some(custom("NUMBER", &["3"])),
+            DataType::Int16 => Some(custom("NUMBER", &["5"])),
+            DataType::Int32 => Some(custom("NUMBER", &["10"])),
+            DataType::Int64 => Some(custom("NUMBER", &["19"])),
+            DataType::Float32 => Some(custom("BINARY_FLOAT", &[])),
+            DataType::Float64 => Some(custom("BINARY_DOUBLE", &[])),
+            DataType::Utf8 | DataType::LargeUtf8 => Some(custom("VARCHAR2", &["4000"])),
+            _ => ansi_data_type(data_type),
+        }
+    }
+
+    // The format masks make the literals independent of NLS_DATE_FORMAT
+    fn date_literal(&self, date: &str) -> SQLExpr {
+        function_call("TO_DATE", vec![string(date), string("YYYY-MM-DD")], false)
+    }
+
+    fn timestamp_literal(&self, timestamp: &str) -> SQLExpr {
+        let mask = match timestamp.contains('.') {
+            true => "YYYY-MM-DD HH24:MI:SS.FF",
+            false => "YYYY-MM-DD HH24:MI:SS",
+        };
+        function_call("TO_TIMESTAMP", vec![string(timestamp), string(mask)], false)
+    }
+}
+
+fn string(value: &str) -> SQLExpr {
+    SQLExpr::Value(ast::Value::SingleQuotedString(value.to_string()))
+}
+
+#[derive(Debug, Default)]
+pub struct SnowflakeDialect {}
+
+impl SQLDialect for SnowflakeDialect {
+    fn name(&self) -> &str {
+        "snowflake"
+    }
+
+    // Table functions are called as TABLE(fn(args))
+    fn supports_table_functions(&self) -> bool {
+        false
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        Box::new(parser::SnowflakeDialect)
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('"')
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        Some(UpsertStrategy::Merge)
+    }
+
+    fn group_by_strategy(&self) -> GroupByStrategy {
+        GroupByStrategy::Ordinal
+    }
+
+    // Sorts are distributed across the warehouse
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        SortCostProfile::default().with_remote_cost(0.5)
+    }
+
+    // Snapshots are the ids of the statements whose results are read
+    fn time_travel(&self, as_of: &AsOf) -> Option<String> {
+        match as_of {
+            AsOf::Timestamp(ts) => Some(format!(
+                "AT(TIMESTAMP => {}::TIMESTAMP_LTZ)",
+                quote_literal(ts)
+            )),
+            AsOf::Snapshot(id) => Some(format!("AT(STATEMENT => {})", quote_literal(id))),
+        }
+    }
+
+    // The location is a stage, e.g. `@exports/path`, or an external URL
+    fn unload(&self, query: &str, location: &str, options: Option<&str>) -> Option<String> {
+        let location = match location.starts_with('@') {
+            true => format!("{location}/"),
+            false => quote_literal(&format!("{location}/")),
+        };
+        Some(format!(
+            "COPY INTO {location} FROM ({query}) FILE_FORMAT = (TYPE = PARQUET) HEADER = TRUE{}",
+            options.map(|o| format!(" {o}")).unwrap_or_default()
+        ))
+    }
+
+    fn load(&self, table: &str, location: &str, options: Option<&str>) -> Option<String> {
+        let location = match location.starts_with('@') {
+            true => format!("{location}/"),
+            false => quote_literal(&format!("{location}/")),
+        };
+        Some(format!(
+            "COPY INTO {table} FROM {location} FILE_FORMAT = (TYPE = PARQUET) \
+             MATCH_BY_COLUMN_NAME = CASE_INSENSITIVE{}",
+            options.map(|o| format!(" {o}")).unwrap_or_default()
+        ))
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BigQueryDialect {}
+
+impl SQLDialect for BigQueryDialect {
+    fn name(&self) -> &str {
+        "bigquery"
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        Box::new(parser::BigQueryDialect)
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        Some(UpsertStrategy::Merge)
+    }
+
+    // BigQuery has no VALUES tables
+    fn merge(
+        &self,
+        table: &str,
+        rows: &ast::Values,
+        columns: &[String],
+        on: &str,
+        clauses: &str,
+    ) -> String {
+        format!(
+            "MERGE INTO {table} AS target USING ({}) AS source ON {on} {clauses}",
+            select_rows(rows, columns, "")
+        )
+    }
+
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        SnowflakeDialect {}.sort_cost_profile()
+    }
+
+    fn time_travel(&self, as_of: &AsOf) -> Option<String> {
+        match as_of {
+            AsOf::Timestamp(ts) => Some(format!(
+                "FOR SYSTEM_TIME AS OF TIMESTAMP {}",
+                quote_literal(ts)
+            )),
+            AsOf::Snapshot(_) => None,
+        }
+    }
+
+    // The options go before OPTIONS, e.g. `WITH CONNECTION region.connection`
+    fn unload(&self, query: &str, location: &str, options: Option<&str>) -> Option<String> {
+        Some(format!(
+            "EXPORT DATA {}OPTIONS (uri = {}, format = 'PARQUET', overwrite = true) AS {query}",
+            options.map(|o| format!("{o} ")).unwrap_or_default(),
+            quote_literal(&format!("{location}/*.parquet"))
+        ))
+    }
+
+    // The options go after the file options, e.g. `WITH CONNECTION region.connection`
+    fn load(&self, table: &str, location: &str, options: Option<&str>) -> Option<String> {
+        Some(format!(
+            "LOAD DATA INTO {table} FROM FILES (format = 'PARQUET', uris = [{}]){}",
+            quote_literal(&format!("{location}/*.parquet")),
+            options.map(|o| format!(" {o}")).unwrap_or_default()
+        ))
+    }
+}
+
+// RedshiftDialect is for Amazon Redshift, which speaks the PostgreSQL protocol.
+#[derive(Debug, Default)]
+pub struct RedshiftDialect {}
+
+impl SQLDialect for RedshiftDialect {
+    fn name(&self) -> &str {
+        "redshift"
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        Box::new(parser::RedshiftSqlDialect {})
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('"')
+    }
+
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        SnowflakeDialect {}.sort_cost_profile()
+    }
+
+    // The options must hold the credentials, e.g. `IAM_ROLE 'arn:...'`
+    fn unload(&self, query: &str, location: &str, options: Option<&str>) -> Option<String> {
+        Some(format!(
+            "UNLOAD ({}) TO {}{} FORMAT AS PARQUET",
+            quote_literal(query),
+            quote_literal(&format!("{location}/")),
+            options.map(|o| format!(" {o}")).unwrap_or_default()
+        ))
+    }
+
+    fn load(&self, table: &str, location: &str, options: Option<&str>) -> Option<String> {
+        Some(format!(
+            "COPY {table} FROM {}{} FORMAT AS PARQUET",
+            quote_literal(&format!("{location}/")),
+            options.map(|o| format!(" {o}")).unwrap_or_default()
+        ))
+    }
+}
+
+// TrinoDialect is for Trino, reading Iceberg tables at a snapshot.
+#[derive(Debug, Default)]
+pub struct TrinoDialect {}
+
+impl SQLDialect for TrinoDialect {
+    fn name(&self) -> &str {
+        "trino"
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('"')
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        Some(UpsertStrategy::Merge)
+    }
+
+    // Snapshots are Iceberg snapshot ids, or branch and tag names
+    fn time_travel(&self, as_of: &AsOf) -> Option<String> {
+        match as_of {
+            AsOf::Timestamp(ts) => Some(format!(
+                "FOR TIMESTAMP AS OF TIMESTAMP {}",
+                quote_literal(ts)
+            )),
+            AsOf::Snapshot(id) if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) => {
+                Some(format!("FOR VERSION AS OF {id}"))
+            }
+            AsOf::Snapshot(name) => Some(format!("FOR VERSION AS OF {}", quote_literal(name))),
+        }
+    }
+}
+
+// NullSafeFallbackDialect is for engines without null-safe comparison
+// operators, the comparison is expanded with explicit NULL checks.
+#[derive(Debug, Default)]
+pub struct NullSafeFallbackDialect {}
+
+impl SQLDialect for NullSafeFallbackDialect {
+    fn name(&self) -> &str {
+        "null_safe_fallback"
+    }
+
+    fn distinct_from(&self, l: SQLExpr, r: SQLExpr, not_distinct: bool) -> SQLExpr {
+        // ((l = r AND l IS NOT NULL AND r IS NOT NULL) OR (l IS NULL AND r IS NULL)),
+        // unlike `l = r OR ...` this is never NULL, so it can be negated.
+        let and = |left: SQLExpr, right: SQLExpr| SQLExpr::BinaryOp {
+            left: Box::new(left),
+            op: ast::BinaryOperator::And,
+            right: Box::new(right),
+        };
+        let eq = SQLExpr::BinaryOp {
+            left: Box::new(l.clone()),
+            op: ast::BinaryOperator::Eq,
+            right: Box::new(r.clone()),
+        };
+        let both_set = and(
+            and(eq, SQLExpr::IsNotNull(Box::new(l.clone()))),
+            SQLExpr::IsNotNull(Box::new(r.clone())),
+        );
+        let both_null = and(SQLExpr::IsNull(Box::new(l)), SQLExpr::IsNull(Box::new(r)));
+        let not_distinct_expr = SQLExpr::Nested(Box::new(SQLExpr::BinaryOp {
+            left: Box::new(SQLExpr::Nested(Box::new(both_set))),
+            op: ast::BinaryOperator::Or,
+            right: Box::new(SQLExpr::Nested(Box::new(both_null))),
+        }));
+        if not_distinct {
+            not_distinct_expr
+        } else {
+            SQLExpr::UnaryOp {
+                op: ast::UnaryOperator::Not,
+                expr: Box::new(not_distinct_expr),
+            }
+        }
+    }
+}
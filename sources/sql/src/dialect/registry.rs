@@ -0,0 +1,64 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use super::{
+    BigQueryDialect, DefaultDialect, MariaDbDialect, MsSqlDialect, MySqlDialect, OracleDialect,
+    PostgreSqlDialect, RedshiftDialect, SQLDialect, SnowflakeDialect, SqliteDialect, TrinoDialect,
+};
+
+pub type DialectFactory = Arc<dyn Fn() -> Arc<dyn SQLDialect> + Send + Sync>;
+
+// The dialects by lowercase name, the built-in ones are registered on first use.
+fn registry() -> &'static RwLock<BTreeMap<String, DialectFactory>> {
+    static REGISTRY: OnceLock<RwLock<BTreeMap<String, DialectFactory>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut dialects: BTreeMap<String, DialectFactory> = BTreeMap::new();
+        let mut builtin = |name: &str, factory: DialectFactory| {
+            dialects.insert(name.to_string(), factory);
+        };
+        builtin("default", Arc::new(|| Arc::new(DefaultDialect {})));
+        builtin("mysql", Arc::new(|| Arc::new(MySqlDialect {})));
+        builtin("mariadb", Arc::new(|| Arc::new(MariaDbDialect {})));
+        builtin("postgresql", Arc::new(|| Arc::new(PostgreSqlDialect {})));
+        builtin("postgres", Arc::new(|| Arc::new(PostgreSqlDialect {})));
+        builtin("sqlite", Arc::new(|| Arc::new(SqliteDialect {})));
+        builtin("mssql", Arc::new(|| Arc::new(MsSqlDialect {})));
+        builtin("oracle", Arc::new(|| Arc::new(OracleDialect {})));
+        builtin("snowflake", Arc::new(|| Arc::new(SnowflakeDialect {})));
+        builtin("bigquery", Arc::new(|| Arc::new(BigQueryDialect {})));
+        builtin("trino", Arc::new(|| Arc::new(TrinoDialect {})));
+        builtin("redshift", Arc::new(|| Arc::new(RedshiftDialect {})));
+        RwLock::new(dialects)
+    })
+}
+
+// Registers a dialect under the name, replacing any dialect of that name.
+// Crates shipping dialects, e.g. for Teradata, implement SQLDialect, declaring
+// their capabilities and mapping functions with its methods, and register it:
+// `register_dialect("teradata", || Arc::new(TeradataDialect {}))`.
+pub fn register_dialect<F>(name: &str, factory: F)
+where
+    F: Fn() -> Arc<dyn SQLDialect> + Send + Sync + 'static,
+{
+    registry()
+        .write()
+        .unwrap()
+        .insert(name.to_ascii_lowercase(), Arc::new(factory));
+}
+
+// Returns a new instance of the dialect registered under the name.
+pub fn registered_dialect(name: &str) -> Option<Arc<dyn SQLDialect>> {
+    let factory = registry()
+        .read()
+        .unwrap()
+        .get(&name.to_ascii_lowercase())
+        .cloned()?;
+    Some(factory())
+}
+
+// The names of the registered dialects.
+pub fn registered_dialects() -> Vec<String> {
+    registry().read().unwrap().keys().cloned().collect()
+}
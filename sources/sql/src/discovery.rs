@@ -0,0 +1,446 @@
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{
+        array::{Array, AsArray},
+        compute::cast,
+        datatypes::DataType,
+        record_batch::RecordBatch,
+    },
+    catalog::{schema::SchemaProvider, CatalogProvider},
+    common::plan_err,
+    datasource::TableProvider,
+    error::Result,
+    sql::sqlparser::{ast, dialect::GenericDialect, parser::Parser},
+};
+use futures::TryStreamExt;
+use regex::Regex;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use datafusion_federation::FederatedTableProviderAdaptor;
+
+use crate::{
+    schema::{RemoteTableKind, SQLTableSource},
+    SQLFederationProvider,
+};
+
+// TableFilter selects the tables to expose when discovering a remote schema.
+#[derive(Debug, Clone, Default)]
+pub struct TableFilter {
+    pub include: Option<Regex>,
+    pub exclude: Option<Regex>,
+    // Fails the discovery instead of exposing more tables than this
+    pub max_tables: Option<usize>,
+}
+
+impl TableFilter {
+    fn matches(&self, table_name: &str) -> bool {
+        self.include
+            .as_ref()
+            .map_or(true, |include| include.is_match(table_name))
+            && !self
+                .exclude
+                .as_ref()
+                .map_or(false, |exclude| exclude.is_match(table_name))
+    }
+}
+
+// DiscoveredSchemaProvider exposes the tables and views of a remote schema listed
+// from its information_schema. Only the names are loaded up front, the schema of
+// a table is inferred the first time the table is used.
+pub struct DiscoveredSchemaProvider {
+    provider: Arc<SQLFederationProvider>,
+    // The database holding the schema on servers hosting several, e.g.
+    // SQL Server, None for the database of the connection
+    database: Option<String>,
+    schema_name: String,
+    table_names: Vec<String>,
+    kinds: HashMap<String, RemoteTableKind>,
+    // Loaded tables with their load time
+    tables: Mutex<HashMap<String, (Arc<SQLTableSource>, Instant)>>,
+    refresh_interval: Option<Duration>,
+}
+
+impl DiscoveredSchemaProvider {
+    pub async fn new(
+        provider: Arc<SQLFederationProvider>,
+        schema_name: impl Into<String>,
+        filter: TableFilter,
+    ) -> Result<Self> {
+        Self::discover(provider, None, schema_name.into(), filter).await
+    }
+
+    // Discovers a schema of another database of the server, its tables are
+    // read with three-part names `database.schema.table`, so tables of
+    // different databases are joined in the same remote query.
+    pub async fn new_in_database(
+        provider: Arc<SQLFederationProvider>,
+        database: impl Into<String>,
+        schema_name: impl Into<String>,
+        filter: TableFilter,
+    ) -> Result<Self> {
+        Self::discover(provider, Some(database.into()), schema_name.into(), filter).await
+    }
+
+    async fn discover(
+        provider: Arc<SQLFederationProvider>,
+        database: Option<String>,
+        schema_name: String,
+        filter: TableFilter,
+    ) -> Result<Self> {
+        let query = format!(
+            "SELECT table_name, table_type FROM {} WHERE table_schema = '{}'",
+            information_schema(&provider, database.as_deref(), "tables"),
+            quote_literal(&schema_name)
+        );
+        let batches = provider
+            .executor
+            .execute(query.as_str())
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut table_names = vec![];
+        let mut kinds = HashMap::new();
+        for batch in batches {
+            let names = cast(batch.column(0), &DataType::Utf8)?;
+            let types = cast(batch.column(1), &DataType::Utf8)?;
+            for (name, table_type) in names
+                .as_string::<i32>()
+                .iter()
+                .zip(types.as_string::<i32>())
+            {
+                let Some(name) = name else {
+                    continue;
+                };
+                if filter.matches(name) {
+                    table_names.push(name.to_string());
+                    let kind = RemoteTableKind::from_table_type(table_type.unwrap_or_default());
+                    kinds.insert(name.to_string(), kind);
+                }
+            }
+        }
+        if let Some(max_tables) = filter.max_tables {
+            if table_names.len() > max_tables {
+                return plan_err!(
+                    "schema {schema_name} has {} matching tables, more than the maximum of {max_tables}",
+                    table_names.len()
+                );
+            }
+        }
+        table_names.sort();
+
+        Ok(Self {
+            provider,
+            database,
+            schema_name,
+            table_names,
+            kinds,
+            tables: Mutex::new(HashMap::new()),
+            refresh_interval: None,
+        })
+    }
+
+    // Reloads the schema of tables used more than `interval` after they were
+    // loaded, so columns added to the remote tables become visible.
+    pub fn with_auto_refresh(mut self, interval: Duration) -> Self {
+        self.refresh_interval = Some(interval);
+        self
+    }
+
+    // Reloads the schema of the table on its next use.
+    pub fn refresh(&self, name: &str) {
+        if let Some(table_name) = self.find(name) {
+            self.tables.lock().unwrap().remove(table_name);
+        }
+    }
+
+    async fn load(&self, table_name: &str) -> Result<SQLTableSource> {
+        let remote_name = match &self.database {
+            Some(database) => format!("{database}.{}.{table_name}", self.schema_name),
+            None => format!("{}.{table_name}", self.schema_name),
+        };
+        let kind = self.kinds[table_name];
+        let mut source = SQLTableSource::new(self.provider.clone(), remote_name)
+            .await?
+            .with_kind(kind);
+        if kind == RemoteTableKind::Table {
+            let (generated, defaults) = self.column_metadata(table_name).await;
+            source = source.with_column_metadata(generated, defaults);
+        }
+        if kind == RemoteTableKind::View && self.provider.inline_views {
+            if let Some(definition) = self.view_definition(table_name).await? {
+                source = source.with_definition(definition);
+            }
+        }
+        Ok(source)
+    }
+
+    // Reads the generated (identity, serial) columns and the columns with a default.
+    // Sources without `is_identity` in information_schema.columns, e.g. MySQL,
+    // report auto-increment columns through the `extra` column instead.
+    async fn column_metadata(&self, table_name: &str) -> (HashSet<String>, HashSet<String>) {
+        let mut generated = HashSet::new();
+        let mut defaults = HashSet::new();
+        for identity in ["is_identity = 'YES'", "extra LIKE '%auto_increment%'"] {
+            let query = format!(
+                "SELECT column_name, column_default, {identity} FROM {} WHERE table_schema = '{}' AND table_name = '{}'",
+                information_schema(&self.provider, self.database.as_deref(), "columns"),
+                quote_literal(&self.schema_name),
+                quote_literal(table_name)
+            );
+            let Ok(batches) = self.collect(&query).await else {
+                continue;
+            };
+            for batch in batches {
+                let (Ok(names), Ok(column_defaults), Ok(identities)) = (
+                    cast(batch.column(0), &DataType::Utf8),
+                    cast(batch.column(1), &DataType::Utf8),
+                    cast(batch.column(2), &DataType::Boolean),
+                ) else {
+                    continue;
+                };
+                let names = names.as_string::<i32>();
+                let column_defaults = column_defaults.as_string::<i32>();
+                let identities = identities.as_boolean();
+                for row in 0..batch.num_rows() {
+                    if names.is_null(row) {
+                        continue;
+                    }
+                    let name = names.value(row).to_string();
+                    let default =
+                        (!column_defaults.is_null(row)).then(|| column_defaults.value(row));
+                    // Postgres serial columns default to a sequence
+                    let serial = default.is_some_and(|d| d.starts_with("nextval("));
+                    if (!identities.is_null(row) && identities.value(row)) || serial {
+                        generated.insert(name);
+                    } else if default.is_some() {
+                        defaults.insert(name);
+                    }
+                }
+            }
+            break;
+        }
+        (generated, defaults)
+    }
+
+    async fn collect(&self, query: &str) -> Result<Vec<RecordBatch>> {
+        self.provider
+            .executor
+            .execute(query)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await
+    }
+
+    // Reads the definition of a view, None if it can't be parsed
+    async fn view_definition(&self, table_name: &str) -> Result<Option<ast::Query>> {
+        let query = format!(
+            "SELECT view_definition FROM {} WHERE table_schema = '{}' AND table_name = '{}'",
+            information_schema(&self.provider, self.database.as_deref(), "views"),
+            quote_literal(&self.schema_name),
+            quote_literal(table_name)
+        );
+        let batches = self
+            .provider
+            .executor
+            .execute(query.as_str())
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        let Some(batch) = batches.iter().find(|b| b.num_rows() > 0) else {
+            return Ok(None);
+        };
+        let definitions = cast(batch.column(0), &DataType::Utf8)?;
+        let definition = definitions.as_string::<i32>().value(0);
+        let statement = Parser::parse_sql(&GenericDialect {}, definition)
+            .ok()
+            .and_then(|mut statements| statements.pop());
+        Ok(match statement {
+            Some(ast::Statement::Query(query)) => Some(*query),
+            _ => None,
+        })
+    }
+
+    fn find(&self, name: &str) -> Option<&String> {
+        self.table_names
+            .iter()
+            .find(|t| t.eq_ignore_ascii_case(name))
+    }
+}
+
+#[async_trait]
+impl SchemaProvider for DiscoveredSchemaProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn table_names(&self) -> Vec<String> {
+        self.table_names.clone()
+    }
+
+    async fn table(&self, name: &str) -> Option<Arc<dyn TableProvider>> {
+        let table_name = self.find(name)?;
+        let cached = self.tables.lock().unwrap().get(table_name).cloned();
+        let cached = cached.filter(|(_, loaded)| {
+            self.refresh_interval
+                .map_or(true, |interval| loaded.elapsed() < interval)
+        });
+        let source = match cached {
+            Some((source, _)) => source,
+            None => {
+                let source = Arc::new(self.load(table_name).await.ok()?);
+                self.tables
+                    .lock()
+                    .unwrap()
+                    .insert(table_name.clone(), (source.clone(), Instant::now()));
+                source
+            }
+        };
+        Some(Arc::new(FederatedTableProviderAdaptor::new(source)))
+    }
+
+    fn table_exist(&self, name: &str) -> bool {
+        self.find(name).is_some()
+    }
+}
+
+// The schemas holding the engine's own metadata, never exposed.
+const SYSTEM_SCHEMAS: [&str; 5] = [
+    "information_schema",
+    "pg_catalog",
+    "mysql",
+    "performance_schema",
+    "sys",
+];
+
+// DiscoveredCatalogProvider exposes every schema of the remote source listed from
+// its information_schema as a DiscoveredSchemaProvider, so the source is registered
+// with `ctx.register_catalog` without declaring its tables. The schema filter
+// selects schemas by name, the table filter applies to each schema.
+pub struct DiscoveredCatalogProvider {
+    schemas: HashMap<String, Arc<DiscoveredSchemaProvider>>,
+}
+
+impl DiscoveredCatalogProvider {
+    pub async fn new(
+        provider: Arc<SQLFederationProvider>,
+        schema_filter: TableFilter,
+        table_filter: TableFilter,
+    ) -> Result<Self> {
+        Self::discover(provider, None, schema_filter, table_filter).await
+    }
+
+    // Discovers the schemas of another database of the server, e.g. to
+    // register each database of a SQL Server as a catalog of its own, while
+    // sharing the provider so cross-database joins are pushed down.
+    pub async fn new_for_database(
+        provider: Arc<SQLFederationProvider>,
+        database: impl Into<String>,
+        schema_filter: TableFilter,
+        table_filter: TableFilter,
+    ) -> Result<Self> {
+        Self::discover(provider, Some(database.into()), schema_filter, table_filter).await
+    }
+
+    async fn discover(
+        provider: Arc<SQLFederationProvider>,
+        database: Option<String>,
+        schema_filter: TableFilter,
+        table_filter: TableFilter,
+    ) -> Result<Self> {
+        let query = format!(
+            "SELECT schema_name FROM {}",
+            information_schema(&provider, database.as_deref(), "schemata")
+        );
+        let batches = provider
+            .executor
+            .execute(query.as_str())
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        let mut schema_names = vec![];
+        for batch in batches {
+            let names = cast(batch.column(0), &DataType::Utf8)?;
+            for name in names.as_string::<i32>().iter().flatten() {
+                let system = SYSTEM_SCHEMAS.iter().any(|s| s.eq_ignore_ascii_case(name));
+                if !system && schema_filter.matches(name) {
+                    schema_names.push(name.to_string());
+                }
+            }
+        }
+
+        let mut schemas = HashMap::new();
+        for schema_name in schema_names {
+            let schema = DiscoveredSchemaProvider::discover(
+                provider.clone(),
+                database.clone(),
+                schema_name.clone(),
+                table_filter.clone(),
+            )
+            .await?;
+            schemas.insert(schema_name, Arc::new(schema));
+        }
+        Ok(Self { schemas })
+    }
+}
+
+impl CatalogProvider for DiscoveredCatalogProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema_names(&self) -> Vec<String> {
+        self.schemas.keys().cloned().collect()
+    }
+
+    fn schema(&self, name: &str) -> Option<Arc<dyn SchemaProvider>> {
+        self.schemas
+            .get(name)
+            .map(|s| s.clone() as Arc<dyn SchemaProvider>)
+    }
+}
+
+// The information_schema view, of the database if set, e.g.
+// `[sales].information_schema.tables` on SQL Server.
+fn information_schema(
+    provider: &SQLFederationProvider,
+    database: Option<&str>,
+    view: &str,
+) -> String {
+    let Some(database) = database else {
+        return format!("information_schema.{view}");
+    };
+    let database = match provider.planner.dialect.identifier_quote_style() {
+        Some(quote) => ast::Ident::with_quote(quote, database),
+        None => ast::Ident::new(database),
+    };
+    format!("{database}.information_schema.{view}")
+}
+
+fn quote_literal(value: &str) -> String {
+    value.replace('\'', "''")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_filter() {
+        let filter = TableFilter {
+            include: Some(Regex::new("^sales_").unwrap()),
+            exclude: Some(Regex::new("_tmp$").unwrap()),
+            max_tables: None,
+        };
+        assert!(filter.matches("sales_orders"));
+        assert!(!filter.matches("sales_orders_tmp"));
+        assert!(!filter.matches("users"));
+        assert!(TableFilter::default().matches("users"));
+    }
+}
@@ -0,0 +1,42 @@
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray, LargeStringArray, StringArray},
+        datatypes::DataType,
+        record_batch::RecordBatch,
+    },
+    error::Result,
+};
+
+// Replaces NULL strings with empty strings, for engines storing '' as NULL,
+// so fetched values compare like they would locally.
+pub(crate) fn nulls_to_empty_strings(batch: RecordBatch) -> Result<RecordBatch> {
+    let columns = batch
+        .columns()
+        .iter()
+        .map(|column| -> ArrayRef {
+            if column.null_count() == 0 {
+                return column.clone();
+            }
+            match column.data_type() {
+                DataType::Utf8 => Arc::new(
+                    column
+                        .as_string::<i32>()
+                        .iter()
+                        .map(|v| Some(v.unwrap_or_default()))
+                        .collect::<StringArray>(),
+                ),
+                DataType::LargeUtf8 => Arc::new(
+                    column
+                        .as_string::<i64>()
+                        .iter()
+                        .map(|v| Some(v.unwrap_or_default()))
+                        .collect::<LargeStringArray>(),
+                ),
+                _ => column.clone(),
+            }
+        })
+        .collect();
+    Ok(RecordBatch::try_new(batch.schema(), columns)?)
+}
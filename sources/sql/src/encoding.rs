@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{
+        array::{Array, ArrayRef, AsArray, GenericStringBuilder, OffsetSizeTrait},
+        buffer::NullBuffer,
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    },
+    error::{DataFusionError, Result},
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::StreamExt;
+
+// SourceEncoding is the character encoding a source returns strings in.
+// Binary columns declared as strings, e.g. MySQL latin1 columns some drivers
+// return as bytes, are transcoded to UTF-8 when fetched. With UTF-8 their
+// bytes are validated, reporting invalid bytes with their offset. String
+// columns were already decoded by the driver and are left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceEncoding {
+    Utf8,
+    // ISO-8859-1, MySQL's latin1
+    Latin1,
+    // Windows-1252, e.g. SQL Server's default code page
+    Windows1252,
+}
+
+impl SourceEncoding {
+    // Decodes the bytes, or returns the offset of the first invalid byte.
+    fn decode(&self, bytes: &[u8]) -> std::result::Result<String, usize> {
+        match self {
+            SourceEncoding::Utf8 => std::str::from_utf8(bytes)
+                .map(String::from)
+                .map_err(|e| e.valid_up_to()),
+            SourceEncoding::Latin1 => Ok(bytes.iter().map(|b| *b as char).collect()),
+            SourceEncoding::Windows1252 => Ok(bytes
+                .iter()
+                .map(|b| match b {
+                    0x80..=0x9f => WINDOWS_1252[(b - 0x80) as usize],
+                    b => *b as char,
+                })
+                .collect()),
+        }
+    }
+}
+
+// The characters of Windows-1252 bytes 0x80 to 0x9F, the undefined bytes
+// map to the C1 controls as in the WHATWG encoding standard.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+// Transcodes the binary columns the expected schema declares as strings
+// to UTF-8.
+pub(crate) fn transcode_stream(
+    stream: SendableRecordBatchStream,
+    encoding: SourceEncoding,
+    expected: SchemaRef,
+) -> SendableRecordBatchStream {
+    let fields = stream
+        .schema()
+        .fields()
+        .iter()
+        .map(|field| {
+            let data_type = match (field.data_type(), expected_type(&expected, field.name())) {
+                (DataType::Binary, Some(DataType::Utf8 | DataType::LargeUtf8)) => DataType::Utf8,
+                (DataType::LargeBinary, Some(DataType::Utf8 | DataType::LargeUtf8)) => {
+                    DataType::LargeUtf8
+                }
+                (data_type, _) => data_type.clone(),
+            };
+            Arc::new(Field::new(field.name(), data_type, field.is_nullable()))
+        })
+        .collect::<Vec<_>>();
+    let schema = Arc::new(Schema::new_with_metadata(
+        fields,
+        stream.schema().metadata().clone(),
+    ));
+    let transcoded = schema.clone();
+    let stream = stream.map(move |batch| {
+        let batch = batch?;
+        let columns = batch
+            .columns()
+            .iter()
+            .zip(transcoded.fields())
+            .map(|(column, field)| transcode_column(column, field, encoding))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(transcoded.clone(), columns)?)
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, stream))
+}
+
+fn expected_type<'a>(expected: &'a SchemaRef, name: &str) -> Option<&'a DataType> {
+    expected
+        .field_with_name(name)
+        .ok()
+        .map(|field| field.data_type())
+}
+
+fn transcode_column(
+    column: &ArrayRef,
+    field: &Field,
+    encoding: SourceEncoding,
+) -> Result<ArrayRef> {
+    let name = field.name();
+    match (column.data_type(), field.data_type()) {
+        (DataType::Binary, DataType::Utf8) => {
+            let array = column.as_binary::<i32>();
+            transcode::<i32>(
+                array.value_offsets(),
+                array.value_data(),
+                array.nulls(),
+                encoding,
+                name,
+            )
+        }
+        (DataType::LargeBinary, DataType::LargeUtf8) => {
+            let array = column.as_binary::<i64>();
+            transcode::<i64>(
+                array.value_offsets(),
+                array.value_data(),
+                array.nulls(),
+                encoding,
+                name,
+            )
+        }
+        _ => Ok(column.clone()),
+    }
+}
+
+// Decodes the raw values of a binary array into a string array.
+fn transcode<O: OffsetSizeTrait>(
+    offsets: &[O],
+    data: &[u8],
+    nulls: Option<&NullBuffer>,
+    encoding: SourceEncoding,
+    column: &str,
+) -> Result<ArrayRef> {
+    let mut builder = GenericStringBuilder::<O>::new();
+    for (row, window) in offsets.windows(2).enumerate() {
+        if nulls.is_some_and(|nulls| nulls.is_null(row)) {
+            builder.append_null();
+            continue;
+        }
+        let start = window[0].as_usize();
+        let bytes = &data[start..window[1].as_usize()];
+        match encoding.decode(bytes) {
+            Ok(value) => builder.append_value(value),
+            Err(offset) => {
+                return Err(DataFusionError::Execution(format!(
+                    "invalid {encoding:?} in column {column} row {row} at byte offset {offset}: {:02x?}",
+                    &bytes[offset..(offset + 4).min(bytes.len())]
+                )))
+            }
+        }
+    }
+    Ok(Arc::new(builder.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(SourceEncoding::Latin1.decode(b"caf\xe9").unwrap(), "café");
+        assert_eq!(
+            SourceEncoding::Windows1252
+                .decode(b"\x80 \x93ok\x94")
+                .unwrap(),
+            "€ \u{201c}ok\u{201d}"
+        );
+        assert_eq!(SourceEncoding::Utf8.decode(b"ab\xe9c"), Err(2));
+    }
+}
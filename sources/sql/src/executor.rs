@@ -1,25 +1,28 @@
 use async_trait::async_trait;
-use connectorx::{
-    constants::RECORD_BATCH_SIZE,
-    destinations::arrow::ArrowDestinationError,
-    errors::{ConnectorXError, ConnectorXOutError},
-    prelude::{get_arrow, ArrowDestination, CXQuery, SourceConn},
-};
 use core::fmt;
 use datafusion::{
-    arrow::{
-        datatypes::{Field, Schema, SchemaRef},
-        record_batch::RecordBatch,
-    },
-    error::{DataFusionError, Result},
-    physical_plan::{RecordBatchStream, SendableRecordBatchStream},
+    arrow::{record_batch::RecordBatch, util::display::array_value_to_string},
+    common::not_impl_err,
+    error::Result,
+    physical_plan::SendableRecordBatchStream,
 };
-use futures::Stream;
-use std::{
-    sync::Arc,
-    task::{Context, Poll},
+use futures::TryStreamExt;
+use std::sync::Arc;
+
+use crate::{
+    dialect::{DefaultDialect, SQLDialect},
+    RemoteWarnings, SQLSourceAdmin, ServerVersion,
 };
-use tokio::task::{self, JoinError};
+
+#[cfg(feature = "connectorx")]
+mod connectorx;
+#[cfg(feature = "connectorx")]
+pub use self::connectorx::*;
+#[cfg(feature = "connectorx")]
+mod postgres;
+
+mod memory;
+pub use self::memory::*;
 
 pub type SQLExecutorRef = Arc<dyn SQLExecutor>;
 
@@ -27,123 +30,111 @@ pub type SQLExecutorRef = Arc<dyn SQLExecutor>;
 pub trait SQLExecutor: Sync + Send {
     fn name(&self) -> &str;
     fn compute_context(&self) -> Option<String>;
+    // The dialect the SQL sent to this executor is generated in.
+    fn dialect(&self) -> Arc<dyn SQLDialect> {
+        Arc::new(DefaultDialect {})
+    }
     // async since many query libraries will be async
     async fn execute(&self, query: &str) -> Result<SendableRecordBatchStream>;
-}
-
-impl fmt::Debug for dyn SQLExecutor {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {:?}", self.name(), self.compute_context())
+    // Executors that can capture backend notices report them to `warnings`,
+    // they may be pushed until the stream is drained.
+    async fn execute_with_warnings(
+        &self,
+        query: &str,
+        _warnings: RemoteWarnings,
+    ) -> Result<SendableRecordBatchStream> {
+        self.execute(query).await
     }
-}
-
-impl fmt::Display for dyn SQLExecutor {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} {:?}", self.name(), self.compute_context())
+    // The number of partitions queries are split into by split_query, each
+    // fetched as its own DataFusion partition.
+    fn partition_count(&self) -> usize {
+        1
     }
-}
-
-// TODO: break out SQLExecutor implementations
-pub struct CXExecutor {
-    context: String,
-    conn: SourceConn,
-}
-
-impl CXExecutor {
-    pub fn new(dsn: String) -> Result<Self> {
-        let conn = SourceConn::try_from(dsn.as_str()).map_err(cx_error_to_df)?;
-        Ok(Self { context: dsn, conn })
+    // Splits the query into partition_count queries returning disjoint parts
+    // of its result, e.g. by ranges of a column.
+    async fn split_query(&self, query: &str) -> Result<Vec<String>> {
+        Ok(vec![query.to_string()])
     }
-
-    pub fn new_with_conn(conn: SourceConn) -> Self {
-        Self {
-            context: conn.conn.to_string(),
-            conn,
-        }
+    // Executes a statement not returning rows, e.g. an INSERT,
+    // and returns the number of affected rows.
+    async fn execute_statement(&self, _statement: &str) -> Result<u64> {
+        not_impl_err!("{} does not execute statements", self.name())
     }
-
-    pub fn context(&mut self, context: String) {
-        self.context = context;
+    // Runs a `COPY ... FROM STDIN` statement streaming the data to the
+    // source, and returns the number of copied rows.
+    async fn copy_in(&self, _statement: &str, _data: Vec<u8>) -> Result<u64> {
+        not_impl_err!("{} does not copy data in", self.name())
     }
-}
-
-fn cx_error_to_df(err: ConnectorXError) -> DataFusionError {
-    DataFusionError::External(format!("ConnectorX: {err:?}").into())
-}
-
-#[async_trait]
-impl SQLExecutor for CXExecutor {
-    fn name(&self) -> &str {
-        "connector_x_executor"
-    }
-    fn compute_context(&self) -> Option<String> {
-        Some(self.context.clone())
+    // Opens a dedicated connection, for statements that must run on the same
+    // session, e.g. cursors. Executors backed by a pool should override it.
+    async fn connect(&self) -> Result<Box<dyn SQLConnection>> {
+        not_impl_err!("{} does not open dedicated connections", self.name())
     }
-    async fn execute(&self, sql: &str) -> Result<SendableRecordBatchStream> {
-        let conn = self.conn.clone();
-        let query: CXQuery = sql.into();
-
-        let dst = task::spawn_blocking(move || {
-            get_arrow(&conn, None, &[query]).map_err(cx_out_error_to_df)
-        })
-        .await
-        .map_err(join_error_to_df)??;
-
-        Ok(Box::pin(ArrowDestinationStream(dst)))
+    // Detects the server version with the dialect's version query, None if
+    // the dialect has none or the version isn't recognized.
+    async fn server_version(&self) -> Result<Option<ServerVersion>> {
+        let dialect = self.dialect();
+        let Some(query) = dialect.version_query() else {
+            return Ok(None);
+        };
+        let batches: Vec<RecordBatch> = self.execute(query).await?.try_collect().await?;
+        let version = batches
+            .iter()
+            .find(|b| b.num_rows() > 0 && b.num_columns() > 0)
+            .map(|b| array_value_to_string(b.column(0), 0))
+            .transpose()?;
+        Ok(version.as_deref().and_then(ServerVersion::parse))
     }
-}
-
-pub struct ArrowDestinationStream(ArrowDestination);
-
-impl Stream for ArrowDestinationStream {
-    type Item = datafusion::error::Result<RecordBatch>;
-
-    fn poll_next(
-        mut self: std::pin::Pin<&mut Self>,
-        _: &mut Context<'_>,
-    ) -> Poll<Option<Self::Item>> {
-        Poll::Ready({
-            let batch = self.0.record_batch().map_err(cx_dst_error_to_df)?;
-            batch.map(Ok)
-        })
+    // The schema and table management of the source, None if the executor
+    // doesn't run DDL.
+    fn admin(&self) -> Option<&dyn SQLSourceAdmin> {
+        None
     }
-
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        let size = RECORD_BATCH_SIZE;
-        (size, Some(size))
+    // Executes the statements in one transaction and returns the number of
+    // affected rows. Executors that can pin a connection should override it,
+    // callers decide how to write without a transaction.
+    async fn execute_transaction(&self, _statements: &[String]) -> Result<u64> {
+        not_impl_err!("{} does not run transactions", self.name())
     }
 }
 
-fn cx_dst_error_to_df(err: ArrowDestinationError) -> DataFusionError {
-    DataFusionError::External(format!("ConnectorX failed to run query: {err:?}").into())
+// SQLConnection is a dedicated connection of an executor, its queries and
+// statements run in order in one session.
+#[async_trait]
+pub trait SQLConnection: Send {
+    async fn execute(&mut self, query: &str) -> Result<SendableRecordBatchStream>;
+    async fn execute_statement(&mut self, statement: &str) -> Result<u64>;
 }
 
-impl RecordBatchStream for ArrowDestinationStream {
-    /// Get the schema
-    fn schema(&self) -> SchemaRef {
-        let schema = self.0.arrow_schema();
-
-        // DF needs lower case schema
-        let lower_fields: Vec<_> = schema
-            .fields
-            .iter()
-            .map(|f| {
-                Field::new(
-                    f.name().to_ascii_lowercase(),
-                    f.data_type().clone(),
-                    f.is_nullable(),
-                )
-            })
-            .collect();
-
-        Arc::new(Schema::new(lower_fields))
+// Executes the statements in a transaction on the connection, rolled back
+// if one fails.
+pub(crate) async fn transaction_on(
+    connection: &mut dyn SQLConnection,
+    statements: &[String],
+) -> Result<u64> {
+    connection.execute_statement("BEGIN").await?;
+    let mut count = 0;
+    for statement in statements {
+        match connection.execute_statement(statement).await {
+            Ok(rows) => count += rows,
+            Err(e) => {
+                let _ = connection.execute_statement("ROLLBACK").await;
+                return Err(e);
+            }
+        }
     }
+    connection.execute_statement("COMMIT").await?;
+    Ok(count)
 }
 
-fn cx_out_error_to_df(err: ConnectorXOutError) -> DataFusionError {
-    DataFusionError::External(format!("ConnectorX failed to run query: {err:?}").into())
+impl fmt::Debug for dyn SQLExecutor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:?}", self.name(), self.compute_context())
+    }
 }
 
-fn join_error_to_df(err: JoinError) -> DataFusionError {
-    DataFusionError::External(format!("task failed: {err:?}").into())
+impl fmt::Display for dyn SQLExecutor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:?}", self.name(), self.compute_context())
+    }
 }
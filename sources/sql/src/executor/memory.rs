@@ -0,0 +1,176 @@
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{array::AsArray, datatypes::UInt64Type, record_batch::RecordBatch},
+    datasource::TableProvider,
+    error::Result,
+    execution::context::SessionContext,
+    physical_plan::SendableRecordBatchStream,
+};
+use std::sync::Arc;
+
+use super::{SQLConnection, SQLExecutor};
+use crate::SQLSourceAdmin;
+
+// MemorySQLExecutor runs the received SQL in an embedded DataFusion context,
+// acting as a remote engine over in-memory tables. Examples and tests use it
+// to exercise the generated SQL and its dispatch without a database.
+pub struct MemorySQLExecutor {
+    name: String,
+    ctx: SessionContext,
+}
+
+impl MemorySQLExecutor {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ctx: SessionContext::new(),
+        }
+    }
+
+    pub fn with_batch(self, table: &str, batch: RecordBatch) -> Result<Self> {
+        self.ctx.register_batch(table, batch)?;
+        Ok(self)
+    }
+
+    pub fn with_table(self, table: &str, provider: Arc<dyn TableProvider>) -> Result<Self> {
+        self.ctx.register_table(table, provider)?;
+        Ok(self)
+    }
+
+    // The embedded context, e.g. to register tables of other formats.
+    pub fn context(&self) -> &SessionContext {
+        &self.ctx
+    }
+}
+
+#[async_trait]
+impl SQLExecutor for MemorySQLExecutor {
+    fn name(&self) -> &str {
+        "memory_sql_executor"
+    }
+    fn compute_context(&self) -> Option<String> {
+        Some(self.name.clone())
+    }
+    async fn execute(&self, sql: &str) -> Result<SendableRecordBatchStream> {
+        self.ctx.sql(sql).await?.execute_stream().await
+    }
+    // DataFusion reports the affected rows as a single count
+    async fn execute_statement(&self, statement: &str) -> Result<u64> {
+        let batches = self.ctx.sql(statement).await?.collect().await?;
+        Ok(batches
+            .iter()
+            .filter(|b| b.num_rows() > 0)
+            .filter_map(|b| b.column(0).as_primitive_opt::<UInt64Type>())
+            .map(|counts| counts.value(0))
+            .sum())
+    }
+    // A session of its own over the same tables, SET doesn't change the
+    // executor's session
+    async fn connect(&self) -> Result<Box<dyn SQLConnection>> {
+        Ok(Box::new(MemoryConnection {
+            executor: MemorySQLExecutor {
+                name: self.name.clone(),
+                ctx: SessionContext::new_with_state(self.ctx.state()),
+            },
+        }))
+    }
+    fn admin(&self) -> Option<&dyn SQLSourceAdmin> {
+        Some(self)
+    }
+}
+
+struct MemoryConnection {
+    executor: MemorySQLExecutor,
+}
+
+#[async_trait]
+impl SQLConnection for MemoryConnection {
+    async fn execute(&mut self, query: &str) -> Result<SendableRecordBatchStream> {
+        self.executor.execute(query).await
+    }
+    async fn execute_statement(&mut self, statement: &str) -> Result<u64> {
+        self.executor.execute_statement(statement).await
+    }
+}
+
+impl SQLSourceAdmin for MemorySQLExecutor {}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::{Int64Array, StringArray},
+            datatypes::{DataType, Field, Int64Type, Schema},
+        },
+        catalog::schema::SchemaProvider,
+        execution::context::SessionContext,
+    };
+    use datafusion_federation::{FederatedQueryPlanner, FederationAnalyzerRule};
+
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::{SQLFederationProvider, SQLSchemaProvider};
+
+    #[tokio::test]
+    async fn test_federated_query() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+            ],
+        )
+        .unwrap();
+        let executor = MemorySQLExecutor::new("memory")
+            .with_batch("items", batch)
+            .unwrap();
+        let provider = Arc::new(SQLFederationProvider::new(Arc::new(executor)));
+        let schema_provider = SQLSchemaProvider::new(provider, vec!["items".to_string()])
+            .await
+            .unwrap();
+
+        let state = SessionContext::new()
+            .state()
+            .add_analyzer_rule(Arc::new(FederationAnalyzerRule::new()))
+            .with_query_planner(Arc::new(FederatedQueryPlanner::new()));
+        let ctx = SessionContext::new_with_state(state);
+        ctx.register_table("items", schema_provider.table("items").await.unwrap())
+            .unwrap();
+
+        let batches = ctx
+            .sql("SELECT count(*) AS n FROM items WHERE id > 1")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let counts = batches[0].column(0).as_primitive::<Int64Type>();
+        assert_eq!(counts.value(0), 2);
+    }
+
+    #[tokio::test]
+    async fn test_connect() {
+        let executor = MemorySQLExecutor::new("memory");
+        let show = "SHOW datafusion.catalog.information_schema";
+        let mut connection = executor.connect().await.unwrap();
+        connection
+            .execute_statement("SET datafusion.catalog.information_schema = true")
+            .await
+            .unwrap();
+        let batches: Vec<RecordBatch> = connection
+            .execute(show)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(batches[0].column(1).as_string::<i32>().value(0), "true");
+        // The executor's session is unchanged
+        assert!(executor.execute(show).await.is_err());
+    }
+}
@@ -0,0 +1,382 @@
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{
+        array::{ArrayRef, StringArray},
+        compute::cast,
+        datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+        record_batch::RecordBatch,
+    },
+    catalog::{schema::SchemaProvider, CatalogProvider},
+    common::not_impl_err,
+    error::Result,
+};
+use log::warn;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use crate::{schema::SQLTableSource, SQLFederationProvider, SQLSchemaProvider};
+
+// HiveMetastore abstracts the metastore (thrift) client, so any client
+// implementation can be plugged into the HiveCatalogProvider.
+#[async_trait]
+pub trait HiveMetastore: Send + Sync {
+    async fn get_all_databases(&self) -> Result<Vec<String>>;
+    async fn get_all_tables(&self, database: &str) -> Result<Vec<String>>;
+    async fn get_table(&self, database: &str, table: &str) -> Result<HiveTable>;
+    // The values of the partition keys of each partition, in the order of
+    // the keys. Filters on partition keys are then mapped to the partitions
+    // they read, partitions added later are only read once the catalog is
+    // reloaded. None if partitions aren't listed.
+    async fn get_partition_values(
+        &self,
+        _database: &str,
+        _table: &str,
+    ) -> Result<Option<Vec<Vec<String>>>> {
+        Ok(None)
+    }
+}
+
+// The partition value Hive stores NULL partition keys as
+const HIVE_DEFAULT_PARTITION: &str = "__HIVE_DEFAULT_PARTITION__";
+
+pub struct HiveTable {
+    pub name: String,
+    pub columns: Vec<HiveColumn>,
+    // Partition keys are exposed as regular columns, filters on them are
+    // pushed down to the lake engine which prunes the partitions, see
+    // HiveMetastore::get_partition_values.
+    pub partition_keys: Vec<HiveColumn>,
+}
+
+pub struct HiveColumn {
+    pub name: String,
+    pub type_name: String,
+}
+
+// HiveCatalogProvider exposes every database of a Hive Metastore as a schema
+// of federated tables.
+pub struct HiveCatalogProvider {
+    schemas: HashMap<String, Arc<SQLSchemaProvider>>,
+}
+
+impl HiveCatalogProvider {
+    // Loads all databases & tables from the metastore. Each database is federated
+    // through the provider returned by `provider_for_database`, usually wrapping an
+    // executor for the lake engine (Trino, Athena, ...) scoped to that database.
+    pub async fn new<F>(metastore: Arc<dyn HiveMetastore>, provider_for_database: F) -> Result<Self>
+    where
+        F: Fn(&str) -> Result<Arc<SQLFederationProvider>>,
+    {
+        let mut schemas = HashMap::new();
+        for database in metastore.get_all_databases().await? {
+            let provider = provider_for_database(&database)?;
+            let mut sources = vec![];
+            for table_name in metastore.get_all_tables(&database).await? {
+                let table = metastore.get_table(&database, &table_name).await?;
+                // A table of unsupported types doesn't fail the catalog
+                let schema = hive_table_schema(&table, provider.unknown_type_fallback);
+                let (schema, text_columns) = match schema {
+                    Ok(schema) => schema,
+                    Err(e) => {
+                        warn!(
+                            "federation database={} table={} decision=skip reason=\"{}\"",
+                            database, table_name, e
+                        );
+                        continue;
+                    }
+                };
+                let partitions = match table.partition_keys.is_empty() {
+                    true => None,
+                    false => {
+                        metastore
+                            .get_partition_values(&database, &table_name)
+                            .await?
+                    }
+                };
+                let partitions = partitions
+                    .map(|values| partition_batch(&table, &schema, values))
+                    .transpose()?;
+                let mut source =
+                    SQLTableSource::new_with_schema(provider.clone(), table.name, schema)?
+                        .with_text_columns(text_columns);
+                if let Some(partitions) = partitions {
+                    source = source.with_partitions(partitions);
+                }
+                sources.push(Arc::new(source));
+            }
+            schemas.insert(
+                database,
+                Arc::new(SQLSchemaProvider::new_with_sources(sources)),
+            );
+        }
+        Ok(Self { schemas })
+    }
+}
+
+impl CatalogProvider for HiveCatalogProvider {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema_names(&self) -> Vec<String> {
+        self.schemas.keys().cloned().collect()
+    }
+
+    fn schema(&self, name: &str) -> Option<Arc<dyn SchemaProvider>> {
+        self.schemas
+            .get(name)
+            .map(|s| s.clone() as Arc<dyn SchemaProvider>)
+    }
+}
+
+// Returns the table schema and the columns of unsupported types exposed as Utf8.
+fn hive_table_schema(
+    table: &HiveTable,
+    unknown_type_fallback: bool,
+) -> Result<(SchemaRef, HashSet<String>)> {
+    let mut text_columns = HashSet::new();
+    let fields = table
+        .columns
+        .iter()
+        .chain(table.partition_keys.iter())
+        .map(|c| {
+            let name = c.name.to_ascii_lowercase();
+            let data_type = match hive_type_to_arrow(&c.type_name) {
+                Ok(data_type) => data_type,
+                Err(e) if unknown_type_fallback => {
+                    warn!(
+                        "federation table={} column={name} reason=\"{e}, using Utf8\"",
+                        table.name
+                    );
+                    text_columns.insert(name.clone());
+                    DataType::Utf8
+                }
+                Err(e) => return Err(e),
+            };
+            Ok(Field::new(name, data_type, true))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok((Arc::new(Schema::new(fields)), text_columns))
+}
+
+// Returns the partition values as a batch of the partition key columns.
+fn partition_batch(
+    table: &HiveTable,
+    schema: &Schema,
+    values: Vec<Vec<String>>,
+) -> Result<RecordBatch> {
+    let mut fields = vec![];
+    let mut columns: Vec<ArrayRef> = vec![];
+    for (i, key) in table.partition_keys.iter().enumerate() {
+        let field = schema.field_with_name(&key.name.to_ascii_lowercase())?;
+        let strings = values
+            .iter()
+            .map(|partition| {
+                partition
+                    .get(i)
+                    .filter(|v| v.as_str() != HIVE_DEFAULT_PARTITION)
+            })
+            .collect::<StringArray>();
+        fields.push(Field::new(field.name(), field.data_type().clone(), true));
+        columns.push(cast(&strings, field.data_type())?);
+    }
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+fn hive_type_to_arrow(type_name: &str) -> Result<DataType> {
+    let type_name = type_name.trim().to_ascii_lowercase();
+    let (base, args) = match type_name.split_once('(') {
+        Some((base, args)) => (base.trim(), args.trim_end_matches(')')),
+        None => (type_name.as_str(), ""),
+    };
+    Ok(match base {
+        "boolean" => DataType::Boolean,
+        "tinyint" => DataType::Int8,
+        "smallint" => DataType::Int16,
+        "int" | "integer" => DataType::Int32,
+        "bigint" => DataType::Int64,
+        "float" => DataType::Float32,
+        "double" => DataType::Float64,
+        "string" | "varchar" | "char" => DataType::Utf8,
+        "binary" => DataType::Binary,
+        "date" => DataType::Date32,
+        "timestamp" => DataType::Timestamp(TimeUnit::Microsecond, None),
+        "decimal" | "numeric" => {
+            let mut parts = args.split(',').map(|p| p.trim().parse::<u8>());
+            let precision = parts.next().and_then(|p| p.ok()).unwrap_or(10);
+            let scale = parts.next().and_then(|s| s.ok()).unwrap_or(0);
+            DataType::Decimal128(precision, scale as i8)
+        }
+        _ => return not_impl_err!("Unsupported Hive type: {type_name}"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use datafusion::{
+        arrow::array::{Int32Array, Int64Array},
+        execution::context::SessionContext,
+        physical_plan::SendableRecordBatchStream,
+    };
+    use datafusion_federation::{FederatedQueryPlanner, FederationAnalyzerRule};
+
+    use super::*;
+    use crate::executor::{MemorySQLExecutor, SQLExecutor};
+
+    struct Metastore {}
+
+    fn column(name: &str, type_name: &str) -> HiveColumn {
+        HiveColumn {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+        }
+    }
+
+    #[async_trait]
+    impl HiveMetastore for Metastore {
+        async fn get_all_databases(&self) -> Result<Vec<String>> {
+            Ok(vec!["db".to_string()])
+        }
+        async fn get_all_tables(&self, _database: &str) -> Result<Vec<String>> {
+            Ok(vec!["events".to_string(), "tags".to_string()])
+        }
+        async fn get_table(&self, _database: &str, table: &str) -> Result<HiveTable> {
+            Ok(match table {
+                "events" => HiveTable {
+                    name: table.to_string(),
+                    columns: vec![column("id", "bigint")],
+                    partition_keys: vec![column("region", "string"), column("year", "int")],
+                },
+                _ => HiveTable {
+                    name: table.to_string(),
+                    columns: vec![column("tags", "map<string,int>")],
+                    partition_keys: vec![],
+                },
+            })
+        }
+        async fn get_partition_values(
+            &self,
+            _database: &str,
+            _table: &str,
+        ) -> Result<Option<Vec<Vec<String>>>> {
+            let partitions = [["EU", "2023"], ["EU", "2024"], ["US", "2024"]];
+            Ok(Some(
+                partitions
+                    .iter()
+                    .map(|p| p.iter().map(|v| v.to_string()).collect())
+                    .collect(),
+            ))
+        }
+    }
+
+    // Records the queries run on the lake engine
+    struct Recording {
+        memory: MemorySQLExecutor,
+        queries: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl SQLExecutor for Recording {
+        fn name(&self) -> &str {
+            self.memory.name()
+        }
+        fn compute_context(&self) -> Option<String> {
+            self.memory.compute_context()
+        }
+        async fn execute(&self, query: &str) -> Result<SendableRecordBatchStream> {
+            self.queries.lock().unwrap().push(query.to_string());
+            self.memory.execute(query).await
+        }
+    }
+
+    async fn catalog() -> (HiveCatalogProvider, Arc<Recording>) {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new("region", DataType::Utf8, true),
+            Field::new("year", DataType::Int32, true),
+        ]));
+        let events = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["EU", "EU", "US"])),
+                Arc::new(Int32Array::from(vec![2023, 2024, 2024])),
+            ],
+        )
+        .unwrap();
+        let executor = Arc::new(Recording {
+            memory: MemorySQLExecutor::new("lake")
+                .with_batch("events", events)
+                .unwrap(),
+            queries: Mutex::new(vec![]),
+        });
+        let provider = Arc::new(
+            SQLFederationProvider::new(executor.clone()).with_unknown_type_fallback(false),
+        );
+        let catalog = HiveCatalogProvider::new(Arc::new(Metastore {}), |_| Ok(provider.clone()))
+            .await
+            .unwrap();
+        (catalog, executor)
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_table_skipped() {
+        let (catalog, _) = catalog().await;
+        let schema = catalog.schema("db").unwrap();
+        assert_eq!(schema.table_names(), vec!["events".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_partition_pruning() {
+        let (catalog, executor) = catalog().await;
+        let state = SessionContext::new()
+            .state()
+            .add_analyzer_rule(Arc::new(FederationAnalyzerRule::new()))
+            .with_query_planner(Arc::new(FederatedQueryPlanner::new()));
+        let ctx = SessionContext::new_with_state(state);
+        ctx.register_catalog("hive", Arc::new(catalog));
+
+        let rows = |batches: Vec<RecordBatch>| batches.iter().map(|b| b.num_rows()).sum::<usize>();
+        let batches = ctx
+            .sql("SELECT id FROM hive.db.events WHERE lower(region) = 'eu' AND year > 2023")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(rows(batches), 1);
+        let query = executor.queries.lock().unwrap().pop().unwrap();
+        assert!(query.contains("IN ('EU')"), "{query}");
+        assert!(query.contains("IN (2024)"), "{query}");
+
+        // No partition is read
+        let batches = ctx
+            .sql("SELECT id FROM hive.db.events WHERE lower(region) = 'asia'")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(rows(batches), 0);
+        let query = executor.queries.lock().unwrap().pop().unwrap();
+        assert!(query.to_lowercase().contains("false"), "{query}");
+    }
+
+    #[test]
+    fn test_hive_type_to_arrow() {
+        assert_eq!(hive_type_to_arrow("BIGINT").unwrap(), DataType::Int64);
+        assert_eq!(
+            hive_type_to_arrow("decimal(12, 2)").unwrap(),
+            DataType::Decimal128(12, 2)
+        );
+        assert!(hive_type_to_arrow("map<string,int>").is_err());
+    }
+}
@@ -1,20 +1,49 @@
 use core::fmt;
-use std::{any::Any, sync::Arc, vec};
+use std::{
+    any::Any,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+    vec,
+};
 
 use async_trait::async_trait;
 use datafusion::{
-    arrow::datatypes::{Schema, SchemaRef},
+    arrow::{
+        compute::{cast, SortOptions},
+        datatypes::{DataType, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    },
+    common::{
+        not_impl_err, plan_err,
+        tree_node::{TreeNode, VisitRecursion},
+    },
     config::ConfigOptions,
-    error::Result,
+    datasource::TableProvider,
+    error::{DataFusionError, Result},
     execution::{context::SessionState, TaskContext},
-    logical_expr::{Extension, LogicalPlan},
+    logical_expr::{expr, Expr, Extension, LogicalPlan, LogicalPlanBuilder},
     optimizer::analyzer::{Analyzer, AnalyzerRule},
-    physical_expr::PhysicalSortExpr,
-    physical_plan::{DisplayAs, DisplayFormatType, ExecutionPlan, SendableRecordBatchStream},
+    physical_expr::{expressions::Column, PhysicalSortExpr},
+    physical_plan::{
+        execute_stream,
+        metrics::{ExecutionPlanMetricsSet, MetricBuilder, MetricsSet},
+        stream::RecordBatchStreamAdapter,
+        DisplayAs, DisplayFormatType, EmptyRecordBatchStream, ExecutionPlan,
+        SendableRecordBatchStream,
+    },
+    sql::sqlparser::{ast, parser::Parser},
+};
+use datafusion_federation::{
+    get_table_source, record_fallback, FederatedPlanNode, FederatedQueryPlanner,
+    FederatedTableProviderAdaptor, FederationConfig, FederationPlanner, FederationProvider,
 };
-use datafusion_federation::{FederatedPlanNode, FederationPlanner, FederationProvider};
+use dialect::{AsOf, SQLDialect};
 use executor::SQLExecutor;
+use futures::{StreamExt, TryStreamExt};
+use log::{debug, warn};
 
+pub mod dialect;
 pub mod executor;
 mod schema;
 use futures::executor::block_on;
@@ -24,25 +53,622 @@ pub use schema::*;
 // extern crate derive_builder;
 
 mod producer;
-use producer::query_to_sql;
+use producer::{plan_to_statement, query_to_sql, render_table_aliases};
 
 mod ast_builder;
+mod nesting;
+
+mod subquery_limit;
+use subquery_limit::rewrite_subquery_limits;
+
+mod statement_size;
+use statement_size::split_in_list;
+
+mod canonical;
+use canonical::canonicalize;
+
+mod probe;
+use probe::probe_schema;
+
+mod strict;
+use strict::verify_stream;
+
+mod write;
+pub use write::*;
+
+mod materialize;
+pub use materialize::*;
+
+mod hive;
+pub use hive::*;
+
+mod discovery;
+pub use discovery::*;
+
+mod schema_cache;
+pub use schema_cache::*;
+
+mod verify;
+pub use verify::*;
+
+mod scheduler;
+pub use scheduler::*;
+
+mod slow_query;
+pub use slow_query::*;
+
+mod dedup;
+pub use dedup::*;
+
+mod transform;
+pub use transform::*;
+
+mod cache;
+pub use cache::*;
+
+mod replica;
+pub use replica::*;
+
+mod warnings;
+pub use warnings::*;
+
+mod workload;
+pub use workload::*;
+
+mod sort_cost;
+pub use sort_cost::*;
+
+mod table_function;
+pub use table_function::*;
+
+mod reduce;
+use reduce::{is_pushdown_error, reduced_plans};
+
+mod cursor;
+pub use cursor::*;
+
+mod blocking_pool;
+pub use blocking_pool::*;
+
+mod admin;
+pub use admin::*;
+
+mod recording;
+use recording::record_stream;
+pub use recording::{read_query_records, QueryRecord, QueryRecorder};
+
+mod chunking;
+pub use chunking::*;
+
+mod empty_string;
+use empty_string::nulls_to_empty_strings;
+
+mod retry;
+use retry::execute_partition;
+
+mod row_count;
+use row_count::count_stream;
+pub use row_count::RowCountCheck;
+
+mod server_version;
+pub use server_version::*;
+
+mod session_variables;
+use session_variables::{setting_statements, SessionVariables, SessionVariablesExecutor};
+
+mod unload;
+use unload::unload_stream;
+pub use unload::Unload;
+
+mod default_limit;
+use default_limit::apply_default_limit;
+pub use default_limit::DefaultLimit;
+
+mod capabilities;
+pub use capabilities::*;
+
+mod column_bytes;
+use column_bytes::column_bytes_stream;
+
+mod ship;
+pub use ship::*;
+
+mod complexity;
+pub use complexity::*;
+
+mod prefetch;
+pub use prefetch::*;
+
+mod encoding;
+use encoding::transcode_stream;
+pub use encoding::SourceEncoding;
+
+mod cost_budget;
+pub use cost_budget::*;
+
+mod shared_tables;
+pub use shared_tables::*;
+
+mod manifest;
+use manifest::describe_provider;
+
+mod standard;
+use standard::{non_standard_operator, StandardDialect};
+
+mod provenance;
+use provenance::{next_query_id, provenance_stream};
+pub use provenance::{
+    BatchProvenance, PROVENANCE_COMPUTE_CONTEXT, PROVENANCE_PARTITION, PROVENANCE_QUERY_ID,
+    PROVENANCE_SEQUENCE, PROVENANCE_SOURCE,
+};
+
+mod partition_pruning;
+use partition_pruning::prune_partitions;
 
 // SQLFederationProvider provides federation to SQL DMBSs.
 pub struct SQLFederationProvider {
     analyzer: Arc<Analyzer>,
     executor: Arc<dyn SQLExecutor>,
+    planner: SQLFederationPlanner,
+    search_path: Vec<String>,
+    case_insensitive_columns: bool,
+    unknown_type_fallback: bool,
+    inline_views: bool,
+    returning: bool,
+    write_options: WriteOptions,
+    batch_shipper: Option<Arc<dyn BatchShipper>>,
+    schema_cache: Option<Arc<SchemaCache>>,
 }
 
 impl SQLFederationProvider {
     pub fn new(executor: Arc<dyn SQLExecutor>) -> Self {
+        let planner = SQLFederationPlanner::new(executor.clone());
         Self {
-            analyzer: Arc::new(Analyzer::with_rules(vec![Arc::new(
-                SQLFederationAnalyzerRule::new(executor.clone()),
-            )])),
+            analyzer: new_analyzer(&planner),
             executor,
+            planner,
+            search_path: vec![],
+            case_insensitive_columns: false,
+            unknown_type_fallback: true,
+            inline_views: false,
+            returning: false,
+            write_options: WriteOptions::default(),
+            batch_shipper: None,
+            schema_cache: None,
+        }
+    }
+
+    // Resolves unqualified table names against the given remote schemas, in order,
+    // when tables are registered. Generated SQL always uses the resolved name.
+    pub fn with_search_path(mut self, search_path: Vec<String>) -> Self {
+        self.search_path = search_path;
+        self
+    }
+
+    // Exposes the columns of registered tables in lowercase, so unquoted column
+    // references resolve regardless of the source's casing. Generated SQL uses
+    // the exact source casing.
+    pub fn with_case_insensitive_columns(mut self, enabled: bool) -> Self {
+        self.case_insensitive_columns = enabled;
+        self
+    }
+
+    // Exposes columns of unknown or backend specific types as Utf8, cast to text
+    // in the source, with a warning instead of failing the registration.
+    // Enabled by default.
+    pub fn with_unknown_type_fallback(mut self, enabled: bool) -> Self {
+        self.unknown_type_fallback = enabled;
+        self
+    }
+
+    // Inlines the definition of discovered remote views into the generated SQL,
+    // so the source plans the view together with the rest of the query.
+    pub fn with_view_inlining(mut self, enabled: bool) -> Self {
+        self.inline_views = enabled;
+        self
+    }
+
+    // Reads the schemas of registered tables from the cache, and stores the
+    // inferred ones in it.
+    pub fn with_schema_cache(mut self, cache: Arc<SchemaCache>) -> Self {
+        self.schema_cache = Some(cache);
+        self
+    }
+
+    // Sets how inserts and upserts are split into statements and transactions.
+    pub fn with_write_options(mut self, options: WriteOptions) -> Self {
+        self.write_options = options;
+        self
+    }
+
+    // Sets how local results are shipped into remote tables, e.g. by
+    // materialize_remote. Defaults to the dialect's default_shipper.
+    pub fn with_batch_shipper(mut self, shipper: Arc<dyn BatchShipper>) -> Self {
+        self.batch_shipper = Some(shipper);
+        self
+    }
+
+    pub(crate) fn batch_shipper(&self) -> Arc<dyn BatchShipper> {
+        match &self.batch_shipper {
+            Some(shipper) => shipper.clone(),
+            None => default_shipper(self.planner.dialect.name()),
+        }
+    }
+
+    // Returns the inserted rows, including values generated by the source, from
+    // inserts instead of the row count, if the dialect supports RETURNING.
+    // DataFusion doesn't plan UPDATE and DELETE, so only inserts are pushed down.
+    pub fn with_returning(mut self, enabled: bool) -> Self {
+        self.returning = enabled;
+        self
+    }
+
+    // Refreshes the given remote materialized views before each query reading
+    // them, for queries that must not see stale data.
+    pub fn with_refreshed_views(mut self, views: Vec<String>) -> Self {
+        self.planner.refreshed_views = views;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Dispatches remote queries through the given scheduler.
+    pub fn with_scheduler(mut self, scheduler: Arc<FairScheduler>) -> Self {
+        self.planner.scheduler = Some(scheduler);
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Routes remote queries to replicas fresh enough for the query,
+    // the provider's executor is the primary.
+    pub fn with_replicas(mut self, replicas: Arc<ReplicaSet>) -> Self {
+        self.planner.replicas = Some(replicas);
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Decides whether ORDER BY is pushed down with the given costs instead of
+    // the dialect's default profile.
+    pub fn with_sort_cost_profile(mut self, profile: SortCostProfile) -> Self {
+        self.planner.sort_cost = Some(profile);
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Generates the remote SQL in the given dialect instead of the executor's.
+    pub fn with_dialect(mut self, dialect: Arc<dyn SQLDialect>) -> Self {
+        self.planner.dialect = match self.planner.standard_sql {
+            Some(_) => {
+                self.planner.standard_sql = Some(dialect.clone());
+                Arc::new(StandardDialect::new(dialect))
+            }
+            None => dialect,
+        };
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Generates the remote SQL in the dialect registered under the name,
+    // see dialect::register_dialect.
+    pub fn with_dialect_name(self, name: &str) -> Result<Self> {
+        match dialect::registered_dialect(name) {
+            Some(dialect) => Ok(self.with_dialect(dialect)),
+            None => plan_err!("unknown dialect {name}"),
         }
     }
+
+    // Detects the server version with the executor and adjusts the dialect's
+    // capabilities to it. Executors detecting it themselves, e.g. CXExecutor,
+    // already return the adjusted dialect.
+    pub async fn with_detected_version(self) -> Result<Self> {
+        let Some(version) = self.executor.server_version().await? else {
+            return Ok(self);
+        };
+        debug!(
+            "federation rule=federate_sql server_version={version} dialect={}",
+            self.planner.dialect.name()
+        );
+        let dialect = VersionedDialect::new(self.planner.dialect.clone(), version);
+        Ok(self.with_dialect(Arc::new(dialect)))
+    }
+
+    // Probes the capabilities of the source, the dialect is then adjusted to
+    // the ones it lacks, e.g. for a server of unknown version.
+    pub async fn with_probed_capabilities(self) -> Result<Self> {
+        let report = probe_capabilities(self.executor.as_ref()).await;
+        debug!(
+            "federation rule=federate_sql capabilities dialect={} report={report:?}",
+            self.planner.dialect.name()
+        );
+        let dialect = report.apply(self.planner.dialect.clone());
+        Ok(self.with_dialect(dialect))
+    }
+
+    // Restricts the generated SQL to SQL:2011, without the dialect's extensions,
+    // e.g. for proxies or engines of unknown dialect. Parts of plans with no
+    // standard SQL run locally, counted as `standard_sql` fallbacks.
+    pub fn with_standard_sql(mut self, enabled: bool) -> Self {
+        match (enabled, self.planner.standard_sql.take()) {
+            (true, None) => {
+                let dialect = self.planner.dialect.clone();
+                self.planner.dialect = Arc::new(StandardDialect::new(dialect.clone()));
+                self.planner.standard_sql = Some(dialect);
+            }
+            (true, dialect) => self.planner.standard_sql = dialect,
+            (false, Some(dialect)) => self.planner.dialect = dialect,
+            (false, None) => {}
+        }
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Canonicalizes the generated SQL, so equivalent plans produce byte-identical SQL.
+    pub fn with_canonical_sql(mut self, enabled: bool) -> Self {
+        self.planner.canonical_sql = enabled;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Applies the transform to every batch fetched from this source.
+    pub fn with_batch_transform(mut self, transform: Arc<dyn BatchTransform>) -> Self {
+        self.planner.batch_transform = Some(transform);
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Keeps GROUP BYs local and fetches their input ordered by the group keys,
+    // so DataFusion aggregates in streaming mode with bounded memory instead
+    // of building a full hash table. Setting `datafusion.optimizer.prefer_existing_sort`
+    // keeps the remote ordering across repartitioning.
+    pub fn with_streaming_aggregation(mut self, enabled: bool) -> Self {
+        self.planner.streaming_aggregation = enabled;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Labels remote queries with a workload class, mapped by the governor to the
+    // backend's resource governance. Queries without a WorkloadClass session
+    // extension use the given default class, e.g. per table group.
+    pub fn with_workload(
+        mut self,
+        governor: Arc<dyn WorkloadGovernor>,
+        default_class: WorkloadClass,
+    ) -> Self {
+        self.planner.workload_governor = Some(governor);
+        self.planner.workload_class = default_class;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Probes every generated query at plan time, without fetching rows, so SQL
+    // the source rejects or returning unexpected columns fails planning with
+    // a clear error instead of failing mid-execution.
+    pub fn with_schema_probe(mut self, enabled: bool) -> Self {
+        self.planner.schema_probe = enabled;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Overrides the Arrow type of columns by their remote type name, e.g. `json`
+    // or `year`, for types the executor maps unexpectedly. Tables registered
+    // afterwards declare the overridden types, fetched columns are cast to them.
+    pub fn with_type_overrides(mut self, overrides: HashMap<String, DataType>) -> Self {
+        self.planner.type_overrides = overrides
+            .into_iter()
+            .map(|(type_name, data_type)| (type_name.to_ascii_lowercase(), data_type))
+            .collect();
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Re-parses the generated SQL in the dialect before dispatching it, so SQL
+    // the engine can't parse fails locally with a detailed error.
+    pub fn with_sql_validation(mut self, enabled: bool) -> Self {
+        self.planner.validate_sql = enabled;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Verifies that fetched values fit the declared types and nullability,
+    // failing with the offending column and row otherwise.
+    pub fn with_strict_types(mut self, enabled: bool) -> Self {
+        self.planner.strict_types = enabled;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Limits queries without a limit or aggregation to `limit` rows, so an
+    // interactive query can't fetch a whole large table by accident. Sessions
+    // override it with `SET federation.default_limit` or the DefaultLimit
+    // extension. Queries federated below local operators, e.g. a local join,
+    // aren't limited, their result would be computed from truncated rows.
+    pub fn with_default_limit(mut self, limit: Option<usize>) -> Self {
+        self.planner.default_limit = limit;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Compares the rows received by every completed scan with a `SELECT count(*)`
+    // of the same query, warning or failing on a mismatch. Costs an extra query.
+    pub fn with_row_count_check(mut self, check: Option<RowCountCheck>) -> Self {
+        self.planner.row_count_check = check;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Retries the query of a failed partition up to `retries` times, as long
+    // as it failed before returning rows. Other partitions are not re-run.
+    pub fn with_partition_retries(mut self, retries: usize) -> Self {
+        self.planner.partition_retries = retries;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Returns fetched NULL strings as empty strings, for sources storing ''
+    // as NULL whose dialect also renders comparisons with '' as NULL checks.
+    pub fn with_empty_string_normalization(mut self, enabled: bool) -> Self {
+        self.planner.normalize_empty_strings = enabled;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Sets the character encoding of the source's strings, binary columns
+    // declared as strings are transcoded to UTF-8 when fetched.
+    pub fn with_encoding(mut self, encoding: SourceEncoding) -> Self {
+        self.planner.encoding = Some(encoding);
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Counts the bytes fetched per column in the `bytes_transferred.<column>`
+    // metrics of the scans, shown by EXPLAIN ANALYZE.
+    pub fn with_column_metrics(mut self, enabled: bool) -> Self {
+        self.planner.column_metrics = enabled;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Tags each fetched batch with its source, remote query id and sequence
+    // number in its schema metadata, see BatchProvenance. The query id is
+    // logged with the executed SQL.
+    pub fn with_batch_provenance(mut self, enabled: bool) -> Self {
+        self.planner.batch_provenance = enabled;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Records every executed query with its plan, SQL and result shape.
+    pub fn with_query_recorder(mut self, recorder: Arc<QueryRecorder>) -> Self {
+        self.planner.query_recorder = Some(recorder);
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Retries queries the source rejects as invalid or unsupported with less
+    // pushed down: keeping each predicate of the outermost filter local in
+    // turn, then federating only the table scans. What is kept local is logged.
+    pub fn with_pushdown_reduction(mut self, enabled: bool) -> Self {
+        self.planner.pushdown_reduction = enabled;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Passes the allowed session variables set with
+    // `SET federation.<source>.<variable> = ...` to the remote session, each
+    // query then runs on a dedicated connection with the variables set.
+    // The executor must support SQLExecutor::connect.
+    pub fn with_session_variables(mut self, source: String, allowed: Vec<String>) -> Self {
+        self.planner.session_variables = Some(SessionVariables { source, allowed });
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Sets the settings on the connection of each query with the SET
+    // statement of the dialect, e.g. MySQL's `time_zone` or Oracle's
+    // `NLS_DATE_FORMAT`, so results don't depend on server defaults.
+    // The executor must support SQLExecutor::connect.
+    pub fn with_connection_settings(mut self, settings: Vec<(String, String)>) -> Self {
+        self.planner.connection_settings = settings;
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Reads results through object storage, see Unload. The dialect must
+    // support unloading and the executor must execute statements.
+    pub fn with_unload(mut self, unload: Unload) -> Self {
+        self.planner.unload = Some(unload);
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Warns about generated SQL over the thresholds, e.g. its size, and
+    // reports it to the observer of the alerts.
+    pub fn with_sql_complexity_alerts(mut self, alerts: Arc<SQLComplexityAlerts>) -> Self {
+        self.planner.complexity_alerts = Some(alerts);
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Rejects queries estimated to scan more of the source than the budget
+    // of their session or tenant, before they are sent.
+    pub fn with_cost_budgets(mut self, budgets: CostBudgets) -> Self {
+        self.planner.cost_budgets = Some(Arc::new(budgets));
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+
+    // Logs remote queries exceeding the slow query threshold.
+    pub fn with_slow_query_log(mut self, slow_query_log: Arc<SlowQueryLog>) -> Self {
+        self.planner.slow_query_log = Some(slow_query_log);
+        self.analyzer = new_analyzer(&self.planner);
+        self
+    }
+}
+
+impl SQLFederationProvider {
+    // The schema and table management of the source, None if its executor
+    // doesn't run DDL.
+    pub fn admin(&self) -> Option<&dyn SQLSourceAdmin> {
+        self.executor.admin()
+    }
+
+    // Returns the remote table as it was at a past point in time, read with
+    // the dialect's time travel clause. Fails if the dialect has none.
+    pub async fn table_as_of(
+        self: &Arc<Self>,
+        table: &str,
+        as_of: AsOf,
+    ) -> Result<Arc<dyn TableProvider>> {
+        if self.planner.dialect.time_travel(&as_of).is_none() {
+            return not_impl_err!(
+                "time travel to {as_of:?} for dialect {}",
+                self.planner.dialect.name()
+            );
+        }
+        let source = SQLTableSource::new(self.clone(), table.to_string())
+            .await?
+            .with_as_of(as_of);
+        Ok(Arc::new(FederatedTableProviderAdaptor::new(Arc::new(
+            source,
+        ))))
+    }
+
+    // Upserts the data into the remote table: rows matching an existing row on
+    // the key columns update it, others are inserted. Columns are matched by
+    // name, returns the number of affected rows as reported by the source.
+    pub async fn upsert(
+        &self,
+        table: &str,
+        keys: &[String],
+        mut data: SendableRecordBatchStream,
+    ) -> Result<u64> {
+        let Some(strategy) = self.planner.dialect.upsert_strategy() else {
+            return not_impl_err!("upsert for dialect {}", self.planner.dialect.name());
+        };
+        let table = table.split('.').map(String::from).collect::<Vec<_>>();
+        let mut writer = StatementWriter::new(self.executor.clone(), self.write_options.clone());
+        let chunk_size = writer.rows_per_statement();
+        while let Some(batch) = data.next().await {
+            let batch = batch?;
+            for offset in (0..batch.num_rows()).step_by(chunk_size) {
+                let chunk = batch.slice(offset, chunk_size.min(batch.num_rows() - offset));
+                let statement = upsert_statement(
+                    &table,
+                    &chunk,
+                    keys,
+                    strategy,
+                    self.planner.dialect.as_ref(),
+                )?;
+                writer.push(statement, chunk.num_rows()).await?;
+            }
+        }
+        writer.finish().await
+    }
+}
+
+fn new_analyzer(planner: &SQLFederationPlanner) -> Arc<Analyzer> {
+    Arc::new(Analyzer::with_rules(vec![Arc::new(
+        SQLFederationAnalyzerRule::new(planner.clone()),
+    )]))
 }
 
 impl FederationProvider for SQLFederationProvider {
@@ -57,43 +683,388 @@ impl FederationProvider for SQLFederationProvider {
     fn analyzer(&self) -> Option<Arc<Analyzer>> {
         Some(self.analyzer.clone())
     }
+
+    fn supports_ordering_pushdown(&self) -> bool {
+        true
+    }
+
+    fn binary_collation(&self) -> bool {
+        self.planner.dialect.binary_collation()
+    }
+
+    fn describe(&self) -> serde_json::Value {
+        describe_provider(self)
+    }
 }
 
 struct SQLFederationAnalyzerRule {
     planner: Arc<dyn FederationPlanner>,
+    streaming_aggregation: bool,
+    dialect: Arc<dyn SQLDialect>,
+    sort_cost: SortCostProfile,
+    standard_sql: bool,
 }
 
 impl SQLFederationAnalyzerRule {
-    pub fn new(executor: Arc<dyn SQLExecutor>) -> Self {
+    pub fn new(planner: SQLFederationPlanner) -> Self {
         Self {
-            planner: Arc::new(SQLFederationPlanner::new(executor.clone())),
+            streaming_aggregation: planner.streaming_aggregation,
+            dialect: planner.dialect.clone(),
+            sort_cost: planner
+                .sort_cost
+                .clone()
+                .unwrap_or_else(|| planner.dialect.sort_cost_profile()),
+            standard_sql: planner.standard_sql.is_some(),
+            planner: Arc::new(planner),
         }
     }
-}
 
-impl AnalyzerRule for SQLFederationAnalyzerRule {
-    fn analyze(&self, plan: LogicalPlan, _config: &ConfigOptions) -> Result<LogicalPlan> {
-        // Simply accept the entire plan for now
+    fn federate(&self, plan: LogicalPlan) -> Result<LogicalPlan> {
+        if self.streaming_aggregation && contains_aggregate(&plan) {
+            if let LogicalPlan::Aggregate(agg) = &plan {
+                let sortable = !agg.group_expr.is_empty()
+                    && !agg
+                        .group_expr
+                        .iter()
+                        .any(|e| matches!(e, Expr::GroupingSet(_)));
+                if sortable {
+                    debug!(
+                        "federation rule=federate_sql decision=split reason=streaming_aggregation node=\"{}\"",
+                        plan.display()
+                    );
+                    record_fallback("streaming_aggregation");
+                    let sort_exprs = agg.group_expr.iter().map(|e| e.clone().sort(true, false));
+                    let input = LogicalPlanBuilder::from(agg.input.as_ref().clone())
+                        .sort(sort_exprs)?
+                        .build()?;
+                    let input = self.federate(input)?;
+                    return plan.with_new_inputs(&[input]);
+                }
+            }
+            let inputs = plan
+                .inputs()
+                .into_iter()
+                .map(|input| self.federate(input.clone()))
+                .collect::<Result<Vec<_>>>()?;
+            return plan.with_new_inputs(&inputs);
+        }
+
+        if !self.dialect.supports_window_functions() && contains_window(&plan) {
+            if matches!(plan, LogicalPlan::Window(_)) {
+                debug!(
+                    "federation rule=federate_sql decision=split reason=window_function node=\"{}\"",
+                    plan.display()
+                );
+                record_fallback("window_function");
+            }
+            let inputs = plan
+                .inputs()
+                .into_iter()
+                .map(|input| self.federate(input.clone()))
+                .collect::<Result<Vec<_>>>()?;
+            return plan.with_new_inputs(&inputs);
+        }
+
+        // Table scans are federated regardless
+        if !plan.inputs().is_empty() && !self.dialect.supports_plan(&plan) {
+            debug!(
+                "federation rule=federate_sql decision=split reason=unsupported_plan node=\"{}\"",
+                plan.display()
+            );
+            record_fallback("unsupported_plan");
+            let inputs = plan
+                .inputs()
+                .into_iter()
+                .map(|input| self.federate(input.clone()))
+                .collect::<Result<Vec<_>>>()?;
+            return plan.with_new_inputs(&inputs);
+        }
+
+        if self.standard_sql && !plan.inputs().is_empty() && !self.is_standard(&plan) {
+            // Unparsing fails at the node itself once its inputs are standard
+            let inputs_standard = plan.inputs().iter().all(|input| self.is_standard(input));
+            if let Some(op) = non_standard_operator(&plan) {
+                debug!(
+                    "federation rule=federate_sql decision=split reason=standard_sql operator={op} node=\"{}\"",
+                    plan.display()
+                );
+                record_fallback("standard_sql");
+            } else if inputs_standard {
+                debug!(
+                    "federation rule=federate_sql decision=split reason=standard_sql node=\"{}\"",
+                    plan.display()
+                );
+                record_fallback("standard_sql");
+            }
+            let inputs = plan
+                .inputs()
+                .into_iter()
+                .map(|input| self.federate(input.clone()))
+                .collect::<Result<Vec<_>>>()?;
+            return plan.with_new_inputs(&inputs);
+        }
+
+        if let Some(parts) = self.split_oversized(&plan)? {
+            debug!(
+                "federation rule=federate_sql decision=split reason=statement_size parts={} node=\"{}\"",
+                parts.len(),
+                plan.display()
+            );
+            record_fallback("statement_size");
+            let mut parts = parts.into_iter().map(|part| self.federate(part));
+            let mut builder = LogicalPlanBuilder::from(parts.next().unwrap()?);
+            for part in parts {
+                builder = builder.union(part?)?;
+            }
+            return builder.build();
+        }
 
-        let fed_plan = FederatedPlanNode::new(plan.clone(), self.planner.clone());
+        debug!(
+            "federation rule=federate_sql decision=accept node=\"{}\"",
+            plan.display()
+        );
+        let plan = prune_partitions(plan)?;
+        let fed_plan = FederatedPlanNode::new(plan, self.planner.clone());
         let ext_node = Extension {
             node: Arc::new(fed_plan),
         };
         Ok(LogicalPlan::Extension(ext_node))
     }
 
+    // Keeps the ORDER BY of the plan local if the sort cost profile says it's
+    // cheaper, the plan's input is federated.
+    fn sort_locally(&self, plan: &LogicalPlan) -> Result<Option<LogicalPlan>> {
+        let sort = match plan {
+            LogicalPlan::Sort(sort) => sort,
+            LogicalPlan::Projection(p) => match p.input.as_ref() {
+                LogicalPlan::Sort(sort) => sort,
+                _ => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        if self.sort_cost.push_sort(sort) {
+            return Ok(None);
+        }
+        debug!(
+            "federation rule=federate_sql decision=split reason=local_sort node=\"{}\"",
+            plan.display()
+        );
+        record_fallback("local_sort");
+        let input = self.federate(sort.input.as_ref().clone())?;
+        let local = LogicalPlan::Sort(sort.clone()).with_new_inputs(&[input])?;
+        match plan {
+            LogicalPlan::Projection(_) => Ok(Some(plan.with_new_inputs(&[local])?)),
+            _ => Ok(Some(local)),
+        }
+    }
+
+    // Whether the plan is expressible in standard SQL.
+    fn is_standard(&self, plan: &LogicalPlan) -> bool {
+        let mut standard = true;
+        let _ = plan.apply(&mut |p| {
+            standard = non_standard_operator(p).is_none();
+            Ok(if standard {
+                VisitRecursion::Continue
+            } else {
+                VisitRecursion::Stop
+            })
+        });
+        standard && query_to_sql(plan, self.dialect.as_ref()).is_ok()
+    }
+
+    // Splits the plan into parts if its SQL exceeds the dialect's statement size limit.
+    fn split_oversized(&self, plan: &LogicalPlan) -> Result<Option<Vec<LogicalPlan>>> {
+        let Some(max_size) = self.dialect.max_statement_size() else {
+            return Ok(None);
+        };
+        let Ok(ast) = query_to_sql(plan, self.dialect.as_ref()) else {
+            return Ok(None);
+        };
+        let size = format!("{ast}").len();
+        if size <= max_size {
+            return Ok(None);
+        }
+        let parts = split_in_list(plan, size / max_size + 1)?;
+        if parts.is_none() {
+            debug!(
+                "federation rule=federate_sql decision=accept reason=statement_size_unsplittable size={size}"
+            );
+        }
+        Ok(parts)
+    }
+}
+
+fn contains_aggregate(plan: &LogicalPlan) -> bool {
+    let mut found = false;
+    let _ = plan.apply(&mut |p| {
+        found = matches!(p, LogicalPlan::Aggregate(_));
+        Ok(if found {
+            VisitRecursion::Stop
+        } else {
+            VisitRecursion::Continue
+        })
+    });
+    found
+}
+
+fn contains_window(plan: &LogicalPlan) -> bool {
+    let mut found = false;
+    let _ = plan.apply(&mut |p| {
+        found = matches!(p, LogicalPlan::Window(_));
+        Ok(if found {
+            VisitRecursion::Stop
+        } else {
+            VisitRecursion::Continue
+        })
+    });
+    found
+}
+
+impl AnalyzerRule for SQLFederationAnalyzerRule {
+    fn analyze(&self, plan: LogicalPlan, config: &ConfigOptions) -> Result<LogicalPlan> {
+        let plan = if self.dialect.supports_limit_in_subquery() {
+            plan
+        } else {
+            rewrite_subquery_limits(plan)?
+        };
+        // Merge joins over federated inputs rely on the remote ordering
+        if config.optimizer.prefer_hash_join {
+            if let Some(plan) = self.sort_locally(&plan)? {
+                return Ok(plan);
+            }
+        }
+        self.federate(plan)
+    }
+
     /// A human readable name for this analyzer rule
     fn name(&self) -> &str {
         "federate_sql"
     }
 }
+
+// SQLFederationPlanner carries the per-source execution settings
+// down to the VirtualExecutionPlan.
+#[derive(Debug, Clone)]
 struct SQLFederationPlanner {
     executor: Arc<dyn SQLExecutor>,
+    scheduler: Option<Arc<FairScheduler>>,
+    slow_query_log: Option<Arc<SlowQueryLog>>,
+    complexity_alerts: Option<Arc<SQLComplexityAlerts>>,
+    cost_budgets: Option<Arc<CostBudgets>>,
+    batch_transform: Option<Arc<dyn BatchTransform>>,
+    streaming_aggregation: bool,
+    replicas: Option<Arc<ReplicaSet>>,
+    dialect: Arc<dyn SQLDialect>,
+    canonical_sql: bool,
+    // The unrestricted dialect in standard SQL mode, `dialect` then wraps it
+    // in a StandardDialect. Restored when the mode is disabled.
+    standard_sql: Option<Arc<dyn SQLDialect>>,
+    workload_governor: Option<Arc<dyn WorkloadGovernor>>,
+    workload_class: WorkloadClass,
+    schema_probe: bool,
+    refreshed_views: Vec<String>,
+    strict_types: bool,
+    type_overrides: HashMap<String, DataType>,
+    validate_sql: bool,
+    sort_cost: Option<SortCostProfile>,
+    default_limit: Option<usize>,
+    row_count_check: Option<RowCountCheck>,
+    partition_retries: usize,
+    normalize_empty_strings: bool,
+    encoding: Option<SourceEncoding>,
+    column_metrics: bool,
+    batch_provenance: bool,
+    query_recorder: Option<Arc<QueryRecorder>>,
+    pushdown_reduction: bool,
+    session_variables: Option<SessionVariables>,
+    connection_settings: Vec<(String, String)>,
+    unload: Option<Unload>,
 }
 
 impl SQLFederationPlanner {
     pub fn new(executor: Arc<dyn SQLExecutor>) -> Self {
-        Self { executor }
+        Self {
+            dialect: executor.dialect(),
+            executor,
+            scheduler: None,
+            slow_query_log: None,
+            complexity_alerts: None,
+            cost_budgets: None,
+            batch_transform: None,
+            streaming_aggregation: false,
+            replicas: None,
+            canonical_sql: false,
+            standard_sql: None,
+            workload_governor: None,
+            workload_class: WorkloadClass::Interactive,
+            schema_probe: false,
+            refreshed_views: vec![],
+            strict_types: false,
+            type_overrides: HashMap::new(),
+            validate_sql: false,
+            sort_cost: None,
+            default_limit: None,
+            row_count_check: None,
+            partition_retries: 0,
+            normalize_empty_strings: false,
+            encoding: None,
+            column_metrics: false,
+            batch_provenance: false,
+            query_recorder: None,
+            pushdown_reduction: false,
+            session_variables: None,
+            connection_settings: vec![],
+            unload: None,
+        }
+    }
+
+    // Returns the remote names of the materialized views read by the plan
+    // that are refreshed before each query.
+    fn views_to_refresh(&self, plan: &LogicalPlan) -> Result<Vec<String>> {
+        let mut views = vec![];
+        if self.refreshed_views.is_empty() {
+            return Ok(views);
+        }
+        plan.apply(&mut |plan| {
+            if let LogicalPlan::TableScan(scan) = plan {
+                let source = get_table_source(scan.source.clone()).ok();
+                if let Some(source) = source
+                    .as_ref()
+                    .and_then(|s| s.as_any().downcast_ref::<SQLTableSource>())
+                {
+                    let name = source.remote_name().join(".");
+                    let refreshed = self
+                        .refreshed_views
+                        .iter()
+                        .any(|v| v == &name || v == &scan.table_name.to_string());
+                    if refreshed && !views.contains(&name) {
+                        views.push(name);
+                    }
+                }
+            }
+            Ok(VisitRecursion::Continue)
+        })?;
+        Ok(views)
+    }
+
+    fn validate(&self, query: &str) -> Result<()> {
+        let dialect = self.dialect.parser_dialect();
+        match Parser::parse_sql(dialect.as_ref(), query) {
+            Ok(_) => Ok(()),
+            Err(e) => plan_err!(
+                "generated SQL is not valid {} SQL: {e}\nsql: {query}",
+                self.dialect.name()
+            ),
+        }
+    }
+
+    fn unparse(&self, plan: &LogicalPlan) -> Result<ast::Statement> {
+        let mut statement = plan_to_statement(plan, self.dialect.as_ref())?;
+        if self.canonical_sql {
+            canonicalize(&mut statement);
+        }
+        render_table_aliases(&mut statement, self.dialect.as_ref());
+        Ok(statement)
     }
 }
 
@@ -102,24 +1073,172 @@ impl FederationPlanner for SQLFederationPlanner {
     async fn plan_federation(
         &self,
         node: &FederatedPlanNode,
-        _session_state: &SessionState,
+        session_state: &SessionState,
     ) -> Result<Arc<dyn ExecutionPlan>> {
+        if let Some(budgets) = &self.cost_budgets {
+            let query = format!("{}", self.unparse(node.plan())?);
+            budgets.admit(node.plan(), &query, session_state)?;
+        }
+        if self.schema_probe {
+            let query = format!("{}", self.unparse(node.plan())?);
+            probe_schema(self.executor.as_ref(), &query, node.plan().schema()).await?;
+        }
         Ok(Arc::new(VirtualExecutionPlan::new(
             node.plan().clone(),
-            self.executor.clone(),
+            self.clone(),
+            node.is_root(),
         )))
     }
+
+    fn compute_context(&self) -> Option<String> {
+        self.executor.compute_context()
+    }
+
+    fn federated_query(&self, node: &FederatedPlanNode) -> Result<Option<String>> {
+        Ok(Some(format!("{}", self.unparse(node.plan())?)))
+    }
 }
 
 #[derive(Debug, Clone)]
 struct VirtualExecutionPlan {
     plan: LogicalPlan,
-    executor: Arc<dyn SQLExecutor>,
+    planner: SQLFederationPlanner,
+    metrics: ExecutionPlanMetricsSet,
+    ordering: Option<Vec<PhysicalSortExpr>>,
+    partitions: usize,
+    // Whether the rows are the query's result, the default limit only
+    // applies then
+    root: bool,
+    // The partition queries of the current execution, and how many
+    // partitions have taken theirs
+    splits: Arc<Mutex<Option<(Vec<String>, usize)>>>,
+    // The replica of the current execution, None for the primary, and how
+    // many partitions have taken it
+    route: Arc<Mutex<Option<(Option<Arc<dyn SQLExecutor>>, usize)>>>,
 }
 
 impl VirtualExecutionPlan {
-    pub fn new(plan: LogicalPlan, executor: Arc<dyn SQLExecutor>) -> Self {
-        Self { plan, executor }
+    pub fn new(plan: LogicalPlan, planner: SQLFederationPlanner, root: bool) -> Self {
+        let partitions = planner.executor.partition_count().max(1);
+        // The order of a split query is only kept within each partition
+        let ordering = match partitions {
+            1 => output_ordering(&plan, planner.dialect.as_ref()),
+            _ => None,
+        };
+        Self {
+            plan,
+            planner,
+            metrics: ExecutionPlanMetricsSet::new(),
+            ordering,
+            partitions,
+            root,
+            splits: Arc::new(Mutex::new(None)),
+            route: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // Returns the replica the partitions of the current execution run on.
+    // It is routed once, a result doesn't mix rows of replicas that lag
+    // differently.
+    fn route_replica(
+        &self,
+        replicas: &ReplicaSet,
+        freshness: Option<&Freshness>,
+    ) -> Result<Option<Arc<dyn SQLExecutor>>> {
+        let mut route = self.route.lock().unwrap();
+        if route.is_none() {
+            *route = Some((block_on(replicas.route(freshness))?, 0));
+        }
+        let (replica, taken) = route.as_mut().unwrap();
+        let replica = replica.clone();
+        *taken += 1;
+        if *taken >= self.partitions {
+            *route = None;
+        }
+        Ok(replica)
+    }
+
+    // Executes the plan with less pushed down after the source rejected it,
+    // returning the first reduced plan the source accepts.
+    async fn execute_reduced(
+        self,
+        partition: usize,
+        context: Arc<TaskContext>,
+        error: DataFusionError,
+    ) -> Result<SendableRecordBatchStream> {
+        let mut planner = self.planner.clone();
+        planner.pushdown_reduction = false;
+        let planner: Arc<dyn FederationPlanner> = Arc::new(planner);
+        let state = SessionState::new_with_config_rt(
+            context.session_config().clone(),
+            context.runtime_env(),
+        )
+        .with_query_planner(Arc::new(FederatedQueryPlanner::new()));
+        let reductions =
+            MetricBuilder::new(&self.metrics).counter("pushdown_reductions", partition);
+
+        let mut error = error;
+        for (local, plan) in reduced_plans(&self.plan, &planner)? {
+            warn!(
+                "federation rule=federate_sql decision=reduce context={:?} local=\"{local}\" reason=\"{error}\"",
+                self.planner.executor.compute_context()
+            );
+            reductions.add(1);
+            let result = async {
+                let physical = state.create_physical_plan(&plan).await?;
+                let mut stream = execute_stream(physical, context.clone())?;
+                // Sources report rejected SQL when the query starts
+                let first = stream.next().await.transpose()?;
+                let stream = futures::stream::iter(first.map(Ok)).chain(stream);
+                Ok::<_, DataFusionError>(stream)
+            }
+            .await;
+            match result {
+                Ok(stream) => {
+                    return Ok(Box::pin(RecordBatchStreamAdapter::new(
+                        self.schema(),
+                        stream,
+                    )));
+                }
+                Err(e) if is_pushdown_error(&e) => error = e,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(error)
+    }
+
+    // Returns the query of the partition, None if it has nothing to fetch.
+    // The query is split once per execution, if the executor doesn't split
+    // it into one query per partition the first partition runs it whole.
+    fn partition_query(
+        &self,
+        executor: &Arc<dyn SQLExecutor>,
+        query: String,
+        partition: usize,
+    ) -> Option<String> {
+        if self.partitions == 1 {
+            return Some(query);
+        }
+        let mut splits = self.splits.lock().unwrap();
+        let (parts, taken) = splits.get_or_insert_with(|| {
+            let parts = block_on(executor.split_query(&query)).unwrap_or_else(|e| {
+                debug!(
+                    "federation rule=federate_sql decision=unsplit context={:?} reason=\"{e}\"",
+                    executor.compute_context()
+                );
+                vec![]
+            });
+            (parts, 0)
+        });
+        let part = match parts.len() == self.partitions {
+            true => parts.get(partition).cloned(),
+            false => (partition == 0).then_some(query),
+        };
+        *taken += 1;
+        if *taken >= self.partitions {
+            *splits = None;
+        }
+        part
     }
 
     fn schema(&self) -> SchemaRef {
@@ -128,9 +1247,97 @@ impl VirtualExecutionPlan {
     }
 }
 
+// The remote ORDER BY determines the output ordering, as long as it only
+// sorts on output columns ordered as in Arrow. Strings are ordered by the
+// source's collation, which only matches when it compares bytes.
+fn output_ordering(plan: &LogicalPlan, dialect: &dyn SQLDialect) -> Option<Vec<PhysicalSortExpr>> {
+    let LogicalPlan::Sort(sort) = plan else {
+        return None;
+    };
+    sort.expr
+        .iter()
+        .map(|e| match e {
+            Expr::Sort(expr::Sort {
+                expr,
+                asc,
+                nulls_first,
+            }) => match expr.as_ref() {
+                Expr::Column(col) => {
+                    let index = plan.schema().index_of_column(col).ok()?;
+                    let data_type = plan.schema().field(index).data_type();
+                    if !(data_type.is_numeric()
+                        || data_type.is_temporal()
+                        || dialect.binary_collation())
+                    {
+                        return None;
+                    }
+                    Some(PhysicalSortExpr {
+                        expr: Arc::new(Column::new(&col.name, index)),
+                        options: SortOptions {
+                            descending: !asc,
+                            nulls_first: *nulls_first,
+                        },
+                    })
+                }
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
 impl DisplayAs for VirtualExecutionPlan {
-    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> std::fmt::Result {
-        write!(f, "VirtualExecutionPlan")
+    fn fmt_as(&self, t: DisplayFormatType, f: &mut fmt::Formatter) -> std::fmt::Result {
+        write!(f, "VirtualExecutionPlan")?;
+        if let DisplayFormatType::Verbose = t {
+            let source = self.planner.executor.compute_context();
+            let columns = self
+                .remote_columns()
+                .iter()
+                .map(|(name, expr)| format!("{name} := {expr}"))
+                .collect::<Vec<_>>();
+            let sql = self
+                .planner
+                .unparse(&self.plan)
+                .map(|ast| ast.to_string())
+                .unwrap_or_default();
+            write!(
+                f,
+                ": source={} columns=[{}] sql={sql}",
+                source.as_deref().unwrap_or("unknown"),
+                columns.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl VirtualExecutionPlan {
+    // Returns the remote expression every output column is computed from, as
+    // rendered in the generated SQL. Empty if the query isn't a plain SELECT.
+    fn remote_columns(&self) -> Vec<(String, String)> {
+        let Ok(ast::Statement::Query(query)) = self.planner.unparse(&self.plan) else {
+            return vec![];
+        };
+        let ast::SetExpr::Select(select) = query.body.as_ref() else {
+            return vec![];
+        };
+        if select.projection.len() != self.plan.schema().fields().len() {
+            return vec![];
+        }
+        select
+            .projection
+            .iter()
+            .zip(self.plan.schema().fields())
+            .map(|(item, field)| {
+                let expr = match item {
+                    ast::SelectItem::UnnamedExpr(expr)
+                    | ast::SelectItem::ExprWithAlias { expr, .. } => expr.to_string(),
+                    item => item.to_string(),
+                };
+                (field.name().clone(), expr)
+            })
+            .collect()
     }
 }
 
@@ -144,11 +1351,11 @@ impl ExecutionPlan for VirtualExecutionPlan {
     }
 
     fn output_partitioning(&self) -> datafusion::physical_plan::Partitioning {
-        datafusion::physical_plan::Partitioning::UnknownPartitioning(1)
+        datafusion::physical_plan::Partitioning::UnknownPartitioning(self.partitions)
     }
 
     fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
-        None
+        self.ordering.as_deref()
     }
 
     fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
@@ -164,12 +1371,643 @@ impl ExecutionPlan for VirtualExecutionPlan {
 
     fn execute(
         &self,
-        _partition: usize,
-        _context: Arc<TaskContext>,
+        partition: usize,
+        context: Arc<TaskContext>,
     ) -> Result<SendableRecordBatchStream> {
-        let ast = query_to_sql(&self.plan)?;
-        let query = format!("{ast}");
+        let mut executor = &self.planner.executor;
+        let replica = match &self.planner.replicas {
+            Some(replicas) => {
+                let freshness = context.session_config().get_extension::<Freshness>();
+                self.route_replica(replicas, freshness.as_deref())?
+            }
+            None => None,
+        };
+        if let Some(replica) = &replica {
+            executor = replica;
+        }
+        let class = context
+            .session_config()
+            .get_extension::<WorkloadClass>()
+            .map(|c| c.as_ref().clone())
+            .unwrap_or_else(|| self.planner.workload_class.clone());
+        let routed = match (&self.planner.workload_governor, &replica) {
+            (Some(governor), None) => governor.executor(&class),
+            _ => None,
+        };
+        if let Some(routed) = &routed {
+            executor = routed;
+        }
+        // The session variables run last, they override the connection settings
+        let mut statements = setting_statements(
+            self.planner.dialect.as_ref(),
+            &self.planner.connection_settings,
+        );
+        if let (Some(session), Some(config)) = (
+            &self.planner.session_variables,
+            context
+                .session_config()
+                .options()
+                .extensions
+                .get::<FederationConfig>(),
+        ) {
+            let variables = config.session_variables(&session.source);
+            statements.extend(session.statements(self.planner.dialect.as_ref(), variables)?);
+        }
+        let session = match statements.is_empty() {
+            true => None,
+            false => {
+                let session: Arc<dyn SQLExecutor> =
+                    Arc::new(SessionVariablesExecutor::new(executor.clone(), statements));
+                Some(session)
+            }
+        };
+        if let Some(session) = &session {
+            executor = session;
+        }
+        let config = context
+            .session_config()
+            .options()
+            .extensions
+            .get::<FederationConfig>();
+        let limit = match (
+            config.map(|c| c.default_limit()).transpose()?.flatten(),
+            context.session_config().get_extension::<DefaultLimit>(),
+        ) {
+            (Some(limit), _) => limit,
+            (None, Some(limit)) => limit.0,
+            (None, None) => self.planner.default_limit,
+        };
+        let limited = match limit {
+            Some(limit) => match apply_default_limit(&self.plan, limit)? {
+                // Local operators above, e.g. joins or aggregates, would
+                // compute their result from the truncated rows
+                Some(_) if !self.root => {
+                    warn!(
+                        "federation rule=federate_sql decision=skip_default_limit context={:?} limit={limit} reason=\"below local operators\"",
+                        executor.compute_context()
+                    );
+                    None
+                }
+                limited => limited,
+            },
+            None => None,
+        };
+        if limited.is_some() {
+            debug!(
+                "federation rule=federate_sql decision=default_limit context={:?} limit={limit:?}",
+                executor.compute_context()
+            );
+        }
+        let ast = self
+            .planner
+            .unparse(limited.as_ref().unwrap_or(&self.plan))
+            .map_err(|e| {
+                debug!(
+                    "federation rule=federate_sql decision=reject context={:?} reason=\"{e}\"",
+                    executor.compute_context()
+                );
+                e
+            })?;
+        let mut query = format!("{ast}");
+        if let Some(governor) = &self.planner.workload_governor {
+            query = governor.rewrite(&class, query);
+        }
+        if self.planner.validate_sql {
+            self.planner.validate(&query)?;
+        }
+        // Every partition generates the same SQL, it is measured once
+        if partition == 0 {
+            let complexity = SQLComplexity::measure(&ast, &query);
+            let gauge = |name: &'static str, value: usize| {
+                MetricBuilder::new(&self.metrics)
+                    .gauge(name, partition)
+                    .set(value)
+            };
+            gauge("sql_bytes", complexity.bytes);
+            gauge("sql_nesting_depth", complexity.nesting_depth);
+            gauge("sql_predicates", complexity.predicates);
+            if let Some(alerts) = &self.planner.complexity_alerts {
+                alerts.check(executor.compute_context(), &query, complexity);
+            }
+        }
+        // Unloaded results are read whole by the first partition
+        if let Some(unload) = &self.planner.unload {
+            if partition > 0 {
+                return Ok(Box::pin(EmptyRecordBatchStream::new(self.schema())));
+            }
+            return block_on(unload_stream(
+                executor.as_ref(),
+                self.planner.dialect.as_ref(),
+                unload,
+                &query,
+                self.schema(),
+                context,
+            ));
+        }
+        let Some(query) = self.partition_query(executor, query, partition) else {
+            return Ok(Box::pin(EmptyRecordBatchStream::new(self.schema())));
+        };
+        if let (Some(replicas), Some(replica)) = (&self.planner.replicas, &replica) {
+            replicas.sample_consistency(&query, replica, &self.planner.executor);
+        }
+        debug!(
+            "federation rule=federate_sql decision=execute context={:?} workload={class} sql=\"{query}\"",
+            executor.compute_context()
+        );
+        let query_id = self.planner.batch_provenance.then(next_query_id);
+        if let Some(query_id) = &query_id {
+            debug!(
+                "federation rule=federate_sql provenance context={:?} query_id={query_id} partition={partition} sql=\"{query}\"",
+                executor.compute_context()
+            );
+        }
+
+        // Warnings are counted in the metrics even if the session doesn't collect them
+        let warnings = context
+            .session_config()
+            .get_extension::<RemoteWarnings>()
+            .map(|w| w.as_ref().clone())
+            .unwrap_or_default();
+        let before = warnings.len();
+
+        for view in self.planner.views_to_refresh(&self.plan)? {
+            let refresh = format!("REFRESH MATERIALIZED VIEW {view}");
+            debug!(
+                "federation rule=federate_sql decision=refresh context={:?} sql=\"{refresh}\"",
+                executor.compute_context()
+            );
+            block_on(async {
+                executor
+                    .execute(refresh.as_str())
+                    .await?
+                    .try_collect::<Vec<_>>()
+                    .await
+            })?;
+        }
+
+        let attempts = MetricBuilder::new(&self.metrics).counter("attempts", partition);
+        // Split queries can't be reduced, each partition would return everything
+        let reduce = self.planner.pushdown_reduction && self.partitions == 1;
+        let start = Instant::now();
+        let mut stream: SendableRecordBatchStream = if self.planner.scheduler.is_some() || reduce {
+            let scheduler = self.planner.scheduler.clone();
+            let tenant = context.session_config().get_extension::<Tenant>();
+            let executor = executor.clone();
+            let query = query.clone();
+            let warnings = warnings.clone();
+            let retries = self.planner.partition_retries;
+            let attempts = attempts.clone();
+            // DataFusion executes every input of a plan before polling any
+            // of them, the slot is only waited for once the stream is polled.
+            // A rejected query is then reported by the stream, see below.
+            let stream = futures::stream::once(async move {
+                let permit = match &scheduler {
+                    Some(scheduler) => Some(scheduler.acquire(tenant.as_deref()).await?),
+                    None => None,
+                };
+                let stream =
+                    execute_partition(&executor, &query, warnings, retries, attempts).await?;
+                // Hold the slot until the stream is dropped
+                Ok::<_, DataFusionError>(stream.map(move |batch| {
+                    let _permit = &permit;
+                    batch
+                }))
+            })
+            .try_flatten();
+            Box::pin(RecordBatchStreamAdapter::new(self.schema(), stream))
+        } else {
+            block_on(execute_partition(
+                executor,
+                query.as_str(),
+                warnings.clone(),
+                self.planner.partition_retries,
+                attempts,
+            ))?
+        };
+
+        let remote_warnings =
+            MetricBuilder::new(&self.metrics).counter("remote_warnings", partition);
+        let schema = stream.schema();
+        let sql = query.clone();
+        let verified_sql = query.clone();
+        stream = Box::pin(RecordBatchStreamAdapter::new(
+            schema,
+            stream.chain(futures::stream::poll_fn(move |_| {
+                // Count the warnings once the remote stream is drained
+                for warning in warnings.warnings().iter().skip(before) {
+                    if warning.sql == sql {
+                        warn!(
+                            "federation remote_warning context={:?} message=\"{}\"",
+                            warning.compute_context, warning.message
+                        );
+                        remote_warnings.add(1);
+                    }
+                }
+                std::task::Poll::Ready(None)
+            })),
+        ));
+
+        if let Some(encoding) = self.planner.encoding {
+            stream = transcode_stream(stream, encoding, self.schema());
+        }
+
+        if self.planner.column_metrics {
+            stream = column_bytes_stream(stream, &self.metrics, partition);
+        }
+
+        if let Some(check) = self.planner.row_count_check {
+            stream = count_stream(stream, executor.clone(), query.clone(), check);
+        }
+
+        if let Some(recorder) = &self.planner.query_recorder {
+            let record = QueryRecord {
+                compute_context: executor.compute_context(),
+                plan: format!("{}", self.plan.display_indent()),
+                sql: query.clone(),
+                schema: vec![],
+                rows: 0,
+            };
+            stream = record_stream(stream, recorder.clone(), record);
+        }
+
+        if let Some(slow_query_log) = &self.planner.slow_query_log {
+            let query = SlowQuery {
+                compute_context: executor.compute_context(),
+                plan: format!("{}", self.plan.display_indent()),
+                sql: query,
+                time_to_first_batch: Duration::ZERO,
+                elapsed: Duration::ZERO,
+                rows: 0,
+            };
+            stream = Box::pin(SlowQueryStream::new(
+                stream,
+                slow_query_log.clone(),
+                query,
+                start,
+            ));
+        }
+
+        // Sources may return columns added after registration, e.g. for `*` in
+        // inlined views, which are dropped. Batches otherwise take the declared
+        // schema, keeping its field metadata, e.g. Arrow extension types, which
+        // sources don't return.
+        let expected = self.schema();
+        stream = Box::pin(RecordBatchStreamAdapter::new(
+            expected.clone(),
+            stream.map(move |batch| {
+                let batch = batch?;
+                if batch.num_columns() <= expected.fields().len() {
+                    // Mismatching batches are cast or reported further down
+                    return Ok(batch.clone().with_schema(expected.clone()).unwrap_or(batch));
+                }
+                let columns = expected
+                    .fields()
+                    .iter()
+                    .map(|field| {
+                        let index = batch.schema().index_of(field.name())?;
+                        Ok(batch.column(index).clone())
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(RecordBatch::try_new(expected.clone(), columns)?)
+            }),
+        ));
+
+        if !self.planner.type_overrides.is_empty() {
+            let expected = self.schema();
+            stream = Box::pin(RecordBatchStreamAdapter::new(
+                expected.clone(),
+                stream.map(move |batch| {
+                    let batch = batch?;
+                    let columns = batch
+                        .columns()
+                        .iter()
+                        .zip(expected.fields())
+                        .map(|(column, field)| {
+                            if column.data_type() == field.data_type() {
+                                Ok(column.clone())
+                            } else {
+                                cast(column, field.data_type())
+                            }
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(RecordBatch::try_new(expected.clone(), columns)?)
+                }),
+            ));
+        }
+
+        if self.planner.normalize_empty_strings {
+            stream = Box::pin(RecordBatchStreamAdapter::new(
+                self.schema(),
+                stream.map(|batch| nulls_to_empty_strings(batch?)),
+            ));
+        }
+
+        if self.planner.strict_types {
+            stream = verify_stream(stream, self.schema(), verified_sql);
+        }
+
+        if let Some(transform) = &self.planner.batch_transform {
+            let rows_affected =
+                MetricBuilder::new(&self.metrics).counter("rows_affected", partition);
+            stream = transform_stream(stream, transform.clone(), rows_affected);
+        }
+
+        // Added last, so the batches keep it through the stages above
+        if let Some(query_id) = query_id {
+            stream = provenance_stream(
+                stream,
+                executor.name().to_string(),
+                executor.compute_context(),
+                query_id,
+                partition,
+            );
+        }
+
+        // The source rejects the query before the first batch, the plan is
+        // then retried with less pushed down
+        if reduce {
+            let plan = self.clone();
+            let schema = self.schema();
+            let stream = futures::stream::once(async move {
+                let mut stream = stream;
+                let schema = stream.schema();
+                match stream.next().await {
+                    Some(Err(e)) if is_pushdown_error(&e) => {
+                        plan.execute_reduced(partition, context, e).await
+                    }
+                    first => Ok(Box::pin(RecordBatchStreamAdapter::new(
+                        schema,
+                        futures::stream::iter(first).chain(stream),
+                    )) as SendableRecordBatchStream),
+                }
+            })
+            .try_flatten();
+            return Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)));
+        }
+
+        Ok(stream)
+    }
+
+    fn metrics(&self) -> Option<MetricsSet> {
+        Some(self.metrics.clone_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::{AsArray, Int64Array, StringArray},
+            datatypes::{Field, Int64Type},
+        },
+        catalog::schema::SchemaProvider,
+        execution::context::{SessionConfig, SessionContext},
+    };
+    use datafusion_federation::FederationAnalyzerRule;
+
+    use super::*;
+    use crate::executor::MemorySQLExecutor;
+
+    fn items_batch() -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["a", "b", "c"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    // Returns a source with the table `<name>_items`.
+    fn items(name: &str) -> MemorySQLExecutor {
+        MemorySQLExecutor::new(name)
+            .with_batch(&format!("{name}_items"), items_batch())
+            .unwrap()
+    }
+
+    // Returns a federating context with the tables of the providers.
+    async fn federated_context(
+        config: SessionConfig,
+        providers: Vec<(&str, SQLFederationProvider)>,
+    ) -> SessionContext {
+        let state = SessionContext::new_with_config(config)
+            .state()
+            .add_analyzer_rule(Arc::new(FederationAnalyzerRule::new()))
+            .with_query_planner(Arc::new(FederatedQueryPlanner::new()));
+        let ctx = SessionContext::new_with_state(state);
+        for (table, provider) in providers {
+            let schema_provider = SQLSchemaProvider::new(Arc::new(provider), vec![table.into()])
+                .await
+                .unwrap();
+            ctx.register_table(table, schema_provider.table(table).await.unwrap())
+                .unwrap();
+        }
+        ctx
+    }
+
+    async fn query(ctx: &SessionContext, sql: &str) -> Result<Vec<RecordBatch>> {
+        ctx.sql(sql).await?.collect().await
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_slot_per_stream() {
+        // A single slot shared by both sides of the join, each side takes it
+        // once polled
+        let scheduler = Arc::new(FairScheduler::new(1));
+        let a = SQLFederationProvider::new(Arc::new(items("a"))).with_scheduler(scheduler.clone());
+        let b = SQLFederationProvider::new(Arc::new(items("b"))).with_scheduler(scheduler);
+        let config = SessionConfig::new().with_target_partitions(1);
+        let ctx = federated_context(config, vec![("a_items", a), ("b_items", b)]).await;
+
+        let batches = query(
+            &ctx,
+            "SELECT count(*) FROM a_items JOIN b_items ON a_items.id = b_items.id",
+        )
+        .await
+        .unwrap();
+        let counts = batches[0].column(0).as_primitive::<Int64Type>();
+        assert_eq!(counts.value(0), 3);
+    }
+
+    fn rows(batches: &[RecordBatch]) -> usize {
+        batches.iter().map(|b| b.num_rows()).sum()
+    }
+
+    #[tokio::test]
+    async fn test_default_limit() {
+        let a = SQLFederationProvider::new(Arc::new(items("a"))).with_default_limit(Some(2));
+        let config = SessionConfig::new().with_option_extension(FederationConfig::default());
+        let ctx = federated_context(config, vec![("a_items", a)]).await;
+        ctx.register_batch("local_items", items_batch()).unwrap();
+
+        let batches = query(&ctx, "SELECT * FROM a_items").await.unwrap();
+        assert_eq!(rows(&batches), 2);
+        // Not applied below local operators
+        let batches = query(
+            &ctx,
+            "SELECT a_items.id FROM a_items JOIN local_items ON a_items.id = local_items.id",
+        )
+        .await
+        .unwrap();
+        assert_eq!(rows(&batches), 3);
+
+        query(&ctx, "SET federation.default_limit = 'none'")
+            .await
+            .unwrap();
+        let batches = query(&ctx, "SELECT * FROM a_items").await.unwrap();
+        assert_eq!(rows(&batches), 3);
+        assert!(query(&ctx, "SET federation.default_limit = 'all'")
+            .await
+            .is_err());
+    }
+
+    // Splits every query in two halves that both return the whole table.
+    struct TwoPartitions(MemorySQLExecutor);
+
+    #[async_trait]
+    impl SQLExecutor for TwoPartitions {
+        fn name(&self) -> &str {
+            self.0.name()
+        }
+        fn compute_context(&self) -> Option<String> {
+            self.0.compute_context()
+        }
+        async fn execute(&self, query: &str) -> Result<SendableRecordBatchStream> {
+            self.0.execute(query).await
+        }
+        fn partition_count(&self) -> usize {
+            2
+        }
+        async fn split_query(&self, query: &str) -> Result<Vec<String>> {
+            Ok(vec![query.to_string(), query.to_string()])
+        }
+    }
+
+    #[derive(Default)]
+    struct CountingLag(std::sync::atomic::AtomicUsize);
+
+    #[async_trait]
+    impl ReplicationLag for CountingLag {
+        async fn lag(&self) -> Result<Duration> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(Duration::ZERO)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replica_routed_once() {
+        let lag = Arc::new(CountingLag::default());
+        let replicas = ReplicaSet::new(
+            vec![Replica {
+                executor: Arc::new(TwoPartitions(items("a"))),
+                lag: lag.clone(),
+            }],
+            Duration::from_secs(1),
+            ReplicaFallback::Primary,
+        );
+        let a = SQLFederationProvider::new(Arc::new(TwoPartitions(items("a"))))
+            .with_replicas(Arc::new(replicas));
+        let config = SessionConfig::new().with_target_partitions(2);
+        let ctx = federated_context(config, vec![("a_items", a)]).await;
+
+        let batches = query(&ctx, "SELECT * FROM a_items").await.unwrap();
+        assert_eq!(rows(&batches), 6);
+        assert_eq!(lag.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_session_variables() {
+        let a = SQLFederationProvider::new(Arc::new(items("a")))
+            .with_connection_settings(vec![(
+                "datafusion.execution.batch_size".to_string(),
+                "2".to_string(),
+            )])
+            .with_session_variables(
+                "a".to_string(),
+                vec!["datafusion.catalog.information_schema".to_string()],
+            );
+        let config = SessionConfig::new().with_option_extension(FederationConfig::default());
+        let ctx = federated_context(config, vec![("a_items", a)]).await;
+
+        // Each query runs on a dedicated connection of the source
+        query(
+            &ctx,
+            "SET federation.a.datafusion.catalog.information_schema = 'true'",
+        )
+        .await
+        .unwrap();
+        let batches = query(&ctx, "SELECT * FROM a_items").await.unwrap();
+        assert_eq!(rows(&batches), 3);
+
+        query(
+            &ctx,
+            "SET federation.a.datafusion.execution.batch_size = '1'",
+        )
+        .await
+        .unwrap();
+        assert!(query(&ctx, "SELECT * FROM a_items").await.is_err());
+    }
+
+    // Rejects the queries calling `lower`.
+    struct NoLower(MemorySQLExecutor);
+
+    #[async_trait]
+    impl SQLExecutor for NoLower {
+        fn name(&self) -> &str {
+            self.0.name()
+        }
+        fn compute_context(&self) -> Option<String> {
+            self.0.compute_context()
+        }
+        async fn execute(&self, query: &str) -> Result<SendableRecordBatchStream> {
+            if query.to_lowercase().contains("lower(") {
+                return not_impl_err!("function lower is not supported");
+            }
+            self.0.execute(query).await
+        }
+    }
+
+    fn pushdown_reductions(plan: &dyn ExecutionPlan) -> usize {
+        let reductions = plan
+            .metrics()
+            .and_then(|m| m.sum_by_name("pushdown_reductions"))
+            .map_or(0, |m| m.as_usize());
+        let children = plan.children();
+        reductions
+            + children
+                .iter()
+                .map(|c| pushdown_reductions(c.as_ref()))
+                .sum::<usize>()
+    }
+
+    #[tokio::test]
+    async fn test_pushdown_reduction() {
+        let sql = "SELECT id FROM a_items WHERE lower(name) = 'b' AND id > 1";
+        let a = SQLFederationProvider::new(Arc::new(NoLower(items("a"))));
+        let ctx = federated_context(SessionConfig::new(), vec![("a_items", a)]).await;
+        assert!(query(&ctx, sql).await.is_err());
 
-        block_on(self.executor.execute(query.as_str()))
+        let a =
+            SQLFederationProvider::new(Arc::new(NoLower(items("a")))).with_pushdown_reduction(true);
+        let ctx = federated_context(SessionConfig::new(), vec![("a_items", a)]).await;
+        let plan = ctx
+            .sql(sql)
+            .await
+            .unwrap()
+            .create_physical_plan()
+            .await
+            .unwrap();
+        // Retried once polled, with `lower` kept local
+        let batches = datafusion::physical_plan::collect(plan.clone(), ctx.task_ctx())
+            .await
+            .unwrap();
+        assert_eq!(rows(&batches), 1);
+        // The metrics record each reduction
+        assert_eq!(pushdown_reductions(plan.as_ref()), 1);
     }
 }
@@ -0,0 +1,68 @@
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+
+use crate::SQLFederationProvider;
+
+// The dialect capabilities and pushdown settings of the provider, as listed
+// by datafusion_federation::source_manifest.
+pub(crate) fn describe_provider(provider: &SQLFederationProvider) -> Value {
+    let planner = &provider.planner;
+    let dialect = &planner.dialect;
+    json!({
+        "dialect": dialect.name(),
+        "capabilities": {
+            "window_functions": dialect.supports_window_functions(),
+            "limit_in_subquery": dialect.supports_limit_in_subquery(),
+            "table_functions": dialect.supports_table_functions(),
+            "returning": dialect.supports_returning(),
+            "empty_string_is_null": dialect.empty_string_is_null(),
+            "limit_style": format!("{:?}", dialect.limit_style()),
+            "group_by_strategy": format!("{:?}", dialect.group_by_strategy()),
+            "upsert_strategy": dialect.upsert_strategy().map(|s| format!("{s:?}")),
+            "max_statement_size": dialect.max_statement_size(),
+            "identifier_quote_style": dialect.identifier_quote_style().map(String::from),
+        },
+        "pushdown": {
+            "streaming_aggregation": planner.streaming_aggregation,
+            "pushdown_reduction": planner.pushdown_reduction,
+            "canonical_sql": planner.canonical_sql,
+            "standard_sql": planner.standard_sql.is_some(),
+            "validate_sql": planner.validate_sql,
+            "default_limit": planner.default_limit,
+            "sort_cost_profile": planner.sort_cost.is_some(),
+            "inline_views": provider.inline_views,
+            "returning": provider.returning,
+        },
+        "execution": {
+            "workload_class": format!("{:?}", planner.workload_class),
+            "partition_retries": planner.partition_retries,
+            "schema_probe": planner.schema_probe,
+            "strict_types": planner.strict_types,
+            "type_overrides": planner
+                .type_overrides
+                .iter()
+                .map(|(column, data_type)| (column.clone(), data_type.to_string()))
+                .collect::<BTreeMap<_, _>>(),
+            "refreshed_views": planner.refreshed_views,
+            "encoding": planner.encoding.map(|e| format!("{e:?}")),
+            "normalize_empty_strings": planner.normalize_empty_strings,
+            "batch_provenance": planner.batch_provenance,
+            "unload": planner.unload.as_ref().map(|u| u.location.clone()),
+            "replicas": planner.replicas.is_some(),
+            "scheduler": planner.scheduler.is_some(),
+            "cost_budgets": planner.cost_budgets.is_some(),
+            "session_variables": planner
+                .session_variables
+                .as_ref()
+                .map(|v| v.allowed.clone()),
+            "connection_settings": planner.connection_settings,
+        },
+        "tables": {
+            "search_path": provider.search_path,
+            "case_insensitive_columns": provider.case_insensitive_columns,
+            "unknown_type_fallback": provider.unknown_type_fallback,
+            "schema_cache": provider.schema_cache.is_some(),
+        },
+    })
+}
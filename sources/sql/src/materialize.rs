@@ -0,0 +1,129 @@
+use async_trait::async_trait;
+use datafusion::{
+    arrow::datatypes::{DataType, Schema, SchemaRef},
+    common::{not_impl_err, plan_err},
+    dataframe::DataFrame,
+    error::Result,
+    execution::context::SessionContext,
+    sql::sqlparser::ast,
+};
+use datafusion_federation::FederatedTableProviderAdaptor;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{schema::SQLTableSource, SQLFederationProvider, ShipTarget};
+
+// SQLSources names the SQL sources of a session, e.g. for materialize_remote.
+// It is read from the session config extensions:
+// `SessionConfig::new().with_extension(Arc::new(SQLSources::new().with_source("pg", provider)))`
+#[derive(Default)]
+pub struct SQLSources {
+    sources: HashMap<String, Arc<SQLFederationProvider>>,
+}
+
+impl SQLSources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_source(
+        mut self,
+        name: impl Into<String>,
+        provider: Arc<SQLFederationProvider>,
+    ) -> Self {
+        self.sources.insert(name.into(), provider);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<SQLFederationProvider>> {
+        self.sources.get(name)
+    }
+}
+
+// MaterializeRemote writes local results to remote temp tables, so they can be
+// joined remotely later in the session.
+#[async_trait]
+pub trait MaterializeRemote {
+    // Writes the result of `df` into the temp table `table` on the source and
+    // registers it as a federated table of the same name. Temp tables live in
+    // the remote session, so the source's executor must keep its connection
+    // for the session.
+    async fn materialize_remote(&self, source: &str, table: &str, df: DataFrame) -> Result<()>;
+}
+
+#[async_trait]
+impl MaterializeRemote for SessionContext {
+    async fn materialize_remote(&self, source: &str, table: &str, df: DataFrame) -> Result<()> {
+        let provider = {
+            let config = self.copied_config();
+            let Some(sources) = config.get_extension::<SQLSources>() else {
+                return plan_err!("no SQLSources registered in the session");
+            };
+            let Some(provider) = sources.get(source) else {
+                return plan_err!("unknown SQL source {source}");
+            };
+            provider.clone()
+        };
+
+        let schema: SchemaRef = Arc::new(Schema::from(df.schema()));
+        let columns = schema
+            .fields()
+            .iter()
+            .map(|f| {
+                Ok(ast::ColumnDef {
+                    name: ast::Ident::new(f.name()),
+                    data_type: arrow_type_to_sql(f.data_type())?,
+                    collation: None,
+                    options: vec![],
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let columns = columns.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        let create = format!(
+            "CREATE TEMPORARY TABLE {} ({})",
+            ast::Ident::new(table),
+            columns.join(", ")
+        );
+        provider.executor.execute_statement(create.as_str()).await?;
+
+        let target = ShipTarget {
+            executor: provider.executor.clone(),
+            table: &[table.to_string()],
+            schema: schema.clone(),
+            options: &provider.write_options,
+            runtime: self.runtime_env(),
+        };
+        provider
+            .batch_shipper()
+            .ship(&target, df.collect().await?)
+            .await?;
+
+        let source = SQLTableSource::new_with_schema(provider, table.to_string(), schema)?;
+        self.register_table(
+            table,
+            Arc::new(FederatedTableProviderAdaptor::new(Arc::new(source))),
+        )?;
+        Ok(())
+    }
+}
+
+fn arrow_type_to_sql(data_type: &DataType) -> Result<ast::DataType> {
+    Ok(match data_type {
+        DataType::Boolean => ast::DataType::Boolean,
+        DataType::Int8 | DataType::Int16 | DataType::UInt8 => ast::DataType::SmallInt(None),
+        DataType::Int32 | DataType::UInt16 => ast::DataType::Integer(None),
+        DataType::Int64 | DataType::UInt32 => ast::DataType::BigInt(None),
+        DataType::UInt64 => ast::DataType::Decimal(ast::ExactNumberInfo::PrecisionAndScale(20, 0)),
+        DataType::Float32 => ast::DataType::Real,
+        DataType::Float64 => ast::DataType::DoublePrecision,
+        DataType::Utf8 | DataType::LargeUtf8 => ast::DataType::Text,
+        DataType::Date32 | DataType::Date64 => ast::DataType::Date,
+        DataType::Timestamp(_, None) => ast::DataType::Timestamp(None, ast::TimezoneInfo::None),
+        DataType::Timestamp(_, Some(_)) => {
+            ast::DataType::Timestamp(None, ast::TimezoneInfo::WithTimeZone)
+        }
+        DataType::Decimal128(precision, scale) if *scale >= 0 => ast::DataType::Decimal(
+            ast::ExactNumberInfo::PrecisionAndScale(*precision as u64, *scale as u64),
+        ),
+        _ => return not_impl_err!("remote temp table column of type {data_type}"),
+    })
+}
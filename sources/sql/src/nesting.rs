@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use datafusion::{
+    common::{
+        tree_node::{Transformed, TreeNode},
+        Column, DFSchemaRef,
+    },
+    error::Result,
+    logical_expr::{Expr, LogicalPlan, Projection, SubqueryAlias},
+};
+
+// The SELECT clauses in evaluation order, a plan node can only be merged
+// into the SELECT of its parent if it is evaluated before it.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Clause {
+    From,
+    Where,
+    GroupBy,
+    Having,
+    OrderBy,
+    Limit,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Scope {
+    // The earliest clause used by the ancestors in the SELECT
+    clause: Option<Clause>,
+    projected: bool,
+}
+
+// Wraps the plan nodes that can't be rendered in their parent's SELECT in
+// derived tables, e.g. a filter over a projection or an aggregate over an
+// aggregate. The derived table is aliased after the qualifier of its columns
+// if they share one, otherwise `derived_<n>`, and its columns are renamed
+// where their names collide. The expressions above are requalified to it.
+pub(crate) fn nest_selects(plan: &LogicalPlan) -> Result<LogicalPlan> {
+    let mut derived = 0;
+    nest(plan, Scope::default(), &mut derived)
+}
+
+fn nest(plan: &LogicalPlan, scope: Scope, derived: &mut usize) -> Result<LogicalPlan> {
+    if !fits(plan, scope) {
+        let plan = derived_table(plan, derived)?;
+        return nest(&plan, Scope::default(), derived);
+    }
+
+    let inner = match plan {
+        LogicalPlan::Projection(_) => Scope {
+            clause: scope.clause,
+            projected: true,
+        },
+        LogicalPlan::Join(_) | LogicalPlan::CrossJoin(_) => Scope {
+            clause: Some(Clause::From),
+            projected: false,
+        },
+        _ => match clause(plan) {
+            Some(clause) if clause != Clause::From => Scope {
+                clause: Some(clause),
+                projected: scope.projected,
+            },
+            // Derived tables and unsupported nodes start a new SELECT
+            _ => Scope::default(),
+        },
+    };
+
+    let inputs = plan.inputs();
+    let nested = inputs
+        .iter()
+        .map(|input| nest(input, inner, derived))
+        .collect::<Result<Vec<_>>>()?;
+    if nested.iter().zip(&inputs).all(|(n, i)| &n == i) {
+        return Ok(plan.clone());
+    }
+
+    // Requalify the columns read from the inputs
+    let mut columns = HashMap::new();
+    for (input, nested) in inputs.iter().zip(&nested) {
+        for (old, new) in input.schema().fields().iter().zip(nested.schema().fields()) {
+            columns.insert(old.qualified_column(), new.qualified_column());
+        }
+    }
+    let exprs = plan
+        .expressions()
+        .into_iter()
+        .map(|expr| requalify(expr, &columns))
+        .collect::<Result<Vec<_>>>()?;
+    plan.with_new_exprs(exprs, &nested)
+}
+
+fn clause(plan: &LogicalPlan) -> Option<Clause> {
+    match plan {
+        LogicalPlan::TableScan(_)
+        | LogicalPlan::Join(_)
+        | LogicalPlan::CrossJoin(_)
+        | LogicalPlan::SubqueryAlias(_) => Some(Clause::From),
+        LogicalPlan::Filter(filter) => match filter.input.as_ref() {
+            LogicalPlan::Aggregate(_) => Some(Clause::Having),
+            _ => Some(Clause::Where),
+        },
+        LogicalPlan::Aggregate(_) => Some(Clause::GroupBy),
+        LogicalPlan::Sort(_) => Some(Clause::OrderBy),
+        LogicalPlan::Limit(_) => Some(Clause::Limit),
+        _ => None,
+    }
+}
+
+fn fits(plan: &LogicalPlan, scope: Scope) -> bool {
+    match (plan, clause(plan)) {
+        // Projections are row-wise, so they commute with ORDER BY and LIMIT
+        (LogicalPlan::Projection(_), _) => {
+            !scope.projected && scope.clause.map_or(true, |c| c >= Clause::OrderBy)
+        }
+        (_, Some(Clause::From)) | (_, None) => true,
+        (_, Some(clause)) => scope.clause.map_or(true, |c| clause < c),
+    }
+}
+
+fn derived_table(plan: &LogicalPlan, derived: &mut usize) -> Result<LogicalPlan> {
+    let schema = plan.schema();
+    let names = column_names(schema);
+    let projection = match plan {
+        LogicalPlan::Projection(p) => Projection::try_new(
+            p.expr
+                .iter()
+                .zip(schema.fields())
+                .zip(&names)
+                .map(|((e, field), name)| match field.name() == name {
+                    true => e.clone(),
+                    false => e.clone().unalias().alias(name),
+                })
+                .collect(),
+            p.input.clone(),
+        )?,
+        _ => Projection::try_new(
+            schema
+                .fields()
+                .iter()
+                .zip(&names)
+                .map(|(field, name)| {
+                    let col = Expr::Column(field.qualified_column());
+                    match field.name() == name {
+                        true => col,
+                        false => col.alias(name),
+                    }
+                })
+                .collect(),
+            plan.clone().into(),
+        )?,
+    };
+
+    let mut qualifiers = schema.fields().iter().map(|f| f.qualifier());
+    let alias = match qualifiers.next() {
+        Some(Some(first)) if qualifiers.all(|q| q == Some(first)) && names_unique(schema) => {
+            first.table().to_string()
+        }
+        _ => {
+            *derived += 1;
+            format!("derived_{derived}")
+        }
+    };
+    Ok(LogicalPlan::SubqueryAlias(SubqueryAlias::try_new(
+        LogicalPlan::Projection(projection),
+        alias,
+    )?))
+}
+
+// The column names of the derived table, later duplicates get the suffix
+// `_<index>`.
+fn column_names(schema: &DFSchemaRef) -> Vec<String> {
+    let fields = schema.fields();
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let duplicate = fields[..i].iter().any(|f| f.name() == field.name());
+            match duplicate {
+                true => format!("{}_{i}", field.name()),
+                false => field.name().clone(),
+            }
+        })
+        .collect()
+}
+
+fn names_unique(schema: &DFSchemaRef) -> bool {
+    column_names(schema)
+        .iter()
+        .zip(schema.fields())
+        .all(|(name, field)| name == field.name())
+}
+
+fn requalify(expr: Expr, columns: &HashMap<Column, Column>) -> Result<Expr> {
+    expr.transform(&|expr| match &expr {
+        Expr::Column(col) => match columns.get(col) {
+            Some(new) => Ok(Transformed::Yes(Expr::Column(new.clone()))),
+            None => Ok(Transformed::No(expr)),
+        },
+        _ => Ok(Transformed::No(expr)),
+    })
+}
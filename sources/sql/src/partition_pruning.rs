@@ -0,0 +1,215 @@
+use std::{collections::HashSet, sync::Arc};
+
+use datafusion::{
+    arrow::{
+        array::{new_null_array, ArrayRef, AsArray},
+        compute::cast,
+        datatypes::{Field, Schema},
+        record_batch::RecordBatch,
+    },
+    common::{
+        tree_node::{Transformed, TreeNode},
+        Column, ScalarValue,
+    },
+    error::Result,
+    logical_expr::{
+        expr::InList,
+        lit,
+        utils::{conjunction, split_conjunction},
+        BinaryExpr, ColumnarValue, Expr, Filter, LogicalPlan, Operator,
+    },
+    physical_expr::{create_physical_expr, execution_props::ExecutionProps},
+};
+use datafusion_federation::get_table_source;
+use log::debug;
+
+use crate::schema::SQLTableSource;
+
+// Adds the partitions selected by the conjuncts of filters on the partition
+// columns of a table, as `column IN (values)`, which engines prune partitions
+// on, unlike e.g. functions of the column. The partitions are those the
+// table source was created with, the conjuncts are kept as they are.
+pub(crate) fn prune_partitions(plan: LogicalPlan) -> Result<LogicalPlan> {
+    plan.transform_up(&|plan| {
+        let LogicalPlan::Filter(filter) = &plan else {
+            return Ok(Transformed::No(plan));
+        };
+        let Some(partitions) = scan_partitions(filter.input.as_ref()) else {
+            return Ok(Transformed::No(plan));
+        };
+        match pruned_filter(filter, &partitions)? {
+            Some(filter) => Ok(Transformed::Yes(LogicalPlan::Filter(filter))),
+            None => Ok(Transformed::No(plan)),
+        }
+    })
+}
+
+// The partitions of the table the filter reads, by partition column.
+fn scan_partitions(plan: &LogicalPlan) -> Option<RecordBatch> {
+    let scan = match plan {
+        LogicalPlan::TableScan(scan) => scan,
+        LogicalPlan::SubqueryAlias(alias) => match alias.input.as_ref() {
+            LogicalPlan::TableScan(scan) => scan,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let source = get_table_source(scan.source.clone()).ok()?;
+    source
+        .as_any()
+        .downcast_ref::<SQLTableSource>()?
+        .partitions()
+        .filter(|partitions| partitions.num_rows() > 0)
+        .cloned()
+}
+
+fn pruned_filter(filter: &Filter, partitions: &RecordBatch) -> Result<Option<Filter>> {
+    let names: HashSet<&str> = partitions
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| f.name().as_str())
+        .collect();
+    let conjuncts = split_conjunction(&filter.predicate);
+    let mut selecting = vec![];
+    for conjunct in &conjuncts {
+        let columns = conjunct.to_columns()?;
+        if !columns.is_empty() && columns.iter().all(|c| names.contains(c.name.as_str())) {
+            selecting.push((*conjunct).clone());
+        }
+    }
+    let Some(predicate) = conjunction(selecting) else {
+        return Ok(None);
+    };
+
+    // Evaluates the conjuncts on the partition values, the other columns
+    // are null
+    let input = filter.input.schema();
+    let rows = partitions.num_rows();
+    let mut fields = vec![];
+    let mut columns: Vec<ArrayRef> = vec![];
+    for field in input.fields() {
+        let column = match partitions.column_by_name(field.name()) {
+            Some(values) => cast(values, field.data_type())?,
+            None => new_null_array(field.data_type(), rows),
+        };
+        fields.push(Field::new(field.name(), field.data_type().clone(), true));
+        columns.push(column);
+    }
+    let schema = Arc::new(Schema::new(fields));
+    let batch = RecordBatch::try_new(schema.clone(), columns)?;
+    let physical = create_physical_expr(&predicate, input, &schema, &ExecutionProps::new())?;
+    let selected: Vec<bool> = match physical.evaluate(&batch)? {
+        ColumnarValue::Array(array) => {
+            let array = array.as_boolean();
+            (0..rows)
+                .map(|i| array.is_valid(i) && array.value(i))
+                .collect()
+        }
+        ColumnarValue::Scalar(value) => vec![value == ScalarValue::Boolean(Some(true)); rows],
+    };
+
+    let mut pruning = vec![];
+    for field in partitions.schema().fields() {
+        let column = input
+            .field_with_unqualified_name(field.name())?
+            .qualified_column();
+        if conjuncts.iter().any(|c| prunes_on(c, &column)) {
+            continue;
+        }
+        let values = batch.column(input.index_of_column(&column)?);
+        let mut selected_values = vec![];
+        let mut all_values = HashSet::new();
+        for (i, selected) in selected.iter().enumerate() {
+            let value = ScalarValue::try_from_array(values, i)?;
+            if *selected && !selected_values.contains(&value) {
+                selected_values.push(value.clone());
+            }
+            all_values.insert(value);
+        }
+        if selected_values.len() < all_values.len() {
+            pruning.push(partition_predicate(column, selected_values));
+        }
+    }
+    // No partition is read
+    if !selected.contains(&true) {
+        pruning = vec![lit(false)];
+    }
+    if pruning.is_empty() {
+        return Ok(None);
+    }
+    debug!(
+        "federation rule=federate_sql decision=prune_partitions partitions={} selected={}",
+        rows,
+        selected.iter().filter(|s| **s).count()
+    );
+    let predicate = conjunction(conjuncts.into_iter().cloned().chain(pruning)).unwrap();
+    Filter::try_new(predicate, filter.input.clone()).map(Some)
+}
+
+// Whether the conjunct already compares the column to literals.
+fn prunes_on(conjunct: &Expr, column: &Column) -> bool {
+    match conjunct {
+        Expr::BinaryExpr(BinaryExpr {
+            left,
+            op: Operator::Eq,
+            right,
+        }) => {
+            matches!((left.as_ref(), right.as_ref()), (Expr::Column(c), Expr::Literal(_)) if c.name == column.name)
+        }
+        Expr::InList(InList {
+            expr,
+            list,
+            negated: false,
+        }) => {
+            matches!(expr.as_ref(), Expr::Column(c) if c.name == column.name)
+                && list.iter().all(|e| matches!(e, Expr::Literal(_)))
+        }
+        _ => false,
+    }
+}
+
+fn partition_predicate(column: Column, values: Vec<ScalarValue>) -> Expr {
+    let null = values.iter().any(|v| v.is_null());
+    let list: Vec<Expr> = values
+        .into_iter()
+        .filter(|v| !v.is_null())
+        .map(lit)
+        .collect();
+    let column = Expr::Column(column);
+    match (list.is_empty(), null) {
+        (true, _) => column.is_null(),
+        (false, false) => column.in_list(list, false),
+        (false, true) => column.clone().in_list(list, false).or(column.is_null()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::logical_expr::col;
+
+    use super::*;
+
+    #[test]
+    fn test_partition_predicate() {
+        let region = Column::from_name("region");
+        assert!(prunes_on(&col("region").eq(lit("EU")), &region));
+        assert!(prunes_on(
+            &col("region").in_list(vec![lit("EU"), lit("US")], false),
+            &region
+        ));
+        assert!(!prunes_on(&col("region").not_eq(lit("EU")), &region));
+
+        let values = vec![ScalarValue::from("EU"), ScalarValue::Utf8(None)];
+        assert_eq!(
+            partition_predicate(region.clone(), values),
+            col("region")
+                .in_list(vec![lit("EU")], false)
+                .or(col("region").is_null())
+        );
+        assert_eq!(
+            partition_predicate(region, vec![ScalarValue::Utf8(None)]),
+            col("region").is_null()
+        );
+    }
+}
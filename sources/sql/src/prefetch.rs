@@ -0,0 +1,224 @@
+use std::{
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use datafusion::{common::plan_err, error::Result, execution::context::SessionContext};
+use futures::TryStreamExt;
+use log::{debug, warn};
+
+// CronSchedule is a five field cron expression, `minute hour day month
+// weekday`, evaluated in UTC. Fields are `*`, values, ranges `a-b`, steps
+// `*/n` or `a-b/n`, and lists of those. Weekdays are 0-6 from Sunday, 7 is
+// Sunday too. As in cron, restricting both the day and the weekday matches
+// days matching either.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: Vec<bool>,
+    hours: Vec<bool>,
+    days: Vec<bool>,
+    months: Vec<bool>,
+    weekdays: Vec<bool>,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let fields = expr.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return plan_err!("invalid cron schedule {expr}, expected five fields");
+        };
+        let mut weekday_values = parse_field(weekdays, 0, 7)?;
+        // 7 is Sunday
+        weekday_values[0] |= weekday_values[7];
+        weekday_values.truncate(7);
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days: parse_field(days, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            weekdays: weekday_values,
+            any_day: days == "*",
+            any_weekday: weekdays == "*",
+        })
+    }
+
+    // Whether the schedule fires in the minute of the time.
+    pub fn matches(&self, time: SystemTime) -> bool {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let days = (secs / 86400) as i64;
+        let (_, month, day) = civil_from_days(days);
+        // 1970-01-01 was a Thursday
+        let weekday = ((days + 4).rem_euclid(7)) as usize;
+        let minute = (secs % 3600 / 60) as usize;
+        let hour = (secs % 86400 / 3600) as usize;
+
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => self.days[day as usize] || self.weekdays[weekday],
+            _ => self.days[day as usize] && self.weekdays[weekday],
+        };
+        self.minutes[minute] && self.hours[hour] && self.months[month as usize] && day_matches
+    }
+}
+
+// The values of a field as flags indexed by value, up to `max`.
+fn parse_field(field: &str, min: usize, max: usize) -> Result<Vec<bool>> {
+    let mut values = vec![false; max + 1];
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<usize>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return plan_err!("invalid cron step in {field}"),
+            },
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => {
+                let parse = |v: &str| v.parse::<usize>().ok().filter(|v| (min..=max).contains(v));
+                let bounds = match range.split_once('-') {
+                    Some((start, end)) => parse(start).zip(parse(end)),
+                    None => parse(range).map(|v| (v, v)),
+                };
+                match bounds {
+                    Some((start, end)) if start <= end => (start, end),
+                    _ => return plan_err!("invalid cron field {field}, expected {min}-{max}"),
+                }
+            }
+        };
+        for value in (start..=end).step_by(step) {
+            values[value] = true;
+        }
+    }
+    Ok(values)
+}
+
+// The year, month and day of days since 1970-01-01, see
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+struct PrefetchEntry {
+    name: String,
+    sql: String,
+    schedule: CronSchedule,
+    // The minute, since the epoch, of the last run
+    last_run: Option<u64>,
+}
+
+// PrefetchRun is the outcome of a prefetched query.
+#[derive(Debug, Clone)]
+pub struct PrefetchRun {
+    pub name: String,
+    // The rows fetched, or why the query failed
+    pub result: std::result::Result<usize, String>,
+}
+
+// PrefetchScheduler runs the queries of known dashboards on a schedule, e.g.
+// before office hours, so the result caches of their sources, see
+// CachingExecutor, are warm when the dashboards load. The results themselves
+// are dropped. The scheduler doesn't run a timer, run_due is called at least
+// once a minute, e.g. from a tokio interval.
+pub struct PrefetchScheduler {
+    ctx: SessionContext,
+    entries: Mutex<Vec<PrefetchEntry>>,
+}
+
+impl PrefetchScheduler {
+    pub fn new(ctx: SessionContext) -> Self {
+        Self {
+            ctx,
+            entries: Mutex::new(vec![]),
+        }
+    }
+
+    // Registers the query under the name, replacing a query of that name.
+    pub fn register(&self, name: &str, sql: &str, schedule: &str) -> Result<()> {
+        let schedule = CronSchedule::parse(schedule)?;
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|e| e.name != name);
+        entries.push(PrefetchEntry {
+            name: name.to_string(),
+            sql: sql.to_string(),
+            schedule,
+            last_run: None,
+        });
+        Ok(())
+    }
+
+    pub fn unregister(&self, name: &str) {
+        self.entries.lock().unwrap().retain(|e| e.name != name);
+    }
+
+    // Runs the queries due at the time that haven't run in its minute yet.
+    pub async fn run_due(&self, now: SystemTime) -> Vec<PrefetchRun> {
+        let minute = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() / 60;
+        let due = {
+            let mut entries = self.entries.lock().unwrap();
+            entries
+                .iter_mut()
+                .filter(|e| e.last_run != Some(minute) && e.schedule.matches(now))
+                .map(|e| {
+                    e.last_run = Some(minute);
+                    (e.name.clone(), e.sql.clone())
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut runs = vec![];
+        for (name, sql) in due {
+            let result = self.prefetch(&sql).await;
+            match &result {
+                Ok(rows) => debug!("federation prefetch name={name} rows={rows}"),
+                Err(e) => warn!("federation prefetch_failed name={name} error=\"{e}\""),
+            }
+            runs.push(PrefetchRun {
+                name,
+                result: result.map_err(|e| e.to_string()),
+            });
+        }
+        runs
+    }
+
+    async fn prefetch(&self, sql: &str) -> Result<usize> {
+        let stream = self.ctx.sql(sql).await?.execute_stream().await?;
+        stream
+            .try_fold(0, |rows, batch| async move { Ok(rows + batch.num_rows()) })
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cron_schedule() {
+        // 2024-01-01 06:30 UTC, a Monday
+        let monday = UNIX_EPOCH + Duration::from_secs(1704090600);
+        assert!(CronSchedule::parse("30 6 * * 1-5").unwrap().matches(monday));
+        assert!(CronSchedule::parse("*/15 5-7 1 1 *")
+            .unwrap()
+            .matches(monday));
+        assert!(!CronSchedule::parse("30 6 * * 0,6").unwrap().matches(monday));
+        // Either the day or the weekday
+        assert!(CronSchedule::parse("30 6 15 * 1").unwrap().matches(monday));
+        assert!(CronSchedule::parse("30 6 * *").is_err());
+        assert!(CronSchedule::parse("60 6 * * *").is_err());
+    }
+}
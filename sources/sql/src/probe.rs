@@ -0,0 +1,46 @@
+use datafusion::{
+    arrow::{compute::can_cast_types, datatypes::SchemaRef},
+    common::{plan_err, DFSchemaRef},
+    error::Result,
+};
+
+use crate::executor::SQLExecutor;
+
+// Runs the query without fetching rows to validate it against the source
+// before execution, returning the schema reported by the source.
+pub(crate) async fn probe_schema(
+    executor: &dyn SQLExecutor,
+    query: &str,
+    expected: &DFSchemaRef,
+) -> Result<SchemaRef> {
+    // `WHERE 1=0` is more portable than `LIMIT 0`, e.g. SQL Server lacks LIMIT
+    let probe = format!("SELECT * FROM ({query}) AS probe WHERE 1=0");
+    let schema = match executor.execute(probe.as_str()).await {
+        Ok(stream) => stream.schema(),
+        Err(e) => {
+            return plan_err!(
+                "remote query rejected by {:?}: {e}\nsql: {query}",
+                executor.compute_context()
+            )
+        }
+    };
+
+    if schema.fields().len() != expected.fields().len() {
+        return plan_err!(
+            "remote query returns {} columns, expected {}\nsql: {query}",
+            schema.fields().len(),
+            expected.fields().len()
+        );
+    }
+    for (remote, field) in schema.fields().iter().zip(expected.fields()) {
+        if !can_cast_types(remote.data_type(), field.data_type()) {
+            return plan_err!(
+                "remote column {} has type {}, expected {}\nsql: {query}",
+                remote.name(),
+                remote.data_type(),
+                field.data_type()
+            );
+        }
+    }
+    Ok(schema)
+}
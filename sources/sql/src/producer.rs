@@ -3,27 +3,50 @@ use std::sync::Arc;
 use datafusion::logical_expr::{JoinConstraint, JoinType, Like};
 use datafusion::sql::sqlparser::ast::JoinOperator;
 use datafusion::{
+    arrow::temporal_conversions::{
+        date32_to_datetime, date64_to_datetime, timestamp_ms_to_datetime, timestamp_ns_to_datetime,
+        timestamp_s_to_datetime, timestamp_us_to_datetime,
+    },
     error::{DataFusionError, Result},
     scalar::ScalarValue,
     sql::sqlparser::ast::{self, Expr as SQLExpr},
 };
 
 use datafusion::common::not_impl_err;
+use datafusion::common::tree_node::{Transformed, TreeNode};
 use datafusion::common::{Column, DFSchemaRef};
-#[allow(unused_imports)]
 use datafusion::logical_expr::aggregate_function;
 use datafusion::logical_expr::expr::{
-    Alias, BinaryExpr, Case, Cast, InList, ScalarFunction as DFScalarFunction, WindowFunction,
+    AggregateFunction, Alias, BinaryExpr, Case, Cast, InList, ScalarFunction as DFScalarFunction,
+    Sort, WindowFunction,
 };
-use datafusion::logical_expr::{Between, LogicalPlan, Operator};
+use datafusion::logical_expr::{Aggregate, Between, LogicalPlan, Operator, TableScan};
 use datafusion::prelude::Expr;
+use datafusion_federation::get_table_source;
+
+use crate::{
+    dialect::{AsOf, GroupByStrategy, LimitStyle, SQLDialect},
+    nesting::nest_selects,
+    SQLTableSource,
+};
 
 use crate::ast_builder::{
-    BuilderError, QueryBuilder, RelationBuilder, SelectBuilder, TableRelationBuilder,
-    TableWithJoinsBuilder,
+    BuilderError, DerivedRelationBuilder, QueryBuilder, RelationBuilder, SelectBuilder,
+    TableRelationBuilder, TableWithJoinsBuilder,
 };
 
-pub fn query_to_sql(plan: &LogicalPlan) -> Result<ast::Statement> {
+pub fn query_to_sql(plan: &LogicalPlan, dialect: &dyn SQLDialect) -> Result<ast::Statement> {
+    let mut statement = plan_to_statement(plan, dialect)?;
+    render_table_aliases(&mut statement, dialect);
+    Ok(statement)
+}
+
+// Renders the plan without applying the dialect's table alias style, see
+// `render_table_aliases`.
+pub(crate) fn plan_to_statement(
+    plan: &LogicalPlan,
+    dialect: &dyn SQLDialect,
+) -> Result<ast::Statement> {
     match plan {
         LogicalPlan::Projection(_)
         | LogicalPlan::Filter(_)
@@ -42,29 +65,8 @@ pub fn query_to_sql(plan: &LogicalPlan) -> Result<ast::Statement> {
         | LogicalPlan::Statement(_)
         | LogicalPlan::Values(_)
         | LogicalPlan::Distinct(_) => {
-            let mut query_builder = QueryBuilder::default();
-            let mut select_builder = SelectBuilder::default();
-            select_builder.push_from(TableWithJoinsBuilder::default());
-            let mut relation_builder = RelationBuilder::default();
-            select_to_sql(
-                plan,
-                &mut query_builder,
-                &mut select_builder,
-                &mut relation_builder,
-            )?;
-
-            let mut twj = select_builder.pop_from().unwrap();
-            twj.relation(relation_builder);
-            select_builder.push_from(twj);
-
-            let body = ast::SetExpr::Select(Box::new(
-                select_builder.build().map_err(builder_error_to_df)?,
-            ));
-            let query = query_builder
-                .body(Box::new(body))
-                .build()
-                .map_err(builder_error_to_df)?;
-
+            let plan = nest_selects(plan)?;
+            let query = plan_to_query(&plan, dialect)?;
             Ok(ast::Statement::Query(Box::new(query)))
         }
         LogicalPlan::Dml(_) => dml_to_sql(plan),
@@ -81,54 +83,277 @@ pub fn query_to_sql(plan: &LogicalPlan) -> Result<ast::Statement> {
     }
 }
 
+fn plan_to_query(plan: &LogicalPlan, dialect: &dyn SQLDialect) -> Result<ast::Query> {
+    let mut query_builder = QueryBuilder::default();
+    let mut select_builder = SelectBuilder::default();
+    select_builder.push_from(TableWithJoinsBuilder::default());
+    let mut relation_builder = RelationBuilder::default();
+    select_to_sql(
+        plan,
+        &mut query_builder,
+        &mut select_builder,
+        &mut relation_builder,
+        dialect,
+    )?;
+
+    let mut twj = select_builder.pop_from().unwrap();
+    twj.relation(relation_builder);
+    select_builder.push_from(twj);
+
+    // Select the columns explicitly instead of `*`, so columns added to
+    // the remote table after registration aren't fetched
+    if !select_builder.already_projected() {
+        let items = plan
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| {
+                let column = field.qualified_column();
+                let expr = match column.relation {
+                    Some(_) => col_to_sql(&column, dialect)?,
+                    None => SQLExpr::Identifier(new_ident(column.name, dialect)),
+                };
+                Ok(ast::SelectItem::UnnamedExpr(expr))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        select_builder.projection(items);
+    }
+
+    let body = ast::SetExpr::Select(Box::new(
+        select_builder.build().map_err(builder_error_to_df)?,
+    ));
+    let mut query = query_builder
+        .body(Box::new(body))
+        .build()
+        .map_err(builder_error_to_df)?;
+    // OFFSET ... FETCH requires an ORDER BY, which may be arbitrary
+    let needs_order = dialect.limit_style() == LimitStyle::OffsetFetch;
+    if needs_order && query.offset.is_some() && query.order_by.is_empty() {
+        query.order_by.push(ast::OrderByExpr {
+            expr: SQLExpr::Nested(Box::new(SQLExpr::Identifier(ast::Ident::new(
+                "SELECT NULL",
+            )))),
+            asc: None,
+            nulls_first: None,
+        });
+    }
+    Ok(query)
+}
+
+// Renders a derived table, its columns are named as in the plan so they can
+// be referenced from the outer query.
+fn derived_query(plan: &LogicalPlan, dialect: &dyn SQLDialect) -> Result<ast::Query> {
+    let mut query = plan_to_query(plan, dialect)?;
+    if let ast::SetExpr::Select(select) = query.body.as_mut() {
+        for (item, field) in select.projection.iter_mut().zip(plan.schema().fields()) {
+            let ast::SelectItem::UnnamedExpr(expr) = item else {
+                continue;
+            };
+            let named = match expr {
+                SQLExpr::Identifier(ident) => &ident.value == field.name(),
+                SQLExpr::CompoundIdentifier(idents) => {
+                    idents.last().map(|i| &i.value) == Some(field.name())
+                }
+                _ => false,
+            };
+            if !named {
+                *item = ast::SelectItem::ExprWithAlias {
+                    expr: expr.clone(),
+                    alias: new_ident(field.name().clone(), dialect),
+                };
+            }
+        }
+    }
+    Ok(query)
+}
+
 fn select_to_sql(
     plan: &LogicalPlan,
     query: &mut QueryBuilder,
     select: &mut SelectBuilder,
     relation: &mut RelationBuilder,
+    dialect: &dyn SQLDialect,
 ) -> Result<()> {
     match plan {
         LogicalPlan::TableScan(scan) => {
             let mut builder = TableRelationBuilder::default();
-            builder.name(ast::ObjectName(vec![new_ident(
-                scan.table_name.table().to_string(),
-            )]));
-            relation.table(builder);
+            let mut name = remote_table_name(scan, dialect);
+            if let Some(as_of) = table_as_of(scan) {
+                // sqlparser has no AST for most time travel clauses, the
+                // clause is appended to the unquoted table name
+                let Some(clause) = dialect.time_travel(&as_of) else {
+                    return not_impl_err!("time travel for dialect {}", dialect.name());
+                };
+                if let Some(last) = name.pop() {
+                    name.push(ast::Ident::new(format!("{last} {clause}")));
+                }
+            }
+            builder.name(ast::ObjectName(name));
+            if let Some(args) = function_args(scan) {
+                if !dialect.supports_table_functions() {
+                    return not_impl_err!("table functions for dialect {}", dialect.name());
+                }
+                let args = args
+                    .iter()
+                    .map(|arg| {
+                        Ok(ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(
+                            literal_to_sql(arg, dialect)?,
+                        )))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                builder.args(Some(args)).alias(Some(new_table_alias(
+                    scan.table_name.table().to_string(),
+                    dialect,
+                )));
+            }
+            let mut table = RelationBuilder::default();
+            match view_definition(scan) {
+                // Inlined views are read from a derived table of the same name
+                Some(definition) => {
+                    let mut derived = DerivedRelationBuilder::default();
+                    derived
+                        .lateral(false)
+                        .subquery(Box::new(definition))
+                        .alias(Some(new_table_alias(
+                            scan.table_name.table().to_string(),
+                            dialect,
+                        )));
+                    table.derived(derived);
+                }
+                None => {
+                    table.table(builder);
+                }
+            }
+
+            let mapped = mapped_columns(scan);
+            if mapped.is_empty() {
+                *relation = table;
+                return Ok(());
+            }
+
+            // Map the exposed columns to the source columns in a derived table
+            let items = scan
+                .source
+                .schema()
+                .fields()
+                .iter()
+                .map(|field| {
+                    let (remote, text) = mapped
+                        .iter()
+                        .find(|(_, name, _)| name == field.name())
+                        .map_or((field.name().clone(), false), |(remote, _, text)| {
+                            (remote.clone(), *text)
+                        });
+                    let mut expr = SQLExpr::Identifier(new_ident(remote, dialect));
+                    if text {
+                        expr = SQLExpr::Cast {
+                            expr: Box::new(expr),
+                            data_type: ast::DataType::Varchar(None),
+                            format: None,
+                        };
+                    }
+                    ast::SelectItem::ExprWithAlias {
+                        expr,
+                        alias: new_ident(field.name().clone(), dialect),
+                    }
+                })
+                .collect::<Vec<_>>();
+            let mut twj = TableWithJoinsBuilder::default();
+            twj.relation(table);
+            let mut derived_select = SelectBuilder::default();
+            derived_select.projection(items).push_from(twj);
+            let body = ast::SetExpr::Select(Box::new(
+                derived_select.build().map_err(builder_error_to_df)?,
+            ));
+            let subquery = QueryBuilder::default()
+                .body(Box::new(body))
+                .build()
+                .map_err(builder_error_to_df)?;
+
+            let mut derived = DerivedRelationBuilder::default();
+            derived
+                .lateral(false)
+                .subquery(Box::new(subquery))
+                .alias(Some(new_table_alias(
+                    scan.table_name.table().to_string(),
+                    dialect,
+                )));
+            relation.derived(derived);
 
             Ok(())
         }
         LogicalPlan::Projection(p) => {
-            let items = p
-                .expr
+            // Above an aggregate its outputs are referenced by name, they are
+            // replaced with the aggregate's expressions
+            let exprs = match aggregate_input(p.input.as_ref()) {
+                Some(agg) => p
+                    .expr
+                    .iter()
+                    .map(|e| unproject_agg_expr(e, agg))
+                    .collect::<Result<Vec<_>>>()?,
+                None => p.expr.clone(),
+            };
+            let items = exprs
                 .iter()
-                .map(|e| select_item_to_sql(e, p.input.schema(), 0).unwrap())
+                .map(|e| select_item_to_sql(e, p.input.schema(), 0, dialect).unwrap())
                 .collect::<Vec<_>>();
             select.projection(items);
 
-            select_to_sql(p.input.as_ref(), query, select, relation)
+            select_to_sql(p.input.as_ref(), query, select, relation, dialect)
         }
         LogicalPlan::Filter(filter) => {
-            let filter_expr = expr_to_sql(&filter.predicate, filter.input.schema(), 0)?;
+            if let LogicalPlan::Aggregate(agg) = filter.input.as_ref() {
+                let predicate = unproject_agg_expr(&filter.predicate, agg)?;
+                let having = expr_to_sql(&predicate, filter.input.schema(), 0, dialect)?;
+                select.having(Some(having));
+                return select_to_sql(filter.input.as_ref(), query, select, relation, dialect);
+            }
+
+            let filter_expr = expr_to_sql(&filter.predicate, filter.input.schema(), 0, dialect)?;
 
             select.selection(Some(filter_expr));
 
-            select_to_sql(filter.input.as_ref(), query, select, relation)
+            select_to_sql(filter.input.as_ref(), query, select, relation, dialect)
         }
         LogicalPlan::Limit(limit) => {
-            if let Some(fetch) = limit.fetch {
-                query.limit(Some(ast::Expr::Value(ast::Value::Number(
-                    fetch.to_string(),
-                    false,
-                ))));
-            }
+            limit_to_sql(query, limit.skip, limit.fetch, dialect);
 
-            select_to_sql(limit.input.as_ref(), query, select, relation)
+            select_to_sql(limit.input.as_ref(), query, select, relation, dialect)
         }
-        LogicalPlan::Sort(_sort) => {
-            not_impl_err!("Unsupported operator: {plan:?}")
+        LogicalPlan::Sort(sort) => {
+            query.order_by(sort_to_sql(&sort.expr, sort.input.schema(), dialect)?);
+            if sort.fetch.is_some() {
+                limit_to_sql(query, 0, sort.fetch, dialect);
+            }
+
+            select_to_sql(sort.input.as_ref(), query, select, relation, dialect)
         }
-        LogicalPlan::Aggregate(_agg) => {
-            not_impl_err!("Unsupported operator: {plan:?}")
+        LogicalPlan::Aggregate(agg) => {
+            if !select.already_projected() {
+                // Name the outputs as the aggregate does, it is read by name
+                let items = agg
+                    .group_expr
+                    .iter()
+                    .chain(agg.aggr_expr.iter())
+                    .zip(agg.schema.fields())
+                    .map(|(e, field)| {
+                        Ok(ast::SelectItem::ExprWithAlias {
+                            expr: expr_to_sql(e, agg.input.schema(), 0, dialect)?,
+                            alias: new_ident(field.name().clone(), dialect),
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                select.projection(items);
+            }
+
+            let group_by = agg
+                .group_expr
+                .iter()
+                .map(|e| group_by_to_sql(e, select.projection_items(), agg.input.schema(), dialect))
+                .collect::<Result<Vec<_>>>()?;
+            select.group_by(ast::GroupByExpr::Expressions(group_by));
+
+            select_to_sql(agg.input.as_ref(), query, select, relation, dialect)
         }
         LogicalPlan::Distinct(_distinct) => {
             not_impl_err!("Unsupported operator: {plan:?}")
@@ -144,14 +369,18 @@ fn select_to_sql(
             // parse filter if exists
             let in_join_schema = join.left.schema().join(join.right.schema())?;
             let join_filter = match &join.filter {
-                Some(filter) => Some(expr_to_sql(filter, &Arc::new(in_join_schema), 0)?),
+                Some(filter) => Some(expr_to_sql(filter, &Arc::new(in_join_schema), 0, dialect)?),
                 None => None,
             };
 
             // map join.on to `l.a = r.a AND l.b = r.b AND ...`
-            let eq_op = ast::BinaryOperator::Eq;
-            let join_on =
-                join_conditions_to_sql(&join.on, eq_op, join.left.schema(), join.right.schema())?;
+            let join_on = join_conditions_to_sql(
+                &join.on,
+                join.null_equals_null,
+                join.left.schema(),
+                join.right.schema(),
+                dialect,
+            )?;
 
             // Merge `join_on` and `join_filter`
             let join_expr = match (join_filter, join_on) {
@@ -167,8 +396,14 @@ fn select_to_sql(
 
             let mut right_relation = RelationBuilder::default();
 
-            select_to_sql(join.left.as_ref(), query, select, relation)?;
-            select_to_sql(join.right.as_ref(), query, select, &mut right_relation)?;
+            select_to_sql(join.left.as_ref(), query, select, relation, dialect)?;
+            select_to_sql(
+                join.right.as_ref(),
+                query,
+                select,
+                &mut right_relation,
+                dialect,
+            )?;
 
             let ast_join = ast::Join {
                 relation: right_relation.build().map_err(builder_error_to_df)?,
@@ -181,10 +416,26 @@ fn select_to_sql(
             Ok(())
         }
         LogicalPlan::SubqueryAlias(plan_alias) => {
+            if !matches!(plan_alias.input.as_ref(), LogicalPlan::TableScan(_)) {
+                let mut derived = DerivedRelationBuilder::default();
+                derived
+                    .lateral(false)
+                    .subquery(Box::new(derived_query(plan_alias.input.as_ref(), dialect)?))
+                    .alias(Some(new_table_alias(
+                        plan_alias.alias.table().to_string(),
+                        dialect,
+                    )));
+                relation.derived(derived);
+                return Ok(());
+            }
+
             // Handle bottom-up to allocate relation
-            select_to_sql(plan_alias.input.as_ref(), query, select, relation)?;
+            select_to_sql(plan_alias.input.as_ref(), query, select, relation, dialect)?;
 
-            relation.alias(Some(new_table_alias(plan_alias.alias.table().to_string())));
+            relation.alias(Some(new_table_alias(
+                plan_alias.alias.table().to_string(),
+                dialect,
+            )));
 
             Ok(())
         }
@@ -199,39 +450,206 @@ fn select_to_sql(
     }
 }
 
+// The aggregate a select's projection or HAVING filter is evaluated over.
+fn aggregate_input(plan: &LogicalPlan) -> Option<&Aggregate> {
+    match plan {
+        LogicalPlan::Aggregate(agg) => Some(agg),
+        LogicalPlan::Filter(filter) => match filter.input.as_ref() {
+            LogicalPlan::Aggregate(agg) => Some(agg),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Replaces references to the aggregate's outputs with the group and
+// aggregate expressions computing them.
+fn unproject_agg_expr(expr: &Expr, agg: &Aggregate) -> Result<Expr> {
+    expr.clone().transform(&|e| match &e {
+        Expr::Column(c) => match agg.schema.index_of_column(c) {
+            Ok(i) => {
+                let group_len = agg.group_expr.len();
+                let inner = if i < group_len {
+                    agg.group_expr[i].clone()
+                } else {
+                    agg.aggr_expr[i - group_len].clone()
+                };
+                Ok(Transformed::Yes(inner.unalias()))
+            }
+            Err(_) => Ok(Transformed::No(e)),
+        },
+        _ => Ok(Transformed::No(e)),
+    })
+}
+
+// Renders a GROUP BY key. Computed keys refer to the select list as the
+// dialect requires, plain columns are always grouped by name.
+fn group_by_to_sql(
+    expr: &Expr,
+    projection: &[ast::SelectItem],
+    schema: &DFSchemaRef,
+    dialect: &dyn SQLDialect,
+) -> Result<SQLExpr> {
+    let key = expr_to_sql(expr, schema, 0, dialect)?;
+    if matches!(expr, Expr::Column(_)) {
+        return Ok(key);
+    }
+
+    let position = projection.iter().position(|item| match item {
+        ast::SelectItem::UnnamedExpr(e) => e == &key,
+        ast::SelectItem::ExprWithAlias { expr, .. } => expr == &key,
+        _ => false,
+    });
+    match (dialect.group_by_strategy(), position) {
+        (GroupByStrategy::Ordinal, Some(i)) => Ok(SQLExpr::Value(ast::Value::Number(
+            (i + 1).to_string(),
+            false,
+        ))),
+        (GroupByStrategy::Alias, Some(i)) => match &projection[i] {
+            ast::SelectItem::ExprWithAlias { alias, .. } => Ok(SQLExpr::Identifier(alias.clone())),
+            _ => Ok(key),
+        },
+        _ => Ok(key),
+    }
+}
+
+fn aggregate_to_sql(
+    agg: &AggregateFunction,
+    schema: &DFSchemaRef,
+    dialect: &dyn SQLDialect,
+) -> Result<SQLExpr> {
+    if agg.filter.is_some() || agg.order_by.is_some() {
+        return not_impl_err!("Unsupported aggregate: {agg:?}");
+    }
+    let name = match agg.fun {
+        aggregate_function::AggregateFunction::Count => "COUNT",
+        aggregate_function::AggregateFunction::Sum => "SUM",
+        aggregate_function::AggregateFunction::Min => "MIN",
+        aggregate_function::AggregateFunction::Max => "MAX",
+        aggregate_function::AggregateFunction::Avg => "AVG",
+        _ => return not_impl_err!("Unsupported aggregate: {agg:?}"),
+    };
+    let args = agg
+        .args
+        .iter()
+        .map(|e| {
+            Ok(ast::FunctionArg::Unnamed(ast::FunctionArgExpr::Expr(
+                expr_to_sql(e, schema, 0, dialect)?,
+            )))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(SQLExpr::Function(ast::Function {
+        name: ast::ObjectName(vec![ast::Ident::new(name)]),
+        args,
+        filter: None,
+        null_treatment: None,
+        over: None,
+        distinct: agg.distinct,
+        special: false,
+        order_by: vec![],
+    }))
+}
+
+// Renders the OFFSET and LIMIT of the query in the dialect's syntax.
+fn limit_to_sql(
+    query: &mut QueryBuilder,
+    skip: usize,
+    fetch: Option<usize>,
+    dialect: &dyn SQLDialect,
+) {
+    let number = |n: usize| ast::Expr::Value(ast::Value::Number(n.to_string(), false));
+    match dialect.limit_style() {
+        LimitStyle::LimitOffset => {
+            // MySQL and SQLite reject OFFSET without LIMIT
+            if let Some(fetch) = fetch {
+                query.limit(Some(number(fetch)));
+            } else if skip > 0 {
+                query.limit(Some(number(i64::MAX as usize)));
+            }
+            if skip > 0 {
+                query.offset(Some(ast::Offset {
+                    value: number(skip),
+                    rows: ast::OffsetRows::None,
+                }));
+            }
+        }
+        LimitStyle::OffsetFetch | LimitStyle::FetchFirst => {
+            // FETCH requires an OFFSET
+            if skip > 0 || fetch.is_some() {
+                query.offset(Some(ast::Offset {
+                    value: number(skip),
+                    rows: ast::OffsetRows::Rows,
+                }));
+            }
+            if let Some(fetch) = fetch {
+                query.fetch(Some(ast::Fetch {
+                    with_ties: false,
+                    percent: false,
+                    quantity: Some(number(fetch)),
+                }));
+            }
+        }
+    }
+}
+
 fn select_item_to_sql(
     expr: &Expr,
     schema: &DFSchemaRef,
     col_ref_offset: usize,
+    dialect: &dyn SQLDialect,
 ) -> Result<ast::SelectItem> {
     match expr {
         Expr::Alias(Alias { expr, name, .. }) => {
-            let inner = expr_to_sql(expr, schema, col_ref_offset)?;
+            let inner = expr_to_sql(expr, schema, col_ref_offset, dialect)?;
 
             Ok(ast::SelectItem::ExprWithAlias {
                 expr: inner,
-                alias: new_ident(name.to_string()),
+                alias: new_ident(name.to_string(), dialect),
             })
         }
         _ => {
-            let inner = expr_to_sql(expr, schema, col_ref_offset)?;
+            let inner = expr_to_sql(expr, schema, col_ref_offset, dialect)?;
 
             Ok(ast::SelectItem::UnnamedExpr(inner))
         }
     }
 }
 
-fn expr_to_sql(expr: &Expr, _schema: &DFSchemaRef, _col_ref_offset: usize) -> Result<SQLExpr> {
+fn expr_to_sql(
+    expr: &Expr,
+    _schema: &DFSchemaRef,
+    _col_ref_offset: usize,
+    dialect: &dyn SQLDialect,
+) -> Result<SQLExpr> {
     match expr {
         Expr::InList(InList {
             expr,
-            list: _,
-            negated: _,
+            list,
+            negated,
         }) => {
-            not_impl_err!("Unsupported expression: {expr:?}")
+            let list = list
+                .iter()
+                .map(|e| expr_to_sql(e, _schema, _col_ref_offset, dialect))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(SQLExpr::InList {
+                expr: Box::new(expr_to_sql(expr, _schema, _col_ref_offset, dialect)?),
+                list,
+                negated: *negated,
+            })
         }
-        Expr::ScalarFunction(DFScalarFunction { .. }) => {
-            not_impl_err!("Unsupported expression: {expr:?}")
+        Expr::ScalarFunction(DFScalarFunction { func_def, args }) => {
+            let args = args
+                .iter()
+                .map(|e| expr_to_sql(e, _schema, _col_ref_offset, dialect))
+                .collect::<Result<Vec<_>>>()?;
+            match dialect.scalar_function(func_def.name(), args) {
+                Some(function) => Ok(function),
+                None => not_impl_err!(
+                    "function {} for dialect {}",
+                    func_def.name(),
+                    dialect.name()
+                ),
+            }
         }
         Expr::Between(Between {
             expr,
@@ -241,13 +659,31 @@ fn expr_to_sql(expr: &Expr, _schema: &DFSchemaRef, _col_ref_offset: usize) -> Re
         }) => {
             not_impl_err!("Unsupported expression: {expr:?}")
         }
-        Expr::Column(col) => col_to_sql(col),
+        Expr::Column(col) => col_to_sql(col, dialect),
         Expr::BinaryExpr(BinaryExpr { left, op, right }) => {
-            let l = expr_to_sql(left.as_ref(), _schema, 0)?;
-            let r = expr_to_sql(right.as_ref(), _schema, 0)?;
-            let op = op_to_sql(op)?;
-
-            Ok(binary_op_to_sql(l, r, op))
+            // Engines storing '' as NULL never match a comparison with '',
+            // the values compared with '' locally are NULL in the source
+            if dialect.empty_string_is_null() && matches!(op, Operator::Eq | Operator::NotEq) {
+                let other = match (is_empty_string(left), is_empty_string(right)) {
+                    (true, false) => Some(right),
+                    (false, true) => Some(left),
+                    _ => None,
+                };
+                if let Some(other) = other {
+                    let other = Box::new(expr_to_sql(other.as_ref(), _schema, 0, dialect)?);
+                    return Ok(match op {
+                        Operator::Eq => SQLExpr::IsNull(other),
+                        _ => SQLExpr::IsNotNull(other),
+                    });
+                }
+            }
+            let l = expr_to_sql(left.as_ref(), _schema, 0, dialect)?;
+            let r = expr_to_sql(right.as_ref(), _schema, 0, dialect)?;
+            match op {
+                Operator::IsDistinctFrom => Ok(dialect.distinct_from(l, r, false)),
+                Operator::IsNotDistinctFrom => Ok(dialect.distinct_from(l, r, true)),
+                _ => Ok(binary_op_to_sql(l, r, op_to_sql(op)?)),
+            }
         }
         Expr::Case(Case {
             expr,
@@ -256,11 +692,21 @@ fn expr_to_sql(expr: &Expr, _schema: &DFSchemaRef, _col_ref_offset: usize) -> Re
         }) => {
             not_impl_err!("Unsupported expression: {expr:?}")
         }
-        Expr::Cast(Cast { expr, data_type: _ }) => {
-            not_impl_err!("Unsupported expression: {expr:?}")
+        Expr::Cast(Cast { expr, data_type }) => {
+            let Some(sql_type) = dialect.cast_data_type(data_type) else {
+                return not_impl_err!("cast to {data_type} for dialect {}", dialect.name());
+            };
+            Ok(SQLExpr::Cast {
+                expr: Box::new(expr_to_sql(expr, _schema, _col_ref_offset, dialect)?),
+                data_type: sql_type,
+                format: None,
+            })
+        }
+        Expr::Literal(value) => literal_to_sql(value, dialect),
+        Expr::AggregateFunction(agg) => aggregate_to_sql(agg, _schema, dialect),
+        Expr::Alias(Alias { expr, name: _, .. }) => {
+            expr_to_sql(expr, _schema, _col_ref_offset, dialect)
         }
-        Expr::Literal(value) => Ok(ast::Expr::Value(scalar_to_sql(value)?)),
-        Expr::Alias(Alias { expr, name: _, .. }) => expr_to_sql(expr, _schema, _col_ref_offset),
         Expr::WindowFunction(WindowFunction {
             fun: _,
             args: _,
@@ -283,6 +729,53 @@ fn expr_to_sql(expr: &Expr, _schema: &DFSchemaRef, _col_ref_offset: usize) -> Re
     }
 }
 
+fn sort_to_sql(
+    exprs: &[Expr],
+    schema: &DFSchemaRef,
+    dialect: &dyn SQLDialect,
+) -> Result<Vec<ast::OrderByExpr>> {
+    let mut order_by = vec![];
+    for e in exprs {
+        let Expr::Sort(Sort {
+            expr,
+            asc,
+            nulls_first,
+        }) = e
+        else {
+            return not_impl_err!("Unsupported sort expression: {e:?}");
+        };
+        let expr = expr_to_sql(expr, schema, 0, dialect)?;
+        if dialect.supports_nulls_ordering() {
+            order_by.push(ast::OrderByExpr {
+                expr,
+                asc: Some(*asc),
+                nulls_first: Some(*nulls_first),
+            });
+            continue;
+        }
+        // The null ordering is a leading key, `CASE WHEN x IS NULL THEN 0 ELSE 1 END`
+        // sorts NULLs first
+        let number = |n: u8| Box::new(SQLExpr::Value(ast::Value::Number(n.to_string(), false)));
+        let (null, not_null) = if *nulls_first { (0, 1) } else { (1, 0) };
+        order_by.push(ast::OrderByExpr {
+            expr: SQLExpr::Case {
+                operand: None,
+                conditions: vec![SQLExpr::IsNull(Box::new(expr.clone()))],
+                results: vec![*number(null)],
+                else_result: Some(number(not_null)),
+            },
+            asc: Some(true),
+            nulls_first: None,
+        });
+        order_by.push(ast::OrderByExpr {
+            expr,
+            asc: Some(*asc),
+            nulls_first: None,
+        });
+    }
+    Ok(order_by)
+}
+
 fn op_to_sql(op: &Operator) -> Result<ast::BinaryOperator> {
     match op {
         Operator::Eq => Ok(ast::BinaryOperator::Eq),
@@ -315,6 +808,85 @@ fn op_to_sql(op: &Operator) -> Result<ast::BinaryOperator> {
     }
 }
 
+fn is_empty_string(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Literal(ScalarValue::Utf8(Some(s)) | ScalarValue::LargeUtf8(Some(s))) if s.is_empty()
+    )
+}
+
+// Renders values without an exact SQL number literal as a quoted
+// string cast to DECIMAL, instead of degrading them to floats.
+// Dates and timestamps are rendered by the dialect from their ISO 8601 form.
+pub(crate) fn literal_to_sql(v: &ScalarValue, dialect: &dyn SQLDialect) -> Result<SQLExpr> {
+    if let Some(datetime) = datetime_to_iso(v)? {
+        return Ok(match v {
+            ScalarValue::Date32(_) | ScalarValue::Date64(_) => dialect.date_literal(&datetime),
+            _ => dialect.timestamp_literal(&datetime),
+        });
+    }
+    match v {
+        ScalarValue::Decimal128(Some(value), precision, scale) => {
+            Ok(decimal_to_sql(value.to_string(), *precision, *scale))
+        }
+        ScalarValue::Decimal256(Some(value), precision, scale) => {
+            Ok(decimal_to_sql(value.to_string(), *precision, *scale))
+        }
+        ScalarValue::UInt64(Some(ui)) if *ui > i64::MAX as u64 => {
+            Ok(decimal_to_sql(ui.to_string(), 20, 0))
+        }
+        _ => Ok(ast::Expr::Value(scalar_to_sql(v)?)),
+    }
+}
+
+// Formats dates as `YYYY-MM-DD` and timestamps without time zone as
+// `YYYY-MM-DD HH:MM:SS[.fraction]`, None for other values.
+fn datetime_to_iso(v: &ScalarValue) -> Result<Option<String>> {
+    let datetime = match v {
+        ScalarValue::Date32(Some(d)) => {
+            return Ok(date32_to_datetime(*d).map(|d| d.format("%Y-%m-%d").to_string()))
+        }
+        ScalarValue::Date64(Some(d)) => {
+            return Ok(date64_to_datetime(*d).map(|d| d.format("%Y-%m-%d").to_string()))
+        }
+        ScalarValue::TimestampSecond(Some(ts), None) => timestamp_s_to_datetime(*ts),
+        ScalarValue::TimestampMillisecond(Some(ts), None) => timestamp_ms_to_datetime(*ts),
+        ScalarValue::TimestampMicrosecond(Some(ts), None) => timestamp_us_to_datetime(*ts),
+        ScalarValue::TimestampNanosecond(Some(ts), None) => timestamp_ns_to_datetime(*ts),
+        _ => return Ok(None),
+    };
+    match datetime {
+        Some(datetime) => Ok(Some(datetime.format("%Y-%m-%d %H:%M:%S%.f").to_string())),
+        None => not_impl_err!("Unsupported scalar: {v:?}"),
+    }
+}
+
+fn decimal_to_sql(unscaled: String, precision: u8, scale: i8) -> SQLExpr {
+    let (sign, digits) = match unscaled.strip_prefix('-') {
+        Some(digits) => ("-", digits.to_string()),
+        None => ("", unscaled),
+    };
+    let value = if scale <= 0 {
+        format!(
+            "{sign}{digits}{}",
+            "0".repeat(scale.unsigned_abs() as usize)
+        )
+    } else {
+        let scale = scale as usize;
+        let digits = format!("{digits:0>width$}", width = scale + 1);
+        let (int, frac) = digits.split_at(digits.len() - scale);
+        format!("{sign}{int}.{frac}")
+    };
+    SQLExpr::Cast {
+        expr: Box::new(SQLExpr::Value(ast::Value::SingleQuotedString(value))),
+        data_type: ast::DataType::Decimal(ast::ExactNumberInfo::PrecisionAndScale(
+            precision as u64,
+            scale.max(0) as u64,
+        )),
+        format: None,
+    }
+}
+
 fn scalar_to_sql(v: &ScalarValue) -> Result<ast::Value> {
     match v {
         ScalarValue::Null => Ok(ast::Value::Null),
@@ -402,14 +974,14 @@ fn scalar_to_sql(v: &ScalarValue) -> Result<ast::Value> {
     }
 }
 
-fn col_to_sql(col: &Column) -> Result<ast::Expr> {
+fn col_to_sql(col: &Column, dialect: &dyn SQLDialect) -> Result<ast::Expr> {
     Ok(ast::Expr::CompoundIdentifier(
         [
             col.relation.as_ref().unwrap().table().to_string(),
             col.name.to_string(),
         ]
         .iter()
-        .map(|i| new_ident(i.to_string()))
+        .map(|i| new_ident(i.to_string(), dialect))
         .collect(),
     ))
 }
@@ -429,23 +1001,29 @@ fn join_operator_to_sql(join_type: JoinType, constraint: ast::JoinConstraint) ->
 
 fn join_conditions_to_sql(
     join_conditions: &Vec<(Expr, Expr)>,
-    eq_op: ast::BinaryOperator,
+    null_equals_null: bool,
     left_schema: &DFSchemaRef,
     right_schema: &DFSchemaRef,
+    dialect: &dyn SQLDialect,
 ) -> Result<Option<SQLExpr>> {
     // Only support AND conjunction for each binary expression in join conditions
     let mut exprs: Vec<SQLExpr> = vec![];
     for (left, right) in join_conditions {
         // Parse left
-        let l = expr_to_sql(left, left_schema, 0)?;
+        let l = expr_to_sql(left, left_schema, 0, dialect)?;
         // Parse right
         let r = expr_to_sql(
             right,
             right_schema,
             left_schema.fields().len(), // offset to return the correct index
+            dialect,
         )?;
         // AND with existing expression
-        exprs.push(binary_op_to_sql(l, r, eq_op.clone()));
+        if null_equals_null {
+            exprs.push(dialect.distinct_from(l, r, true));
+        } else {
+            exprs.push(binary_op_to_sql(l, r, ast::BinaryOperator::Eq));
+        }
     }
     let join_expr: Option<SQLExpr> = exprs.into_iter().reduce(and_op_to_sql);
     Ok(join_expr)
@@ -463,17 +1041,145 @@ pub fn binary_op_to_sql(lhs: SQLExpr, rhs: SQLExpr, op: ast::BinaryOperator) ->
     }
 }
 
-fn new_table_alias(alias: String) -> ast::TableAlias {
+// Renders the table aliases of the statement without AS for dialects that
+// reject it. sqlparser always renders AS, so the alias is appended to the
+// unquoted table name, like time travel clauses, and derived tables are
+// rendered as a table of that name.
+pub(crate) fn render_table_aliases(statement: &mut ast::Statement, dialect: &dyn SQLDialect) {
+    if dialect.supports_table_alias_as() {
+        return;
+    }
+    if let ast::Statement::Query(query) = statement {
+        unalias_set_expr(&mut query.body);
+    }
+}
+
+fn unalias_set_expr(body: &mut ast::SetExpr) {
+    match body {
+        ast::SetExpr::Select(select) => {
+            for twj in select.from.iter_mut() {
+                unalias_table_factor(&mut twj.relation);
+                for join in twj.joins.iter_mut() {
+                    unalias_table_factor(&mut join.relation);
+                }
+            }
+        }
+        ast::SetExpr::Query(query) => unalias_set_expr(&mut query.body),
+        ast::SetExpr::SetOperation { left, right, .. } => {
+            unalias_set_expr(left);
+            unalias_set_expr(right);
+        }
+        _ => {}
+    }
+}
+
+fn unalias_table_factor(relation: &mut ast::TableFactor) {
+    match relation {
+        // The arguments of table functions are rendered after the name
+        ast::TableFactor::Table {
+            name,
+            alias,
+            args: None,
+            ..
+        } => {
+            if let (Some(alias), Some(last)) = (alias.take(), name.0.pop()) {
+                name.0.push(ast::Ident::new(format!("{last} {alias}")));
+            }
+        }
+        ast::TableFactor::Derived {
+            lateral,
+            subquery,
+            alias,
+        } => {
+            unalias_set_expr(&mut subquery.body);
+            let Some(alias) = alias.take() else {
+                return;
+            };
+            let lateral = if *lateral { "LATERAL " } else { "" };
+            let name = ast::Ident::new(format!("{lateral}({subquery}) {alias}"));
+            *relation = ast::TableFactor::Table {
+                name: ast::ObjectName(vec![name]),
+                alias: None,
+                args: None,
+                with_hints: vec![],
+                version: None,
+                partitions: vec![],
+            };
+        }
+        _ => {}
+    }
+}
+
+fn new_table_alias(alias: String, dialect: &dyn SQLDialect) -> ast::TableAlias {
     ast::TableAlias {
-        name: new_ident(alias),
+        name: new_ident(alias, dialect),
         columns: Vec::new(),
     }
 }
 
-fn new_ident(str: String) -> ast::Ident {
+// Returns the table name resolved when the source was registered,
+// scans of other sources use the unqualified DataFusion table name.
+fn remote_table_name(scan: &TableScan, dialect: &dyn SQLDialect) -> Vec<ast::Ident> {
+    let source = get_table_source(scan.source.clone()).ok();
+    match source
+        .as_ref()
+        .and_then(|s| s.as_any().downcast_ref::<SQLTableSource>())
+    {
+        Some(source) => source
+            .remote_name()
+            .iter()
+            .map(|part| new_ident(part.clone(), dialect))
+            .collect(),
+        None => vec![new_ident(scan.table_name.table().to_string(), dialect)],
+    }
+}
+
+fn table_as_of(scan: &TableScan) -> Option<AsOf> {
+    let source = get_table_source(scan.source.clone()).ok()?;
+    source
+        .as_any()
+        .downcast_ref::<SQLTableSource>()?
+        .as_of()
+        .cloned()
+}
+
+fn function_args(scan: &TableScan) -> Option<Vec<ScalarValue>> {
+    let source = get_table_source(scan.source.clone()).ok()?;
+    source
+        .as_any()
+        .downcast_ref::<SQLTableSource>()?
+        .function_args()
+        .map(|args| args.to_vec())
+}
+
+fn view_definition(scan: &TableScan) -> Option<ast::Query> {
+    let source = get_table_source(scan.source.clone()).ok()?;
+    source
+        .as_any()
+        .downcast_ref::<SQLTableSource>()?
+        .definition()
+        .cloned()
+}
+
+fn mapped_columns(scan: &TableScan) -> Vec<(String, String, bool)> {
+    let source = get_table_source(scan.source.clone()).ok();
+    match source
+        .as_ref()
+        .and_then(|s| s.as_any().downcast_ref::<SQLTableSource>())
+    {
+        Some(source) => source
+            .mapped_columns()
+            .into_iter()
+            .map(|(remote, name, text)| (remote.to_string(), name.to_string(), text))
+            .collect(),
+        None => vec![],
+    }
+}
+
+fn new_ident(str: String, dialect: &dyn SQLDialect) -> ast::Ident {
     ast::Ident {
         value: str,
-        quote_style: Some('`'),
+        quote_style: dialect.identifier_quote_style(),
     }
 }
 
@@ -489,21 +1195,37 @@ fn builder_error_to_df(e: BuilderError) -> DataFusionError {
 
 #[cfg(test)]
 mod tests {
-    use datafusion::{execution::context::SessionContext, test_util::TestTableFactory};
+    use datafusion::{
+        common::DFSchema,
+        execution::context::SessionContext,
+        prelude::{col, count, lit},
+        test_util::TestTableFactory,
+    };
 
     use super::*;
+    use crate::dialect::{
+        DefaultDialect, MsSqlDialect, MySqlDialect, OracleDialect, PostgreSqlDialect,
+        SnowflakeDialect,
+    };
 
-    #[tokio::test]
-    async fn test_select() {
+    // Returns a context with the mock tables table_a, table_b and table_c.
+    async fn mock_context() -> SessionContext {
         let mut state = SessionContext::new().state();
         state
             .table_factories_mut()
             .insert("MOCKTABLE".to_string(), Arc::new(TestTableFactory {}));
         let ctx = SessionContext::new_with_state(state);
+        for table in ["table_a", "table_b", "table_c"] {
+            ctx.sql(&format!("CREATE EXTERNAL TABLE {table} (id integer, value string) STORED AS MOCKTABLE LOCATION 'mock://path';"))
+                .await
+                .unwrap();
+        }
+        ctx
+    }
 
-        ctx.sql("CREATE EXTERNAL TABLE table_a (id integer, value string) STORED AS MOCKTABLE LOCATION 'mock://path';").await.unwrap();
-        ctx.sql("CREATE EXTERNAL TABLE table_b (id integer, value string) STORED AS MOCKTABLE LOCATION 'mock://path';").await.unwrap();
-        ctx.sql("CREATE EXTERNAL TABLE table_c (id integer, value string) STORED AS MOCKTABLE LOCATION 'mock://path';").await.unwrap();
+    #[tokio::test]
+    async fn test_select() {
+        let ctx = mock_context().await;
 
         let tests: Vec<(&str, &str)> = vec![
             (
@@ -526,21 +1248,225 @@ mod tests {
                 "select ta.id, tb.value from table_a ta join table_b tb on ta.id = tb.id join table_c tc on ta.id = tc.id;",
                 r#"SELECT `ta`.`id`, `tb`.`value` FROM `table_a` AS `ta` JOIN `table_b` AS `tb` ON `ta`.`id` = `tb`.`id` JOIN `table_c` AS `tc` ON `ta`.`id` = `tc`.`id`"#,
             ),
+            (
+                "select ta.id from table_a ta where ta.id in (1, 2, 3);",
+                r#"SELECT `ta`.`id` FROM `table_a` AS `ta` WHERE `ta`.`id` IN (1, 2, 3)"#,
+            ),
+            (
+                "select ta.id from table_a ta order by ta.id desc limit 5;",
+                r#"SELECT `ta`.`id` FROM `table_a` AS `ta` ORDER BY `ta`.`id` DESC NULLS FIRST LIMIT 5"#,
+            ),
+            (
+                "select ta.id, count(ta.value) from table_a ta group by ta.id having count(ta.value) > 1;",
+                r#"SELECT `ta`.`id`, COUNT(`ta`.`value`) FROM `table_a` AS `ta` GROUP BY `ta`.`id` HAVING COUNT(`ta`.`value`) > 1"#,
+            ),
+            (
+                "select sq2.id from (select sq1.id from (select ta.id from table_a ta where ta.id > 1) sq1 where sq1.id < 10) sq2 where sq2.id <> 5;",
+                r#"SELECT `sq2`.`id` FROM (SELECT `sq1`.`id` FROM (SELECT `ta`.`id` FROM `table_a` AS `ta` WHERE `ta`.`id` > 1) AS `sq1` WHERE `sq1`.`id` < 10) AS `sq2` WHERE `sq2`.`id` <> 5"#,
+            ),
+            (
+                "select s.id, s.n from (select ta.id, count(tb.value) as n from table_a ta join table_b tb on ta.id = tb.id group by ta.id) s where s.n > 1;",
+                r#"SELECT `s`.`id`, `s`.`n` FROM (SELECT `ta`.`id`, COUNT(`tb`.`value`) AS `n` FROM `table_a` AS `ta` JOIN `table_b` AS `tb` ON `ta`.`id` = `tb`.`id` GROUP BY `ta`.`id`) AS `s` WHERE `s`.`n` > 1"#,
+            ),
         ];
 
         for (query, expected) in tests {
-            // let dialect = GenericDialect {};
-            // let orig_ast = Parser::parse_sql(&dialect, query).unwrap();
-            // println!("{}", orig_ast[0]);
             let plan = ctx.sql(query).await.unwrap().into_unoptimized_plan();
-            // println!("{:?}", plan);
 
-            let ast = query_to_sql(&plan);
-            // println!("{:?}", ast);
+            let ast = query_to_sql(&plan, &DefaultDialect {});
 
             assert!(ast.is_ok());
             let actual = format!("{}", ast.unwrap());
             assert_eq!(actual, expected);
         }
+
+        // Plain scans select their columns explicitly
+        let plan = ctx.table("table_a").await.unwrap().into_unoptimized_plan();
+        let actual = format!("{}", query_to_sql(&plan, &DefaultDialect {}).unwrap());
+        assert_eq!(
+            actual,
+            "SELECT `table_a`.`id`, `table_a`.`value` FROM `table_a`"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_nested_selects() {
+        let ctx = mock_context().await;
+
+        // Sorting by a column that isn't selected
+        let plan = ctx
+            .table("table_a")
+            .await
+            .unwrap()
+            .select_columns(&["id", "value"])
+            .unwrap()
+            .sort(vec![col("value").sort(true, false)])
+            .unwrap()
+            .select_columns(&["id"])
+            .unwrap()
+            .into_unoptimized_plan();
+        let actual = format!("{}", query_to_sql(&plan, &DefaultDialect {}).unwrap());
+        assert_eq!(
+            actual,
+            r#"SELECT `table_a`.`id` FROM (SELECT `table_a`.`id`, `table_a`.`value` FROM `table_a`) AS `table_a` ORDER BY `table_a`.`value` ASC NULLS LAST"#
+        );
+
+        // Filter over a projection over an aggregate over a join, the join
+        // columns share a name
+        let table_b = ctx.table("table_b").await.unwrap();
+        let plan = ctx
+            .table("table_a")
+            .await
+            .unwrap()
+            .join(table_b, JoinType::Inner, &["id"], &["id"], None)
+            .unwrap()
+            .aggregate(
+                vec![col("table_a.id"), col("table_b.id")],
+                vec![count(col("table_b.value")).alias("n")],
+            )
+            .unwrap()
+            .select(vec![col("table_a.id"), col("table_b.id"), col("n")])
+            .unwrap()
+            .filter(col("n").gt(lit(1_i64)))
+            .unwrap()
+            .into_unoptimized_plan();
+        let actual = format!("{}", query_to_sql(&plan, &DefaultDialect {}).unwrap());
+        assert_eq!(
+            actual,
+            r#"SELECT `derived_1`.`id`, `derived_1`.`id_1`, `derived_1`.`n` FROM (SELECT `table_a`.`id`, `table_b`.`id` AS `id_1`, COUNT(`table_b`.`value`) AS `n` FROM `table_a` JOIN `table_b` ON `table_a`.`id` = `table_b`.`id` GROUP BY `table_a`.`id`, `table_b`.`id`) AS `derived_1` WHERE `derived_1`.`n` > 1"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_group_by_strategy() {
+        let ctx = mock_context().await;
+
+        let query = "select ta.id + 1 as k, count(ta.value) from table_a ta group by ta.id + 1;";
+        let plan = ctx.sql(query).await.unwrap().into_unoptimized_plan();
+        let tests: Vec<(&dyn SQLDialect, &str)> = vec![
+            (
+                &DefaultDialect {},
+                r#"SELECT `ta`.`id` + 1 AS `k`, COUNT(`ta`.`value`) FROM `table_a` AS `ta` GROUP BY `ta`.`id` + 1"#,
+            ),
+            (
+                &MySqlDialect {},
+                r#"SELECT `ta`.`id` + 1 AS `k`, COUNT(`ta`.`value`) FROM `table_a` AS `ta` GROUP BY `k`"#,
+            ),
+            (
+                &SnowflakeDialect {},
+                r#"SELECT "ta"."id" + 1 AS "k", COUNT("ta"."value") FROM "table_a" AS "ta" GROUP BY 1"#,
+            ),
+        ];
+
+        for (dialect, expected) in tests {
+            let actual = format!("{}", query_to_sql(&plan, dialect).unwrap());
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dialect_sql() {
+        let ctx = mock_context().await;
+
+        let tests: Vec<(&str, &dyn SQLDialect, &str)> = vec![
+            (
+                "select ta.id from table_a ta limit 5 offset 2;",
+                &PostgreSqlDialect {},
+                r#"SELECT "ta"."id" FROM "table_a" AS "ta" LIMIT 5 OFFSET 2"#,
+            ),
+            (
+                "select ta.id from table_a ta limit 5 offset 2;",
+                &MsSqlDialect {},
+                r#"SELECT [ta].[id] FROM [table_a] AS [ta] ORDER BY (SELECT NULL) OFFSET 2 ROWS FETCH FIRST 5 ROWS ONLY"#,
+            ),
+            (
+                "select ta.id from table_a ta order by ta.id desc limit 5;",
+                &MySqlDialect {},
+                r#"SELECT `ta`.`id` FROM `table_a` AS `ta` ORDER BY CASE WHEN `ta`.`id` IS NULL THEN 0 ELSE 1 END ASC, `ta`.`id` DESC LIMIT 5"#,
+            ),
+            (
+                "select cast(ta.id as bigint) from table_a ta;",
+                &PostgreSqlDialect {},
+                r#"SELECT CAST("ta"."id" AS BIGINT) FROM "table_a" AS "ta""#,
+            ),
+            (
+                "select cast(ta.id as bigint) from table_a ta;",
+                &MySqlDialect {},
+                r#"SELECT CAST(`ta`.`id` AS SIGNED) FROM `table_a` AS `ta`"#,
+            ),
+            (
+                "select upper(ta.value) from table_a ta;",
+                &DefaultDialect {},
+                r#"SELECT UPPER(`ta`.`value`) FROM `table_a` AS `ta`"#,
+            ),
+            (
+                "select ta.id from table_a ta where date '2024-01-31' > date '2023-12-01';",
+                &PostgreSqlDialect {},
+                r#"SELECT "ta"."id" FROM "table_a" AS "ta" WHERE (CAST('2024-01-31' AS DATE) > CAST('2023-12-01' AS DATE))"#,
+            ),
+            (
+                "select ta.id from table_a ta where date '2024-01-31' > date '2023-12-01';",
+                &OracleDialect {},
+                r#"SELECT "ta"."id" FROM "table_a" "ta" WHERE (TO_DATE('2024-01-31', 'YYYY-MM-DD') > TO_DATE('2023-12-01', 'YYYY-MM-DD'))"#,
+            ),
+            (
+                "select cast(ta.id as bigint), cast(ta.value as double), cast(ta.id as string) from table_a ta;",
+                &OracleDialect {},
+                r#"SELECT CAST("ta"."id" AS NUMBER(19)), CAST("ta"."value" AS BINARY_DOUBLE), CAST("ta"."id" AS VARCHAR2(4000)) FROM "table_a" "ta""#,
+            ),
+        ];
+
+        for (query, dialect, expected) in tests {
+            let plan = ctx.sql(query).await.unwrap().into_unoptimized_plan();
+            let actual = format!("{}", query_to_sql(&plan, dialect).unwrap());
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_large_literals() {
+        let schema = Arc::new(DFSchema::empty());
+        let tests: Vec<(ScalarValue, &str)> = vec![
+            (
+                ScalarValue::Decimal128(Some(-12345), 10, 2),
+                "CAST('-123.45' AS DECIMAL(10,2))",
+            ),
+            (
+                ScalarValue::Decimal128(Some(5), 38, 3),
+                "CAST('0.005' AS DECIMAL(38,3))",
+            ),
+            (
+                ScalarValue::Decimal128(Some(i128::MAX), 38, 0),
+                "CAST('170141183460469231731687303715884105727' AS DECIMAL(38,0))",
+            ),
+            (
+                ScalarValue::UInt64(Some(u64::MAX)),
+                "CAST('18446744073709551615' AS DECIMAL(20,0))",
+            ),
+        ];
+
+        for (value, expected) in tests {
+            let ast = expr_to_sql(&Expr::Literal(value), &schema, 0, &DefaultDialect {}).unwrap();
+            assert_eq!(format!("{ast}"), expected);
+        }
+    }
+
+    #[test]
+    fn test_null_safe_equality() {
+        let schema = Arc::new(DFSchema::empty());
+        let expr = Expr::BinaryExpr(BinaryExpr::new(
+            Box::new(Expr::Literal(ScalarValue::Int64(Some(1)))),
+            Operator::IsNotDistinctFrom,
+            Box::new(Expr::Literal(ScalarValue::Int64(None))),
+        ));
+        let tests: Vec<(&dyn SQLDialect, &str)> = vec![
+            (&DefaultDialect {}, "1 IS NOT DISTINCT FROM NULL"),
+            (&MySqlDialect {}, "1 <=> NULL"),
+        ];
+
+        for (dialect, expected) in tests {
+            let ast = expr_to_sql(&expr, &schema, 0, dialect).unwrap();
+            assert_eq!(format!("{ast}"), expected);
+        }
     }
 }
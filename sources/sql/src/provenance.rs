@@ -0,0 +1,92 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use datafusion::{
+    arrow::{datatypes::Schema, record_batch::RecordBatch},
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::StreamExt;
+
+pub const PROVENANCE_SOURCE: &str = "federation.source";
+pub const PROVENANCE_COMPUTE_CONTEXT: &str = "federation.compute_context";
+pub const PROVENANCE_QUERY_ID: &str = "federation.query_id";
+pub const PROVENANCE_PARTITION: &str = "federation.partition";
+pub const PROVENANCE_SEQUENCE: &str = "federation.sequence";
+
+// BatchProvenance traces a fetched batch back to the remote execution it
+// came from, read from the schema metadata of batches of providers with
+// batch provenance. The query id is logged with the SQL when it's executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchProvenance {
+    // The executor's name
+    pub source: String,
+    pub compute_context: Option<String>,
+    pub query_id: String,
+    pub partition: usize,
+    // The batch's position in the partition's stream, from 0
+    pub sequence: u64,
+}
+
+impl BatchProvenance {
+    // The provenance of the batch, None if it has none, e.g. once an operator
+    // rebuilt it with its own schema.
+    pub fn of(batch: &RecordBatch) -> Option<Self> {
+        let schema = batch.schema();
+        let metadata = schema.metadata();
+        Some(Self {
+            source: metadata.get(PROVENANCE_SOURCE)?.clone(),
+            compute_context: metadata.get(PROVENANCE_COMPUTE_CONTEXT).cloned(),
+            query_id: metadata.get(PROVENANCE_QUERY_ID)?.clone(),
+            partition: metadata.get(PROVENANCE_PARTITION)?.parse().ok()?,
+            sequence: metadata.get(PROVENANCE_SEQUENCE)?.parse().ok()?,
+        })
+    }
+}
+
+// An id unique to each remote execution of the process.
+pub(crate) fn next_query_id() -> String {
+    static COUNT: AtomicU64 = AtomicU64::new(0);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    format!("{now}-{}", COUNT.fetch_add(1, Ordering::Relaxed))
+}
+
+// Adds the provenance to the schema metadata of each batch. The stream's
+// schema is left as declared, only the batches carry the metadata.
+pub(crate) fn provenance_stream(
+    stream: SendableRecordBatchStream,
+    source: String,
+    compute_context: Option<String>,
+    query_id: String,
+    partition: usize,
+) -> SendableRecordBatchStream {
+    let schema = stream.schema();
+    let mut metadata = HashMap::from([
+        (PROVENANCE_SOURCE.to_string(), source),
+        (PROVENANCE_QUERY_ID.to_string(), query_id),
+        (PROVENANCE_PARTITION.to_string(), partition.to_string()),
+    ]);
+    if let Some(context) = compute_context {
+        metadata.insert(PROVENANCE_COMPUTE_CONTEXT.to_string(), context);
+    }
+    let mut sequence = 0u64;
+    let stream = stream.map(move |batch| {
+        let batch = batch?;
+        let mut batch_metadata = batch.schema().metadata().clone();
+        batch_metadata.extend(metadata.clone());
+        batch_metadata.insert(PROVENANCE_SEQUENCE.to_string(), sequence.to_string());
+        sequence += 1;
+        let batch_schema =
+            Schema::new_with_metadata(batch.schema().fields().clone(), batch_metadata);
+        Ok(batch.with_schema(Arc::new(batch_schema))?)
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, stream))
+}
@@ -0,0 +1,155 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use datafusion::{
+    arrow::datatypes::SchemaRef,
+    error::{DataFusionError, Result},
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+
+// QueryRecord is a federated query as executed: the local plan, the SQL it
+// was sent as, and the schema and row count of its result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryRecord {
+    pub compute_context: Option<String>,
+    pub plan: String,
+    pub sql: String,
+    // The fields of the result as (name, data type, nullable)
+    pub schema: Vec<(String, String, bool)>,
+    pub rows: usize,
+}
+
+impl QueryRecord {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "compute_context": self.compute_context,
+            "plan": self.plan,
+            "sql": self.sql,
+            "schema": self
+                .schema
+                .iter()
+                .map(|(name, data_type, nullable)| json!({
+                    "name": name,
+                    "data_type": data_type,
+                    "nullable": nullable,
+                }))
+                .collect::<Vec<_>>(),
+            "rows": self.rows,
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self> {
+        let invalid = || DataFusionError::Execution(format!("invalid query record: {value}"));
+        let str_field = |v: &Value, name: &str| {
+            v.get(name)
+                .and_then(Value::as_str)
+                .map(String::from)
+                .ok_or_else(invalid)
+        };
+        let schema = value
+            .get("schema")
+            .and_then(Value::as_array)
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|field| {
+                Ok((
+                    str_field(field, "name")?,
+                    str_field(field, "data_type")?,
+                    field
+                        .get("nullable")
+                        .and_then(Value::as_bool)
+                        .ok_or_else(invalid)?,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            compute_context: value
+                .get("compute_context")
+                .and_then(Value::as_str)
+                .map(String::from),
+            plan: str_field(value, "plan")?,
+            sql: str_field(value, "sql")?,
+            schema,
+            rows: value
+                .get("rows")
+                .and_then(Value::as_u64)
+                .ok_or_else(invalid)? as usize,
+        })
+    }
+}
+
+// QueryRecorder appends a QueryRecord per executed federated query to a
+// JSON lines file, building a corpus of real query shapes. Replay it with
+// read_query_records, e.g. to check that the recorded plans still generate
+// the recorded SQL.
+#[derive(Debug)]
+pub struct QueryRecorder {
+    file: Mutex<File>,
+}
+
+impl QueryRecorder {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, record: &QueryRecord) {
+        let mut file = self.file.lock().unwrap();
+        // Recording must not fail the query
+        let _ = writeln!(file, "{}", record.to_json());
+    }
+}
+
+pub fn read_query_records(path: impl AsRef<Path>) -> Result<Vec<QueryRecord>> {
+    let file = File::open(path)?;
+    BufReader::new(file)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| {
+            let value: Value =
+                serde_json::from_str(&line?).map_err(|e| DataFusionError::External(Box::new(e)))?;
+            QueryRecord::from_json(&value)
+        })
+        .collect()
+}
+
+// Records the query once the stream is drained, with the rows it returned.
+pub(crate) fn record_stream(
+    stream: SendableRecordBatchStream,
+    recorder: Arc<QueryRecorder>,
+    mut record: QueryRecord,
+) -> SendableRecordBatchStream {
+    let schema: SchemaRef = stream.schema();
+    record.schema = schema
+        .fields()
+        .iter()
+        .map(|f| (f.name().clone(), f.data_type().to_string(), f.is_nullable()))
+        .collect();
+    let rows = Arc::new(AtomicUsize::new(0));
+    let counted = {
+        let rows = rows.clone();
+        stream.map(move |batch| {
+            if let Ok(batch) = &batch {
+                rows.fetch_add(batch.num_rows(), Ordering::Relaxed);
+            }
+            batch
+        })
+    };
+    let done = futures::stream::poll_fn(move |_| {
+        record.rows = rows.load(Ordering::Relaxed);
+        recorder.record(&record);
+        std::task::Poll::Ready(None)
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, counted.chain(done)))
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use datafusion::{
+    common::tree_node::{Transformed, TreeNode},
+    error::{DataFusionError, Result},
+    logical_expr::{
+        utils::{conjunction, split_conjunction},
+        Extension, Filter, LogicalPlan, TableScan,
+    },
+};
+use datafusion_federation::{FederatedPlanNode, FederationPlanner};
+
+// Returns true if the error looks like the source rejecting the SQL, as
+// opposed to e.g. a connection failure.
+pub(crate) fn is_pushdown_error(e: &DataFusionError) -> bool {
+    let msg = e.to_string().to_ascii_lowercase();
+    [
+        "syntax",
+        "not supported",
+        "unsupported",
+        "no such function",
+        "unknown function",
+    ]
+    .iter()
+    .any(|pattern| msg.contains(pattern))
+}
+
+// Returns the plans to retry a rejected plan with, each with less pushed
+// down, and a description of what is no longer pushed down. The conjuncts of
+// the topmost filter are kept local one at a time, then only the table scans
+// are federated. Federated parts are planned by the planner.
+pub(crate) fn reduced_plans(
+    plan: &LogicalPlan,
+    planner: &Arc<dyn FederationPlanner>,
+) -> Result<Vec<(String, LogicalPlan)>> {
+    let federate = |plan: LogicalPlan| {
+        LogicalPlan::Extension(Extension {
+            node: Arc::new(FederatedPlanNode::new(plan, planner.clone())),
+        })
+    };
+
+    let mut plans = vec![];
+    if let Some(filter) = topmost_filter(plan) {
+        let conjuncts = split_conjunction(&filter.predicate);
+        for (i, local) in conjuncts.iter().enumerate() {
+            let remote = conjuncts
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, c)| (*c).clone());
+            let input = match conjunction(remote) {
+                Some(predicate) => {
+                    LogicalPlan::Filter(Filter::try_new(predicate, filter.input.clone())?)
+                }
+                None => filter.input.as_ref().clone(),
+            };
+            let split = LogicalPlan::Filter(Filter::try_new(
+                (*local).clone(),
+                Arc::new(federate(input)),
+            )?);
+            plans.push((
+                format!("predicate {local}"),
+                replace_filter(plan, filter, &split)?,
+            ));
+        }
+    }
+
+    let scans = plan.clone().transform_up(&|plan| match plan {
+        LogicalPlan::TableScan(scan) => {
+            let scan = TableScan {
+                filters: vec![],
+                fetch: None,
+                ..scan
+            };
+            Ok(Transformed::Yes(federate(LogicalPlan::TableScan(scan))))
+        }
+        _ => Ok(Transformed::No(plan)),
+    })?;
+    plans.push(("everything but table scans".to_string(), scans));
+    Ok(plans)
+}
+
+fn topmost_filter(plan: &LogicalPlan) -> Option<&Filter> {
+    match plan {
+        LogicalPlan::Filter(filter) => Some(filter),
+        _ => plan.inputs().into_iter().find_map(topmost_filter),
+    }
+}
+
+// Replaces the filter, found by topmost_filter, with the plan.
+fn replace_filter(plan: &LogicalPlan, filter: &Filter, with: &LogicalPlan) -> Result<LogicalPlan> {
+    if let LogicalPlan::Filter(f) = plan {
+        if std::ptr::eq(f, filter) {
+            return Ok(with.clone());
+        }
+    }
+    let inputs = plan
+        .inputs()
+        .into_iter()
+        .map(|input| replace_filter(input, filter, with))
+        .collect::<Result<Vec<_>>>()?;
+    plan.with_new_inputs(&inputs)
+}
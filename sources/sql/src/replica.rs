@@ -0,0 +1,355 @@
+use core::fmt;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{array::Array, util::display::array_value_to_string},
+    error::{DataFusionError, Result},
+};
+use futures::TryStreamExt;
+use log::{debug, warn};
+use tokio::runtime::Handle;
+
+use crate::executor::SQLExecutor;
+
+// Freshness is the maximum replication lag a query accepts. It is read from
+// the session config extensions:
+// `SessionConfig::new().with_extension(Arc::new(Freshness(Duration::from_secs(5))))`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Freshness(pub Duration);
+
+// ReplicationLag reports how far a replica is behind its primary.
+#[async_trait]
+pub trait ReplicationLag: Send + Sync {
+    async fn lag(&self) -> Result<Duration>;
+}
+
+// LagQuery reads the lag in seconds by running `query` on the replica, e.g.
+// `SELECT EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp())`
+pub struct LagQuery {
+    executor: Arc<dyn SQLExecutor>,
+    query: String,
+}
+
+impl LagQuery {
+    pub fn new(executor: Arc<dyn SQLExecutor>, query: String) -> Self {
+        Self { executor, query }
+    }
+}
+
+#[async_trait]
+impl ReplicationLag for LagQuery {
+    async fn lag(&self) -> Result<Duration> {
+        let batches = self
+            .executor
+            .execute(&self.query)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        let batch = batches
+            .iter()
+            .find(|b| b.num_rows() > 0 && b.num_columns() > 0)
+            .ok_or_else(|| {
+                DataFusionError::Execution(format!("no lag returned: {}", self.query))
+            })?;
+        // A replica that never replayed anything reports NULL
+        if batch.column(0).is_null(0) {
+            return Ok(Duration::MAX);
+        }
+        let value = array_value_to_string(batch.column(0), 0)?;
+        let seconds = value.trim().parse::<f64>().map_err(|_| {
+            DataFusionError::Execution(format!("invalid lag {value}: {}", self.query))
+        })?;
+        Duration::try_from_secs_f64(seconds.max(0.0)).map_err(|e| {
+            DataFusionError::Execution(format!("invalid lag {value}: {e}: {}", self.query))
+        })
+    }
+}
+
+pub struct Replica {
+    pub executor: Arc<dyn SQLExecutor>,
+    pub lag: Arc<dyn ReplicationLag>,
+}
+
+// ReplicaFallback decides what happens when no replica is fresh enough.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplicaFallback {
+    Primary,
+    Error,
+}
+
+// ReplicaSet routes queries to the least lagging replica within the
+// query's freshness requirement, `max_lag` applies to queries without
+// a Freshness hint.
+pub struct ReplicaSet {
+    replicas: Vec<Replica>,
+    max_lag: Duration,
+    fallback: ReplicaFallback,
+    sampling: Option<ConsistencySampling>,
+}
+
+impl ReplicaSet {
+    pub fn new(replicas: Vec<Replica>, max_lag: Duration, fallback: ReplicaFallback) -> Self {
+        Self {
+            replicas,
+            max_lag,
+            fallback,
+            sampling: None,
+        }
+    }
+
+    // Runs every `every`th query routed to a replica again on another replica,
+    // or the primary, and reports results that differ to the observer. The
+    // comparison runs in the background and doesn't delay the query.
+    pub fn with_consistency_sampling(
+        mut self,
+        every: usize,
+        observer: Arc<dyn ConsistencyObserver>,
+    ) -> Self {
+        self.sampling = Some(ConsistencySampling {
+            every: every.max(1),
+            observer,
+            queries: AtomicUsize::new(0),
+        });
+        self
+    }
+
+    // Compares the results of the query on the routed replica and another
+    // executor, if the query is sampled. The comparison is spawned on the
+    // runtime of the caller, executors may need it.
+    pub(crate) fn sample_consistency(
+        &self,
+        sql: &str,
+        routed: &Arc<dyn SQLExecutor>,
+        primary: &Arc<dyn SQLExecutor>,
+    ) {
+        let Some(sampling) = &self.sampling else {
+            return;
+        };
+        if sampling.queries.fetch_add(1, Ordering::Relaxed) % sampling.every != 0 {
+            return;
+        }
+        let other = self
+            .replicas
+            .iter()
+            .map(|r| &r.executor)
+            .find(|e| !Arc::ptr_eq(e, routed))
+            .unwrap_or(primary)
+            .clone();
+        let routed = routed.clone();
+        let observer = sampling.observer.clone();
+        let sql = sql.to_string();
+        let Ok(handle) = Handle::try_current() else {
+            warn!("federation consistency_sample error=\"no runtime\" sql=\"{sql}\"");
+            return;
+        };
+        handle.spawn(async move {
+            let results = async {
+                Ok::<_, DataFusionError>((
+                    result_hash(routed.as_ref(), &sql).await?,
+                    result_hash(other.as_ref(), &sql).await?,
+                ))
+            }
+            .await;
+            match results {
+                Ok((left, right)) if left != right => {
+                    observer.diverged(&ReplicaDivergence {
+                        sql,
+                        left: format!("{routed}"),
+                        right: format!("{other}"),
+                        left_hash: left.0,
+                        right_hash: right.0,
+                        left_rows: left.1,
+                        right_rows: right.1,
+                    });
+                }
+                Ok(_) => {
+                    debug!("federation decision=consistency_sample result=match sql=\"{sql}\"")
+                }
+                Err(e) => warn!("federation consistency_sample error=\"{e}\" sql=\"{sql}\""),
+            }
+        });
+    }
+
+    // Returns the executor to run the query on, `None` means the primary.
+    pub async fn route(
+        &self,
+        freshness: Option<&Freshness>,
+    ) -> Result<Option<Arc<dyn SQLExecutor>>> {
+        let max_lag = freshness.map_or(self.max_lag, |f| f.0);
+        let mut lags = Vec::with_capacity(self.replicas.len());
+        let mut best: Option<(Duration, &Replica)> = None;
+        for replica in &self.replicas {
+            // An unreachable replica is skipped like a stale one
+            let lag = match replica.lag.lag().await {
+                Ok(lag) => lag,
+                Err(e) => {
+                    lags.push(format!("{}: {e}", replica.executor));
+                    continue;
+                }
+            };
+            lags.push(format!("{}: {lag:?}", replica.executor));
+            if lag <= max_lag && best.map_or(true, |(b, _)| lag < b) {
+                best = Some((lag, replica));
+            }
+        }
+
+        if let Some((lag, replica)) = best {
+            debug!(
+                "federation decision=route_replica replica=\"{}\" lag_ms={}",
+                replica.executor,
+                lag.as_millis()
+            );
+            return Ok(Some(replica.executor.clone()));
+        }
+        match self.fallback {
+            ReplicaFallback::Primary => {
+                debug!("federation decision=route_primary reason=stale_replicas");
+                Ok(None)
+            }
+            ReplicaFallback::Error => Err(DataFusionError::Execution(format!(
+                "no replica within freshness {max_lag:?}: {}",
+                lags.join(", ")
+            ))),
+        }
+    }
+}
+
+// ReplicaDivergence describes a query whose results differed between two
+// executors of a replica set.
+#[derive(Debug, Clone)]
+pub struct ReplicaDivergence {
+    pub sql: String,
+    pub left: String,
+    pub right: String,
+    pub left_hash: u64,
+    pub right_hash: u64,
+    pub left_rows: usize,
+    pub right_rows: usize,
+}
+
+pub trait ConsistencyObserver: Send + Sync {
+    fn diverged(&self, divergence: &ReplicaDivergence);
+}
+
+impl<F> ConsistencyObserver for F
+where
+    F: Fn(&ReplicaDivergence) + Send + Sync,
+{
+    fn diverged(&self, divergence: &ReplicaDivergence) {
+        self(divergence)
+    }
+}
+
+struct ConsistencySampling {
+    every: usize,
+    observer: Arc<dyn ConsistencyObserver>,
+    queries: AtomicUsize,
+}
+
+// Hashes the rows of the result independent of their order, returns the
+// hash and the row count.
+async fn result_hash(executor: &dyn SQLExecutor, sql: &str) -> Result<(u64, usize)> {
+    let batches = executor.execute(sql).await?.try_collect::<Vec<_>>().await?;
+    let mut hash = 0u64;
+    let mut rows = 0;
+    for batch in &batches {
+        for row in 0..batch.num_rows() {
+            let mut hasher = DefaultHasher::new();
+            for column in batch.columns() {
+                column.is_null(row).hash(&mut hasher);
+                array_value_to_string(column, row)?.hash(&mut hasher);
+            }
+            hash = hash.wrapping_add(hasher.finish());
+            rows += 1;
+        }
+    }
+    Ok((hash, rows))
+}
+
+impl fmt::Debug for ReplicaSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ReplicaSet({} replicas, {:?}, {:?})",
+            self.replicas.len(),
+            self.max_lag,
+            self.fallback
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::{
+        array::Int64Array,
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::executor::MemorySQLExecutor;
+
+    struct FixedLag(Duration);
+
+    #[async_trait]
+    impl ReplicationLag for FixedLag {
+        async fn lag(&self) -> Result<Duration> {
+            Ok(self.0)
+        }
+    }
+
+    fn executor(name: &str, ids: Vec<i64>) -> Arc<dyn SQLExecutor> {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch = RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(ids))]).unwrap();
+        Arc::new(
+            MemorySQLExecutor::new(name)
+                .with_batch("items", batch)
+                .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_sample_consistency() {
+        let primary = executor("primary", vec![1, 2, 3]);
+        let replica = executor("replica", vec![1, 2]);
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let observer = move |divergence: &ReplicaDivergence| {
+            let _ = sender.send(divergence.clone());
+        };
+        let replicas = ReplicaSet::new(
+            vec![Replica {
+                executor: replica.clone(),
+                lag: Arc::new(FixedLag(Duration::ZERO)),
+            }],
+            Duration::from_secs(1),
+            ReplicaFallback::Primary,
+        )
+        .with_consistency_sampling(1, Arc::new(observer));
+
+        replicas.sample_consistency("SELECT id FROM items", &replica, &primary);
+        let divergence = receiver.recv().await.unwrap();
+        assert_eq!(divergence.sql, "SELECT id FROM items");
+        assert_eq!((divergence.left_rows, divergence.right_rows), (2, 3));
+    }
+
+    #[tokio::test]
+    async fn test_lag_query() {
+        let executor = executor("replica", vec![1]);
+        let lag = LagQuery::new(executor.clone(), "SELECT 1.5".to_string());
+        assert_eq!(lag.lag().await.unwrap(), Duration::from_millis(1500));
+        let lag = LagQuery::new(executor.clone(), "SELECT CAST(NULL AS DOUBLE)".to_string());
+        assert_eq!(lag.lag().await.unwrap(), Duration::MAX);
+        let lag = LagQuery::new(executor, "SELECT CAST('inf' AS DOUBLE)".to_string());
+        assert!(lag.lag().await.is_err());
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use datafusion::{
+    error::Result,
+    physical_plan::{metrics::Count, stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::StreamExt;
+use log::debug;
+
+use crate::{executor::SQLExecutor, RemoteWarnings};
+
+// Executes the query of one partition, retrying it up to `retries` times if
+// it fails before returning its first batch. Once a batch is returned the
+// partition is not retried, as its rows would be fetched twice.
+pub(crate) async fn execute_partition(
+    executor: &Arc<dyn SQLExecutor>,
+    query: &str,
+    warnings: RemoteWarnings,
+    retries: usize,
+    attempts: Count,
+) -> Result<SendableRecordBatchStream> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        attempts.add(1);
+        let error = match executor
+            .execute_with_warnings(query, warnings.clone())
+            .await
+        {
+            Ok(mut stream) => match stream.next().await {
+                Some(Err(e)) => e,
+                first => {
+                    let schema = stream.schema();
+                    let stream = futures::stream::iter(first).chain(stream);
+                    return Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)));
+                }
+            },
+            Err(e) => e,
+        };
+        if attempt > retries {
+            return Err(error);
+        }
+        debug!(
+            "federation rule=federate_sql decision=retry context={:?} attempt={attempt} reason=\"{error}\"",
+            executor.compute_context()
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+use datafusion::{
+    arrow::{array::AsArray, compute::cast, datatypes::DataType, datatypes::Int64Type},
+    error::{DataFusionError, Result},
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::{future, StreamExt, TryStreamExt};
+use log::warn;
+
+use crate::executor::SQLExecutor;
+
+// RowCountCheck is what happens when the rows received from a source differ
+// from the rows it counts for the same query, e.g. when a driver silently
+// truncates a result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowCountCheck {
+    Warn,
+    Error,
+}
+
+// Counts the rows of the stream and, once it completes, compares them with
+// `SELECT count(*)` over the same query.
+pub(crate) fn count_stream(
+    stream: SendableRecordBatchStream,
+    executor: Arc<dyn SQLExecutor>,
+    sql: String,
+    check: RowCountCheck,
+) -> SendableRecordBatchStream {
+    let schema = stream.schema();
+    let rows = Arc::new(AtomicUsize::new(0));
+    let failed = Arc::new(AtomicBool::new(false));
+    let counted = {
+        let rows = rows.clone();
+        let failed = failed.clone();
+        stream.inspect(move |batch| match batch {
+            Ok(batch) => {
+                rows.fetch_add(batch.num_rows(), Ordering::Relaxed);
+            }
+            Err(_) => failed.store(true, Ordering::Relaxed),
+        })
+    };
+    let verification = futures::stream::once(async move {
+        // A failed stream has no meaningful row count
+        if failed.load(Ordering::Relaxed) {
+            return None;
+        }
+        let received = rows.load(Ordering::Relaxed);
+        let remote = match remote_count(executor.as_ref(), &sql).await {
+            Ok(remote) => remote,
+            Err(e) => {
+                warn!(
+                    "federation row_count context={:?} error=\"{e}\"",
+                    executor.compute_context()
+                );
+                return None;
+            }
+        };
+        if remote == received {
+            return None;
+        }
+        let msg = format!("received {received} rows, the source counts {remote}\nsql: {sql}");
+        match check {
+            RowCountCheck::Warn => {
+                warn!(
+                    "federation row_count context={:?} mismatch=\"{msg}\"",
+                    executor.compute_context()
+                );
+                None
+            }
+            RowCountCheck::Error => Some(Err(DataFusionError::Execution(msg))),
+        }
+    })
+    .filter_map(future::ready);
+    Box::pin(RecordBatchStreamAdapter::new(
+        schema,
+        counted.chain(verification),
+    ))
+}
+
+async fn remote_count(executor: &dyn SQLExecutor, sql: &str) -> Result<usize> {
+    let query = format!("SELECT count(*) FROM ({sql}) AS row_count");
+    let batches: Vec<_> = executor.execute(&query).await?.try_collect().await?;
+    let Some(batch) = batches.iter().find(|b| b.num_rows() > 0) else {
+        return Err(DataFusionError::Execution(format!(
+            "no row count returned\nsql: {query}"
+        )));
+    };
+    let count = cast(batch.column(0), &DataType::Int64)?;
+    Ok(count.as_primitive::<Int64Type>().value(0) as usize)
+}
@@ -0,0 +1,165 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use datafusion::error::{DataFusionError, Result};
+use tokio::sync::oneshot;
+
+// Tenant identifies the tenant a query is executed for. It is read from
+// the session config extensions:
+// `SessionConfig::new().with_extension(Arc::new(Tenant("acme".to_string())))`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Tenant(pub String);
+
+const DEFAULT_TENANT: &str = "default";
+
+// FairScheduler hands out a fixed number of remote execution slots using
+// weighted fair queueing, so one tenant's burst of federated queries
+// can't monopolize the slots (and the connection pools behind them).
+// A scheduler can be shared by multiple providers.
+#[derive(Debug)]
+pub struct FairScheduler {
+    state: Mutex<SchedulerState>,
+}
+
+#[derive(Debug)]
+struct SchedulerState {
+    available: usize,
+    weights: HashMap<String, u32>,
+    // Virtual finish time per tenant, the waiting tenant
+    // with the lowest virtual time is served first.
+    virtual_time: HashMap<String, f64>,
+    clock: f64,
+    waiters: HashMap<String, VecDeque<oneshot::Sender<()>>>,
+}
+
+impl FairScheduler {
+    pub fn new(slots: usize) -> Self {
+        Self {
+            state: Mutex::new(SchedulerState {
+                available: slots,
+                weights: HashMap::new(),
+                virtual_time: HashMap::new(),
+                clock: 0.0,
+                waiters: HashMap::new(),
+            }),
+        }
+    }
+
+    // Sets the relative share of slots for a tenant, tenants default to 1.
+    pub fn weight(&self, tenant: &str, weight: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.weights.insert(tenant.to_string(), weight.max(1));
+    }
+
+    // Waits for a slot, the slot is released once the permit is dropped.
+    pub async fn acquire(self: &Arc<Self>, tenant: Option<&Tenant>) -> Result<SchedulerPermit> {
+        let tenant = tenant.map_or(DEFAULT_TENANT, |t| t.0.as_str());
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+            let idle = state.waiters.values().all(|q| q.is_empty());
+            if state.available > 0 && idle {
+                state.available -= 1;
+                state.charge(tenant);
+                return Ok(SchedulerPermit {
+                    scheduler: self.clone(),
+                });
+            }
+            let (sender, receiver) = oneshot::channel();
+            state
+                .waiters
+                .entry(tenant.to_string())
+                .or_default()
+                .push_back(sender);
+            receiver
+        };
+
+        receiver.await.map_err(|_| {
+            DataFusionError::Execution("scheduler dropped waiting query".to_string())
+        })?;
+        Ok(SchedulerPermit {
+            scheduler: self.clone(),
+        })
+    }
+
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        while let Some(tenant) = state.next_tenant() {
+            let sender = state
+                .waiters
+                .get_mut(&tenant)
+                .and_then(|q| q.pop_front())
+                .unwrap();
+            // The slot is handed over directly, skip waiters that went away.
+            if sender.send(()).is_ok() {
+                state.charge(&tenant);
+                return;
+            }
+        }
+        state.available += 1;
+    }
+}
+
+impl SchedulerState {
+    fn next_tenant(&self) -> Option<String> {
+        self.waiters
+            .iter()
+            .filter(|(_, q)| !q.is_empty())
+            .map(|(t, _)| (t, self.virtual_time.get(t).copied().unwrap_or(0.0)))
+            .min_by(|(ta, a), (tb, b)| a.total_cmp(b).then_with(|| ta.cmp(tb)))
+            .map(|(t, _)| t.clone())
+    }
+
+    fn charge(&mut self, tenant: &str) {
+        let weight = self.weights.get(tenant).copied().unwrap_or(1) as f64;
+        let finish = self.virtual_time.get(tenant).copied().unwrap_or(0.0);
+        // Tenants becoming active don't get credit for their idle time.
+        let start = finish.max(self.clock);
+        self.clock = start;
+        self.virtual_time
+            .insert(tenant.to_string(), start + 1.0 / weight);
+    }
+}
+
+pub struct SchedulerPermit {
+    scheduler: Arc<FairScheduler>,
+}
+
+impl Drop for SchedulerPermit {
+    fn drop(&mut self) {
+        self.scheduler.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_weighted_dispatch() {
+        let scheduler = Arc::new(FairScheduler::new(1));
+        scheduler.weight("a", 2);
+        let a = Tenant("a".to_string());
+        let b = Tenant("b".to_string());
+
+        let permit = scheduler.acquire(Some(&a)).await.unwrap();
+        let order = Arc::new(Mutex::new(vec![]));
+        let mut handles = vec![];
+        for tenant in [&b, &b, &a, &a, &a, &b] {
+            let (scheduler, order, tenant) = (scheduler.clone(), order.clone(), tenant.clone());
+            handles.push(tokio::spawn(async move {
+                let _permit = scheduler.acquire(Some(&tenant)).await.unwrap();
+                order.lock().unwrap().push(tenant.0);
+            }));
+            tokio::task::yield_now().await;
+        }
+        drop(permit);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let order = order.lock().unwrap().join("");
+        assert_eq!(order, "baabab");
+    }
+}
@@ -1,17 +1,39 @@
 use async_trait::async_trait;
-use datafusion::logical_expr::{TableSource, TableType};
+use datafusion::logical_expr::{LogicalPlanBuilder, TableSource, TableType};
 use datafusion::{
-    arrow::datatypes::SchemaRef, catalog::schema::SchemaProvider, datasource::TableProvider,
-    error::Result,
+    arrow::{
+        array::AsArray,
+        compute::cast,
+        datatypes::{DataType, Field, Schema, SchemaRef},
+        record_batch::RecordBatch,
+    },
+    catalog::schema::SchemaProvider,
+    datasource::{provider_as_source, TableProvider},
+    error::{DataFusionError, Result},
+    scalar::ScalarValue,
+    sql::sqlparser::ast,
+};
+use datafusion::{
+    execution::context::SessionState,
+    physical_plan::{insert::FileSinkExec, ExecutionPlan},
+};
+use futures::{future::join_all, TryStreamExt};
+use log::warn;
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+    sync::Arc,
 };
-use futures::future::join_all;
-use std::{any::Any, sync::Arc};
 
 use datafusion_federation::{
-    FederatedTableProviderAdaptor, FederatedTableSource, FederationProvider,
+    FederatedTableProviderAdaptor, FederatedTableSource, FederationProvider, TablePreview,
 };
 
-use crate::SQLFederationProvider;
+use crate::{
+    dialect::AsOf,
+    write::{SQLInsertReturningExec, SQLInsertSink},
+    SQLFederationProvider,
+};
 
 pub struct SQLSchemaProvider {
     // provider: Arc<SQLFederationProvider>,
@@ -31,6 +53,30 @@ impl SQLSchemaProvider {
             tables: sources,
         })
     }
+
+    // Registers the tables with declared schemas instead of inferring them,
+    // e.g. to attach field metadata such as Arrow extension types, which is
+    // kept on the fetched batches.
+    pub fn new_with_schemas(
+        provider: Arc<SQLFederationProvider>,
+        tables: Vec<(String, SchemaRef)>,
+    ) -> Result<Self> {
+        let tables = tables
+            .into_iter()
+            .map(|(table, schema)| {
+                Ok(Arc::new(SQLTableSource::new_with_schema(
+                    provider.clone(),
+                    table,
+                    schema,
+                )?))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { tables })
+    }
+
+    pub(crate) fn new_with_sources(tables: Vec<Arc<SQLTableSource>>) -> Self {
+        Self { tables }
+    }
 }
 
 #[async_trait]
@@ -62,25 +108,104 @@ impl SchemaProvider for SQLSchemaProvider {
     }
 }
 
-struct SQLTableSource {
+// RemoteTableKind is the kind of relation a remote table name refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoteTableKind {
+    Table,
+    View,
+    MaterializedView,
+}
+
+impl RemoteTableKind {
+    // Parses the `table_type` of information_schema.tables
+    pub fn from_table_type(table_type: &str) -> Self {
+        match table_type.to_ascii_uppercase().as_str() {
+            "VIEW" => RemoteTableKind::View,
+            "MATERIALIZED VIEW" => RemoteTableKind::MaterializedView,
+            _ => RemoteTableKind::Table,
+        }
+    }
+}
+
+pub(crate) struct SQLTableSource {
     provider: Arc<SQLFederationProvider>,
     table_name: String,
+    // The qualified name of the table in the source
+    remote_name: Vec<String>,
+    // The name of each column in the source, in schema order
+    remote_columns: Vec<String>,
+    // Columns of unknown type, exposed as Utf8 and cast to text in the source
+    text_columns: HashSet<String>,
+    kind: RemoteTableKind,
+    // Source names of identity or auto-increment columns, never written by inserts
+    generated_columns: HashSet<String>,
+    // Source names of columns with a default expression
+    default_columns: HashSet<String>,
+    // The view definition, inlined into the generated SQL when set
+    definition: Option<ast::Query>,
+    // The point in time the table is read at, None for the current state
+    as_of: Option<AsOf>,
+    // The arguments of a remote table function, read as `fn(args)`
+    function_args: Option<Vec<ScalarValue>>,
+    // The values of the partition columns, a row per partition
+    partitions: Option<RecordBatch>,
     schema: SchemaRef,
 }
 
 impl SQLTableSource {
     // creates a SQLTableSource and infers the table schema
     pub async fn new(provider: Arc<SQLFederationProvider>, table_name: String) -> Result<Self> {
-        // Simple schema inference
-        let query = format!("SELECT * FROM {table_name} LIMIT 1");
-        let schema = provider
-            .clone()
-            .executor
-            .execute(query.as_str())
-            .await?
-            .schema();
+        let cache_key = format!(
+            "{:?} {} {}",
+            provider.executor.compute_context(),
+            provider.search_path.join("."),
+            table_name
+        );
+        if let Some((remote_name, schema)) = provider
+            .schema_cache
+            .as_ref()
+            .and_then(|cache| cache.get(&cache_key))
+        {
+            let mut source = Self::new_with_schema(provider.clone(), table_name, schema)?;
+            source.remote_name = remote_name;
+            return Ok(source);
+        }
 
-        Self::new_with_schema(provider, table_name, schema)
+        // Unqualified names are resolved against the provider's search path in order,
+        // instead of depending on the connection's default schema
+        let mut candidates = vec![];
+        if table_name.contains('.') || provider.search_path.is_empty() {
+            candidates.push(table_name.split('.').map(String::from).collect::<Vec<_>>());
+        } else {
+            for schema in &provider.search_path {
+                candidates.push(vec![schema.clone(), table_name.clone()]);
+            }
+        }
+
+        let mut last_err = None;
+        for remote_name in candidates {
+            // Simple schema inference
+            let query = format!("SELECT * FROM {} LIMIT 1", remote_name.join("."));
+            match provider.executor.execute(query.as_str()).await {
+                Ok(stream) => {
+                    let schema = override_types(&provider, &remote_name, stream.schema()).await;
+                    if let Some(cache) = &provider.schema_cache {
+                        if let Err(e) = cache.put(&cache_key, &remote_name, &schema) {
+                            warn!(
+                                "federation table={table_name} reason=\"schema not cached: {e}\""
+                            );
+                        }
+                    }
+                    let mut source = Self::new_with_schema(provider.clone(), table_name, schema)?;
+                    source.remote_name = remote_name;
+                    return Ok(source);
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            DataFusionError::Plan(format!("table {table_name} not found in search path"))
+        }))
     }
 
     pub fn new_with_schema(
@@ -88,18 +213,306 @@ impl SQLTableSource {
         table_name: String,
         schema: SchemaRef,
     ) -> Result<Self> {
+        let remote_columns = schema
+            .fields()
+            .iter()
+            .map(|f| f.name().clone())
+            .collect::<Vec<_>>();
+
+        // Remote NULL typed columns are reported as Null
+        let mut text_columns = HashSet::new();
+        let schema = if provider.unknown_type_fallback
+            && schema
+                .fields()
+                .iter()
+                .any(|f| f.data_type() == &DataType::Null)
+        {
+            let fields = schema
+                .fields()
+                .iter()
+                .map(|f| {
+                    if f.data_type() != &DataType::Null {
+                        return Field::clone(f);
+                    }
+                    warn!(
+                        "federation table={table_name} column={} reason=\"unknown type, using Utf8\"",
+                        f.name()
+                    );
+                    text_columns.insert(f.name().clone());
+                    Field::clone(f).with_data_type(DataType::Utf8)
+                })
+                .collect::<Vec<_>>();
+            Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()))
+        } else {
+            schema
+        };
+        // Expose lowercase column names, which unquoted identifiers are normalized to
+        let schema = if provider.case_insensitive_columns {
+            text_columns = text_columns
+                .into_iter()
+                .map(|name| name.to_lowercase())
+                .collect();
+            let fields = schema
+                .fields()
+                .iter()
+                .map(|f| Field::clone(f).with_name(f.name().to_lowercase()))
+                .collect::<Vec<_>>();
+            let lowercase = Schema::new_with_metadata(fields, schema.metadata().clone());
+            for (i, field) in lowercase.fields().iter().enumerate() {
+                if lowercase.fields()[..i]
+                    .iter()
+                    .any(|f| f.name() == field.name())
+                {
+                    return Err(DataFusionError::Plan(format!(
+                        "table {table_name} has columns differing only in case: {}",
+                        field.name()
+                    )));
+                }
+            }
+            Arc::new(lowercase)
+        } else {
+            schema
+        };
+
         Ok(Self {
             provider,
+            remote_name: table_name.split('.').map(String::from).collect(),
+            remote_columns,
+            text_columns,
+            kind: RemoteTableKind::Table,
+            generated_columns: HashSet::new(),
+            default_columns: HashSet::new(),
+            definition: None,
+            as_of: None,
+            function_args: None,
+            partitions: None,
             table_name,
             schema,
         })
     }
+
+    pub(crate) fn with_kind(mut self, kind: RemoteTableKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    // Sets the source columns that are generated or have a default.
+    pub(crate) fn with_column_metadata(
+        mut self,
+        generated_columns: HashSet<String>,
+        default_columns: HashSet<String>,
+    ) -> Self {
+        self.generated_columns = generated_columns;
+        self.default_columns = default_columns;
+        self
+    }
+
+    pub(crate) fn with_definition(mut self, definition: ast::Query) -> Self {
+        self.definition = Some(definition);
+        self
+    }
+
+    pub(crate) fn definition(&self) -> Option<&ast::Query> {
+        self.definition.as_ref()
+    }
+
+    pub(crate) fn with_as_of(mut self, as_of: AsOf) -> Self {
+        self.as_of = Some(as_of);
+        self
+    }
+
+    pub(crate) fn as_of(&self) -> Option<&AsOf> {
+        self.as_of.as_ref()
+    }
+
+    pub(crate) fn with_function_args(mut self, args: Vec<ScalarValue>) -> Self {
+        self.function_args = Some(args);
+        self
+    }
+
+    pub(crate) fn function_args(&self) -> Option<&[ScalarValue]> {
+        self.function_args.as_deref()
+    }
+
+    // Sets the partitions of the table, filters on the partition columns
+    // are mapped to the partitions they read.
+    pub(crate) fn with_partitions(mut self, partitions: RecordBatch) -> Self {
+        self.partitions = Some(partitions);
+        self
+    }
+
+    pub(crate) fn partitions(&self) -> Option<&RecordBatch> {
+        self.partitions.as_ref()
+    }
+
+    // Exposes the given columns, which have a type unknown to DataFusion, as Utf8.
+    pub(crate) fn with_text_columns(mut self, columns: HashSet<String>) -> Self {
+        self.text_columns.extend(columns);
+        self
+    }
+
+    // Returns the columns that are renamed or cast to text between the source and
+    // the exposed schema, as (source name, exposed name, cast to text).
+    pub(crate) fn mapped_columns(&self) -> Vec<(&str, &str, bool)> {
+        self.remote_columns
+            .iter()
+            .zip(self.schema.fields())
+            .map(|(remote, field)| {
+                let text = self.text_columns.contains(field.name());
+                (remote.as_str(), field.name().as_str(), text)
+            })
+            .filter(|(remote, name, text)| remote != name || *text)
+            .collect()
+    }
+
+    pub(crate) fn remote_name(&self) -> &[String] {
+        &self.remote_name
+    }
+
+    pub(crate) fn provider(&self) -> &Arc<SQLFederationProvider> {
+        &self.provider
+    }
+}
+
+// Applies the provider's type overrides, by remote type name, to the inferred schema.
+// Fetched batches are cast to the overridden types.
+async fn override_types(
+    provider: &SQLFederationProvider,
+    remote_name: &[String],
+    schema: SchemaRef,
+) -> SchemaRef {
+    let overrides = &provider.planner.type_overrides;
+    if overrides.is_empty() {
+        return schema;
+    }
+    let (table_schema, table) = match remote_name {
+        [.., table_schema, table] => (Some(table_schema), table),
+        [table] => (None, table),
+        [] => return schema,
+    };
+    let mut query = format!(
+        "SELECT column_name, data_type FROM information_schema.columns WHERE table_name = '{}'",
+        table.replace('\'', "''")
+    );
+    if let Some(table_schema) = table_schema {
+        query.push_str(&format!(
+            " AND table_schema = '{}'",
+            table_schema.replace('\'', "''")
+        ));
+    }
+    let batches = match provider.executor.execute(query.as_str()).await {
+        Ok(stream) => stream.try_collect::<Vec<_>>().await.unwrap_or_default(),
+        Err(_) => vec![],
+    };
+
+    let mut type_names = HashMap::new();
+    for batch in batches {
+        let (Ok(names), Ok(types)) = (
+            cast(batch.column(0), &DataType::Utf8),
+            cast(batch.column(1), &DataType::Utf8),
+        ) else {
+            continue;
+        };
+        for (name, type_name) in names
+            .as_string::<i32>()
+            .iter()
+            .zip(types.as_string::<i32>())
+        {
+            if let (Some(name), Some(type_name)) = (name, type_name) {
+                type_names.insert(name.to_string(), type_name.to_ascii_lowercase());
+            }
+        }
+    }
+
+    let fields = schema
+        .fields()
+        .iter()
+        .map(|f| {
+            match type_names
+                .get(f.name())
+                .and_then(|type_name| overrides.get(type_name))
+            {
+                Some(data_type) => Field::clone(f).with_data_type(data_type.clone()),
+                None => Field::clone(f),
+            }
+        })
+        .collect::<Vec<_>>();
+    Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()))
 }
 
+#[async_trait]
 impl FederatedTableSource for SQLTableSource {
     fn federation_provider(&self) -> Arc<dyn FederationProvider> {
         self.provider.clone()
     }
+
+    fn describe(&self) -> serde_json::Value {
+        let mut text_columns = self.text_columns.iter().collect::<Vec<_>>();
+        text_columns.sort();
+        serde_json::json!({
+            "remote_name": self.remote_name.join("."),
+            "kind": format!("{:?}", self.kind),
+            "remote_columns": self.remote_columns,
+            "text_columns": text_columns,
+            "inlined_view": self.definition.is_some(),
+            "as_of": self.as_of.as_ref().map(|as_of| format!("{as_of:?}")),
+            "function_args": self
+                .function_args
+                .as_ref()
+                .map(|args| args.iter().map(|a| a.to_string()).collect::<Vec<_>>()),
+        })
+    }
+
+    async fn insert_into(
+        &self,
+        _state: &SessionState,
+        input: Arc<dyn ExecutionPlan>,
+        overwrite: bool,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        if overwrite || self.kind != RemoteTableKind::Table {
+            return Err(DataFusionError::NotImplemented(format!(
+                "insert overwrite or into views for {}",
+                self.table_name
+            )));
+        }
+        let sink = SQLInsertSink::new(
+            self.provider.executor.clone(),
+            self.remote_name.clone(),
+            self.remote_columns.clone(),
+            self.generated_columns.clone(),
+            self.default_columns.clone(),
+            self.provider.write_options.clone(),
+        );
+        if self.provider.returning && self.provider.planner.dialect.supports_returning() {
+            return Ok(Arc::new(SQLInsertReturningExec::new(
+                Arc::new(sink),
+                input,
+                self.schema.clone(),
+            )));
+        }
+        Ok(Arc::new(FileSinkExec::new(
+            input,
+            Arc::new(sink),
+            self.schema.clone(),
+            None,
+        )))
+    }
+
+    // Renders the limited scan like any federated query, so the preview reads
+    // the same remote columns in the source's dialect
+    async fn preview(self: Arc<Self>, limit: usize) -> Result<TablePreview> {
+        let table_name = self.table_name.clone();
+        let provider = self.provider.clone();
+        let source = provider_as_source(Arc::new(FederatedTableProviderAdaptor::new(self)));
+        let plan = LogicalPlanBuilder::scan(table_name, source, None)?
+            .limit(0, Some(limit))?
+            .build()?;
+        let sql = format!("{}", provider.planner.unparse(&plan)?);
+        let stream = provider.executor.execute(&sql).await?;
+        let schema = stream.schema();
+        let batches = stream.try_collect().await?;
+        Ok(TablePreview { schema, batches })
+    }
 }
 
 impl TableSource for SQLTableSource {
@@ -110,6 +523,9 @@ impl TableSource for SQLTableSource {
         self.schema.clone()
     }
     fn table_type(&self) -> TableType {
-        TableType::Temporary
+        match self.kind {
+            RemoteTableKind::Table => TableType::Temporary,
+            RemoteTableKind::View | RemoteTableKind::MaterializedView => TableType::View,
+        }
     }
 }
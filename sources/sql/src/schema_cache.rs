@@ -0,0 +1,180 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::Cursor,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use datafusion::{
+    arrow::{
+        datatypes::{Schema, SchemaRef},
+        ipc::{reader::StreamReader, writer::StreamWriter},
+    },
+    error::{DataFusionError, Result},
+};
+use log::warn;
+
+const REMOTE_NAME_KEY: &str = "federation.remote_name";
+
+// CacheCipher encrypts the cached schemas at rest, e.g. with AES-GCM and a
+// key from a secret manager.
+pub trait CacheCipher: Send + Sync {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>>;
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>>;
+}
+
+// SchemaCache persists the inferred schemas of registered tables in a
+// directory, keyed by the source's compute context and the table name, so
+// restarts don't introspect every remote table again. Entries are replaced
+// atomically, so the directory can be shared by several processes.
+pub struct SchemaCache {
+    dir: PathBuf,
+    cipher: Option<Arc<dyn CacheCipher>>,
+    max_age: Option<Duration>,
+}
+
+impl SchemaCache {
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            cipher: None,
+            max_age: None,
+        })
+    }
+
+    pub fn with_cipher(mut self, cipher: Arc<dyn CacheCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
+    }
+
+    // Entries older than `max_age` are inferred again.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    // Removes all entries.
+    pub fn clear(&self) -> Result<()> {
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "schema") {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the resolved remote name and the schema of the table.
+    pub(crate) fn get(&self, key: &str) -> Option<(Vec<String>, SchemaRef)> {
+        let path = self.path(key);
+        if let Some(max_age) = self.max_age {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            let age = SystemTime::now()
+                .duration_since(modified)
+                .unwrap_or_default();
+            if age > max_age {
+                return None;
+            }
+        }
+        let bytes = fs::read(&path).ok()?;
+        match self.decode(bytes) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("federation schema_cache={} reason=\"{e}\"", path.display());
+                None
+            }
+        }
+    }
+
+    pub(crate) fn put(&self, key: &str, remote_name: &[String], schema: &SchemaRef) -> Result<()> {
+        let mut metadata = schema.metadata().clone();
+        metadata.insert(REMOTE_NAME_KEY.to_string(), remote_name.join("\u{1f}"));
+        let schema = Schema::new_with_metadata(schema.fields().clone(), metadata);
+
+        let mut writer = StreamWriter::try_new(vec![], &schema)?;
+        writer.finish()?;
+        let mut bytes = writer.into_inner()?;
+        if let Some(cipher) = &self.cipher {
+            bytes = cipher.encrypt(&bytes)?;
+        }
+
+        // Write to a unique file and rename it over the entry
+        let path = self.path(key);
+        let tmp = path.with_extension(format!("{}.tmp", std::process::id()));
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn decode(&self, mut bytes: Vec<u8>) -> Result<(Vec<String>, SchemaRef)> {
+        if let Some(cipher) = &self.cipher {
+            bytes = cipher.decrypt(&bytes)?;
+        }
+        let reader = StreamReader::try_new(Cursor::new(bytes), None)?;
+        let schema = reader.schema();
+        let mut metadata: HashMap<String, String> = schema.metadata().clone();
+        let remote_name = metadata
+            .remove(REMOTE_NAME_KEY)
+            .ok_or_else(|| DataFusionError::Internal("schema cache entry without name".into()))?;
+        let schema = Schema::new_with_metadata(schema.fields().clone(), metadata);
+        Ok((
+            remote_name.split('\u{1f}').map(String::from).collect(),
+            Arc::new(schema),
+        ))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.schema", fnv1a(key.as_bytes())))
+    }
+}
+
+// A hash that is stable across processes and Rust versions, unlike the
+// std hashers. The key contains the DSN, which isn't written in clear.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::datatypes::{DataType, Field};
+
+    use super::*;
+
+    struct Xor;
+
+    impl CacheCipher for Xor {
+        fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+            Ok(plaintext.iter().map(|b| b ^ 0x5a).collect())
+        }
+        fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+            self.encrypt(ciphertext)
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let dir = std::env::temp_dir().join(format!("schema_cache_{}", std::process::id()));
+        let cache = SchemaCache::open(&dir).unwrap().with_cipher(Arc::new(Xor));
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let name = vec!["public".to_string(), "users".to_string()];
+
+        assert!(cache.get("pg users").is_none());
+        cache.put("pg users", &name, &schema).unwrap();
+        assert_eq!(cache.get("pg users"), Some((name, schema)));
+        assert!(cache.get("pg orders").is_none());
+
+        cache.clear().unwrap();
+        assert!(cache.get("pg users").is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
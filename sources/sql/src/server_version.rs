@@ -0,0 +1,251 @@
+use core::fmt;
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::datatypes::DataType,
+    logical_expr::LogicalPlan,
+    sql::sqlparser::{
+        ast::{self, Expr as SQLExpr},
+        dialect as parser,
+    },
+};
+
+use crate::{
+    dialect::{AsOf, GroupByStrategy, LimitStyle, SQLDialect, UpsertStrategy},
+    SortCostProfile,
+};
+
+// ServerVersion is the major and minor version of a remote server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ServerVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ServerVersion {
+    pub fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    // Parses the first `major.minor` in a version string, e.g.
+    // "PostgreSQL 15.3 on x86_64-pc-linux-gnu" or "8.0.33".
+    pub fn parse(version: &str) -> Option<Self> {
+        let bytes = version.as_bytes();
+        let mut start = 0;
+        while start < bytes.len() {
+            if !bytes[start].is_ascii_digit() {
+                start += 1;
+                continue;
+            }
+            let major_end = digits_end(bytes, start);
+            if bytes.get(major_end) == Some(&b'.') {
+                let minor_end = digits_end(bytes, major_end + 1);
+                if minor_end > major_end + 1 {
+                    return Some(Self {
+                        major: version[start..major_end].parse().ok()?,
+                        minor: version[major_end + 1..minor_end].parse().ok()?,
+                    });
+                }
+            }
+            start = major_end;
+        }
+        None
+    }
+}
+
+fn digits_end(bytes: &[u8], start: usize) -> usize {
+    start
+        + bytes[start..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count()
+}
+
+impl fmt::Display for ServerVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+// VersionedDialect adjusts the capabilities of a dialect to the detected
+// server version, so features missing on older servers aren't pushed down.
+pub struct VersionedDialect {
+    dialect: Arc<dyn SQLDialect>,
+    version: ServerVersion,
+}
+
+impl VersionedDialect {
+    pub fn new(dialect: Arc<dyn SQLDialect>, version: ServerVersion) -> Self {
+        Self { dialect, version }
+    }
+
+    pub fn version(&self) -> ServerVersion {
+        self.version
+    }
+
+    fn at_least(&self, major: u32, minor: u32) -> bool {
+        self.version >= ServerVersion::new(major, minor)
+    }
+}
+
+impl SQLDialect for VersionedDialect {
+    fn name(&self) -> &str {
+        self.dialect.name()
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        self.dialect.parser_dialect()
+    }
+
+    fn distinct_from(&self, l: SQLExpr, r: SQLExpr, not_distinct: bool) -> SQLExpr {
+        // SQL Server 2022 is version 16
+        match (self.name(), not_distinct) {
+            ("mssql", true) if self.at_least(16, 0) => {
+                SQLExpr::IsNotDistinctFrom(Box::new(l), Box::new(r))
+            }
+            ("mssql", false) if self.at_least(16, 0) => {
+                SQLExpr::IsDistinctFrom(Box::new(l), Box::new(r))
+            }
+            _ => self.dialect.distinct_from(l, r, not_distinct),
+        }
+    }
+
+    fn supports_window_functions(&self) -> bool {
+        let supported = match self.name() {
+            "mysql" => self.at_least(8, 0),
+            "mariadb" => self.at_least(10, 2),
+            "sqlite" => self.at_least(3, 25),
+            _ => true,
+        };
+        supported && self.dialect.supports_window_functions()
+    }
+
+    fn supports_plan(&self, plan: &LogicalPlan) -> bool {
+        self.dialect.supports_plan(plan)
+    }
+
+    fn version_query(&self) -> Option<&str> {
+        self.dialect.version_query()
+    }
+
+    fn set_variable(&self, name: &str, value: &str) -> String {
+        self.dialect.set_variable(name, value)
+    }
+
+    fn supports_limit_in_subquery(&self) -> bool {
+        self.dialect.supports_limit_in_subquery()
+    }
+
+    fn max_statement_size(&self) -> Option<usize> {
+        self.dialect.max_statement_size()
+    }
+
+    fn supports_returning(&self) -> bool {
+        let supported = match self.name() {
+            "mariadb" => self.at_least(10, 5),
+            "sqlite" => self.at_least(3, 35),
+            _ => true,
+        };
+        supported && self.dialect.supports_returning()
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        self.dialect.upsert_strategy()
+    }
+
+    fn merge(
+        &self,
+        table: &str,
+        rows: &ast::Values,
+        columns: &[String],
+        on: &str,
+        clauses: &str,
+    ) -> String {
+        self.dialect.merge(table, rows, columns, on, clauses)
+    }
+
+    fn group_by_strategy(&self) -> GroupByStrategy {
+        self.dialect.group_by_strategy()
+    }
+
+    fn time_travel(&self, as_of: &AsOf) -> Option<String> {
+        self.dialect.time_travel(as_of)
+    }
+
+    fn unload(&self, query: &str, location: &str, options: Option<&str>) -> Option<String> {
+        self.dialect.unload(query, location, options)
+    }
+
+    fn load(&self, table: &str, location: &str, options: Option<&str>) -> Option<String> {
+        self.dialect.load(table, location, options)
+    }
+
+    fn empty_string_is_null(&self) -> bool {
+        self.dialect.empty_string_is_null()
+    }
+
+    fn supports_table_functions(&self) -> bool {
+        self.dialect.supports_table_functions()
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        self.dialect.identifier_quote_style()
+    }
+
+    fn limit_style(&self) -> LimitStyle {
+        self.dialect.limit_style()
+    }
+
+    fn supports_nulls_ordering(&self) -> bool {
+        self.dialect.supports_nulls_ordering()
+    }
+
+    fn binary_collation(&self) -> bool {
+        self.dialect.binary_collation()
+    }
+
+    fn supports_table_alias_as(&self) -> bool {
+        self.dialect.supports_table_alias_as()
+    }
+
+    fn cast_data_type(&self, data_type: &DataType) -> Option<ast::DataType> {
+        self.dialect.cast_data_type(data_type)
+    }
+
+    fn scalar_function(&self, name: &str, args: Vec<SQLExpr>) -> Option<SQLExpr> {
+        self.dialect.scalar_function(name, args)
+    }
+
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        self.dialect.sort_cost_profile()
+    }
+
+    fn date_literal(&self, date: &str) -> SQLExpr {
+        self.dialect.date_literal(date)
+    }
+
+    fn timestamp_literal(&self, timestamp: &str) -> SQLExpr {
+        self.dialect.timestamp_literal(timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_server_version() {
+        let tests = vec![
+            ("PostgreSQL 15.3 on x86_64-pc-linux-gnu", Some((15, 3))),
+            ("8.0.33", Some((8, 0))),
+            ("10.6.12-MariaDB-0ubuntu0.22.04.1", Some((10, 6))),
+            ("16.0.1000.6", Some((16, 0))),
+            ("3.45.1", Some((3, 45))),
+            ("unknown", None),
+        ];
+        for (version, expected) in tests {
+            let expected = expected.map(|(major, minor)| ServerVersion::new(major, minor));
+            assert_eq!(ServerVersion::parse(version), expected, "{version}");
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::{
+    common::plan_err,
+    error::Result,
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::StreamExt;
+
+use crate::{dialect::SQLDialect, executor::SQLExecutor};
+
+// SessionVariables are the DataFusion session variables passed through to
+// the remote sessions of a source, set with
+// `SET federation.<source>.<variable> = ...`.
+#[derive(Debug, Clone)]
+pub(crate) struct SessionVariables {
+    pub source: String,
+    // The variables that may be set, others are rejected
+    pub allowed: Vec<String>,
+}
+
+impl SessionVariables {
+    // Returns the statements setting the variables in the dialect.
+    pub fn statements(
+        &self,
+        dialect: &dyn SQLDialect,
+        variables: Vec<(String, String)>,
+    ) -> Result<Vec<String>> {
+        variables
+            .into_iter()
+            .map(|(name, value)| {
+                let allowed = self.allowed.iter().any(|a| a.eq_ignore_ascii_case(&name));
+                let valid = name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+                if !allowed || !valid {
+                    return plan_err!(
+                        "session variable federation.{}.{name} is not passed through",
+                        self.source
+                    );
+                }
+                Ok(dialect.set_variable(&name, &render_value(&value)))
+            })
+            .collect()
+    }
+}
+
+// Returns the statements applying the connection settings in the dialect.
+pub(crate) fn setting_statements(
+    dialect: &dyn SQLDialect,
+    settings: &[(String, String)],
+) -> Vec<String> {
+    settings
+        .iter()
+        .map(|(name, value)| dialect.set_variable(name, &render_value(value)))
+        .collect()
+}
+
+// Numbers are rendered as is, other values as string literals.
+fn render_value(value: &str) -> String {
+    if value.parse::<f64>().is_ok() {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "''"))
+    }
+}
+
+// SessionVariablesExecutor runs each query on a dedicated connection of the
+// executor, once the statements setting the session variables ran on it.
+pub(crate) struct SessionVariablesExecutor {
+    executor: Arc<dyn SQLExecutor>,
+    statements: Vec<String>,
+}
+
+impl SessionVariablesExecutor {
+    pub fn new(executor: Arc<dyn SQLExecutor>, statements: Vec<String>) -> Self {
+        Self {
+            executor,
+            statements,
+        }
+    }
+}
+
+#[async_trait]
+impl SQLExecutor for SessionVariablesExecutor {
+    fn name(&self) -> &str {
+        self.executor.name()
+    }
+    fn compute_context(&self) -> Option<String> {
+        self.executor.compute_context()
+    }
+    fn dialect(&self) -> Arc<dyn SQLDialect> {
+        self.executor.dialect()
+    }
+    async fn execute(&self, query: &str) -> Result<SendableRecordBatchStream> {
+        let mut connection = self.executor.connect().await?;
+        for statement in &self.statements {
+            connection.execute_statement(statement).await?;
+        }
+        let stream = connection.execute(query).await?;
+        let schema = stream.schema();
+        // The connection is kept until the stream is dropped
+        let stream = stream.map(move |batch| {
+            let _connection = &connection;
+            batch
+        });
+        Ok(Box::pin(RecordBatchStreamAdapter::new(schema, stream)))
+    }
+    async fn execute_statement(&self, statement: &str) -> Result<u64> {
+        let mut connection = self.executor.connect().await?;
+        for statement in &self.statements {
+            connection.execute_statement(statement).await?;
+        }
+        connection.execute_statement(statement).await
+    }
+    fn partition_count(&self) -> usize {
+        self.executor.partition_count()
+    }
+    async fn split_query(&self, query: &str) -> Result<Vec<String>> {
+        self.executor.split_query(query).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::arrow::{array::AsArray, record_batch::RecordBatch};
+    use futures::TryStreamExt;
+
+    use super::*;
+    use crate::{dialect::DefaultDialect, executor::MemorySQLExecutor};
+
+    #[test]
+    fn test_statements() {
+        let variables = SessionVariables {
+            source: "pg".to_string(),
+            allowed: vec!["statement_timeout".to_string()],
+        };
+        let statements = variables
+            .statements(
+                &DefaultDialect {},
+                vec![("STATEMENT_TIMEOUT".to_string(), "5s".to_string())],
+            )
+            .unwrap();
+        assert_eq!(statements, vec!["SET STATEMENT_TIMEOUT = '5s'"]);
+        assert!(variables
+            .statements(
+                &DefaultDialect {},
+                vec![("work_mem".to_string(), "64MB".to_string())],
+            )
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_session_variables_executor() {
+        let memory: Arc<dyn SQLExecutor> = Arc::new(MemorySQLExecutor::new("memory"));
+        let statements = setting_statements(
+            &DefaultDialect {},
+            &[(
+                "datafusion.catalog.information_schema".to_string(),
+                "true".to_string(),
+            )],
+        );
+        let executor = SessionVariablesExecutor::new(memory.clone(), statements);
+        let show = "SHOW datafusion.catalog.information_schema";
+        let batches: Vec<RecordBatch> = executor
+            .execute(show)
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(batches[0].column(1).as_string::<i32>().value(0), "true");
+        // Set on a dedicated connection only
+        assert!(memory.execute(show).await.is_err());
+    }
+}
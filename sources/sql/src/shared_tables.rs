@@ -0,0 +1,74 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+};
+
+use datafusion::{datasource::TableProvider, error::Result};
+use datafusion_federation::FederatedTableProviderAdaptor;
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::{schema::SQLTableSource, SQLFederationProvider};
+
+type SharedSlot = Arc<AsyncMutex<Weak<SQLTableSource>>>;
+
+// SharedTables lets the sessions of a gateway register the same remote table
+// in their catalogs, under any name, sharing one table source: its schema is
+// inferred once and its queries use the provider's executor, and so its
+// connection pool. The source lives as long as any registration, e.g. a
+// session dropping its catalog doesn't affect the others. Tables of the same
+// source are keyed by their compute context, the provider of the first
+// registration is used by all.
+#[derive(Default)]
+pub struct SharedTables {
+    slots: Mutex<HashMap<String, SharedSlot>>,
+}
+
+impl SharedTables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns a table provider of the remote table, to register in a
+    // catalog, reusing the table source of live registrations.
+    pub async fn table(
+        &self,
+        provider: Arc<SQLFederationProvider>,
+        table: &str,
+    ) -> Result<Arc<dyn TableProvider>> {
+        let key = format!("{:?} {table}", provider.executor.compute_context());
+        let slot = {
+            let mut slots = self.slots.lock().unwrap();
+            // Tables whose registrations are all gone are forgotten
+            slots.retain(|_, slot| {
+                slot.try_lock()
+                    .map_or(true, |source| source.strong_count() > 0)
+            });
+            slots.entry(key).or_default().clone()
+        };
+
+        // Concurrent first registrations of a table infer its schema once
+        let mut shared = slot.lock().await;
+        let source = match shared.upgrade() {
+            Some(source) => source,
+            None => {
+                let source = Arc::new(SQLTableSource::new(provider, table.to_string()).await?);
+                *shared = Arc::downgrade(&source);
+                source
+            }
+        };
+        Ok(Arc::new(FederatedTableProviderAdaptor::new(source)))
+    }
+
+    // The number of remote tables with live registrations.
+    pub fn live_tables(&self) -> usize {
+        self.slots
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|slot| {
+                slot.try_lock()
+                    .map_or(true, |source| source.strong_count() > 0)
+            })
+            .count()
+    }
+}
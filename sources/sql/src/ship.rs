@@ -0,0 +1,212 @@
+use core::fmt;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use datafusion::{
+    arrow::{csv, datatypes::SchemaRef, record_batch::RecordBatch},
+    common::{not_impl_err, DataFusionError},
+    datasource::listing::ListingTableUrl,
+    error::Result,
+    execution::runtime_env::RuntimeEnv,
+    parquet::arrow::ArrowWriter,
+    sql::sqlparser::ast,
+};
+use log::{debug, warn};
+
+use crate::{
+    executor::SQLExecutor, unload::next_prefix, SQLInsertSink, StatementWriter, WriteOptions,
+};
+
+// ShipTarget is the remote table batches are shipped into.
+pub struct ShipTarget<'a> {
+    pub executor: Arc<dyn SQLExecutor>,
+    pub table: &'a [String],
+    pub schema: SchemaRef,
+    pub options: &'a WriteOptions,
+    // Resolves the object stores of staged files
+    pub runtime: Arc<RuntimeEnv>,
+}
+
+impl ShipTarget<'_> {
+    fn table_name(&self) -> String {
+        ast::ObjectName(self.table.iter().map(ast::Ident::new).collect()).to_string()
+    }
+}
+
+// BatchShipper writes local batches into a remote table, e.g. the temp
+// tables of materialize_remote. The wire format is up to the shipper, from
+// portable INSERT statements to bulk loads of the source.
+#[async_trait]
+pub trait BatchShipper: Send + Sync + fmt::Debug {
+    fn name(&self) -> &str;
+
+    // Ships the batches, returns the number of rows written.
+    async fn ship(&self, target: &ShipTarget<'_>, batches: Vec<RecordBatch>) -> Result<u64>;
+}
+
+// The fastest shipper a dialect supports without further configuration,
+// staged loads need a location and are set with with_batch_shipper.
+pub fn default_shipper(dialect: &str) -> Arc<dyn BatchShipper> {
+    match dialect {
+        "postgresql" | "postgres" => Arc::new(CsvCopyShipper {}),
+        _ => Arc::new(InsertShipper {}),
+    }
+}
+
+// InsertShipper writes the batches with INSERT statements, split as set in
+// the WriteOptions. It works with every source.
+#[derive(Debug, Default)]
+pub struct InsertShipper {}
+
+#[async_trait]
+impl BatchShipper for InsertShipper {
+    fn name(&self) -> &str {
+        "insert"
+    }
+
+    async fn ship(&self, target: &ShipTarget<'_>, batches: Vec<RecordBatch>) -> Result<u64> {
+        let sink = SQLInsertSink::new(
+            target.executor.clone(),
+            target.table.to_vec(),
+            target
+                .schema
+                .fields()
+                .iter()
+                .map(|f| f.name().clone())
+                .collect(),
+            Default::default(),
+            Default::default(),
+            target.options.clone(),
+        );
+        let mut writer = StatementWriter::new(target.executor.clone(), target.options.clone());
+        let chunk_size = writer.rows_per_statement();
+        for batch in batches {
+            for offset in (0..batch.num_rows()).step_by(chunk_size) {
+                let chunk = batch.slice(offset, chunk_size.min(batch.num_rows() - offset));
+                writer
+                    .push(sink.insert_statement(&chunk)?, chunk.num_rows())
+                    .await?;
+            }
+        }
+        writer.finish().await
+    }
+}
+
+// CsvCopyShipper streams the batches as CSV with PostgreSQL's
+// `COPY ... FROM STDIN`, falling back to INSERT statements if the executor
+// doesn't implement SQLExecutor::copy_in. COPY reads empty strings as NULL.
+#[derive(Debug, Default)]
+pub struct CsvCopyShipper {}
+
+#[async_trait]
+impl BatchShipper for CsvCopyShipper {
+    fn name(&self) -> &str {
+        "csv_copy"
+    }
+
+    async fn ship(&self, target: &ShipTarget<'_>, batches: Vec<RecordBatch>) -> Result<u64> {
+        let mut data = vec![];
+        {
+            let mut writer = csv::Writer::new(&mut data);
+            for batch in &batches {
+                writer.write(batch)?;
+            }
+        }
+        let columns = target
+            .schema
+            .fields()
+            .iter()
+            .map(|f| ast::Ident::new(f.name()).to_string())
+            .collect::<Vec<_>>();
+        let statement = format!(
+            "COPY {} ({}) FROM STDIN WITH (FORMAT csv, HEADER true)",
+            target.table_name(),
+            columns.join(", ")
+        );
+        debug!(
+            "federation decision=ship shipper=csv_copy context={:?} bytes={}",
+            target.executor.compute_context(),
+            data.len()
+        );
+        match target.executor.copy_in(&statement, data).await {
+            Err(DataFusionError::NotImplemented(_)) => {
+                warn!(
+                    "federation ship_fallback context={:?} shipper=csv_copy reason=copy_in_not_implemented",
+                    target.executor.compute_context()
+                );
+                InsertShipper {}.ship(target, batches).await
+            }
+            result => result,
+        }
+    }
+}
+
+// ParquetStageShipper writes the batches as a Parquet file to a stage in
+// object storage and loads it with the dialect's load statement, e.g. for
+// Snowflake, BigQuery and Redshift, where inserts are slow.
+#[derive(Debug, Clone)]
+pub struct ParquetStageShipper {
+    // Where the source reads the files, e.g. a Snowflake stage `@imports/federation`
+    // or `s3://bucket/federation`. Each shipment is written under its own prefix.
+    pub location: String,
+    // The URL the files are written to, its object store must be registered
+    // in the runtime env, e.g. `s3://bucket/federation`
+    pub url: String,
+    // Extra clauses of the load statement, e.g. Redshift's `IAM_ROLE '...'`
+    pub options: Option<String>,
+}
+
+impl ParquetStageShipper {
+    pub fn new(location: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            location: location.into(),
+            url: url.into(),
+            options: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: impl Into<String>) -> Self {
+        self.options = Some(options.into());
+        self
+    }
+}
+
+#[async_trait]
+impl BatchShipper for ParquetStageShipper {
+    fn name(&self) -> &str {
+        "parquet_stage"
+    }
+
+    async fn ship(&self, target: &ShipTarget<'_>, batches: Vec<RecordBatch>) -> Result<u64> {
+        let prefix = next_prefix();
+        let location = format!("{}/{prefix}", self.location.trim_end_matches('/'));
+        let dialect = target.executor.dialect();
+        let Some(statement) =
+            dialect.load(&target.table_name(), &location, self.options.as_deref())
+        else {
+            return not_impl_err!("staged loads for dialect {}", dialect.name());
+        };
+
+        let mut data = vec![];
+        let mut writer = ArrowWriter::try_new(&mut data, target.schema.clone(), None)?;
+        for batch in &batches {
+            writer.write(batch)?;
+        }
+        writer.close()?;
+
+        let url = ListingTableUrl::parse(format!("{}/{prefix}/", self.url.trim_end_matches('/')))?;
+        let store = target.runtime.object_store(&url)?;
+        let path = url.prefix().child("part-0.parquet");
+        store.put(&path, data.into()).await?;
+        debug!(
+            "federation decision=ship shipper=parquet_stage context={:?} location={location}",
+            target.executor.compute_context()
+        );
+        let result = target.executor.execute_statement(&statement).await;
+        if let Err(e) = store.delete(&path).await {
+            warn!("federation ship cleanup failed: {e}");
+        }
+        result?;
+        Ok(batches.iter().map(|b| b.num_rows() as u64).sum())
+    }
+}
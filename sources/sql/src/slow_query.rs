@@ -0,0 +1,227 @@
+use core::fmt;
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use datafusion::{
+    arrow::{datatypes::SchemaRef, record_batch::RecordBatch},
+    error::Result,
+    physical_plan::{RecordBatchStream, SendableRecordBatchStream},
+};
+use futures::{Stream, StreamExt};
+
+// SlowQuery describes a remote query that exceeded the slow query threshold.
+#[derive(Debug, Clone)]
+pub struct SlowQuery {
+    pub compute_context: Option<String>,
+    // The local plan that was federated
+    pub plan: String,
+    // The SQL sent to the remote source
+    pub sql: String,
+    pub time_to_first_batch: Duration,
+    pub elapsed: Duration,
+    pub rows: usize,
+}
+
+impl fmt::Display for SlowQuery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "-- context={:?} elapsed_ms={} first_batch_ms={} rows={}",
+            self.compute_context,
+            self.elapsed.as_millis(),
+            self.time_to_first_batch.as_millis(),
+            self.rows
+        )?;
+        for line in self.plan.lines() {
+            writeln!(f, "-- {line}")?;
+        }
+        writeln!(f, "{};", self.sql)
+    }
+}
+
+pub trait SlowQuerySink: Send + Sync {
+    fn log(&self, query: &SlowQuery);
+}
+
+impl<F> SlowQuerySink for F
+where
+    F: Fn(&SlowQuery) + Send + Sync,
+{
+    fn log(&self, query: &SlowQuery) {
+        self(query)
+    }
+}
+
+// FileSlowQuerySink appends slow queries to a file.
+pub struct FileSlowQuerySink {
+    file: Mutex<File>,
+}
+
+impl FileSlowQuerySink {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl SlowQuerySink for FileSlowQuerySink {
+    fn log(&self, query: &SlowQuery) {
+        let mut file = self.file.lock().unwrap();
+        // Logging must not fail the query
+        let _ = write!(file, "{query}");
+    }
+}
+
+pub struct SlowQueryLog {
+    threshold: Duration,
+    sink: Arc<dyn SlowQuerySink>,
+}
+
+impl SlowQueryLog {
+    pub fn new(threshold: Duration, sink: Arc<dyn SlowQuerySink>) -> Self {
+        Self { threshold, sink }
+    }
+}
+
+impl fmt::Debug for SlowQueryLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SlowQueryLog({:?})", self.threshold)
+    }
+}
+
+// SlowQueryStream times a remote query and logs it once it is drained, or
+// dropped before, e.g. below a LIMIT.
+pub(crate) struct SlowQueryStream {
+    inner: SendableRecordBatchStream,
+    log: Arc<SlowQueryLog>,
+    query: SlowQuery,
+    start: Instant,
+    first_batch: bool,
+    finished: bool,
+}
+
+impl SlowQueryStream {
+    pub fn new(
+        inner: SendableRecordBatchStream,
+        log: Arc<SlowQueryLog>,
+        query: SlowQuery,
+        start: Instant,
+    ) -> Self {
+        Self {
+            inner,
+            log,
+            query,
+            start,
+            first_batch: true,
+            finished: false,
+        }
+    }
+
+    fn finish(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        self.query.elapsed = self.start.elapsed();
+        if self.query.elapsed >= self.log.threshold {
+            self.log.sink.log(&self.query);
+        }
+    }
+}
+
+impl Stream for SlowQueryStream {
+    type Item = Result<RecordBatch>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.poll_next_unpin(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(batch))) => {
+                if self.first_batch {
+                    self.first_batch = false;
+                    self.query.time_to_first_batch = self.start.elapsed();
+                }
+                self.query.rows += batch.num_rows();
+            }
+            Poll::Ready(None) => self.finish(),
+            _ => {}
+        }
+        poll
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl RecordBatchStream for SlowQueryStream {
+    fn schema(&self) -> SchemaRef {
+        self.inner.schema()
+    }
+}
+
+impl Drop for SlowQueryStream {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use datafusion::{
+        arrow::{
+            array::Int64Array,
+            datatypes::{DataType, Field, Schema},
+        },
+        physical_plan::memory::MemoryStream,
+    };
+
+    use super::*;
+
+    fn stream(log: Arc<SlowQueryLog>) -> SlowQueryStream {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![1, 2]))])
+                .unwrap();
+        let inner = MemoryStream::try_new(vec![batch.clone(), batch], schema, None).unwrap();
+        let query = SlowQuery {
+            compute_context: None,
+            plan: String::new(),
+            sql: "SELECT id FROM t".to_string(),
+            time_to_first_batch: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            rows: 0,
+        };
+        SlowQueryStream::new(Box::pin(inner), log, query, Instant::now())
+    }
+
+    #[tokio::test]
+    async fn test_slow_query_stream() {
+        let logged = Arc::new(Mutex::new(vec![]));
+        let sink = {
+            let logged = logged.clone();
+            move |query: &SlowQuery| logged.lock().unwrap().push(query.rows)
+        };
+        let log = Arc::new(SlowQueryLog::new(Duration::ZERO, Arc::new(sink)));
+
+        // Drained, logged once
+        let mut drained = stream(log.clone());
+        while drained.next().await.is_some() {}
+        drop(drained);
+        assert_eq!(*logged.lock().unwrap(), vec![4]);
+
+        // Dropped after the first batch
+        let mut dropped = stream(log);
+        dropped.next().await.unwrap().unwrap();
+        drop(dropped);
+        assert_eq!(*logged.lock().unwrap(), vec![4, 2]);
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use datafusion::{
+    common::tree_node::{TreeNode, VisitRecursion},
+    logical_expr::{LogicalPlan, Sort},
+};
+
+// SortCostProfile decides whether a federated ORDER BY is sent to the source
+// or the fetched rows are sorted locally. Costs are relative, e.g. backends
+// serving ORDER BY from an index sort cheaper than DataFusion.
+#[derive(Debug, Clone)]
+pub struct SortCostProfile {
+    // The cost of sorting a row in the source
+    pub remote_cost_per_row: f64,
+    // The cost of sorting a fetched row locally
+    pub local_cost_per_row: f64,
+    // Sorts of more rows are pushed down regardless of the costs, as they
+    // would spill locally
+    pub local_max_rows: usize,
+    // The estimated row counts of tables by name
+    pub row_counts: HashMap<String, usize>,
+    // The row count assumed for tables without an estimate
+    pub default_row_count: usize,
+}
+
+impl Default for SortCostProfile {
+    // Sorts are pushed down unless a profile says otherwise
+    fn default() -> Self {
+        Self {
+            remote_cost_per_row: 1.0,
+            local_cost_per_row: 1.0,
+            local_max_rows: 10_000_000,
+            row_counts: HashMap::new(),
+            default_row_count: 1_000_000,
+        }
+    }
+}
+
+impl SortCostProfile {
+    pub fn with_remote_cost(mut self, cost_per_row: f64) -> Self {
+        self.remote_cost_per_row = cost_per_row;
+        self
+    }
+
+    pub fn with_local_cost(mut self, cost_per_row: f64) -> Self {
+        self.local_cost_per_row = cost_per_row;
+        self
+    }
+
+    pub fn with_local_max_rows(mut self, rows: usize) -> Self {
+        self.local_max_rows = rows;
+        self
+    }
+
+    pub fn with_row_count(mut self, table: impl Into<String>, rows: usize) -> Self {
+        self.row_counts.insert(table.into(), rows);
+        self
+    }
+
+    // Returns true if the sort is cheaper in the source. Top-k sorts are always
+    // pushed down, they cut the fetched rows.
+    pub(crate) fn push_sort(&self, sort: &Sort) -> bool {
+        if sort.fetch.is_some() {
+            return true;
+        }
+        let rows = self.estimate_rows(&sort.input);
+        if rows > self.local_max_rows {
+            return true;
+        }
+        self.remote_cost_per_row <= self.local_cost_per_row
+    }
+
+    // Estimates the rows of a plan as its largest table, bounded by its limits.
+    fn estimate_rows(&self, plan: &LogicalPlan) -> usize {
+        match plan {
+            LogicalPlan::Limit(limit) => limit
+                .fetch
+                .unwrap_or(usize::MAX)
+                .min(self.estimate_rows(&limit.input)),
+            _ => {
+                let mut rows = 0;
+                let _ = plan.apply(&mut |plan| {
+                    if let LogicalPlan::TableScan(scan) = plan {
+                        let name = scan.table_name.to_string();
+                        let table_rows = self
+                            .row_counts
+                            .get(&name)
+                            .or_else(|| self.row_counts.get(scan.table_name.table()))
+                            .copied()
+                            .unwrap_or(self.default_row_count);
+                        rows = rows.max(scan.fetch.unwrap_or(usize::MAX).min(table_rows));
+                    }
+                    Ok(VisitRecursion::Continue)
+                });
+                rows
+            }
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::datatypes::DataType,
+    common::tree_node::{TreeNode, VisitRecursion},
+    logical_expr::{BinaryExpr, Expr, LogicalPlan, Operator},
+    sql::sqlparser::{
+        ast::{self, Expr as SQLExpr},
+        dialect as parser,
+    },
+};
+
+use crate::{
+    dialect::{
+        ansi_data_type, ansi_function, AsOf, GroupByStrategy, LimitStyle, SQLDialect,
+        UpsertStrategy,
+    },
+    SortCostProfile,
+};
+
+// StandardDialect restricts the generated queries of a dialect to SQL:2011,
+// e.g. for proxies or engines of unknown dialect. Settings of the remote
+// session and unload statements, which are never standard, are kept.
+pub(crate) struct StandardDialect {
+    dialect: Arc<dyn SQLDialect>,
+}
+
+impl StandardDialect {
+    pub fn new(dialect: Arc<dyn SQLDialect>) -> Self {
+        Self { dialect }
+    }
+}
+
+impl SQLDialect for StandardDialect {
+    fn name(&self) -> &str {
+        self.dialect.name()
+    }
+
+    fn parser_dialect(&self) -> Box<dyn parser::Dialect> {
+        Box::new(parser::AnsiDialect {})
+    }
+
+    fn distinct_from(&self, l: SQLExpr, r: SQLExpr, not_distinct: bool) -> SQLExpr {
+        match not_distinct {
+            true => SQLExpr::IsNotDistinctFrom(Box::new(l), Box::new(r)),
+            false => SQLExpr::IsDistinctFrom(Box::new(l), Box::new(r)),
+        }
+    }
+
+    fn supports_limit_in_subquery(&self) -> bool {
+        self.dialect.supports_limit_in_subquery()
+    }
+
+    fn max_statement_size(&self) -> Option<usize> {
+        self.dialect.max_statement_size()
+    }
+
+    fn supports_window_functions(&self) -> bool {
+        self.dialect.supports_window_functions()
+    }
+
+    fn supports_plan(&self, plan: &LogicalPlan) -> bool {
+        self.dialect.supports_plan(plan)
+    }
+
+    fn version_query(&self) -> Option<&str> {
+        self.dialect.version_query()
+    }
+
+    fn set_variable(&self, name: &str, value: &str) -> String {
+        self.dialect.set_variable(name, value)
+    }
+
+    fn supports_returning(&self) -> bool {
+        false
+    }
+
+    fn upsert_strategy(&self) -> Option<UpsertStrategy> {
+        match self.dialect.upsert_strategy() {
+            Some(UpsertStrategy::Merge) => Some(UpsertStrategy::Merge),
+            _ => None,
+        }
+    }
+
+    fn merge(
+        &self,
+        table: &str,
+        rows: &ast::Values,
+        columns: &[String],
+        on: &str,
+        clauses: &str,
+    ) -> String {
+        self.dialect.merge(table, rows, columns, on, clauses)
+    }
+
+    fn group_by_strategy(&self) -> GroupByStrategy {
+        GroupByStrategy::Expression
+    }
+
+    // System-versioned tables, snapshots have no standard syntax
+    fn time_travel(&self, as_of: &AsOf) -> Option<String> {
+        match as_of {
+            AsOf::Timestamp(ts) => Some(format!(
+                "FOR SYSTEM_TIME AS OF TIMESTAMP '{}'",
+                ts.replace('\'', "''")
+            )),
+            AsOf::Snapshot(_) => None,
+        }
+    }
+
+    fn unload(&self, query: &str, location: &str, options: Option<&str>) -> Option<String> {
+        self.dialect.unload(query, location, options)
+    }
+
+    fn load(&self, table: &str, location: &str, options: Option<&str>) -> Option<String> {
+        self.dialect.load(table, location, options)
+    }
+
+    fn empty_string_is_null(&self) -> bool {
+        self.dialect.empty_string_is_null()
+    }
+
+    fn supports_table_functions(&self) -> bool {
+        false
+    }
+
+    fn identifier_quote_style(&self) -> Option<char> {
+        Some('"')
+    }
+
+    fn limit_style(&self) -> LimitStyle {
+        LimitStyle::FetchFirst
+    }
+
+    fn supports_nulls_ordering(&self) -> bool {
+        self.dialect.supports_nulls_ordering()
+    }
+
+    fn binary_collation(&self) -> bool {
+        self.dialect.binary_collation()
+    }
+
+    fn supports_table_alias_as(&self) -> bool {
+        self.dialect.supports_table_alias_as()
+    }
+
+    fn cast_data_type(&self, data_type: &DataType) -> Option<ast::DataType> {
+        ansi_data_type(data_type)
+    }
+
+    fn scalar_function(&self, name: &str, args: Vec<SQLExpr>) -> Option<SQLExpr> {
+        ansi_function(name, args)
+    }
+
+    fn sort_cost_profile(&self) -> SortCostProfile {
+        self.dialect.sort_cost_profile()
+    }
+}
+
+// Returns the first operator of the node's own expressions that has no
+// SQL:2011 form, e.g. PostgreSQL's `~` regex match or `%`.
+pub(crate) fn non_standard_operator(plan: &LogicalPlan) -> Option<Operator> {
+    let mut found = None;
+    for expr in plan.expressions() {
+        let _ = expr.apply(&mut |e| {
+            if let Expr::BinaryExpr(BinaryExpr { op, .. }) = e {
+                if !is_standard(op) {
+                    found = Some(*op);
+                    return Ok(VisitRecursion::Stop);
+                }
+            }
+            Ok(VisitRecursion::Continue)
+        });
+        if found.is_some() {
+            break;
+        }
+    }
+    found
+}
+
+fn is_standard(op: &Operator) -> bool {
+    !matches!(
+        op,
+        Operator::Modulo
+            | Operator::RegexMatch
+            | Operator::RegexIMatch
+            | Operator::RegexNotMatch
+            | Operator::RegexNotIMatch
+            | Operator::BitwiseAnd
+            | Operator::BitwiseOr
+            | Operator::BitwiseXor
+            | Operator::BitwiseShiftRight
+            | Operator::BitwiseShiftLeft
+            | Operator::AtArrow
+            | Operator::ArrowAt
+    )
+}
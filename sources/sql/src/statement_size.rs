@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+use datafusion::{
+    error::Result,
+    logical_expr::{
+        expr::InList,
+        utils::{conjunction, split_conjunction},
+        Expr, Filter, LogicalPlan,
+    },
+};
+
+// Splits a plan on the largest IN list of its filter into `parts` plans,
+// the union of which returns the same rows. Only plans with projections
+// and filters on top of the IN list are split, since e.g. a LIMIT or
+// aggregate can't be applied to the parts separately.
+pub(crate) fn split_in_list(plan: &LogicalPlan, parts: usize) -> Result<Option<Vec<LogicalPlan>>> {
+    match plan {
+        LogicalPlan::Filter(filter) => {
+            let conjuncts = split_conjunction(&filter.predicate);
+            let largest = conjuncts
+                .iter()
+                .enumerate()
+                .filter_map(|(i, e)| match e {
+                    Expr::InList(InList {
+                        list,
+                        negated: false,
+                        ..
+                    }) if list.len() > 1 => Some((i, list.len())),
+                    _ => None,
+                })
+                .max_by_key(|(_, len)| *len);
+            let Some((index, _)) = largest else {
+                return split_input(plan, parts);
+            };
+            let Expr::InList(in_list) = conjuncts[index] else {
+                return Ok(None);
+            };
+
+            // Duplicate values would return rows twice
+            let mut seen = HashSet::new();
+            let list = in_list
+                .list
+                .iter()
+                .filter(|e| seen.insert(*e))
+                .cloned()
+                .collect::<Vec<_>>();
+            let chunk_size = list.len().div_ceil(parts).max(1);
+            list.chunks(chunk_size)
+                .map(|values| {
+                    let mut predicates = conjuncts.iter().map(|e| (*e).clone()).collect::<Vec<_>>();
+                    predicates[index] =
+                        Expr::InList(InList::new(in_list.expr.clone(), values.to_vec(), false));
+                    let predicate = conjunction(predicates).unwrap();
+                    Ok(LogicalPlan::Filter(Filter::try_new(
+                        predicate,
+                        filter.input.clone(),
+                    )?))
+                })
+                .collect::<Result<Vec<_>>>()
+                .map(Some)
+        }
+        LogicalPlan::Projection(_) | LogicalPlan::SubqueryAlias(_) => split_input(plan, parts),
+        _ => Ok(None),
+    }
+}
+
+fn split_input(plan: &LogicalPlan, parts: usize) -> Result<Option<Vec<LogicalPlan>>> {
+    let Some(inputs) = split_in_list(plan.inputs()[0], parts)? else {
+        return Ok(None);
+    };
+    inputs
+        .into_iter()
+        .map(|input| plan.with_new_inputs(&[input]))
+        .collect::<Result<Vec<_>>>()
+        .map(Some)
+}
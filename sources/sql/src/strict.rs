@@ -0,0 +1,90 @@
+use datafusion::{
+    arrow::{array::Array, datatypes::SchemaRef, record_batch::RecordBatch},
+    error::{DataFusionError, Result},
+    physical_plan::{stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::StreamExt;
+
+// Checks every fetched batch against the declared schema, so values that
+// don't fit fail with the offending column and row instead of panicking
+// in downstream kernels.
+pub(crate) fn verify_stream(
+    stream: SendableRecordBatchStream,
+    expected: SchemaRef,
+    sql: String,
+) -> SendableRecordBatchStream {
+    let mut batch_index = 0;
+    let mut rows = 0;
+    let schema = expected.clone();
+    let stream = stream.map(move |batch| {
+        let batch = batch?;
+        verify_batch(&batch, &expected, batch_index, rows)
+            .map_err(|msg| DataFusionError::Execution(format!("{msg}\nsql: {sql}")))?;
+        batch_index += 1;
+        rows += batch.num_rows();
+        Ok(batch)
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, stream))
+}
+
+fn verify_batch(
+    batch: &RecordBatch,
+    expected: &SchemaRef,
+    batch_index: usize,
+    rows: usize,
+) -> std::result::Result<(), String> {
+    if batch.num_columns() != expected.fields().len() {
+        return Err(format!(
+            "batch {batch_index} has {} columns, declared {}",
+            batch.num_columns(),
+            expected.fields().len()
+        ));
+    }
+    for (column, field) in batch.columns().iter().zip(expected.fields()) {
+        if column.data_type() != field.data_type() {
+            return Err(format!(
+                "column {} in batch {batch_index} has type {}, declared {}",
+                field.name(),
+                column.data_type(),
+                field.data_type()
+            ));
+        }
+        if !field.is_nullable() && column.null_count() > 0 {
+            let row = (0..column.len()).find(|i| column.is_null(*i)).unwrap_or(0);
+            return Err(format!(
+                "NULL in non-nullable column {} at row {} (batch {batch_index}, offset {row})",
+                field.name(),
+                rows + row
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use datafusion::arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field, Schema},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_verify_batch() {
+        let declared = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, false)]));
+        let fetched = Arc::new(Schema::new(vec![Field::new("id", DataType::Int32, true)]));
+        let batch = RecordBatch::try_new(
+            fetched,
+            vec![Arc::new(Int32Array::from(vec![Some(1), None]))],
+        )
+        .unwrap();
+
+        assert_eq!(
+            verify_batch(&batch, &declared, 2, 10).unwrap_err(),
+            "NULL in non-nullable column id at row 11 (batch 2, offset 1)"
+        );
+    }
+}
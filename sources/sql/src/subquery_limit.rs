@@ -0,0 +1,114 @@
+use std::sync::Arc;
+
+use datafusion::{
+    common::tree_node::{Transformed, TreeNode},
+    error::Result,
+    logical_expr::{
+        expr::{Exists, InSubquery, WindowFunction},
+        window_function, BuiltInWindowFunction, Expr, Limit, LogicalPlan, LogicalPlanBuilder,
+        Subquery, WindowFrame,
+    },
+    prelude::{col, lit},
+};
+
+const ROW_NUMBER: &str = "__federation_row_number";
+
+// Rewrites LIMITs inside IN/EXISTS/scalar subqueries and derived tables to
+// a ROW_NUMBER() filter, for dialects that don't accept them there.
+pub(crate) fn rewrite_subquery_limits(plan: LogicalPlan) -> Result<LogicalPlan> {
+    plan.transform_up(&|plan| {
+        let plan = match plan {
+            LogicalPlan::SubqueryAlias(_) | LogicalPlan::Subquery(_) => {
+                let inputs = plan
+                    .inputs()
+                    .into_iter()
+                    .map(|input| rewrite_limits(input.clone()))
+                    .collect::<Result<Vec<_>>>()?;
+                plan.with_new_inputs(&inputs)?
+            }
+            _ => plan,
+        };
+
+        let exprs = plan
+            .expressions()
+            .into_iter()
+            .map(|expr| expr.transform_up(&rewrite_subquery_expr))
+            .collect::<Result<Vec<_>>>()?;
+        if exprs == plan.expressions() {
+            return Ok(Transformed::Yes(plan));
+        }
+        let inputs = plan.inputs().into_iter().cloned().collect::<Vec<_>>();
+        Ok(Transformed::Yes(plan.with_new_exprs(exprs, &inputs)?))
+    })
+}
+
+fn rewrite_subquery_expr(expr: Expr) -> Result<Transformed<Expr>> {
+    let rewrite = |subquery: Subquery| -> Result<Subquery> {
+        Ok(Subquery {
+            subquery: Arc::new(rewrite_limits(subquery.subquery.as_ref().clone())?),
+            outer_ref_columns: subquery.outer_ref_columns,
+        })
+    };
+    Ok(match expr {
+        Expr::InSubquery(InSubquery {
+            expr,
+            subquery,
+            negated,
+        }) => Transformed::Yes(Expr::InSubquery(InSubquery::new(
+            expr,
+            rewrite(subquery)?,
+            negated,
+        ))),
+        Expr::Exists(Exists { subquery, negated }) => Transformed::Yes(Expr::Exists(Exists {
+            subquery: rewrite(subquery)?,
+            negated,
+        })),
+        Expr::ScalarSubquery(subquery) => {
+            Transformed::Yes(Expr::ScalarSubquery(rewrite(subquery)?))
+        }
+        _ => Transformed::No(expr),
+    })
+}
+
+fn rewrite_limits(plan: LogicalPlan) -> Result<LogicalPlan> {
+    // Nested subqueries are rewritten as well
+    let plan = rewrite_subquery_limits(plan)?;
+    plan.transform_up(&|plan| match plan {
+        LogicalPlan::Limit(limit) => Ok(Transformed::Yes(limit_to_row_number(&limit)?)),
+        _ => Ok(Transformed::No(plan)),
+    })
+}
+
+// LIMIT n OFFSET m becomes
+// `SELECT cols FROM (SELECT *, ROW_NUMBER() OVER (ORDER BY ..) AS rn FROM input) WHERE rn > m AND rn <= m + n`
+fn limit_to_row_number(limit: &Limit) -> Result<LogicalPlan> {
+    let (order_by, input) = match limit.input.as_ref() {
+        LogicalPlan::Sort(sort) if sort.fetch.is_none() => (sort.expr.clone(), sort.input.clone()),
+        _ => (vec![], limit.input.clone()),
+    };
+    let columns = input
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| Expr::Column(f.qualified_column()))
+        .collect::<Vec<_>>();
+    let row_number = Expr::WindowFunction(WindowFunction::new(
+        window_function::WindowFunction::BuiltInWindowFunction(BuiltInWindowFunction::RowNumber),
+        vec![],
+        vec![],
+        order_by.clone(),
+        WindowFrame::new(!order_by.is_empty()),
+    ))
+    .alias(ROW_NUMBER);
+
+    let skip = limit.skip as u64;
+    let mut predicate = col(ROW_NUMBER).gt(lit(skip));
+    if let Some(fetch) = limit.fetch {
+        predicate = predicate.and(col(ROW_NUMBER).lt_eq(lit(skip + fetch as u64)));
+    }
+    LogicalPlanBuilder::from(input.as_ref().clone())
+        .window(vec![row_number])?
+        .filter(predicate)?
+        .project(columns)?
+        .build()
+}
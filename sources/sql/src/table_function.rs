@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::datatypes::{DataType, SchemaRef},
+    common::plan_err,
+    datasource::{function::TableFunctionImpl, TableProvider},
+    error::Result,
+    logical_expr::Expr,
+    scalar::ScalarValue,
+};
+use datafusion_federation::FederatedTableProviderAdaptor;
+
+use crate::{SQLFederationProvider, SQLTableSource};
+
+// SQLTableFunction is a remote table function, or a procedure returning rows,
+// read as a federated table with `SELECT * FROM fn(args)`. Registered with
+// `SessionContext::register_udtf`, its arguments are bound per query:
+// `SELECT * FROM orders_since(DATE '2024-01-01')`.
+pub struct SQLTableFunction {
+    provider: Arc<SQLFederationProvider>,
+    // The qualified name of the function in the source
+    name: String,
+    parameters: Vec<DataType>,
+    schema: SchemaRef,
+}
+
+impl SQLTableFunction {
+    // The parameter types and the result schema are declared, as they can't
+    // be inferred without calling the function.
+    pub fn new(
+        provider: Arc<SQLFederationProvider>,
+        name: impl Into<String>,
+        parameters: Vec<DataType>,
+        schema: SchemaRef,
+    ) -> Self {
+        Self {
+            provider,
+            name: name.into(),
+            parameters,
+            schema,
+        }
+    }
+
+    // Returns the function called with the given arguments, e.g. to register
+    // it as a table.
+    pub fn table(&self, args: Vec<ScalarValue>) -> Result<Arc<dyn TableProvider>> {
+        if args.len() != self.parameters.len() {
+            return plan_err!(
+                "table function {} takes {} arguments, got {}",
+                self.name,
+                self.parameters.len(),
+                args.len()
+            );
+        }
+        let args = args
+            .into_iter()
+            .zip(&self.parameters)
+            .map(|(arg, data_type)| arg.cast_to(data_type))
+            .collect::<Result<Vec<_>>>()?;
+        let source = SQLTableSource::new_with_schema(
+            self.provider.clone(),
+            self.name.clone(),
+            self.schema.clone(),
+        )?
+        .with_function_args(args);
+        Ok(Arc::new(FederatedTableProviderAdaptor::new(Arc::new(
+            source,
+        ))))
+    }
+}
+
+impl TableFunctionImpl for SQLTableFunction {
+    fn call(&self, args: &[Expr]) -> Result<Arc<dyn TableProvider>> {
+        let args = args
+            .iter()
+            .map(|arg| match arg {
+                Expr::Literal(value) => Ok(value.clone()),
+                _ => plan_err!("table function {} takes literal arguments", self.name),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.table(args)
+    }
+}
@@ -0,0 +1,39 @@
+use core::fmt;
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::record_batch::RecordBatch,
+    error::Result,
+    physical_plan::{metrics::Count, stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::StreamExt;
+
+// BatchTransform is applied to every batch fetched from a source before it
+// enters the local plan, e.g. to drop rows matching a deny-list or to hash
+// a column, for compliance layers that can't rely on the remote side.
+// The transformed batch must keep the schema of the fetched batch.
+pub trait BatchTransform: Send + Sync {
+    fn name(&self) -> &str;
+    // Returns the transformed batch and the number of rows affected.
+    fn transform(&self, batch: RecordBatch) -> Result<(RecordBatch, usize)>;
+}
+
+impl fmt::Debug for dyn BatchTransform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub(crate) fn transform_stream(
+    stream: SendableRecordBatchStream,
+    transform: Arc<dyn BatchTransform>,
+    rows_affected: Count,
+) -> SendableRecordBatchStream {
+    let schema = stream.schema();
+    let stream = stream.map(move |batch| {
+        let (batch, affected) = transform.transform(batch?)?;
+        rows_affected.add(affected);
+        Ok(batch)
+    });
+    Box::pin(RecordBatchStreamAdapter::new(schema, stream))
+}
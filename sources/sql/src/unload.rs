@@ -0,0 +1,227 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use datafusion::{
+    arrow::{datatypes::SchemaRef, record_batch::RecordBatch},
+    common::not_impl_err,
+    datasource::{
+        file_format::parquet::ParquetFormat,
+        listing::{ListingOptions, ListingTable, ListingTableConfig, ListingTableUrl},
+        TableProvider,
+    },
+    error::{DataFusionError, Result},
+    execution::{context::SessionState, TaskContext},
+    physical_plan::{execute_stream, stream::RecordBatchStreamAdapter, SendableRecordBatchStream},
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use log::{debug, warn};
+
+use crate::{dialect::SQLDialect, executor::SQLExecutor};
+
+// Unload hands results over through object storage instead of the driver:
+// the source writes them as Parquet files, which are then read from the
+// object store registered for `url` in the runtime env. Meant for very large
+// results of warehouses, e.g. Snowflake, BigQuery and Redshift.
+#[derive(Debug, Clone)]
+pub struct Unload {
+    // Where the source writes the files, e.g. a Snowflake stage `@exports/federation`
+    // or `s3://bucket/federation`. Each query writes under its own prefix.
+    pub location: String,
+    // The URL the location is read from, e.g. `s3://bucket/federation`
+    pub url: String,
+    // Extra clauses of the unload statement, e.g. Redshift's `IAM_ROLE '...'`
+    pub options: Option<String>,
+    // Deletes the files once they are read
+    pub cleanup: bool,
+}
+
+impl Unload {
+    pub fn new(location: impl Into<String>, url: impl Into<String>) -> Self {
+        Self {
+            location: location.into(),
+            url: url.into(),
+            options: None,
+            cleanup: true,
+        }
+    }
+
+    pub fn with_options(mut self, options: impl Into<String>) -> Self {
+        self.options = Some(options.into());
+        self
+    }
+
+    pub fn with_cleanup(mut self, cleanup: bool) -> Self {
+        self.cleanup = cleanup;
+        self
+    }
+}
+
+// A prefix unique to each unloaded query.
+pub(crate) fn next_prefix() -> String {
+    static COUNT: AtomicU64 = AtomicU64::new(0);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    format!("federation-{now}-{}", COUNT.fetch_add(1, Ordering::Relaxed))
+}
+
+// Unloads the query's result and streams the files written, in the schema.
+pub(crate) async fn unload_stream(
+    executor: &dyn SQLExecutor,
+    dialect: &dyn SQLDialect,
+    unload: &Unload,
+    query: &str,
+    schema: SchemaRef,
+    context: Arc<TaskContext>,
+) -> Result<SendableRecordBatchStream> {
+    let prefix = next_prefix();
+    let location = format!("{}/{prefix}", unload.location.trim_end_matches('/'));
+    let Some(statement) = dialect.unload(query, &location, unload.options.as_deref()) else {
+        return not_impl_err!("unload for dialect {}", dialect.name());
+    };
+    debug!(
+        "federation rule=federate_sql decision=unload context={:?} location={location}",
+        executor.compute_context()
+    );
+    executor.execute_statement(&statement).await?;
+
+    let url = ListingTableUrl::parse(format!("{}/{prefix}/", unload.url.trim_end_matches('/')))?;
+    let state =
+        SessionState::new_with_config_rt(context.session_config().clone(), context.runtime_env());
+    let options =
+        ListingOptions::new(Arc::new(ParquetFormat::default())).with_file_extension(".parquet");
+    let config = ListingTableConfig::new(url.clone())
+        .with_listing_options(options)
+        .with_schema(schema.clone());
+    let plan = ListingTable::try_new(config)?
+        .scan(&state, None, &[], None)
+        .await?;
+    let files = execute_stream(plan, context.clone())?;
+    if !unload.cleanup {
+        return Ok(files);
+    }
+
+    // The files are deleted once the stream is drained
+    let cleanup = stream::once(async move {
+        let store = context.runtime_env().object_store(&url)?;
+        let objects = store
+            .list(Some(url.prefix()))
+            .try_collect::<Vec<_>>()
+            .await?;
+        for object in objects {
+            store.delete(&object.location).await?;
+        }
+        Ok::<_, DataFusionError>(())
+    })
+    .filter_map(|result| async move {
+        if let Err(e) = result {
+            warn!("federation unload cleanup failed: {e}");
+        }
+        None::<Result<RecordBatch>>
+    });
+    Ok(Box::pin(RecordBatchStreamAdapter::new(
+        schema,
+        files.chain(cleanup),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use async_trait::async_trait;
+    use datafusion::{
+        arrow::{
+            array::Int64Array,
+            datatypes::{DataType, Field, Schema},
+        },
+        execution::context::SessionContext,
+        parquet::arrow::ArrowWriter,
+    };
+
+    use super::*;
+    use crate::executor::MemorySQLExecutor;
+
+    // Unloads as `<location>|<query>`
+    struct UnloadDialect {}
+
+    impl SQLDialect for UnloadDialect {
+        fn name(&self) -> &str {
+            "unload"
+        }
+        fn unload(&self, query: &str, location: &str, _options: Option<&str>) -> Option<String> {
+            Some(format!("{location}|{query}"))
+        }
+    }
+
+    // Writes the result of the unloaded query to a Parquet file.
+    struct Unloader(MemorySQLExecutor);
+
+    #[async_trait]
+    impl SQLExecutor for Unloader {
+        fn name(&self) -> &str {
+            self.0.name()
+        }
+        fn compute_context(&self) -> Option<String> {
+            self.0.compute_context()
+        }
+        async fn execute(&self, query: &str) -> Result<SendableRecordBatchStream> {
+            self.0.execute(query).await
+        }
+        async fn execute_statement(&self, statement: &str) -> Result<u64> {
+            let (location, query) = statement.split_once('|').unwrap();
+            let batches: Vec<RecordBatch> = self.0.execute(query).await?.try_collect().await?;
+            fs::create_dir_all(location)?;
+            let file = File::create(format!("{location}/part-0.parquet"))?;
+            let mut writer = ArrowWriter::try_new(file, batches[0].schema(), None)?;
+            for batch in &batches {
+                writer.write(batch)?;
+            }
+            writer.close()?;
+            Ok(0)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unload_stream() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![1, 2]))])
+                .unwrap();
+        let executor = Unloader(
+            MemorySQLExecutor::new("memory")
+                .with_batch("t", batch)
+                .unwrap(),
+        );
+        let dir = std::env::temp_dir().join(next_prefix());
+        let dir = dir.to_str().unwrap();
+        let unload = Unload::new(dir, dir);
+
+        let stream = unload_stream(
+            &executor,
+            &UnloadDialect {},
+            &unload,
+            "SELECT id FROM t",
+            schema,
+            SessionContext::new().task_ctx(),
+        )
+        .await
+        .unwrap();
+        let batches: Vec<RecordBatch> = stream.try_collect().await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+
+        // The files are deleted once read
+        let files = fs::read_dir(dir)
+            .unwrap()
+            .flatten()
+            .flat_map(|prefix| fs::read_dir(prefix.path()).into_iter().flatten().flatten());
+        assert_eq!(files.count(), 0);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
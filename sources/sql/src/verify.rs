@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use datafusion::{
+    arrow::{
+        record_batch::RecordBatch,
+        util::display::{ArrayFormatter, FormatOptions},
+    },
+    common::tree_node::{Transformed, TreeNode},
+    dataframe::DataFrame,
+    error::Result,
+    execution::context::SessionContext,
+    logical_expr::{Expr, Extension, LogicalPlan},
+};
+use datafusion_federation::{get_table_source, FederatedPlanNode};
+
+use crate::SQLTableSource;
+
+// PushdownCheck is the outcome of verify_pushdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PushdownCheck {
+    pub sql: String,
+    pub remote_rows: usize,
+    pub local_rows: usize,
+    pub mismatches: Vec<RowMismatch>,
+}
+
+impl PushdownCheck {
+    pub fn passed(&self) -> bool {
+        self.remote_rows == self.local_rows && self.mismatches.is_empty()
+    }
+}
+
+// RowMismatch is a sampled row that differs, None if the side has fewer rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RowMismatch {
+    pub row: usize,
+    pub remote: Option<String>,
+    pub local: Option<String>,
+}
+
+// Runs the query twice, once with the federation pushing it down and once
+// computing it locally from the raw remote tables, and compares the first
+// `sample` rows of both in the order of all output columns. Meant for CI
+// against a live backend, to validate a dialect's pushdown. Values are
+// compared as displayed, so floating point aggregates may differ in the
+// last digits.
+pub async fn verify_pushdown(
+    ctx: &SessionContext,
+    sql: &str,
+    sample: usize,
+) -> Result<PushdownCheck> {
+    let df = ctx.sql(sql).await?;
+    let order = df
+        .schema()
+        .fields()
+        .iter()
+        .map(|f| Expr::Column(f.qualified_column()).sort(true, true))
+        .collect::<Vec<_>>();
+    let df = df.sort(order)?.limit(0, Some(sample))?;
+
+    let local_plan = scans_only(df.logical_plan().clone())?;
+    let remote = rows(&df.collect().await?)?;
+    let local = rows(&DataFrame::new(ctx.state(), local_plan).collect().await?)?;
+
+    let mismatches = (0..remote.len().max(local.len()))
+        .filter_map(|row| {
+            let (remote, local) = (remote.get(row), local.get(row));
+            (remote != local).then(|| RowMismatch {
+                row,
+                remote: remote.cloned(),
+                local: local.cloned(),
+            })
+        })
+        .collect();
+    Ok(PushdownCheck {
+        sql: sql.to_string(),
+        remote_rows: remote.len(),
+        local_rows: local.len(),
+        mismatches,
+    })
+}
+
+// Federates only the table scans, everything else is computed locally.
+fn scans_only(plan: LogicalPlan) -> Result<LogicalPlan> {
+    plan.transform_up(&|plan| {
+        let LogicalPlan::TableScan(scan) = &plan else {
+            return Ok(Transformed::No(plan));
+        };
+        let Ok(source) = get_table_source(scan.source.clone()) else {
+            return Ok(Transformed::No(plan));
+        };
+        let Some(source) = source.as_any().downcast_ref::<SQLTableSource>() else {
+            return Ok(Transformed::No(plan));
+        };
+        let planner = Arc::new(source.provider().planner.clone());
+        let node = FederatedPlanNode::new(plan.clone(), planner);
+        Ok(Transformed::Yes(LogicalPlan::Extension(Extension {
+            node: Arc::new(node),
+        })))
+    })
+}
+
+fn rows(batches: &[RecordBatch]) -> Result<Vec<String>> {
+    let options = FormatOptions::default().with_null("NULL");
+    let mut rows = vec![];
+    for batch in batches {
+        let formatters = batch
+            .columns()
+            .iter()
+            .map(|c| ArrayFormatter::try_new(c.as_ref(), &options))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        for row in 0..batch.num_rows() {
+            let values = formatters
+                .iter()
+                .map(|f| f.value(row).to_string())
+                .collect::<Vec<_>>();
+            rows.push(format!("({})", values.join(", ")));
+        }
+    }
+    Ok(rows)
+}
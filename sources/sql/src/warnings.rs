@@ -0,0 +1,42 @@
+use std::sync::{Arc, Mutex};
+
+// RemoteWarning is a notice or warning a backend raised while executing
+// a federated query, e.g. a Postgres NOTICE or a truncation warning.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemoteWarning {
+    pub compute_context: Option<String>,
+    pub sql: String,
+    pub message: String,
+}
+
+// RemoteWarnings collects the warnings raised during federated execution.
+// Install it in the session config extensions to read them after the query:
+// `SessionConfig::new().with_extension(Arc::new(RemoteWarnings::default()))`
+#[derive(Debug, Clone, Default)]
+pub struct RemoteWarnings {
+    warnings: Arc<Mutex<Vec<RemoteWarning>>>,
+}
+
+impl RemoteWarnings {
+    pub fn push(&self, warning: RemoteWarning) {
+        self.warnings.lock().unwrap().push(warning);
+    }
+
+    // Returns the warnings collected so far.
+    pub fn warnings(&self) -> Vec<RemoteWarning> {
+        self.warnings.lock().unwrap().clone()
+    }
+
+    // Returns and clears the warnings collected so far.
+    pub fn take(&self) -> Vec<RemoteWarning> {
+        std::mem::take(&mut self.warnings.lock().unwrap())
+    }
+
+    pub fn len(&self) -> usize {
+        self.warnings.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -0,0 +1,113 @@
+use core::fmt;
+use std::{collections::HashMap, sync::Arc};
+
+use crate::executor::SQLExecutor;
+
+// WorkloadClass labels remote queries for the backend's resource governor.
+// It is read from the session config extensions, queries without it use
+// the provider's default class:
+// `SessionConfig::new().with_extension(Arc::new(WorkloadClass::Batch))`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum WorkloadClass {
+    Interactive,
+    Batch,
+    Etl,
+    Custom(String),
+}
+
+impl fmt::Display for WorkloadClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WorkloadClass::Interactive => write!(f, "interactive"),
+            WorkloadClass::Batch => write!(f, "batch"),
+            WorkloadClass::Etl => write!(f, "etl"),
+            WorkloadClass::Custom(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+// WorkloadGovernor maps a workload class to the backend's governance
+// primitives when a query is dispatched.
+pub trait WorkloadGovernor: Send + Sync {
+    // Returns the executor to run the class on, e.g. one connected to a
+    // dedicated Snowflake warehouse. `None` uses the provider's executor.
+    fn executor(&self, _class: &WorkloadClass) -> Option<Arc<dyn SQLExecutor>> {
+        None
+    }
+
+    // Rewrites the query for the class, e.g. to add a hint or label.
+    fn rewrite(&self, _class: &WorkloadClass, sql: String) -> String {
+        sql
+    }
+}
+
+impl fmt::Debug for dyn WorkloadGovernor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WorkloadGovernor")
+    }
+}
+
+// WorkloadRouter runs each class on its own executor, e.g. per Snowflake
+// warehouse or BigQuery reservation.
+#[derive(Default)]
+pub struct WorkloadRouter {
+    executors: HashMap<WorkloadClass, Arc<dyn SQLExecutor>>,
+}
+
+impl WorkloadRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn route(mut self, class: WorkloadClass, executor: Arc<dyn SQLExecutor>) -> Self {
+        self.executors.insert(class, executor);
+        self
+    }
+}
+
+impl WorkloadGovernor for WorkloadRouter {
+    fn executor(&self, class: &WorkloadClass) -> Option<Arc<dyn SQLExecutor>> {
+        self.executors.get(class).cloned()
+    }
+}
+
+// WorkloadPrefix prepends a per class prefix to the query, e.g. a pg_hint_plan
+// hint `/*+ Set(work_mem "1GB") */` or a `/* workload=etl */` label.
+#[derive(Default)]
+pub struct WorkloadPrefix {
+    prefixes: HashMap<WorkloadClass, String>,
+}
+
+impl WorkloadPrefix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn prefix(mut self, class: WorkloadClass, prefix: impl Into<String>) -> Self {
+        self.prefixes.insert(class, prefix.into());
+        self
+    }
+
+    // Labels every class with a `/* workload=<class> */` comment.
+    pub fn labels() -> Self {
+        let mut prefixes = Self::new();
+        for class in [
+            WorkloadClass::Interactive,
+            WorkloadClass::Batch,
+            WorkloadClass::Etl,
+        ] {
+            let label = format!("/* workload={class} */");
+            prefixes = prefixes.prefix(class, label);
+        }
+        prefixes
+    }
+}
+
+impl WorkloadGovernor for WorkloadPrefix {
+    fn rewrite(&self, class: &WorkloadClass, sql: String) -> String {
+        match self.prefixes.get(class) {
+            Some(prefix) => format!("{prefix} {sql}"),
+            None => sql,
+        }
+    }
+}
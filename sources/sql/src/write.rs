@@ -0,0 +1,717 @@
+use async_trait::async_trait;
+use core::fmt;
+use datafusion::{
+    arrow::{compute::cast, datatypes::SchemaRef, record_batch::RecordBatch},
+    common::{plan_err, ScalarValue},
+    error::{DataFusionError, Result},
+    execution::TaskContext,
+    physical_expr::PhysicalSortExpr,
+    physical_plan::{
+        insert::DataSink, stream::RecordBatchStreamAdapter, DisplayAs, DisplayFormatType,
+        ExecutionPlan, Partitioning, SendableRecordBatchStream,
+    },
+    sql::sqlparser::ast,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use log::warn;
+use std::{any::Any, collections::HashSet, sync::Arc};
+
+use crate::{
+    dialect::{SQLDialect, UpsertStrategy},
+    executor::SQLExecutor,
+    producer::literal_to_sql,
+};
+
+// WriteOptions control how writes are split into statements and transactions.
+#[derive(Clone)]
+pub struct WriteOptions {
+    pub rows_per_statement: usize,
+    // Statements run in one transaction, each statement commits on its own if None
+    pub statements_per_transaction: Option<usize>,
+    pub on_failure: WriteFailurePolicy,
+    // Called after every statement or transaction
+    pub progress: Option<Arc<dyn Fn(&WriteProgress) + Send + Sync>>,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self {
+            rows_per_statement: 1000,
+            statements_per_transaction: None,
+            on_failure: WriteFailurePolicy::Abort,
+            progress: None,
+        }
+    }
+}
+
+impl fmt::Debug for WriteOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WriteOptions")
+            .field("rows_per_statement", &self.rows_per_statement)
+            .field(
+                "statements_per_transaction",
+                &self.statements_per_transaction,
+            )
+            .field("on_failure", &self.on_failure)
+            .finish()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteFailurePolicy {
+    // Fail the write on the first failed statement or transaction
+    Abort,
+    // Skip the rows of failed statements or transactions and report them
+    SkipAndReport,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WriteProgress {
+    pub rows_written: u64,
+    pub rows_failed: u64,
+    pub statements: usize,
+    pub errors: Vec<String>,
+}
+
+// StatementWriter executes write statements in chunks as configured by the options.
+pub(crate) struct StatementWriter {
+    executor: Arc<dyn SQLExecutor>,
+    options: WriteOptions,
+    // Pending statements of the current transaction, with their row counts
+    pending: Vec<(String, usize)>,
+    progress: WriteProgress,
+}
+
+impl StatementWriter {
+    pub(crate) fn new(executor: Arc<dyn SQLExecutor>, options: WriteOptions) -> Self {
+        Self {
+            executor,
+            options,
+            pending: vec![],
+            progress: WriteProgress::default(),
+        }
+    }
+
+    pub(crate) fn rows_per_statement(&self) -> usize {
+        self.options.rows_per_statement.max(1)
+    }
+
+    pub(crate) async fn push(&mut self, statement: String, rows: usize) -> Result<()> {
+        self.pending.push((statement, rows));
+        if self.pending.len() >= self.options.statements_per_transaction.unwrap_or(1) {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    // Flushes the pending statements, returns the number of written rows.
+    pub(crate) async fn finish(mut self) -> Result<u64> {
+        self.flush().await?;
+        Ok(self.progress.rows_written)
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let pending = std::mem::take(&mut self.pending);
+        let rows = pending.iter().map(|(_, rows)| *rows as u64).sum::<u64>();
+        let result = if self.options.statements_per_transaction.is_some() {
+            let statements = pending.iter().map(|(s, _)| s.clone()).collect::<Vec<_>>();
+            match self.executor.execute_transaction(&statements).await {
+                // Each statement commits on its own instead
+                Err(DataFusionError::NotImplemented(reason)) => {
+                    warn!(
+                        "federation write decision=no_transaction statements={} reason=\"{reason}\"",
+                        statements.len()
+                    );
+                    self.execute_each(&statements).await
+                }
+                result => result,
+            }
+        } else {
+            self.executor.execute_statement(pending[0].0.as_str()).await
+        };
+        self.progress.statements += pending.len();
+        match result {
+            Ok(count) => self.progress.rows_written += count,
+            Err(e) if self.options.on_failure == WriteFailurePolicy::SkipAndReport => {
+                warn!("federation write_failed rows={rows} error=\"{e}\"");
+                self.progress.rows_failed += rows;
+                self.progress.errors.push(e.to_string());
+            }
+            Err(e) => return Err(e),
+        }
+        if let Some(progress) = &self.options.progress {
+            progress(&self.progress);
+        }
+        Ok(())
+    }
+
+    async fn execute_each(&self, statements: &[String]) -> Result<u64> {
+        let mut count = 0;
+        for statement in statements {
+            count += self.executor.execute_statement(statement).await?;
+        }
+        Ok(count)
+    }
+}
+
+// SQLInsertSink writes batches to a remote table with INSERT statements.
+pub(crate) struct SQLInsertSink {
+    executor: Arc<dyn SQLExecutor>,
+    options: WriteOptions,
+    table: Vec<String>,
+    // The source name of each input column
+    columns: Vec<String>,
+    // Columns generated by the source, e.g. identity or auto-increment columns
+    generated: HashSet<String>,
+    // Columns with a default in the source
+    defaults: HashSet<String>,
+}
+
+impl SQLInsertSink {
+    pub(crate) fn new(
+        executor: Arc<dyn SQLExecutor>,
+        table: Vec<String>,
+        columns: Vec<String>,
+        generated: HashSet<String>,
+        defaults: HashSet<String>,
+        options: WriteOptions,
+    ) -> Self {
+        Self {
+            executor,
+            options,
+            table,
+            columns,
+            generated,
+            defaults,
+        }
+    }
+
+    // Generated columns are always left to the source. Columns with a default
+    // are left out if the batch has no values for them, so the default applies.
+    pub(crate) fn insert_statement(&self, batch: &RecordBatch) -> Result<String> {
+        let included = self
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(i, name)| {
+                !self.generated.contains(*name)
+                    && !(self.defaults.contains(*name)
+                        && batch.column(*i).null_count() == batch.num_rows())
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+
+        let dialect = self.executor.dialect();
+        let table = object_name(&self.table, dialect.as_ref());
+        // Only a single row can be inserted without values
+        if included.is_empty() {
+            return match batch.num_rows() {
+                1 => Ok(format!("INSERT INTO {table} DEFAULT VALUES")),
+                rows => plan_err!(
+                    "cannot insert {rows} rows into {table}, every column is generated or defaulted"
+                ),
+            };
+        }
+        let columns = included
+            .iter()
+            .map(|i| quoted_ident(&self.columns[*i], dialect.as_ref()).to_string())
+            .collect::<Vec<_>>();
+        let values = batch_values(batch, &included, dialect.as_ref())?;
+        Ok(format!(
+            "INSERT INTO {table} ({}) {values}",
+            columns.join(", ")
+        ))
+    }
+}
+
+// Renders an upsert of the batch into the table, rows matching an existing row
+// on the key columns update it. The batch columns are named as in the source.
+pub(crate) fn upsert_statement(
+    table: &[String],
+    batch: &RecordBatch,
+    keys: &[String],
+    strategy: UpsertStrategy,
+    dialect: &dyn SQLDialect,
+) -> Result<String> {
+    let schema = batch.schema();
+    let names = schema.fields().iter().map(|f| f.name()).collect::<Vec<_>>();
+    for key in keys {
+        if !names.contains(&key) {
+            return plan_err!("upsert key {key} is not a column of the input");
+        }
+    }
+    let ident = |name: &str| quoted_ident(name, dialect).to_string();
+    let columns = names.iter().map(|n| ident(n)).collect::<Vec<_>>();
+    let updated = names
+        .iter()
+        .filter(|n| !keys.contains(*n))
+        .map(|n| ident(n))
+        .collect::<Vec<_>>();
+    let table = object_name(table, dialect);
+    let values = batch_values(batch, &(0..names.len()).collect::<Vec<_>>(), dialect)?;
+
+    Ok(match strategy {
+        UpsertStrategy::OnConflict => {
+            let keys = keys.iter().map(|k| ident(k)).collect::<Vec<_>>();
+            let action = if updated.is_empty() {
+                "DO NOTHING".to_string()
+            } else {
+                let set = updated
+                    .iter()
+                    .map(|c| format!("{c} = EXCLUDED.{c}"))
+                    .collect::<Vec<_>>();
+                format!("DO UPDATE SET {}", set.join(", "))
+            };
+            format!(
+                "INSERT INTO {table} ({}) {values} ON CONFLICT ({}) {action}",
+                columns.join(", "),
+                keys.join(", ")
+            )
+        }
+        UpsertStrategy::OnDuplicateKey => {
+            // Without non-key columns the keys are set to themselves to ignore duplicates
+            let set = if updated.is_empty() {
+                &columns
+            } else {
+                &updated
+            }
+            .iter()
+            .map(|c| format!("{c} = VALUES({c})"))
+            .collect::<Vec<_>>();
+            format!(
+                "INSERT INTO {table} ({}) {values} ON DUPLICATE KEY UPDATE {}",
+                columns.join(", "),
+                set.join(", ")
+            )
+        }
+        UpsertStrategy::Merge => {
+            let on = keys
+                .iter()
+                .map(|k| format!("target.{0} = source.{0}", ident(k)))
+                .collect::<Vec<_>>();
+            let matched = if updated.is_empty() {
+                String::new()
+            } else {
+                let set = updated
+                    .iter()
+                    .map(|c| format!("{c} = source.{c}"))
+                    .collect::<Vec<_>>();
+                format!("WHEN MATCHED THEN UPDATE SET {} ", set.join(", "))
+            };
+            let inserted = columns
+                .iter()
+                .map(|c| format!("source.{c}"))
+                .collect::<Vec<_>>();
+            let clauses = format!(
+                "{matched}WHEN NOT MATCHED THEN INSERT ({}) VALUES ({})",
+                columns.join(", "),
+                inserted.join(", ")
+            );
+            dialect.merge(
+                &table.to_string(),
+                &values,
+                &columns,
+                &on.join(" AND "),
+                &clauses,
+            )
+        }
+    })
+}
+
+fn batch_values(
+    batch: &RecordBatch,
+    columns: &[usize],
+    dialect: &dyn SQLDialect,
+) -> Result<ast::Values> {
+    let rows = (0..batch.num_rows())
+        .map(|row| {
+            columns
+                .iter()
+                .map(|i| {
+                    literal_to_sql(
+                        &ScalarValue::try_from_array(batch.column(*i), row)?,
+                        dialect,
+                    )
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(ast::Values {
+        explicit_row: false,
+        rows,
+    })
+}
+
+// Identifiers are quoted, as in generated queries, since the names are
+// the source's own.
+fn quoted_ident(name: &str, dialect: &dyn SQLDialect) -> ast::Ident {
+    ast::Ident {
+        value: name.to_string(),
+        quote_style: dialect.identifier_quote_style(),
+    }
+}
+
+fn object_name(name: &[String], dialect: &dyn SQLDialect) -> ast::ObjectName {
+    ast::ObjectName(name.iter().map(|p| quoted_ident(p, dialect)).collect())
+}
+
+impl fmt::Debug for SQLInsertSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SQLInsertSink {}", self.table.join("."))
+    }
+}
+
+impl DisplayAs for SQLInsertSink {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SQLInsertSink table={}", self.table.join("."))
+    }
+}
+
+#[async_trait]
+impl DataSink for SQLInsertSink {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn write_all(
+        &self,
+        mut data: SendableRecordBatchStream,
+        _context: &Arc<TaskContext>,
+    ) -> Result<u64> {
+        let mut writer = StatementWriter::new(self.executor.clone(), self.options.clone());
+        let chunk_size = writer.rows_per_statement();
+        while let Some(batch) = data.next().await {
+            for chunk in chunks(&batch?, chunk_size) {
+                let statement = self.insert_statement(&chunk)?;
+                writer.push(statement, chunk.num_rows()).await?;
+            }
+        }
+        writer.finish().await
+    }
+}
+
+// Splits the batch into batches of at most `size` rows, one per statement.
+fn chunks(batch: &RecordBatch, size: usize) -> Vec<RecordBatch> {
+    (0..batch.num_rows())
+        .step_by(size)
+        .map(|offset| batch.slice(offset, size.min(batch.num_rows() - offset)))
+        .collect()
+}
+
+// SQLInsertReturningExec inserts the input with `INSERT ... RETURNING *` and
+// returns the inserted rows, including the values generated by the source,
+// instead of the inserted row count.
+#[derive(Debug)]
+pub(crate) struct SQLInsertReturningExec {
+    sink: Arc<SQLInsertSink>,
+    input: Arc<dyn ExecutionPlan>,
+    schema: SchemaRef,
+}
+
+impl SQLInsertReturningExec {
+    pub(crate) fn new(
+        sink: Arc<SQLInsertSink>,
+        input: Arc<dyn ExecutionPlan>,
+        schema: SchemaRef,
+    ) -> Self {
+        Self {
+            sink,
+            input,
+            schema,
+        }
+    }
+}
+
+impl DisplayAs for SQLInsertReturningExec {
+    fn fmt_as(&self, _t: DisplayFormatType, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SQLInsertReturningExec table={}",
+            self.sink.table.join(".")
+        )
+    }
+}
+
+impl ExecutionPlan for SQLInsertReturningExec {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn output_partitioning(&self) -> Partitioning {
+        Partitioning::UnknownPartitioning(1)
+    }
+
+    fn output_ordering(&self) -> Option<&[PhysicalSortExpr]> {
+        None
+    }
+
+    fn children(&self) -> Vec<Arc<dyn ExecutionPlan>> {
+        vec![self.input.clone()]
+    }
+
+    fn with_new_children(
+        self: Arc<Self>,
+        children: Vec<Arc<dyn ExecutionPlan>>,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        Ok(Arc::new(Self::new(
+            self.sink.clone(),
+            children[0].clone(),
+            self.schema.clone(),
+        )))
+    }
+
+    fn execute(
+        &self,
+        _partition: usize,
+        context: Arc<TaskContext>,
+    ) -> Result<SendableRecordBatchStream> {
+        let inputs = (0..self.input.output_partitioning().partition_count())
+            .map(|p| self.input.execute(p, context.clone()))
+            .collect::<Result<Vec<_>>>()?;
+        let sink = self.sink.clone();
+        let schema = self.schema.clone();
+        let chunk_size = self.sink.options.rows_per_statement.max(1);
+        let returned = stream::iter(inputs)
+            .flatten()
+            .map_ok(move |batch| {
+                stream::iter(
+                    chunks(&batch, chunk_size)
+                        .into_iter()
+                        .map(Ok::<_, DataFusionError>),
+                )
+            })
+            .try_flatten()
+            .and_then(move |batch| {
+                let sink = sink.clone();
+                let schema = schema.clone();
+                async move {
+                    let statement = format!("{} RETURNING *", sink.insert_statement(&batch)?);
+                    let batches = sink
+                        .executor
+                        .execute(statement.as_str())
+                        .await?
+                        .try_collect::<Vec<_>>()
+                        .await?;
+                    // Expose the returned rows with the table's column names and types
+                    let batches = batches
+                        .into_iter()
+                        .map(|batch| {
+                            let columns = batch
+                                .columns()
+                                .iter()
+                                .zip(schema.fields())
+                                .map(|(column, field)| cast(column, field.data_type()))
+                                .collect::<Result<Vec<_>, _>>()?;
+                            Ok(RecordBatch::try_new(schema.clone(), columns)?)
+                        })
+                        .collect::<Vec<Result<_>>>();
+                    Ok(stream::iter(batches))
+                }
+            })
+            .try_flatten();
+        Ok(Box::pin(RecordBatchStreamAdapter::new(
+            self.schema.clone(),
+            returned,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use datafusion::{
+        arrow::{
+            array::{Int64Array, StringArray},
+            datatypes::{DataType, Field, Schema},
+        },
+        physical_plan::memory::{MemoryExec, MemoryStream},
+    };
+
+    use super::*;
+    use crate::{
+        dialect::{DefaultDialect, MsSqlDialect, OracleDialect},
+        executor::MemorySQLExecutor,
+    };
+
+    #[test]
+    fn test_upsert_statement() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(Int64Array::from(vec![1])),
+                Arc::new(StringArray::from(vec!["a"])),
+            ],
+        )
+        .unwrap();
+        let upsert = |strategy, dialect: &dyn SQLDialect| {
+            upsert_statement(
+                &["t".to_string()],
+                &batch,
+                &["id".to_string()],
+                strategy,
+                dialect,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            upsert(UpsertStrategy::OnConflict, &DefaultDialect {}),
+            "INSERT INTO `t` (`id`, `name`) VALUES (1, 'a') ON CONFLICT (`id`) DO UPDATE SET `name` = EXCLUDED.`name`"
+        );
+        assert_eq!(
+            upsert(UpsertStrategy::OnDuplicateKey, &DefaultDialect {}),
+            "INSERT INTO `t` (`id`, `name`) VALUES (1, 'a') ON DUPLICATE KEY UPDATE `name` = VALUES(`name`)"
+        );
+        assert_eq!(
+            upsert(UpsertStrategy::Merge, &DefaultDialect {}),
+            "MERGE INTO `t` AS target USING (VALUES (1, 'a')) AS source (`id`, `name`) ON target.`id` = source.`id` \
+             WHEN MATCHED THEN UPDATE SET `name` = source.`name` \
+             WHEN NOT MATCHED THEN INSERT (`id`, `name`) VALUES (source.`id`, source.`name`)"
+        );
+        assert_eq!(
+            upsert(UpsertStrategy::Merge, &MsSqlDialect {}),
+            "MERGE INTO [t] AS target USING (VALUES (1, 'a')) AS source ([id], [name]) ON target.[id] = source.[id] \
+             WHEN MATCHED THEN UPDATE SET [name] = source.[name] \
+             WHEN NOT MATCHED THEN INSERT ([id], [name]) VALUES (source.[id], source.[name]);"
+        );
+        assert_eq!(
+            upsert(UpsertStrategy::Merge, &OracleDialect {}),
+            r#"MERGE INTO "t" target USING (SELECT 1 AS "id", 'a' AS "name" FROM DUAL) source ON (target."id" = source."id") "#
+                .to_string()
+                + r#"WHEN MATCHED THEN UPDATE SET "name" = source."name" "#
+                + r#"WHEN NOT MATCHED THEN INSERT ("id", "name") VALUES (source."id", source."name")"#
+        );
+    }
+
+    // Records the statements and returns no rows
+    struct Recording {
+        memory: MemorySQLExecutor,
+        statements: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl SQLExecutor for Recording {
+        fn name(&self) -> &str {
+            self.memory.name()
+        }
+        fn compute_context(&self) -> Option<String> {
+            self.memory.compute_context()
+        }
+        async fn execute(&self, query: &str) -> Result<SendableRecordBatchStream> {
+            self.statements.lock().unwrap().push(query.to_string());
+            let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+            Ok(Box::pin(MemoryStream::try_new(vec![], schema, None)?))
+        }
+    }
+
+    fn ids(ids: Vec<Option<i64>>) -> RecordBatch {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, true)]));
+        RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(ids))]).unwrap()
+    }
+
+    fn sink(executor: Arc<dyn SQLExecutor>, defaults: &[&str]) -> SQLInsertSink {
+        let options = WriteOptions {
+            rows_per_statement: 2,
+            ..Default::default()
+        };
+        SQLInsertSink::new(
+            executor,
+            vec!["t".to_string()],
+            vec!["id".to_string()],
+            HashSet::new(),
+            defaults.iter().map(|d| d.to_string()).collect(),
+            options,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_insert_returning_chunks() {
+        let executor = Arc::new(Recording {
+            memory: MemorySQLExecutor::new("memory"),
+            statements: Mutex::new(vec![]),
+        });
+        let batch = ids((1..=5).map(Some).collect());
+        let input = MemoryExec::try_new(&[vec![batch.clone()]], batch.schema(), None).unwrap();
+        let exec = SQLInsertReturningExec::new(
+            Arc::new(sink(executor.clone(), &[])),
+            Arc::new(input),
+            batch.schema(),
+        );
+        let batches: Vec<RecordBatch> = exec
+            .execute(0, Arc::new(TaskContext::default()))
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert!(batches.is_empty());
+        assert_eq!(
+            *executor.statements.lock().unwrap(),
+            vec![
+                "INSERT INTO `t` (`id`) VALUES (1), (2) RETURNING *",
+                "INSERT INTO `t` (`id`) VALUES (3), (4) RETURNING *",
+                "INSERT INTO `t` (`id`) VALUES (5) RETURNING *",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_default_values() {
+        let sink = sink(Arc::new(MemorySQLExecutor::new("memory")), &["id"]);
+        assert_eq!(
+            sink.insert_statement(&ids(vec![None])).unwrap(),
+            "INSERT INTO `t` DEFAULT VALUES"
+        );
+        assert!(sink.insert_statement(&ids(vec![None, None])).is_err());
+        assert_eq!(
+            sink.insert_statement(&ids(vec![None, Some(2)])).unwrap(),
+            "INSERT INTO `t` (`id`) VALUES (NULL), (2)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_without_transaction() {
+        let schema = Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)]));
+        let batch =
+            RecordBatch::try_new(schema, vec![Arc::new(Int64Array::from(vec![1]))]).unwrap();
+        let memory = Arc::new(
+            MemorySQLExecutor::new("memory")
+                .with_batch("t", batch)
+                .unwrap(),
+        );
+        let err = memory.execute_transaction(&[]).await.unwrap_err();
+        assert!(matches!(err, DataFusionError::NotImplemented(_)));
+
+        // The statements run one by one instead
+        let options = WriteOptions {
+            statements_per_transaction: Some(2),
+            ..Default::default()
+        };
+        let mut writer = StatementWriter::new(memory.clone(), options);
+        for id in 2..5 {
+            let statement = format!("INSERT INTO t VALUES ({id})");
+            writer.push(statement, 1).await.unwrap();
+        }
+        assert_eq!(writer.finish().await.unwrap(), 3);
+        let batches = memory
+            .context()
+            .sql("SELECT * FROM t")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 4);
+    }
+}